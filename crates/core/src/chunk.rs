@@ -7,9 +7,18 @@
 //! to be sent efficiently while not blocking other messages that share
 //! the same connection, or even the same MSRP session.
 
+use std::collections::BTreeMap;
+
 use bytes::Bytes;
+use futures::channel::mpsc;
+use futures::stream;
+use futures::Stream;
+use futures::StreamExt;
 use itertools::Itertools;
+use serde::de::SeqAccess;
+use serde::de::Visitor;
 use serde::Deserialize;
+use serde::Deserializer;
 use serde::Serialize;
 use uuid::Uuid;
 
@@ -18,6 +27,7 @@ use crate::consts::MAX_TTL_MS;
 use crate::consts::TS_OFFSET_TOLERANCE_MS;
 use crate::error::Error;
 use crate::error::Result;
+use crate::message::types::next_trailing_field;
 use crate::utils::get_epoch_ms;
 
 /// A data structure to presenting Chunks
@@ -57,7 +67,7 @@ impl PartialEq for Chunk {
 }
 
 /// Meta data of a chunk
-#[derive(Debug, Copy, Clone, Deserialize, Serialize)]
+#[derive(Debug, Copy, Clone, Serialize)]
 pub struct ChunkMeta {
     /// uuid of msg
     pub id: uuid::Uuid,
@@ -65,6 +75,68 @@ pub struct ChunkMeta {
     pub ts_ms: u128,
     /// Time to live
     pub ttl_ms: u64,
+    /// CRC32 of the whole msg, verified after reassembly if present.
+    /// It's a trailing field, see [next_trailing_field], and is absent in chunks of earlier versions.
+    pub checksum: Option<u32>,
+}
+
+impl<'de> Deserialize<'de> for ChunkMeta {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where D: Deserializer<'de> {
+        #[derive(Deserialize)]
+        struct Fields {
+            id: uuid::Uuid,
+            ts_ms: u128,
+            ttl_ms: u64,
+            #[serde(default)]
+            checksum: Option<u32>,
+        }
+
+        struct FieldsVisitor;
+        impl<'de> Visitor<'de> for FieldsVisitor {
+            type Value = Fields;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("struct ChunkMeta")
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> std::result::Result<Fields, A::Error>
+            where A: SeqAccess<'de> {
+                let id = seq
+                    .next_element()?
+                    .ok_or_else(|| serde::de::Error::invalid_length(0, &self))?;
+                let ts_ms = seq
+                    .next_element()?
+                    .ok_or_else(|| serde::de::Error::invalid_length(1, &self))?;
+                let ttl_ms = seq
+                    .next_element()?
+                    .ok_or_else(|| serde::de::Error::invalid_length(2, &self))?;
+                let checksum = next_trailing_field(&mut seq);
+                Ok(Fields {
+                    id,
+                    ts_ms,
+                    ttl_ms,
+                    checksum,
+                })
+            }
+        }
+
+        let fields = if deserializer.is_human_readable() {
+            Fields::deserialize(deserializer)?
+        } else {
+            deserializer.deserialize_struct(
+                "ChunkMeta",
+                &["id", "ts_ms", "ttl_ms", "checksum"],
+                FieldsVisitor,
+            )?
+        };
+        Ok(Self {
+            id: fields.id,
+            ts_ms: fields.ts_ms,
+            ttl_ms: fields.ttl_ms,
+            checksum: fields.checksum,
+        })
+    }
 }
 
 impl Default for ChunkMeta {
@@ -73,6 +145,7 @@ impl Default for ChunkMeta {
             id: uuid::Uuid::new_v4(),
             ts_ms: get_epoch_ms(),
             ttl_ms: DEFAULT_TTL_MS,
+            checksum: None,
         }
     }
 }
@@ -90,8 +163,15 @@ pub trait ChunkManager {
     fn remove(&mut self, id: Uuid);
     /// remove expired chunks by ttl
     fn remove_expired(&mut self);
-    /// handle a chunk
-    fn handle(&mut self, chunk: Chunk) -> Option<Bytes>;
+    /// remove pending chunks created earlier than timeout,
+    /// returns [Error::ChunkIncomplete] for each of the dropped messages
+    fn remove_incomplete(&mut self, timeout_ms: u64) -> Vec<Error>;
+    /// handle a chunk, returns the msg if it's completed
+    /// a completed msg failed on checksum will return error
+    fn handle(&mut self, chunk: Chunk) -> Result<Option<Bytes>>;
+    /// ratio of received chunks of a pending msg, in range [0, 1]
+    /// if there is no chunk of id, it will returns None
+    fn progress(&self, id: Uuid) -> Option<f32>;
 }
 
 /// List of Chunk, simply wrapped `Vec<Chunk>`
@@ -99,6 +179,30 @@ pub trait ChunkManager {
 pub struct ChunkList<const MTU: usize>(Vec<Chunk>);
 
 impl<const MTU: usize> ChunkList<MTU> {
+    /// Split bytes into chunks of `size` bytes, instead of `MTU`.
+    pub fn split(bytes: &Bytes, size: usize) -> Self {
+        let chunks: Vec<Bytes> = bytes
+            .chunks(size.max(1))
+            .map(|c| c.to_vec().into())
+            .collect();
+        let chunks_len: usize = chunks.len();
+        let meta = ChunkMeta {
+            checksum: Some(crc32fast::hash(bytes)),
+            ..Default::default()
+        };
+        Self(
+            chunks
+                .into_iter()
+                .enumerate()
+                .map(|(i, data)| Chunk {
+                    meta,
+                    chunk: [i, chunks_len],
+                    data,
+                })
+                .collect::<Vec<Chunk>>(),
+        )
+    }
+
     /// ChunkList to Vec
     pub fn to_vec(&self) -> Vec<Chunk> {
         self.0.clone()
@@ -114,12 +218,12 @@ impl<const MTU: usize> ChunkList<MTU> {
         &mut self.0
     }
 
-    /// dedup and sort elements in list
+    /// sort and dedup elements in list, chunks may arrive out of order
     pub fn formalize(&self) -> Self {
         let mut chunks = self.to_vec();
-        // dedup same chunk id
-        chunks.dedup_by_key(|c| c.chunk[0]);
         chunks.sort_by_key(|a| a.chunk[0]);
+        // dedup same chunk id, only adjacent elements are removed so it must be sorted first
+        chunks.dedup_by_key(|c| c.chunk[0]);
         Self::from(chunks)
     }
 
@@ -142,6 +246,14 @@ impl<const MTU: usize> ChunkList<MTU> {
         !chunks.is_empty() && chunks.len() == chunks.first().unwrap().chunk[1]
     }
 
+    /// indices of received chunks of msg `id` and count of its chunks,
+    /// if there is no chunk of id, it will returns None
+    pub fn received(&self, id: Uuid) -> Option<(Vec<usize>, usize)> {
+        let chunks = self.search(id).to_vec();
+        let total = chunks.first()?.chunk[1];
+        Some((chunks.iter().map(|c| c.chunk[0]).collect(), total))
+    }
+
     /// if list is completed, withdraw data, or return None
     pub fn try_withdraw(&self) -> Option<Bytes> {
         if !self.is_completed() {
@@ -180,20 +292,7 @@ impl<const MTU: usize> IntoIterator for ChunkList<MTU> {
 
 impl<const MTU: usize> From<&Bytes> for ChunkList<MTU> {
     fn from(bytes: &Bytes) -> Self {
-        let chunks: Vec<Bytes> = bytes.chunks(MTU).map(|c| c.to_vec().into()).collect();
-        let chunks_len: usize = chunks.len();
-        let meta = ChunkMeta::default();
-        Self(
-            chunks
-                .into_iter()
-                .enumerate()
-                .map(|(i, data)| Chunk {
-                    meta,
-                    chunk: [i, chunks_len],
-                    data,
-                })
-                .collect::<Vec<Chunk>>(),
-        )
+        Self::split(bytes, MTU)
     }
 }
 
@@ -253,23 +352,139 @@ impl<const MTU: usize> ChunkManager for ChunkList<MTU> {
             .retain(|e| e.meta.ts_ms + e.meta.ttl_ms as u128 > now)
     }
 
-    fn handle(&mut self, chunk: Chunk) -> Option<Bytes> {
+    fn remove_incomplete(&mut self, timeout_ms: u64) -> Vec<Error> {
+        let now = get_epoch_ms();
+        let stale = self
+            .list_pending()
+            .into_iter()
+            .unique()
+            .filter_map(|id| {
+                let chunks = self.search(id).to_vec();
+                let first = chunks.first()?;
+                if first.meta.ts_ms + timeout_ms as u128 > now {
+                    return None;
+                }
+                Some(Error::ChunkIncomplete {
+                    id,
+                    received: chunks.len(),
+                    total: first.chunk[1],
+                })
+            })
+            .collect_vec();
+
+        for e in stale.iter() {
+            if let Error::ChunkIncomplete { id, .. } = e {
+                self.remove(*id);
+            }
+        }
+        stale
+    }
+
+    fn handle(&mut self, chunk: Chunk) -> Result<Option<Bytes>> {
         if chunk.meta.ttl_ms > MAX_TTL_MS {
-            return None;
+            return Ok(None);
         }
 
         if chunk.meta.ts_ms - TS_OFFSET_TOLERANCE_MS > get_epoch_ms() {
-            return None;
+            return Ok(None);
         }
 
-        self.as_vec_mut().push(chunk.clone());
+        let id = chunk.meta.id;
+        let checksum = chunk.meta.checksum;
+        self.as_vec_mut().push(chunk);
         self.remove_expired();
 
-        let id = chunk.meta.id;
-        let data = self.get(id)?;
+        let Some(data) = self.get(id) else {
+            return Ok(None);
+        };
 
         self.remove(id);
-        Some(data)
+        if checksum.is_some_and(|c| crc32fast::hash(&data) != c) {
+            return Err(Error::ChunkChecksumMismatch(id));
+        }
+        Ok(Some(data))
+    }
+
+    fn progress(&self, id: Uuid) -> Option<f32> {
+        let chunks = self.search(id).to_vec();
+        let total = chunks.first()?.chunk[1];
+        Some(chunks.len() as f32 / total as f32)
+    }
+}
+
+/// Receiver of a chunked msg, which yields data as soon as its prefix is contiguous,
+/// so that a large msg can be processed incrementally.
+pub struct ChunkReceiver {
+    id: Uuid,
+    rx: mpsc::UnboundedReceiver<Chunk>,
+}
+
+struct ChunkReceiverState {
+    receiver: ChunkReceiver,
+    pending: BTreeMap<usize, Chunk>,
+    next: usize,
+    total: usize,
+    hasher: crc32fast::Hasher,
+    finished: bool,
+}
+
+impl ChunkReceiver {
+    /// Create a receiver of msg `id`, chunks should be sent to the returned sender.
+    pub fn new(id: Uuid) -> (mpsc::UnboundedSender<Chunk>, Self) {
+        let (tx, rx) = mpsc::unbounded();
+        (tx, Self { id, rx })
+    }
+
+    /// Convert to a stream of in-order chunk data. The final item carries the result of
+    /// checksum verification, and the stream ends with [Error::ChunkIncomplete] if the
+    /// sender is dropped before all chunks arrived.
+    pub fn into_stream(self) -> impl Stream<Item = Result<Bytes>> {
+        let state = ChunkReceiverState {
+            receiver: self,
+            pending: BTreeMap::new(),
+            next: 0,
+            total: 0,
+            hasher: crc32fast::Hasher::new(),
+            finished: false,
+        };
+        stream::unfold(state, |mut st| async move {
+            if st.finished {
+                return None;
+            }
+            let id = st.receiver.id;
+            loop {
+                if let Some(chunk) = st.pending.remove(&st.next) {
+                    st.next += 1;
+                    st.hasher.update(&chunk.data);
+                    if st.next < st.total {
+                        return Some((Ok(chunk.data), st));
+                    }
+                    st.finished = true;
+                    let checksum = st.hasher.clone().finalize();
+                    let item = match chunk.meta.checksum {
+                        Some(c) if c != checksum => Err(Error::ChunkChecksumMismatch(id)),
+                        _ => Ok(chunk.data),
+                    };
+                    return Some((item, st));
+                }
+
+                let Some(chunk) = st.receiver.rx.next().await else {
+                    st.finished = true;
+                    let e = Error::ChunkIncomplete {
+                        id,
+                        received: st.next + st.pending.len(),
+                        total: st.total,
+                    };
+                    return Some((Err(e), st));
+                };
+                // ignore chunks of other msg and duplicated ones
+                if chunk.meta.id != id || chunk.chunk[0] < st.next {
+                    continue;
+                }
+                st.total = chunk.chunk[1];
+                st.pending.insert(chunk.chunk[0], chunk);
+            }
+        })
     }
 }
 
@@ -288,6 +503,12 @@ mod test {
         let ret: Vec<Chunk> = ChunkList::<32>::from(&data).into();
         assert_eq!(ret.len(), 10 * 1024 / 32);
         assert_eq!(ret[ret.len() - 1].chunk, [319, 320]);
+
+        let ret: Vec<Chunk> = ChunkList::<32>::split(&data, 1000).into();
+        assert_eq!(ret.len(), 11);
+        assert_eq!(ret[ret.len() - 1].data.len(), 240);
+        let wd = ChunkList::<32>::from(ret).try_withdraw().unwrap();
+        assert_eq!(wd, data);
     }
 
     #[test]
@@ -335,7 +556,7 @@ mod test {
 
         let mut cl = ChunkList::<32>::default();
         for c in fin {
-            let ret = cl.handle(c);
+            let ret = cl.handle(c).unwrap();
             if let Some(data) = ret {
                 assert_eq!(data, data2);
                 assert_eq!(cl.to_vec().len(), 0);
@@ -349,7 +570,7 @@ mod test {
 
         let mut cl = ChunkList::<32>::default();
         for c in part {
-            let ret = cl.handle(c);
+            let ret = cl.handle(c).unwrap();
             if let Some(data) = ret {
                 assert_eq!(data, data2);
                 assert_eq!(cl.to_vec().len(), 3);
@@ -371,6 +592,7 @@ mod test {
                 id: Uuid::new_v4(),
                 ts_ms: now,
                 ttl_ms: DEFAULT_TTL_MS,
+                checksum: None,
             },
         };
         let expired = Chunk {
@@ -380,34 +602,190 @@ mod test {
                 id: Uuid::new_v4(),
                 ts_ms: now - 1000,
                 ttl_ms: 100,
+                checksum: None,
             },
         };
 
-        cl.handle(regular.clone());
+        cl.handle(regular.clone()).unwrap();
         assert_eq!(cl.as_vec().len(), 1);
 
-        cl.handle(regular.clone());
+        cl.handle(regular.clone()).unwrap();
         assert_eq!(cl.as_vec().len(), 2);
 
-        cl.handle(expired.clone());
+        cl.handle(expired.clone()).unwrap();
         assert_eq!(cl.as_vec().len(), 2);
 
-        cl.handle(expired.clone());
+        cl.handle(expired.clone()).unwrap();
         assert_eq!(cl.as_vec().len(), 2);
 
-        cl.handle(regular.clone());
+        cl.handle(regular.clone()).unwrap();
         assert_eq!(cl.as_vec().len(), 3);
 
-        cl.handle(regular.clone());
+        cl.handle(regular.clone()).unwrap();
         assert_eq!(cl.as_vec().len(), 4);
 
-        cl.handle(expired);
+        cl.handle(expired).unwrap();
         assert_eq!(cl.as_vec().len(), 4);
 
-        cl.handle(regular.clone());
+        cl.handle(regular.clone()).unwrap();
         assert_eq!(cl.as_vec().len(), 5);
 
-        cl.handle(regular);
+        cl.handle(regular).unwrap();
         assert_eq!(cl.as_vec().len(), 6);
     }
+
+    #[test]
+    fn test_handle_chunks_out_of_order() {
+        let data = "helloworld".repeat(1024).into();
+        let chunks: Vec<Chunk> = ChunkList::<32>::from(&data).into();
+        let id = chunks[0].meta.id;
+        let total = chunks.len();
+
+        let mut cl = ChunkList::<32>::default();
+        for (i, c) in chunks.iter().rev().enumerate() {
+            if i == total - 1 {
+                assert_eq!(cl.handle(c.clone()).unwrap().unwrap(), data);
+                break;
+            }
+            assert!(cl.handle(c.clone()).unwrap().is_none());
+            // A duplicated chunk should not be counted.
+            assert!(cl.handle(c.clone()).unwrap().is_none());
+            assert_eq!(cl.progress(id).unwrap(), (i + 1) as f32 / total as f32);
+        }
+        assert!(cl.progress(id).is_none());
+    }
+
+    #[test]
+    fn test_handle_corrupted_chunks() {
+        let data = "helloworld".repeat(1024).into();
+        let chunks: Vec<Chunk> = ChunkList::<32>::from(&data).into();
+        let last = chunks.len() - 1;
+
+        let mut corrupted = chunks.clone();
+        corrupted[0].data = "x".repeat(32).into();
+        let mut cl = ChunkList::<32>::default();
+        for c in corrupted[..last].iter() {
+            assert!(cl.handle(c.clone()).unwrap().is_none());
+        }
+        assert!(matches!(
+            cl.handle(corrupted[last].clone()),
+            Err(Error::ChunkChecksumMismatch(id)) if id == chunks[0].meta.id
+        ));
+        assert!(cl.as_vec().is_empty());
+
+        // A truncated chunk is detected by the checksum as well.
+        let mut truncated = chunks.clone();
+        truncated[last].data = truncated[last].data.slice(1..);
+        let mut cl = ChunkList::<32>::default();
+        for c in truncated[..last].iter() {
+            assert!(cl.handle(c.clone()).unwrap().is_none());
+        }
+        assert!(matches!(
+            cl.handle(truncated[last].clone()),
+            Err(Error::ChunkChecksumMismatch(id)) if id == chunks[0].meta.id
+        ));
+    }
+
+    #[test]
+    fn test_chunk_checksum_is_trailing() {
+        let data: Bytes = "helloworld".into();
+        let id = Uuid::new_v4();
+        let now = get_epoch_ms();
+
+        // Chunk of earlier versions, which has no checksum in its meta.
+        let legacy =
+            bincode::serialize(&([0usize, 1usize], &data, (id, now, DEFAULT_TTL_MS))).unwrap();
+        let chunk = Chunk::from_bincode(&legacy).unwrap();
+        assert_eq!(chunk.meta.id, id);
+        assert!(chunk.meta.checksum.is_none());
+        let mut cl = ChunkList::<32>::default();
+        assert_eq!(cl.handle(chunk).unwrap().unwrap(), data);
+
+        // Earlier versions decode the fields they know and ignore the checksum.
+        let chunks: Vec<Chunk> = ChunkList::<32>::from(&data).into();
+        let encoded = chunks[0].to_bincode().unwrap();
+        let (index, bytes, (legacy_id, _, _)): ([usize; 2], Vec<u8>, (Uuid, u128, u64)) =
+            bincode::deserialize(&encoded).unwrap();
+        assert_eq!(index, [0, 1]);
+        assert_eq!(bytes, data.to_vec());
+        assert_eq!(legacy_id, chunks[0].meta.id);
+
+        let chunk = Chunk::from_bincode(&encoded).unwrap();
+        assert_eq!(chunk.meta.checksum, Some(crc32fast::hash(&data)));
+    }
+
+    #[test]
+    fn test_chunk_receiver_stream() {
+        let data: Bytes = "helloworld".repeat(1024).into();
+        let chunks: Vec<Chunk> = ChunkList::<32>::from(&data).into();
+        let id = chunks[0].meta.id;
+
+        let (tx, receiver) = ChunkReceiver::new(id);
+        for c in chunks.iter().rev() {
+            tx.unbounded_send(c.clone()).unwrap();
+        }
+        let items: Vec<Result<Bytes>> =
+            futures::executor::block_on(receiver.into_stream().collect());
+        assert_eq!(items.len(), chunks.len());
+        let received: Bytes = items.into_iter().flat_map(|b| b.unwrap()).collect();
+        assert_eq!(received, data);
+
+        let (tx, receiver) = ChunkReceiver::new(id);
+        for c in chunks[..3].iter() {
+            tx.unbounded_send(c.clone()).unwrap();
+        }
+        drop(tx);
+        let items: Vec<Result<Bytes>> =
+            futures::executor::block_on(receiver.into_stream().collect());
+        assert_eq!(items.len(), 4);
+        assert!(items[..3].iter().all(|i| i.is_ok()));
+        assert!(matches!(
+            items[3],
+            Err(Error::ChunkIncomplete { received: 3, total, .. }) if total == chunks.len()
+        ));
+    }
+
+    #[test]
+    fn test_received_chunks() {
+        let data = "hello".repeat(1024).into();
+        let chunks: Vec<Chunk> = ChunkList::<32>::from(&data).into();
+        let id = chunks[0].meta.id;
+
+        let mut cl = ChunkList::<32>::default();
+        assert!(cl.received(id).is_none());
+        for c in [&chunks[3], &chunks[0], &chunks[3]] {
+            assert!(cl.handle(c.clone()).unwrap().is_none());
+        }
+        assert_eq!(cl.received(id), Some((vec![0, 3], chunks.len())));
+    }
+
+    #[test]
+    fn test_remove_incomplete_chunks() {
+        let data1 = "hello".repeat(1024).into();
+        let data2 = "world".repeat(1024).into();
+        let chunks1: Vec<Chunk> = ChunkList::<32>::from(&data1).into();
+        let chunks2: Vec<Chunk> = ChunkList::<32>::from(&data2).into();
+        let id = chunks1[0].meta.id;
+        let total = chunks1.len();
+
+        let mut cl = ChunkList::<32>::default();
+        // The last chunk is lost.
+        for c in chunks1[..total - 1].iter() {
+            assert!(cl.handle(c.clone()).unwrap().is_none());
+        }
+        assert!(cl.remove_incomplete(60 * 1000).is_empty());
+        assert_eq!(cl.as_vec().len(), total - 1);
+
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert!(cl.handle(chunks2[0].clone()).unwrap().is_none());
+
+        let evicted = cl.remove_incomplete(10);
+        assert_eq!(evicted.len(), 2);
+        assert!(evicted.iter().any(|e| matches!(
+            e,
+            Error::ChunkIncomplete { id: i, received, total: t }
+                if *i == id && *received == total - 1 && *t == total
+        )));
+        assert!(cl.as_vec().is_empty());
+    }
 }
@@ -0,0 +1,95 @@
+//! Source of the current time, so that expiry logic can be tested without sleeping.
+
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use crate::utils::get_epoch_ms;
+
+/// A source of the current time, used to check session expiry, message ttl and replay windows.
+pub trait Clock: Send + Sync {
+    /// Get the current time.
+    fn now(&self) -> SystemTime;
+
+    /// Get the current timestamp in milliseconds.
+    fn now_ms(&self) -> u128 {
+        self.now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or_default()
+    }
+}
+
+/// A [Clock] shared by the components of a swarm.
+pub type SharedClock = Arc<dyn Clock>;
+
+/// The wall clock. It reads [get_epoch_ms], which also works in browsers.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(get_epoch_ms() as u64)
+    }
+
+    fn now_ms(&self) -> u128 {
+        get_epoch_ms()
+    }
+}
+
+/// A clock for tests, which only moves when it's set or advanced.
+#[derive(Debug, Default)]
+pub struct MockClock {
+    ms: AtomicU64,
+}
+
+impl MockClock {
+    /// Create a clock stopped at `now`.
+    pub fn new(now: SystemTime) -> Self {
+        let clock = Self::default();
+        clock.set(now);
+        clock
+    }
+
+    /// Set the current time.
+    pub fn set(&self, now: SystemTime) {
+        let ms = now
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+        self.ms.store(ms, Ordering::SeqCst);
+    }
+
+    /// Move the current time forward.
+    pub fn advance(&self, duration: Duration) {
+        self.ms
+            .fetch_add(duration.as_millis() as u64, Ordering::SeqCst);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(self.ms.load(Ordering::SeqCst))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_clock() {
+        let clock = MockClock::new(SystemClock.now());
+        let start = clock.now_ms();
+        assert!(start.abs_diff(get_epoch_ms()) < 1000);
+
+        clock.advance(Duration::from_millis(1500));
+        assert_eq!(clock.now_ms(), start + 1500);
+
+        clock.set(UNIX_EPOCH + Duration::from_secs(1));
+        assert_eq!(clock.now_ms(), 1000);
+    }
+}
@@ -9,4 +9,57 @@ pub const DEFAULT_SESSION_TTL_MS: u64 = 30 * 24 * 3600 * 1000;
 pub const TRANSPORT_MTU: usize = 60000;
 /// 60M
 pub const TRANSPORT_MAX_SIZE: usize = TRANSPORT_MTU * 1000;
+/// 64k, the typical SCTP message size limit of data channels, larger messages are chunked
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 64 * 1024;
+/// bytes reserved for the signed payload wrapping a chunk
+pub const CHUNK_PAYLOAD_OVERHEAD: usize = DEFAULT_MAX_MESSAGE_SIZE - TRANSPORT_MTU;
+/// 4k, least bytes of data carried by a chunk, so that a large message is not split too small
+pub const MIN_CHUNK_DATA_SIZE: usize = 4 * 1024;
 pub const VNODE_DATA_MAX_LEN: usize = 1024;
+/// default count of recently seen tx_ids kept for deduplication
+pub const DEFAULT_DEDUP_CACHE_SIZE: usize = 1024;
+/// default count of peers whose session public keys are kept for encryption
+pub const DEFAULT_SESSION_PUBKEYS_CACHE_SIZE: usize = 1024;
+/// default window in ms, handshake payloads signed before it are rejected.
+/// It's long enough for exchanging offer and answer manually, such as by copy and paste
+pub const DEFAULT_HANDSHAKE_REPLAY_WINDOW_MS: u64 = 10 * 60 * 1000;
+/// default timeout in ms, incomplete chunked messages older than it are dropped
+pub const DEFAULT_CHUNK_REASSEMBLY_TIMEOUT_MS: u64 = 60 * 1000;
+/// 64M, bytes of sent chunked messages kept for resuming interrupted transfers
+pub const DEFAULT_CHUNK_RESUME_CACHE_BYTES: usize = 64 * 1024 * 1024;
+/// default interval in ms between stabilization rounds
+pub const DEFAULT_STABILIZE_INTERVAL_MS: u64 = 3 * 1000;
+/// count of consecutive stabilization rounds without topology change to treat the ring as stabilized
+pub const STABILIZED_ROUNDS: usize = 3;
+/// default timeout in ms of waiting for the ack of a reliable message before resending it
+pub const DEFAULT_ACK_TIMEOUT_MS: u64 = 5 * 1000;
+/// default count of sending a reliable message before giving up
+pub const DEFAULT_ACK_MAX_ATTEMPTS: u32 = 3;
+/// default delay in ms before the first attempt of reconnecting an important peer
+pub const DEFAULT_RECONNECT_INITIAL_BACKOFF_MS: u64 = 1000;
+/// default cap in ms of the delay between attempts of reconnecting an important peer
+pub const DEFAULT_RECONNECT_MAX_BACKOFF_MS: u64 = 60 * 1000;
+/// interval in ms the reconnect supervisor checks for peers due to reconnect
+pub const RECONNECT_CHECK_INTERVAL_MS: u64 = 500;
+/// timeout in ms of handing off data and saying goodbye to peers when leaving the ring
+pub const LEAVE_TIMEOUT_MS: u64 = 5 * 1000;
+/// default timeout in ms of a recursive lookup, or of each hop of an iterative lookup
+pub const DEFAULT_LOOKUP_TIMEOUT_MS: u64 = 3 * 1000;
+/// maximum count of hops of an iterative lookup before giving up
+pub const MAX_LOOKUP_HOPS: usize = 32;
+/// environment variable, set to `1` or `true` to dump full SDP in debug logs, see [crate::swarm::Swarm::set_debug_sdp]
+pub const DEBUG_SDP_ENV: &str = "RINGS_DEBUG_SDP";
+/// default count of hops a message can be relayed. A lookup takes no more than log2(N) hops
+/// in a ring of N nodes, so it allows rings of up to 2^32 nodes. A node cannot learn N from
+/// its own routing table, and the ttl only has to stop looping messages, so a constant bound
+/// above any practical ring size is used instead of an estimation
+pub const DEFAULT_RELAY_TTL: u8 = 32;
+/// version of the extension appended to the bincode of a message payload, see
+/// [crate::message::MessagePayload::to_bincode]. Peers sending it are known to decode
+/// the compact form of payload, see [crate::message::MessagePayload::to_compact_bincode],
+/// and the messages appended to [crate::message::Message] in the same version, such as
+/// [crate::message::LeaveDhtHandoff]
+pub const PAYLOAD_VERSION: u8 = 1;
+/// first byte of the compact form of a message payload, which never starts the bincode of
+/// earlier versions, that is the length of the hex string of destination, 42 in u64
+pub const COMPACT_PAYLOAD_TAG: u8 = 0xff;
@@ -1,5 +1,7 @@
 //! Chord algorithm implement.
 #![warn(missing_docs)]
+use std::collections::BTreeSet;
+use std::collections::HashMap;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::sync::Mutex;
@@ -20,6 +22,9 @@ use super::types::CorrectChord;
 use super::vnode::VNodeOperation;
 use super::vnode::VirtualNode;
 use super::FingerTable;
+use crate::clock::SharedClock;
+use crate::clock::SystemClock;
+use crate::consts::STABILIZED_ROUNDS;
 use crate::dht::Did;
 use crate::dht::LiveDid;
 use crate::dht::SuccessorReader;
@@ -59,6 +64,25 @@ pub struct PeerRing {
     pub storage: VNodeStorage,
     /// Local cache for [ChordStorage].
     pub cache: VNodeStorage,
+    /// Replicas kept for predecessors, apart from `storage`, see [PeerRing::with_replicas].
+    pub replica_storage: VNodeStorage,
+    /// The node each replica is received from, keyed by vid.
+    replica_sources: Arc<Mutex<HashMap<Did, Did>>>,
+    /// Topology seen by the last stabilization round.
+    stabilization: Arc<Mutex<StabilizationRecord>>,
+    /// Count of nodes keeping each vnode, including the responsible one.
+    replicas: usize,
+    /// Source of the current time, which vnodes expire by.
+    clock: SharedClock,
+}
+
+/// Topology recorded by stabilization rounds, used to tell if the ring has converged.
+#[derive(Default)]
+struct StabilizationRecord {
+    /// Successors, predecessor and finger table seen by the last round.
+    topo: Option<(TopoInfo, Vec<Option<Did>>)>,
+    /// Count of consecutive rounds without any change.
+    unchanged_rounds: usize,
 }
 
 /// Type alias is just for making the code easy to read.
@@ -98,6 +122,8 @@ pub enum RemoteAction {
     Notify(Did),
     /// Let `did_a` sync data with it's successor.
     SyncVNodeWithSuccessor(Vec<VirtualNode>),
+    /// Let `did_a` keep a replica of the virtual node, see [PeerRing::with_replicas].
+    ReplicateVNode(VirtualNode),
 
     /// Need `did_a` to find `did_b` then send back with `for connect` flag.
     FindSuccessorForConnect(Did),
@@ -126,6 +152,23 @@ pub struct TopoInfo {
     pub predecessor: Option<Did>,
 }
 
+/// Stabilization status of a node on the ring
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Clone)]
+pub struct RingStatus {
+    /// Successor list
+    pub successors: Vec<Did>,
+    /// Predecessor
+    pub predecessor: Option<Did>,
+    /// Count of filled entries in finger table
+    pub finger_filled: usize,
+    /// Size of finger table
+    pub finger_size: usize,
+    /// Count of consecutive stabilization rounds without topology change
+    pub unchanged_rounds: usize,
+    /// True if the last [STABILIZED_ROUNDS] stabilization rounds changed nothing
+    pub stabilized: bool,
+}
+
 impl TryFrom<&PeerRing> for TopoInfo {
     type Error = Error;
     fn try_from(dht: &PeerRing) -> Result<TopoInfo> {
@@ -200,10 +243,113 @@ impl PeerRing {
             finger: Arc::new(Mutex::new(FingerTable::new(did, 160))),
             storage,
             cache: Box::new(MemStorage::new()),
+            replica_storage: Box::new(MemStorage::new()),
+            replica_sources: Arc::new(Mutex::new(HashMap::new())),
+            stabilization: Arc::new(Mutex::new(StabilizationRecord::default())),
+            replicas: 1,
+            clock: Arc::new(SystemClock),
             did,
         }
     }
 
+    /// Expire vnodes by `clock` instead of the wall clock.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Check if vnode is expired at the time of the clock of this node.
+    pub fn is_expired(&self, vnode: &VirtualNode) -> bool {
+        vnode.is_expired_with(self.clock.as_ref())
+    }
+
+    /// Replicate each vnode stored by this node to its first `replicas - 1` successors,
+    /// so that the vnode survives up to `replicas - 1` simultaneous failures.
+    /// Replicas are kept in `replica_storage`, apart from the vnodes this node is responsible
+    /// for, and answer lookups once the node they are received from is gone.
+    /// Vnodes are not replicated by default.
+    pub fn with_replicas(mut self, replicas: usize) -> Self {
+        self.replicas = replicas.max(1);
+        self
+    }
+
+    /// Keep a replica of vnode sent by the node responsible for it.
+    /// The replica is rejected with [Error::ReplicaRejected] unless the sender is a predecessor
+    /// within `replicas - 1` hops, and the vnode lies between the sender and this node.
+    pub async fn store_replica(&self, from: Did, vnode: VirtualNode) -> Result<()> {
+        let vid = vnode.did;
+        if !self.is_replica_source(from, vid)? {
+            return Err(Error::ReplicaRejected(vid, from));
+        }
+        self.replica_storage.put(&vid.to_string(), &vnode).await?;
+        self.lock_replica_sources()?.insert(vid, from);
+        Ok(())
+    }
+
+    /// Check if `from` is a predecessor within `replicas - 1` hops, as far as this node knows,
+    /// and `vid` lies between it and this node.
+    /// Nodes in between are counted from the predecessor, successors and finger table, so that
+    /// a far node can't pass as a near one unless this node knows nothing between them.
+    fn is_replica_source(&self, from: Did, vid: Did) -> Result<bool> {
+        if self.replicas <= 1 || from == self.did {
+            return Ok(false);
+        }
+        let Some(predecessor) = *self.lock_predecessor()? else {
+            return Ok(false);
+        };
+        // Nodes before this node have larger bias, the predecessor has the largest one.
+        let bias = self.bias(from);
+        if bias > self.bias(predecessor) || (vid != self.did && self.bias(vid) <= bias) {
+            return Ok(false);
+        }
+        let mut known: BTreeSet<Did> = self.successors().list()?.into_iter().collect();
+        known.extend(self.lock_finger()?.list().iter().flatten());
+        known.insert(predecessor);
+        let between = known.iter().filter(|did| self.bias(**did) > bias).count();
+        Ok(between < self.replicas - 1)
+    }
+
+    fn lock_replica_sources(&self) -> Result<MutexGuard<HashMap<Did, Did>>> {
+        self.replica_sources
+            .lock()
+            .map_err(|_| Error::DHTSyncLockError)
+    }
+
+    /// Successors after `target` keeping replicas of vnodes stored on it, in order.
+    /// They are asked in turn once `target` is unreachable. Empty if `target` is not a successor
+    /// of this node or vnodes are not replicated.
+    pub fn replica_holders(&self, target: Did) -> Result<Vec<Did>> {
+        if self.replicas <= 1 {
+            return Ok(vec![]);
+        }
+        let successors = self.successors().list()?;
+        let Some(i) = successors.iter().position(|did| *did == target) else {
+            return Ok(vec![]);
+        };
+        Ok(successors
+            .into_iter()
+            .skip(i + 1)
+            .take(self.replicas - 1)
+            .collect())
+    }
+
+    /// Actions to replicate a vnode to successors, see [PeerRing::with_replicas].
+    fn replicate(&self, vnode: &VirtualNode) -> Result<Vec<PeerRingAction>> {
+        if self.replicas <= 1 {
+            return Ok(vec![]);
+        }
+        Ok(self
+            .successors()
+            .list()?
+            .into_iter()
+            .filter(|did| *did != self.did)
+            .take(self.replicas - 1)
+            .map(|did| {
+                PeerRingAction::RemoteAction(did, RemoteAction::ReplicateVNode(vnode.clone()))
+            })
+            .collect())
+    }
+
     /// Return successor sequence. This function is deprecated, please use [chord.successors] instead.
     #[deprecated]
     pub fn lock_successor(&self) -> Result<SuccessorSeq> {
@@ -247,10 +393,122 @@ impl PeerRing {
         Ok(())
     }
 
+    /// Record the topology after a stabilization round.
+    /// The round is counted as unchanged if successors, predecessor and finger table are
+    /// all the same as the last round.
+    pub fn record_stabilization(&self) -> Result<()> {
+        let topo = TopoInfo::try_from(self)?;
+        let finger = self.lock_finger()?.list().clone();
+        let mut record = self
+            .stabilization
+            .lock()
+            .map_err(|_| Error::DHTSyncLockError)?;
+        let current = Some((topo, finger));
+        if record.topo == current {
+            record.unchanged_rounds += 1;
+        } else {
+            record.topo = current;
+            record.unchanged_rounds = 0;
+        }
+        Ok(())
+    }
+
+    /// Return the stabilization status of current node.
+    /// A ring with only current node is never treated as stabilized.
+    pub async fn ring_status(&self) -> Result<RingStatus> {
+        let TopoInfo {
+            successors,
+            predecessor,
+        } = TopoInfo::try_from(self)?;
+        let (finger_filled, finger_size) = {
+            let finger = self.lock_finger()?;
+            (finger.len(), finger.list().len())
+        };
+        let unchanged_rounds = self
+            .stabilization
+            .lock()
+            .map_err(|_| Error::DHTSyncLockError)?
+            .unchanged_rounds;
+        Ok(RingStatus {
+            stabilized: !successors.is_empty() && unchanged_rounds >= STABILIZED_ROUNDS,
+            successors,
+            predecessor,
+            finger_filled,
+            finger_size,
+            unchanged_rounds,
+        })
+    }
+
     /// Calculate bias of the Did on the ring.
     pub fn bias(&self, did: Did) -> BiasId {
         BiasId::new(self.did, did)
     }
+
+    /// Get a vnode from local storage. An expired vnode is deleted and treated as not found.
+    async fn storage_get(&self, vid: Did) -> Result<Option<VirtualNode>> {
+        let key = vid.to_string();
+        match self.storage.get(&key).await? {
+            Some(v) if self.is_expired(&v) => {
+                self.storage.remove(&key).await?;
+                Ok(None)
+            }
+            v => Ok(v),
+        }
+    }
+
+    /// Get a replica of vnode kept for a predecessor. An expired replica is treated as not found.
+    pub async fn replica_get(&self, vid: Did) -> Option<VirtualNode> {
+        let key = vid.to_string();
+        match self.replica_storage.get(&key).await.ok().flatten() {
+            Some(v) if self.is_expired(&v) => {
+                let _ = self.replica_storage.remove(&key).await;
+                None
+            }
+            v => v,
+        }
+    }
+
+    /// Get a replica of vnode, only if the node it's received from is gone from the finger
+    /// table, successors and predecessor, so that a live responsible node is always asked for
+    /// the latest vnode instead of serving a stale replica.
+    async fn replica_get_if_source_lost(&self, vid: Did) -> Result<Option<VirtualNode>> {
+        let Some(source) = self.lock_replica_sources()?.get(&vid).copied() else {
+            return Ok(None);
+        };
+        // Take the locks one by one, in case of deadlock with the other lock orders.
+        let is_predecessor = *self.lock_predecessor()? == Some(source);
+        let is_finger = self.lock_finger()?.contains(Some(source));
+        if is_predecessor || is_finger || self.successors().contains(&source)? {
+            return Ok(None);
+        }
+        Ok(self.replica_get(vid).await)
+    }
+
+    /// Purge expired vnodes from local storage and cache.
+    /// Returns the count of purged vnodes in local storage.
+    pub async fn remove_expired_vnodes(&self) -> Result<usize> {
+        let mut count = 0;
+        for (key, vnode) in self.storage.get_all().await? {
+            if self.is_expired(&vnode) {
+                self.storage.remove(&key).await?;
+                count += 1;
+            }
+        }
+        for (key, vnode) in self.cache.get_all().await? {
+            if self.is_expired(&vnode) {
+                self.cache.remove(&key).await?;
+            }
+        }
+        for (key, vnode) in self.replica_storage.get_all().await? {
+            if self.is_expired(&vnode) {
+                self.replica_storage.remove(&key).await?;
+                if let Ok(vid) = Did::from_str(&key) {
+                    self.lock_replica_sources()?.remove(&vid);
+                }
+            }
+        }
+        Ok(count)
+    }
 }
 
 impl Chord<PeerRingAction> for PeerRing {
@@ -395,9 +653,13 @@ impl<const REDUNDANT: u16> ChordStorage<PeerRingAction, REDUNDANT> for PeerRing
         for vid in vid.rotate_affine(REDUNDANT) {
             let maybe_act = match self.find_successor(vid) {
                 // Resource should be stored in current node.
-                Ok(PeerRingAction::Some(succ)) => match self.storage.get(&vid.to_string()).await {
+                Ok(PeerRingAction::Some(succ)) => match self.storage_get(vid).await {
                     Ok(Some(v)) => Ok(PeerRingAction::SomeVNode(v)),
                     Ok(None) => {
+                        // The responsible node may be gone, leaving its vnodes to this node.
+                        if let Ok(Some(v)) = self.replica_get_if_source_lost(vid).await {
+                            return Ok(PeerRingAction::SomeVNode(v));
+                        }
                         tracing::debug!(
                             "Cannot find vnode in local storage, try to query from successor"
                         );
@@ -414,10 +676,14 @@ impl<const REDUNDANT: u16> ChordStorage<PeerRingAction, REDUNDANT> for PeerRing
                     }
                     Err(_) => Ok(PeerRingAction::None),
                 },
-                // Resource is stored in other nodes.
+                // Resource is stored in other nodes, unless a replica is kept here for a
+                // responsible node which is gone.
                 // Return an action to describe how to find it.
                 Ok(PeerRingAction::RemoteAction(n, RemoteAction::FindSuccessor(id))) => {
-                    Ok(PeerRingAction::RemoteAction(n, RemoteAction::FindVNode(id)))
+                    match self.replica_get_if_source_lost(vid).await {
+                        Ok(Some(v)) => Ok(PeerRingAction::SomeVNode(v)),
+                        _ => Ok(PeerRingAction::RemoteAction(n, RemoteAction::FindVNode(id))),
+                    }
                 }
                 Ok(a) => Err(Error::PeerRingUnexpectedAction(a)),
                 Err(e) => Err(e),
@@ -446,13 +712,14 @@ impl<const REDUNDANT: u16> ChordStorage<PeerRingAction, REDUNDANT> for PeerRing
             let maybe_act = match self.find_successor(vid) {
                 // `vnode` should be on current node.
                 Ok(PeerRingAction::Some(_)) => {
-                    let this = if let Ok(Some(this)) = self.storage.get(&vid.to_string()).await {
+                    let this = if let Ok(Some(this)) = self.storage_get(vid).await {
                         Ok(this)
                     } else {
                         op.clone().gen_default_vnode()
                     }?;
                     let vnode = this.operate(op.clone())?;
                     self.storage.put(&vid.to_string(), &vnode).await?;
+                    ret.extend(self.replicate(&vnode)?);
                     Ok(PeerRingAction::None)
                 }
                 // `vnode` should be on other nodes.
@@ -512,9 +779,16 @@ impl ChordStorageCache<PeerRingAction> for PeerRing {
         self.cache.put(&vnode.did.to_string(), &vnode).await
     }
 
-    /// Get vnode from local cache.
+    /// Get vnode from local cache, an expired vnode is treated as not found.
     async fn local_cache_get(&self, vid: Did) -> Result<Option<VirtualNode>> {
-        self.cache.get(&vid.to_string()).await
+        let key = vid.to_string();
+        match self.cache.get(&key).await? {
+            Some(v) if self.is_expired(&v) => {
+                self.cache.remove(&key).await?;
+                Ok(None)
+            }
+            v => Ok(v),
+        }
     }
 }
 
@@ -880,6 +1154,92 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_ring_status() -> Result<()> {
+        let dhts = gen_sorted_dht(2);
+        let (dht, other) = (&dhts[0], &dhts[1]);
+
+        // A lonely node is never stabilized.
+        for _ in 0..STABILIZED_ROUNDS + 1 {
+            dht.record_stabilization()?;
+        }
+        let status = dht.ring_status().await?;
+        assert!(status.successors.is_empty());
+        assert_eq!(status.finger_filled, 0);
+        assert!(!status.stabilized);
+
+        // Topology changed, the counting restarts.
+        dht.join(other.did)?;
+        dht.record_stabilization()?;
+        let status = dht.ring_status().await?;
+        assert_eq!(status.successors, vec![other.did]);
+        assert!(status.finger_filled > 0);
+        assert_eq!(status.finger_size, 160);
+        assert_eq!(status.unchanged_rounds, 0);
+        assert!(!status.stabilized);
+
+        for _ in 0..STABILIZED_ROUNDS {
+            dht.record_stabilization()?;
+        }
+        let status = dht.ring_status().await?;
+        assert_eq!(status.unchanged_rounds, STABILIZED_ROUNDS);
+        assert!(status.stabilized);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_expired_vnode_is_dropped() -> Result<()> {
+        let node = PeerRing::new_with_storage(
+            SecretKey::random().address().into(),
+            3,
+            Box::new(MemStorage::new()),
+        );
+
+        let alive: VirtualNode = "alive".to_string().try_into()?;
+        let expired: VirtualNode = "expired".to_string().try_into()?;
+        let expired = expired.with_ttl(std::time::Duration::ZERO);
+        let staled: VirtualNode = "staled".to_string().try_into()?;
+        let staled = staled.with_ttl(std::time::Duration::ZERO);
+        for vnode in [&alive, &expired, &staled] {
+            node.storage.put(&vnode.did.to_string(), vnode).await?;
+        }
+
+        // Lookup deletes expired vnode lazily.
+        let act = <PeerRing as ChordStorage<_, 1>>::vnode_lookup(&node, expired.did).await?;
+        assert_eq!(act, PeerRingAction::None);
+        assert_eq!(node.storage.count().await?, 2);
+
+        assert_eq!(node.remove_expired_vnodes().await?, 1);
+        assert_eq!(node.storage.count().await?, 1);
+        assert!(node.storage.get(&alive.did.to_string()).await?.is_some());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_vnode_expires_by_clock() -> Result<()> {
+        let clock = Arc::new(crate::clock::MockClock::new(std::time::SystemTime::now()));
+        let node = PeerRing::new_with_storage(
+            SecretKey::random().address().into(),
+            3,
+            Box::new(MemStorage::new()),
+        )
+        .with_clock(clock.clone());
+
+        let vnode: VirtualNode = "expiring".to_string().try_into()?;
+        let vnode = vnode.with_ttl(std::time::Duration::from_secs(60));
+        node.storage.put(&vnode.did.to_string(), &vnode).await?;
+        assert_eq!(node.remove_expired_vnodes().await?, 0);
+
+        clock.advance(std::time::Duration::from_secs(61));
+        let act = <PeerRing as ChordStorage<_, 1>>::vnode_lookup(&node, vnode.did).await?;
+        assert_eq!(act, PeerRingAction::None);
+        assert_eq!(node.storage.count().await?, 0);
+
+        Ok(())
+    }
+
     /// Test Correct Chord implementation
     #[tokio::test]
     async fn test_correct_chord_impl() -> Result<()> {
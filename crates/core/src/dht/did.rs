@@ -270,6 +270,85 @@ impl Sub for Did {
     }
 }
 
+/// Fixed-width serialization of [Did] for wire structures, used as
+/// `#[serde(with = "crate::dht::did::compact")]`.
+///
+/// A [Did] is serialized as a hex string by default, which takes 50 bytes in bincode.
+/// With this module it's serialized as its raw 20 bytes by binary formats, while
+/// human readable formats, such as json of logs and rpc, keep the string form.
+/// Since earlier versions only decode the string form, it's used by the structures sent to
+/// the peers known to decode it, such as the compact form of
+/// [MessagePayload](crate::message::MessagePayload::to_compact_bincode).
+pub mod compact {
+    use serde::Deserializer;
+    use serde::Serializer;
+
+    use super::*;
+
+    /// Serialize a [Did] as 20 bytes, or a hex string for human readable formats.
+    pub fn serialize<S>(did: &Did, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where S: Serializer {
+        if serializer.is_human_readable() {
+            did.serialize(serializer)
+        } else {
+            did.0.as_fixed_bytes().serialize(serializer)
+        }
+    }
+
+    /// Deserialize a [Did] serialized by [serialize].
+    pub fn deserialize<'de, D>(deserializer: D) -> std::result::Result<Did, D::Error>
+    where D: Deserializer<'de> {
+        if deserializer.is_human_readable() {
+            Did::deserialize(deserializer)
+        } else {
+            <[u8; 20]>::deserialize(deserializer).map(|bytes| Did(H160(bytes)))
+        }
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct CompactDid(#[serde(with = "self")] Did);
+
+    /// Fixed-width serialization of `Vec<Did>`.
+    pub mod vec {
+        use super::*;
+
+        /// Serialize each [Did] by [compact::serialize](super::serialize).
+        pub fn serialize<S>(dids: &[Did], serializer: S) -> std::result::Result<S::Ok, S::Error>
+        where S: Serializer {
+            serializer.collect_seq(dids.iter().map(|did| CompactDid(*did)))
+        }
+
+        /// Deserialize a `Vec<Did>` serialized by [serialize].
+        pub fn deserialize<'de, D>(deserializer: D) -> std::result::Result<Vec<Did>, D::Error>
+        where D: Deserializer<'de> {
+            let dids = Vec::<CompactDid>::deserialize(deserializer)?;
+            Ok(dids.into_iter().map(|did| did.0).collect())
+        }
+    }
+
+    /// Fixed-width serialization of `Option<Did>`.
+    pub mod option {
+        use super::*;
+
+        /// Serialize the [Did] by [compact::serialize](super::serialize) if any.
+        pub fn serialize<S>(
+            did: &Option<Did>,
+            serializer: S,
+        ) -> std::result::Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            did.map(CompactDid).serialize(serializer)
+        }
+
+        /// Deserialize an `Option<Did>` serialized by [serialize].
+        pub fn deserialize<'de, D>(deserializer: D) -> std::result::Result<Option<Did>, D::Error>
+        where D: Deserializer<'de> {
+            Ok(Option::<CompactDid>::deserialize(deserializer)?.map(|did| did.0))
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
@@ -380,4 +459,55 @@ mod tests {
             "Did(0x11e807fcc88dd319270493fb2e822e388fe36ab0)"
         );
     }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Wire {
+        #[serde(with = "compact")]
+        did: Did,
+        #[serde(with = "compact::vec")]
+        path: Vec<Did>,
+        #[serde(with = "compact::option")]
+        predecessor: Option<Did>,
+    }
+
+    #[test]
+    fn test_compact_serialization() {
+        let a = Did::from_str("0x11E807fcc88dD319270493fB2e822e388Fe36ab0").unwrap();
+        let b = Did::from_str("0x999999cf1046e68e36E1aA2E0E07105eDDD1f08E").unwrap();
+
+        // Binary form is fixed-width.
+        let bytes = bincode::serialize(&Wire {
+            did: a,
+            path: vec![],
+            predecessor: None,
+        })
+        .unwrap();
+        assert_eq!(bytes.len(), 20 + 8 + 1);
+        assert_eq!(&bytes[..20], a.as_bytes());
+
+        let wire = Wire {
+            did: a,
+            path: vec![a, b],
+            predecessor: Some(b),
+        };
+        let bytes = bincode::serialize(&wire).unwrap();
+        assert_eq!(bytes.len(), 20 + 8 + 20 * 2 + 1 + 20);
+        assert!(
+            bytes.len() < bincode::serialized_size(&(a, vec![a, b], Some(b))).unwrap() as usize
+        );
+
+        // String form is kept by json.
+        let json = serde_json::to_value(&wire).unwrap();
+        assert_eq!(json["did"], "0x11e807fcc88dd319270493fb2e822e388fe36ab0");
+        assert_eq!(
+            json["predecessor"],
+            "0x999999cf1046e68e36e1aa2e0e07105eddd1f08e"
+        );
+
+        // Both forms round trip to the same value.
+        let from_bincode: Wire = bincode::deserialize(&bytes).unwrap();
+        let from_json: Wire = serde_json::from_value(json).unwrap();
+        assert_eq!(from_bincode, wire);
+        assert_eq!(from_json, wire);
+    }
 }
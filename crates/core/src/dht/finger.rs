@@ -137,6 +137,11 @@ impl FingerTable {
         }
     }
 
+    /// The id covered by finger `index`, which is `did + 2^index` on the ring.
+    pub fn target(&self, index: usize) -> Did {
+        self.did + Did::from(BigUint::from(2u16).pow(index as u32))
+    }
+
     /// Check finger is contains some node
     pub fn contains(&self, v: Option<Did>) -> bool {
         self.finger.contains(&v)
@@ -394,4 +399,21 @@ mod test {
             None
         ]);
     }
+
+    #[test]
+    fn test_finger_table_target() {
+        let did = Did::from(10u32);
+        let table = FingerTable::new(did, 160);
+        assert_eq!(table.target(0), Did::from(11u32));
+        assert_eq!(table.target(3), Did::from(18u32));
+        // Targets wrap around the ring.
+        assert_eq!(
+            table.target(159),
+            did + Did::from(BigUint::from(2u16).pow(159))
+        );
+        assert_eq!(
+            FingerTable::new(-Did::from(1u32), 1).target(0),
+            Did::from(0u32)
+        );
+    }
 }
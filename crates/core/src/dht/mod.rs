@@ -18,6 +18,7 @@ pub mod vnode;
 pub use chord::PeerRing;
 pub use chord::PeerRingAction;
 pub use chord::RemoteAction as PeerRingRemoteAction;
+pub use chord::RingStatus;
 pub use chord::TopoInfo;
 pub use chord::VNodeStorage;
 pub use did::Did;
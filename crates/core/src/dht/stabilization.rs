@@ -7,6 +7,7 @@ use rings_transport::core::transport::WebrtcConnectionState;
 use crate::dht::successor::SuccessorReader;
 use crate::dht::types::CorrectChord;
 use crate::dht::Chord;
+use crate::dht::Did;
 use crate::dht::PeerRing;
 use crate::dht::PeerRingAction;
 use crate::dht::PeerRingRemoteAction;
@@ -19,24 +20,91 @@ use crate::message::MessagePayload;
 use crate::message::NotifyPredecessorSend;
 use crate::message::PayloadSender;
 use crate::message::QueryForTopoInfoSend;
+use crate::swarm::callback::InnerSwarmCallback;
+use crate::swarm::callback::SharedSwarmCallback;
 use crate::swarm::transport::SwarmTransport;
+use crate::swarm::LookupMode;
 
 /// The stabilization runner.
 #[derive(Clone)]
 pub struct Stabilizer {
     transport: Arc<SwarmTransport>,
     dht: Arc<PeerRing>,
+    callback: Option<SharedSwarmCallback>,
 }
 
 impl Stabilizer {
     /// Create a new stabilization runner.
     pub fn new(transport: Arc<SwarmTransport>) -> Self {
         let dht = transport.dht.clone();
-        Self { transport, dht }
+        Self {
+            transport,
+            dht,
+            callback: None,
+        }
+    }
+
+    /// Set the callback of connections created by stabilization. Without it, the successors
+    /// found by iterative lookups cannot be connected, see [LookupMode::Iterative].
+    pub fn with_callback(mut self, callback: SharedSwarmCallback) -> Self {
+        self.callback = Some(callback);
+        self
+    }
+
+    fn inner_callback(&self) -> Option<InnerSwarmCallback> {
+        let callback = self.callback.clone()?;
+        Some(InnerSwarmCallback::new(self.transport.clone(), callback))
+    }
+
+    /// Look up the successor of `did` and connect to it, in [LookupMode::Iterative].
+    async fn connect_successor(&self, did: Did) -> Result<()> {
+        let Some(callback) = self.inner_callback() else {
+            tracing::warn!("STABILIZATION cannot connect successor of {did} without callback");
+            return Ok(());
+        };
+        self.transport.connect_successor(did, callback).await
+    }
+
+    /// Redial peers whose ICE failed, which only use TURN candidates now,
+    /// see [SwarmBuilder::relay_fallback](crate::swarm::SwarmBuilder::relay_fallback).
+    async fn redial_by_relay(&self) -> Result<()> {
+        for peer in self.transport.take_relay_redials() {
+            let Some(callback) = self.inner_callback() else {
+                tracing::warn!("STABILIZATION cannot redial {peer} without callback");
+                return Ok(());
+            };
+            tracing::info!("STABILIZATION redial_by_relay: {:?}", peer);
+            if let Err(e) = self.transport.connect(peer, callback).await {
+                tracing::warn!("[stabilize] Failed to redial {peer} by relay: {e:?}");
+            }
+        }
+        Ok(())
+    }
+
+    /// Connect to the successors of dids deferred by message handlers, see
+    /// [SwarmTransport::defer_connect_lookup].
+    async fn connect_deferred(&self) -> Result<()> {
+        for did in self.transport.take_connect_lookups() {
+            tracing::debug!("STABILIZATION connect_deferred: {:?}", did);
+            if let Err(e) = self.connect_successor(did).await {
+                tracing::warn!("[stabilize] Failed to connect successor of {did}: {e:?}");
+            }
+        }
+        Ok(())
     }
 
     /// Run stabilization once.
     pub async fn stabilize(&self) -> Result<()> {
+        tracing::debug!("STABILIZATION redial_by_relay start");
+        if let Err(e) = self.redial_by_relay().await {
+            tracing::error!("[stabilize] Failed on redial by relay {:?}", e);
+        }
+        tracing::debug!("STABILIZATION redial_by_relay end");
+        tracing::debug!("STABILIZATION connect_deferred start");
+        if let Err(e) = self.connect_deferred().await {
+            tracing::error!("[stabilize] Failed on connect deferred {:?}", e);
+        }
+        tracing::debug!("STABILIZATION connect_deferred end");
         tracing::debug!("STABILIZATION notify_predecessor start");
         if let Err(e) = self.notify_predecessor().await {
             tracing::error!("[stabilize] Failed on notify predecessor {:?}", e);
@@ -55,6 +123,18 @@ impl Stabilizer {
             );
         }
         tracing::debug!("STABILIZATION clean_unavailable_connections end");
+        tracing::debug!("STABILIZATION remove_incomplete_chunks start");
+        for e in self.transport.remove_incomplete_chunks().await {
+            tracing::warn!("[stabilize] Drop chunked message: {:?}", e);
+        }
+        tracing::debug!("STABILIZATION remove_incomplete_chunks end");
+        tracing::debug!("STABILIZATION remove_expired_vnodes start");
+        match self.dht.remove_expired_vnodes().await {
+            Ok(0) => {}
+            Ok(n) => tracing::debug!("[stabilize] Removed {n} expired vnodes"),
+            Err(e) => tracing::error!("[stabilize] Failed on remove expired vnodes {:?}", e),
+        }
+        tracing::debug!("STABILIZATION remove_expired_vnodes end");
         #[cfg(feature = "experimental")]
         {
             tracing::debug!("STABILIZATION correct_stabilize start");
@@ -63,6 +143,9 @@ impl Stabilizer {
             }
             tracing::debug!("STABILIZATION correct_stabilize end");
         }
+        if let Err(e) = self.dht.record_stabilization() {
+            tracing::error!("[stabilize] Failed on record stabilization {:?}", e);
+        }
         Ok(())
     }
 
@@ -116,6 +199,9 @@ impl Stabilizer {
                     PeerRingRemoteAction::FindSuccessorForFix(finger_did),
                 ) => {
                     tracing::debug!("STABILIZATION fix_fingers: {:?}", finger_did);
+                    if self.transport.lookup_mode == LookupMode::Iterative {
+                        return self.connect_successor(finger_did).await;
+                    }
                     let msg = Message::FindSuccessorSend(FindSuccessorSend {
                         did: finger_did,
                         then: FindSuccessorThen::Report(FindSuccessorReportHandler::FixFingerTable),
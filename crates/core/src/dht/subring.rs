@@ -44,6 +44,7 @@ impl TryFrom<Subring> for VirtualNode {
             did: Self::gen_did(&ring.name)?,
             data: vec![data.into()],
             kind: VNodeType::Subring,
+            expires_at_ms: None,
         })
     }
 }
@@ -1,12 +1,22 @@
 #![warn(missing_docs)]
 use std::cmp::max;
 use std::str::FromStr;
+use std::time::Duration;
 
 use num_bigint::BigUint;
+use serde::de;
+use serde::de::SeqAccess;
+use serde::de::Visitor;
+use serde::ser::SerializeStruct;
+use serde::ser::SerializeTuple;
 use serde::Deserialize;
+use serde::Deserializer;
 use serde::Serialize;
+use serde::Serializer;
 
 use super::subring::Subring;
+use crate::clock::Clock;
+use crate::clock::SystemClock;
 use crate::consts::VNODE_DATA_MAX_LEN;
 use crate::dht::Did;
 use crate::ecc::HashStr;
@@ -36,10 +46,12 @@ pub enum VNodeOperation {
     Overwrite(VirtualNode),
     /// Extend data to a Data type VirtualNode.
     /// This operation will create VirtualNode if it's not existed.
+    /// The expiry of VirtualNode is refreshed by the extending one, if it's set.
     Extend(VirtualNode),
     /// Extend data to a Data type VirtualNode uniquely.
     /// If any element is already existed, move it to the end of the data vector.
     /// This operation will create VirtualNode if it's not existed.
+    /// The expiry of VirtualNode is refreshed by the extending one, if it's set.
     Touch(VirtualNode),
     /// Join subring.
     JoinSubring(String, Did),
@@ -54,7 +66,11 @@ pub enum VNodeOperation {
 /// * If type value is [VNodeType::RelayMessage], it's the destination Did of
 /// message plus 1 (to ensure that the message is sent to the successor of destination),
 /// thus while destination node going online, it will sync message from its successor.
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+///
+/// In binary formats such as bincode, which storages keep vnodes in, the fields added since
+/// the first version follow a [VNODE_FORM_VERSION] after `kind`. So vnodes stored before
+/// that, which end after `kind`, are still decoded.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct VirtualNode {
     /// The did of `VirtualNode` make it unique, and can be stored and retrieved on DHT.
     pub did: Did,
@@ -62,6 +78,102 @@ pub struct VirtualNode {
     pub data: Vec<Encoded>,
     /// The type indicates how the data is encoded and how the Did is generated.
     pub kind: VNodeType,
+    /// Time in ms after which the `VirtualNode` is dropped from storage. Live forever if None.
+    pub expires_at_ms: Option<u128>,
+}
+
+/// Version of the binary form of [VirtualNode], version 0 has no `expires_at_ms`.
+pub const VNODE_FORM_VERSION: u8 = 1;
+
+/// Fields of [VirtualNode] in human readable formats, such as json.
+#[derive(Deserialize)]
+#[serde(rename = "VirtualNode")]
+struct VirtualNodeFields {
+    did: Did,
+    data: Vec<Encoded>,
+    kind: VNodeType,
+    #[serde(default)]
+    expires_at_ms: Option<u128>,
+}
+
+impl Serialize for VirtualNode {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where S: Serializer {
+        if serializer.is_human_readable() {
+            let mut state = serializer.serialize_struct("VirtualNode", 4)?;
+            state.serialize_field("did", &self.did)?;
+            state.serialize_field("data", &self.data)?;
+            state.serialize_field("kind", &self.kind)?;
+            state.serialize_field("expires_at_ms", &self.expires_at_ms)?;
+            return state.end();
+        }
+        let mut state = serializer.serialize_tuple(5)?;
+        state.serialize_element(&self.did)?;
+        state.serialize_element(&self.data)?;
+        state.serialize_element(&self.kind)?;
+        state.serialize_element(&VNODE_FORM_VERSION)?;
+        state.serialize_element(&self.expires_at_ms)?;
+        state.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for VirtualNode {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where D: Deserializer<'de> {
+        if deserializer.is_human_readable() {
+            let fields = VirtualNodeFields::deserialize(deserializer)?;
+            return Ok(Self {
+                did: fields.did,
+                data: fields.data,
+                kind: fields.kind,
+                expires_at_ms: fields.expires_at_ms,
+            });
+        }
+        deserializer.deserialize_tuple(5, VirtualNodeVisitor)
+    }
+}
+
+struct VirtualNodeVisitor;
+
+impl<'de> Visitor<'de> for VirtualNodeVisitor {
+    type Value = VirtualNode;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "binary form of VirtualNode")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> std::result::Result<VirtualNode, A::Error>
+    where A: SeqAccess<'de> {
+        let did = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let data = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        let kind = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(2, &self))?;
+        // A vnode of version 0 ends here, reading over its end fails.
+        let version = seq.next_element::<u8>().ok().flatten().unwrap_or(0);
+        let expires_at_ms = match version {
+            0 => None,
+            VNODE_FORM_VERSION => seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(4, &self))?,
+            v => {
+                return Err(de::Error::invalid_value(
+                    de::Unexpected::Unsigned(v as u64),
+                    &self,
+                ))
+            }
+        };
+        Ok(VirtualNode {
+            did,
+            data,
+            kind,
+            expires_at_ms,
+        })
+    }
 }
 
 impl VirtualNode {
@@ -72,6 +184,29 @@ impl VirtualNode {
         tracing::debug!("gen_did: topic: {}, did: {:?}", topic, did);
         did
     }
+
+    /// Set the `VirtualNode` to expire after `ttl`.
+    pub fn with_ttl(self, ttl: Duration) -> Self {
+        self.with_ttl_by(ttl, &SystemClock)
+    }
+
+    /// Set the `VirtualNode` to expire after `ttl` from the time of `clock`.
+    pub fn with_ttl_by(mut self, ttl: Duration, clock: &dyn Clock) -> Self {
+        self.expires_at_ms = Some(clock.now_ms() + ttl.as_millis());
+        self
+    }
+
+    /// Check if the `VirtualNode` is expired.
+    pub fn is_expired(&self) -> bool {
+        self.is_expired_with(&SystemClock)
+    }
+
+    /// Check if the `VirtualNode` is expired at the time of `clock`.
+    pub fn is_expired_with(&self, clock: &dyn Clock) -> bool {
+        self.expires_at_ms
+            .map(|t| t <= clock.now_ms())
+            .unwrap_or(false)
+    }
 }
 
 impl VNodeOperation {
@@ -103,6 +238,7 @@ impl VNodeOperation {
                 did: self.did()?,
                 data: vec![],
                 kind: self.kind(),
+                expires_at_ms: None,
             }),
         }
     }
@@ -117,6 +253,7 @@ impl TryFrom<MessagePayload> for VirtualNode {
             did: did.into(),
             data: vec![data],
             kind: VNodeType::RelayMessage,
+            expires_at_ms: None,
         })
     }
 }
@@ -128,6 +265,7 @@ impl TryFrom<(String, Encoded)> for VirtualNode {
             did: Self::gen_did(&topic)?,
             data: vec![e],
             kind: VNodeType::Data,
+            expires_at_ms: None,
         })
     }
 }
@@ -215,6 +353,7 @@ impl VirtualNode {
             did: self.did,
             data,
             kind: self.kind,
+            expires_at_ms: other.expires_at_ms.or(self.expires_at_ms),
         })
     }
 
@@ -254,6 +393,7 @@ impl VirtualNode {
             did: self.did,
             data,
             kind: self.kind,
+            expires_at_ms: other.expires_at_ms.or(self.expires_at_ms),
         })
     }
 
@@ -274,6 +414,82 @@ impl VirtualNode {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_vnode_expiry() {
+        let vnode: VirtualNode = "test".to_string().try_into().unwrap();
+        assert!(!vnode.is_expired());
+
+        let expiring = vnode.clone().with_ttl(Duration::from_secs(60));
+        assert!(!expiring.is_expired());
+
+        let expired = vnode.clone().with_ttl(Duration::ZERO);
+        assert!(expired.is_expired());
+
+        // Extending refreshes the expiry, or keeps it if the extending one has none.
+        let extended = expired.extend(expiring.clone()).unwrap();
+        assert_eq!(extended.expires_at_ms, expiring.expires_at_ms);
+        let extended = expiring.extend(vnode.clone()).unwrap();
+        assert_eq!(extended.expires_at_ms, expiring.expires_at_ms);
+        let touched = expiring.touch(vnode.clone()).unwrap();
+        assert_eq!(touched.expires_at_ms, expiring.expires_at_ms);
+
+        let expires_at = expiring.expires_at_ms.unwrap() as u64;
+        let clock = crate::clock::MockClock::new(
+            std::time::UNIX_EPOCH + Duration::from_millis(expires_at - 1),
+        );
+        assert!(!expiring.is_expired_with(&clock));
+        clock.advance(Duration::from_millis(1));
+        assert!(expiring.is_expired_with(&clock));
+
+        let expiring = vnode.with_ttl_by(Duration::from_secs(60), &clock);
+        assert_eq!(expiring.expires_at_ms, Some(expires_at as u128 + 60_000));
+        assert!(!expiring.is_expired_with(&clock));
+        clock.advance(Duration::from_secs(60));
+        assert!(expiring.is_expired_with(&clock));
+    }
+
+    #[test]
+    fn test_vnode_serde() {
+        #[derive(Serialize)]
+        struct LegacyVirtualNode {
+            did: Did,
+            data: Vec<Encoded>,
+            kind: VNodeType,
+        }
+
+        let vnode: VirtualNode = "test".to_string().try_into().unwrap();
+        let expiring = vnode.clone().with_ttl(Duration::from_secs(60));
+        for v in [&vnode, &expiring] {
+            let bytes = bincode::serialize(v).unwrap();
+            assert_eq!(&bincode::deserialize::<VirtualNode>(&bytes).unwrap(), v);
+            let json = serde_json::to_string(v).unwrap();
+            assert_eq!(&serde_json::from_str::<VirtualNode>(&json).unwrap(), v);
+        }
+
+        // Vnodes stored before expiry was added are decoded without expiry.
+        let legacy = LegacyVirtualNode {
+            did: expiring.did,
+            data: expiring.data.clone(),
+            kind: expiring.kind,
+        };
+        let bytes = bincode::serialize(&legacy).unwrap();
+        assert_eq!(bincode::deserialize::<VirtualNode>(&bytes).unwrap(), vnode);
+        let json = serde_json::to_string(&legacy).unwrap();
+        assert_eq!(serde_json::from_str::<VirtualNode>(&json).unwrap(), vnode);
+
+        // A vnode in a sequence is followed by the next one, instead of ending.
+        let bytes = bincode::serialize(&vec![vnode.clone(), expiring.clone()]).unwrap();
+        assert_eq!(
+            bincode::deserialize::<Vec<VirtualNode>>(&bytes).unwrap(),
+            vec![vnode.clone(), expiring.clone()]
+        );
+
+        // Unknown version is rejected.
+        let mut bytes = bincode::serialize(&legacy).unwrap();
+        bytes.push(VNODE_FORM_VERSION + 1);
+        assert!(bincode::deserialize::<VirtualNode>(&bytes).is_err());
+    }
+
     #[test]
     fn test_vnode_extend_over_max_len() {
         let topic = "test0".to_string();
@@ -322,6 +322,11 @@ impl PublicKey<33> {
     pub fn address(&self) -> PublicKeyAddress {
         public_key_address(self)
     }
+
+    /// Encrypt data with ECIES, which can only be decrypted by the owner of the key.
+    pub fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        ecies::encrypt(&self.0, data).map_err(Error::MessageEncryptionFailed)
+    }
 }
 
 /// Recover PublicKey from RawMessage using signature.
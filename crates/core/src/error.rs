@@ -103,9 +103,28 @@ pub enum Error {
     #[error("Failed on verify message signature")]
     VerifySignatureFailed,
 
+    #[error("Failed on verify message signature claimed by {0}")]
+    VerifyMessageSignatureFailed(crate::dht::Did),
+
+    #[error("Handshake payload from {0} is out of replay window")]
+    HandshakeExpired(crate::dht::Did),
+
+    #[error("Handshake payload from {0} is replayed")]
+    HandshakeReplayed(crate::dht::Did),
+
     #[error("ECDSA Invalid recover Id {0}")]
     InvalidRecoverId(u8),
 
+    #[error("Chunked message {id} is incomplete, received {received} of {total} chunks")]
+    ChunkIncomplete {
+        id: uuid::Uuid,
+        received: usize,
+        total: usize,
+    },
+
+    #[error("Checksum of chunked message {0} mismatch")]
+    ChunkChecksumMismatch(uuid::Uuid),
+
     #[error("Gzip encode error.")]
     GzipEncode,
 
@@ -169,6 +188,12 @@ pub enum Error {
     #[error("PeerRing findsuccessor error, {0}")]
     PeerRingFindSuccessor(String),
 
+    #[error("Failed to send batched storage message to {0}, {1}")]
+    StorageBatchSend(crate::dht::Did, String),
+
+    #[error("Replica of vnode {0} from {1} is rejected, the sender is not a near predecessor")]
+    ReplicaRejected(crate::dht::Did, crate::dht::Did),
+
     #[error("PeerRing cannot find closest preceding node")]
     PeerRingNotFindClosestNode,
 
@@ -303,6 +328,9 @@ pub enum Error {
     #[error("Suspected infinite looping in path")]
     InfiniteRelayPath,
 
+    #[error("Relay ttl of message to {0} is expired")]
+    RelayTtlExpired(crate::dht::Did),
+
     #[error("The destination of report message should always be the first element of path")]
     InvalidRelayDestination,
 
@@ -356,6 +384,36 @@ pub enum Error {
     #[error("Message has {0} bytes which is too large")]
     MessageTooLarge(usize),
 
+    #[error("Message {tx_id} is not acknowledged after {attempts} attempts")]
+    MessageNotAcked { tx_id: uuid::Uuid, attempts: u32 },
+
+    #[error("Lookup of {did} timed out waiting for {peer}")]
+    LookupTimeout {
+        did: crate::dht::Did,
+        peer: crate::dht::Did,
+    },
+
+    #[error("Lookup of {did} is not finished after {hops} hops")]
+    LookupNotFinished { did: crate::dht::Did, hops: usize },
+
+    #[error("Session pubkey of {0} is unknown, no message was received from it")]
+    SessionPubkeyNotFound(crate::dht::Did),
+
+    #[error("Swarm is shutting down")]
+    SwarmShuttingDown,
+
+    #[error("Peer {0} is not connected")]
+    PeerNotFound(crate::dht::Did),
+
+    #[error("Connection limit {0} is reached")]
+    TooManyConnections(usize),
+
+    #[error("Inbound connection from {0} is denied by gater")]
+    InboundConnectionDenied(crate::dht::Did),
+
+    #[error("Connection is rejected by remote: {0}")]
+    ConnectionRejected(crate::message::ConnectRejectReason),
+
     #[cfg(feature = "wasm")]
     #[error("Cannot get property {0} from JsValue")]
     FailedOnGetProperty(String),
@@ -375,6 +433,9 @@ pub enum Error {
     #[error("Session is expired")]
     SessionExpired,
 
+    #[error("Session ttl should be greater than zero")]
+    InvalidSessionTtl,
+
     #[error("Transport error: {0}")]
     Transport(#[from] rings_transport::error::Error),
 
@@ -5,6 +5,8 @@ use crate::dht::vnode::VirtualNode;
 use crate::dht::PeerRing;
 use crate::dht::SuccessorReader;
 use crate::dht::VNodeStorage;
+use crate::swarm::ConnectionStats;
+use crate::swarm::PeerStats;
 use crate::swarm::Swarm;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,6 +21,16 @@ pub struct SwarmInspect {
 pub struct ConnectionInspect {
     pub did: String,
     pub state: String,
+    #[serde(default)]
+    pub ice_state: String,
+    #[serde(default)]
+    pub data_channel_ready: bool,
+    #[serde(default)]
+    pub latency_ms: Option<u64>,
+    #[serde(default)]
+    pub stats: Option<PeerStats>,
+    #[serde(default)]
+    pub connection_stats: Option<ConnectionStats>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
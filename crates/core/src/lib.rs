@@ -69,12 +69,16 @@ pub mod prelude;
 pub mod session;
 pub mod storage;
 pub mod swarm;
+#[cfg(feature = "test-util")]
+pub mod test_util;
 #[cfg(test)]
 mod tests;
 pub mod utils;
 pub use async_trait::async_trait;
 pub use futures;
 pub mod chunk;
+pub mod clock;
 pub mod consts;
 pub mod inspect;
 pub mod measure;
+pub mod metrics;
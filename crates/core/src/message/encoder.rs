@@ -125,12 +125,87 @@ impl Encoded {
         Self(str.to_owned())
     }
 
+    /// Encode the payload in base45, which only uses characters of QR alphanumeric mode,
+    /// so the result fits a smaller QR code and is easier to copy-paste than base58.
+    pub fn to_compact(&self) -> Result<String> {
+        let data: Vec<u8> = self.decode()?;
+        Ok(base45_encode(&data))
+    }
+
+    /// Restore from the output of [Encoded::to_compact].
+    pub fn from_compact(s: &str) -> Result<Self> {
+        base45_decode(s)?.encode()
+    }
+
+    /// Render the compact form as a QR matrix, indexed by row then column,
+    /// where true means a dark module.
+    #[cfg(feature = "qr")]
+    pub fn to_qr_matrix(&self) -> Result<Vec<Vec<bool>>> {
+        let code = qrcode::QrCode::new(self.to_compact()?).map_err(|_| Error::Encode)?;
+        let width = code.width();
+        let colors = code.to_colors();
+        Ok(colors
+            .chunks(width)
+            .map(|row| row.iter().map(|c| *c == qrcode::Color::Dark).collect())
+            .collect())
+    }
+
     pub fn decode<T>(&self) -> Result<T>
     where T: Decoder {
         T::from_encoded(self)
     }
 }
 
+const BASE45_CHARSET: &[u8; 45] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+/// Base45 encoding of RFC 9285.
+fn base45_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity((data.len() + 1) / 2 * 3);
+    for chunk in data.chunks(2) {
+        let (mut n, len) = match chunk {
+            [a, b] => ((*a as usize) * 256 + *b as usize, 3),
+            [a] => (*a as usize, 2),
+            _ => unreachable!(),
+        };
+        for _ in 0..len {
+            out.push(BASE45_CHARSET[n % 45] as char);
+            n /= 45;
+        }
+    }
+    out
+}
+
+/// Base45 decoding of RFC 9285.
+fn base45_decode(s: &str) -> Result<Vec<u8>> {
+    let digits = s
+        .bytes()
+        .map(|c| BASE45_CHARSET.iter().position(|x| *x == c))
+        .collect::<Option<Vec<_>>>()
+        .ok_or(Error::Decode)?;
+
+    let mut out = Vec::with_capacity(digits.len() / 3 * 2 + 1);
+    for chunk in digits.chunks(3) {
+        match chunk {
+            [c, d, e] => {
+                let n = c + d * 45 + e * 45 * 45;
+                if n > u16::MAX as usize {
+                    return Err(Error::Decode);
+                }
+                out.extend_from_slice(&(n as u16).to_be_bytes());
+            }
+            [c, d] => {
+                let n = c + d * 45;
+                if n > u8::MAX as usize {
+                    return Err(Error::Decode);
+                }
+                out.push(n as u8);
+            }
+            _ => return Err(Error::Decode),
+        }
+    }
+    Ok(out)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -167,4 +242,24 @@ mod test {
         let v2 = Encoded::from_encoded_str(&v);
         assert_eq!(encoded, v2);
     }
+
+    #[test]
+    fn test_base45() {
+        // Test vectors of RFC 9285
+        assert_eq!(base45_encode(b"AB"), "BB8");
+        assert_eq!(base45_encode(b"Hello!!"), "%69 VD92EX0");
+        assert_eq!(base45_encode(b"base-45"), "UJCLQE7W581");
+        assert_eq!(base45_decode("QED8WEX0").unwrap(), b"ietf!");
+        assert!(base45_decode("GGW").is_err());
+        assert!(base45_decode("A").is_err());
+        assert!(base45_decode("a0").is_err());
+    }
+
+    #[test]
+    fn test_compact() {
+        let source = "some handshake info".encode().unwrap();
+        let compact = source.to_compact().unwrap();
+        assert!(compact.len() < source.len() * 2);
+        assert_eq!(Encoded::from_compact(&compact).unwrap(), source);
+    }
 }
@@ -1,15 +1,26 @@
 use async_trait::async_trait;
+use rings_transport::core::transport::WebrtcConnectionState;
 
+use super::storage::handle_storage_store_act;
 use crate::dht::types::Chord;
 use crate::dht::types::CorrectChord;
+use crate::dht::vnode::VNodeOperation;
+use crate::dht::ChordStorage;
+use crate::dht::PeerRing;
 use crate::dht::PeerRingAction;
 use crate::dht::TopoInfo;
 use crate::error::Error;
 use crate::error::Result;
+use crate::message::types::ConnectNodeReject;
 use crate::message::types::ConnectNodeReport;
 use crate::message::types::ConnectNodeSend;
+use crate::message::types::ConnectRejectReason;
+use crate::message::types::FindNextHopReport;
+use crate::message::types::FindNextHopSend;
 use crate::message::types::FindSuccessorReport;
 use crate::message::types::FindSuccessorSend;
+use crate::message::types::LeaveDht;
+use crate::message::types::LeaveDhtHandoff;
 use crate::message::types::Message;
 use crate::message::types::QueryForTopoInfoReport;
 use crate::message::types::QueryForTopoInfoSend;
@@ -20,6 +31,8 @@ use crate::message::HandleMsg;
 use crate::message::MessageHandler;
 use crate::message::MessagePayload;
 use crate::message::PayloadSender;
+use crate::swarm::callback::SwarmEvent;
+use crate::swarm::lookup::LookupStep;
 
 /// QueryForTopoInfoSend is direct message
 #[cfg_attr(feature = "wasm", async_trait(?Send))]
@@ -67,12 +80,33 @@ impl HandleMsg<ConnectNodeSend> for MessageHandler {
         if self.dht.did != ctx.relay.destination {
             self.transport.forward_payload(ctx, None).await
         } else {
-            let answer = self
-                .transport
-                .answer_remote_connection(ctx.relay.origin_sender(), self.inner_callback(), msg)
-                .await?;
+            self.transport.check_handshake_replay(ctx)?;
+            let answer = match self.transport.check_inbound(ctx).await {
+                Ok(()) => {
+                    self.transport
+                        .answer_remote_connection(
+                            ctx.relay.origin_sender(),
+                            self.inner_callback(),
+                            msg,
+                        )
+                        .await
+                }
+                Err(e) => Err(e),
+            };
+            let reason = match answer {
+                Ok(answer) => {
+                    return self
+                        .transport
+                        .send_report_message(ctx, Message::ConnectNodeReport(answer))
+                        .await
+                }
+                Err(Error::TooManyConnections(max)) => ConnectRejectReason::TooManyConnections(max),
+                Err(Error::InboundConnectionDenied(_)) => ConnectRejectReason::Denied,
+                Err(e) => return Err(e),
+            };
+            let reject = ConnectNodeReject { reason };
             self.transport
-                .send_report_message(ctx, Message::ConnectNodeReport(answer))
+                .send_report_message(ctx, Message::ConnectNodeReject(reject))
                 .await
         }
     }
@@ -85,6 +119,7 @@ impl HandleMsg<ConnectNodeReport> for MessageHandler {
         if self.dht.did != ctx.relay.destination {
             self.transport.forward_payload(ctx, None).await
         } else {
+            self.transport.check_handshake_replay(ctx)?;
             self.transport
                 .accept_remote_connection(ctx.relay.origin_sender(), msg)
                 .await
@@ -92,6 +127,34 @@ impl HandleMsg<ConnectNodeReport> for MessageHandler {
     }
 }
 
+/// Drop the pending connection and notify the application, so that it can try other nodes.
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+impl HandleMsg<ConnectNodeReject> for MessageHandler {
+    async fn handle(&self, ctx: &MessagePayload, msg: &ConnectNodeReject) -> Result<()> {
+        if self.dht.did != ctx.relay.destination {
+            return self.transport.forward_payload(ctx, None).await;
+        }
+
+        let peer = ctx.relay.origin_sender();
+        tracing::warn!("Connection to {peer} is rejected: {}", msg.reason);
+
+        if self.transport.get_connection(peer).is_some() {
+            self.transport.disconnect(peer).await?;
+        }
+
+        let event = SwarmEvent::ConnectionStateChange {
+            peer,
+            state: WebrtcConnectionState::Failed,
+        };
+        if let Err(e) = self.swarm_callback.on_event(&event).await {
+            tracing::error!("Failed to handle event {event:?}: {e:?}");
+        }
+
+        Ok(())
+    }
+}
+
 #[cfg_attr(feature = "wasm", async_trait(?Send))]
 #[cfg_attr(not(feature = "wasm"), async_trait)]
 impl HandleMsg<FindSuccessorSend> for MessageHandler {
@@ -144,6 +207,14 @@ impl HandleMsg<FindSuccessorReport> for MessageHandler {
                         .await?;
                 }
             }
+            FindSuccessorReportHandler::None => self.transport.handle_lookup_step(
+                ctx.transaction.signer(),
+                ctx.transaction.tx_id,
+                LookupStep {
+                    next_hop: msg.did,
+                    found: true,
+                },
+            ),
             _ => {}
         }
 
@@ -151,6 +222,109 @@ impl HandleMsg<FindSuccessorReport> for MessageHandler {
     }
 }
 
+/// FindNextHopSend is answered by its destination instead of being forwarded to the next hop,
+/// the origin of an iterative lookup sends the next request itself.
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+impl HandleMsg<FindNextHopSend> for MessageHandler {
+    async fn handle(&self, ctx: &MessagePayload, msg: &FindNextHopSend) -> Result<()> {
+        if self.dht.did != ctx.relay.destination {
+            return self.transport.forward_payload(ctx, None).await;
+        }
+
+        let (next_hop, found) = match self.dht.find_successor(msg.did)? {
+            PeerRingAction::Some(did) => (did, true),
+            PeerRingAction::RemoteAction(next, _) => (next, false),
+            act => return Err(Error::PeerRingUnexpectedAction(act)),
+        };
+        self.transport
+            .send_report_message(
+                ctx,
+                Message::FindNextHopReport(FindNextHopReport {
+                    did: msg.did,
+                    next_hop,
+                    found,
+                }),
+            )
+            .await
+    }
+}
+
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+impl HandleMsg<FindNextHopReport> for MessageHandler {
+    async fn handle(&self, ctx: &MessagePayload, msg: &FindNextHopReport) -> Result<()> {
+        if self.dht.did != ctx.relay.destination {
+            return self.transport.forward_payload(ctx, None).await;
+        }
+
+        self.transport.handle_lookup_step(
+            ctx.transaction.signer(),
+            ctx.transaction.tx_id,
+            LookupStep {
+                next_hop: msg.next_hop,
+                found: msg.found,
+            },
+        );
+        Ok(())
+    }
+}
+
+/// LeaveDht is direct message, the sender will close the connection after sending it.
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+impl HandleMsg<LeaveDht> for MessageHandler {
+    async fn handle(&self, ctx: &MessagePayload, msg: &LeaveDht) -> Result<()> {
+        if msg.did != ctx.relay.origin_sender() {
+            return Err(Error::InvalidMessage(
+                "LeaveDht should be sent by the leaving node".to_string(),
+            ));
+        }
+        self.transport.disconnect(msg.did).await
+    }
+}
+
+/// LeaveDhtHandoff is direct message like [LeaveDht].
+/// The handed off data is stored after the sender is removed from DHT, so that it's not
+/// routed back to the leaving node.
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+impl HandleMsg<LeaveDhtHandoff> for MessageHandler {
+    async fn handle(&self, ctx: &MessagePayload, msg: &LeaveDhtHandoff) -> Result<()> {
+        if msg.did != ctx.relay.origin_sender() {
+            return Err(Error::InvalidMessage(
+                "LeaveDhtHandoff should be sent by the leaving node".to_string(),
+            ));
+        }
+        self.transport.disconnect(msg.did).await?;
+
+        for data in msg.data.iter().cloned() {
+            let vid = data.did;
+            let op = VNodeOperation::Overwrite(data);
+            let act = <PeerRing as ChordStorage<_, 1>>::vnode_operate(&self.dht, op).await;
+            let stored = match act {
+                Ok(act) => handle_storage_store_act(self.transport.clone(), act).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = stored {
+                tracing::warn!(
+                    "Failed to store vnode {vid} handed off by {}: {e:?}",
+                    msg.did
+                );
+            }
+        }
+
+        if let Some(successor) = msg.successor {
+            if successor != self.dht.did && self.transport.get_connection(successor).is_none() {
+                self.transport
+                    .connect(successor, self.inner_callback())
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+}
+
 #[cfg(not(feature = "wasm"))]
 #[cfg(test)]
 pub mod tests {
@@ -553,6 +727,10 @@ pub mod tests {
         println!("| test disconnect node1 and node2 |");
         println!("===================================");
         node1.swarm.disconnect(node2.did()).await?;
+        assert!(matches!(
+            node1.swarm.disconnect(node2.did()).await,
+            Err(Error::PeerNotFound(did)) if did == node2.did()
+        ));
 
         for _ in 1..10 {
             println!("wait 3 seconds for node2's transport 2to1 closing");
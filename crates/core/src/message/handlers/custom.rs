@@ -1,10 +1,15 @@
 use async_trait::async_trait;
 
 use crate::error::Result;
+use crate::message::types::Ack;
 use crate::message::types::CustomMessage;
+use crate::message::types::EncryptedMessage;
+use crate::message::types::ReliableMessage;
 use crate::message::HandleMsg;
+use crate::message::Message;
 use crate::message::MessageHandler;
 use crate::message::MessagePayload;
+use crate::message::MessageVerificationExt;
 use crate::message::PayloadSender;
 
 #[cfg_attr(feature = "wasm", async_trait(?Send))]
@@ -17,3 +22,48 @@ impl HandleMsg<CustomMessage> for MessageHandler {
         Ok(())
     }
 }
+
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+impl HandleMsg<EncryptedMessage> for MessageHandler {
+    async fn handle(&self, ctx: &MessagePayload, _: &EncryptedMessage) -> Result<()> {
+        if self.dht.did != ctx.relay.destination {
+            self.transport.forward_payload(ctx, None).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Acknowledge the message to its sender, even if it's a duplicate, since the earlier
+/// [Ack] may be lost. The duplicate is still dropped before reaching the callback.
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+impl HandleMsg<ReliableMessage> for MessageHandler {
+    async fn handle(&self, ctx: &MessagePayload, _: &ReliableMessage) -> Result<()> {
+        if self.dht.did != ctx.relay.destination {
+            return self.transport.forward_payload(ctx, None).await;
+        }
+        // A message looped back to this node needs no acknowledgement.
+        if ctx.transaction.signer() == self.dht.did {
+            return Ok(());
+        }
+        let ack = Ack {
+            tx_id: ctx.transaction.tx_id,
+        };
+        self.transport
+            .send_report_message(ctx, Message::Ack(ack))
+            .await
+    }
+}
+
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+impl HandleMsg<Ack> for MessageHandler {
+    async fn handle(&self, ctx: &MessagePayload, msg: &Ack) -> Result<()> {
+        if self.dht.did != ctx.relay.destination {
+            return self.transport.forward_payload(ctx, None).await;
+        }
+        self.transport.handle_ack(ctx.transaction.signer(), msg);
+        Ok(())
+    }
+}
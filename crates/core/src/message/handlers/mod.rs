@@ -15,6 +15,7 @@ use crate::dht::PeerRingAction;
 use crate::dht::PeerRingRemoteAction;
 use crate::error::Error;
 use crate::error::Result;
+use crate::message::types::EncryptedMessage;
 use crate::message::types::FindSuccessorSend;
 use crate::message::types::Message;
 use crate::message::types::QueryForTopoInfoSend;
@@ -25,6 +26,7 @@ use crate::message::PayloadSender;
 use crate::swarm::callback::InnerSwarmCallback;
 use crate::swarm::callback::SharedSwarmCallback;
 use crate::swarm::transport::SwarmTransport;
+use crate::swarm::LookupMode;
 
 /// Operator and Handler for Connection
 pub mod connection;
@@ -64,6 +66,13 @@ impl MessageHandler {
         }
     }
 
+    /// Encrypt data to the session of a peer, so that only the peer can decrypt it
+    /// even if the message is relayed by other nodes.
+    /// The peer should have sent a message to us before, so that its session key is known.
+    pub fn encrypt_for(&self, peer: Did, data: &[u8]) -> Result<EncryptedMessage> {
+        self.transport.encrypt_for(peer, data)
+    }
+
     fn inner_callback(&self) -> InnerSwarmCallback {
         InnerSwarmCallback::new(self.transport.clone(), self.swarm_callback.clone())
     }
@@ -108,7 +117,10 @@ impl MessageHandler {
                 next,
                 PeerRingRemoteAction::FindSuccessorForConnect(did),
             ) => {
-                if next != did {
+                if self.transport.lookup_mode == LookupMode::Iterative {
+                    self.transport.defer_connect_lookup(*did);
+                    Ok(())
+                } else if next != did {
                     self.transport
                         .send_direct_message(
                             Message::FindSuccessorSend(FindSuccessorSend {
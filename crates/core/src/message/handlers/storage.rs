@@ -1,6 +1,8 @@
 #![warn(missing_docs)]
 
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 
 use async_recursion::async_recursion;
 use async_trait::async_trait;
@@ -16,7 +18,10 @@ use crate::error::Error;
 use crate::error::Result;
 use crate::message::types::FoundVNode;
 use crate::message::types::Message;
+use crate::message::types::OperateVNodeBatch;
+use crate::message::types::ReplicateVNode;
 use crate::message::types::SearchVNode;
+use crate::message::types::SearchVNodeBatch;
 use crate::message::types::SyncVNodeWithSuccessor;
 use crate::message::Encoded;
 use crate::message::HandleMsg;
@@ -35,6 +40,16 @@ pub trait ChordStorageInterface<const REDUNDANT: u16> {
     async fn storage_fetch(&self, vid: Did) -> Result<()>;
     /// store virtual node on DHT
     async fn storage_store(&self, vnode: VirtualNode) -> Result<()>;
+    /// store virtual node on DHT, it will be dropped after `ttl`
+    async fn storage_store_with_ttl(&self, vnode: VirtualNode, ttl: Duration) -> Result<()> {
+        self.storage_store(vnode.with_ttl(ttl)).await
+    }
+    /// store virtual nodes on DHT in batch, sending one message per responsible node.
+    /// The result of each vnode is returned, a failed vnode won't abort the others.
+    async fn storage_store_batch(&self, vnodes: Vec<VirtualNode>) -> Vec<(Did, Result<()>)>;
+    /// fetch virtual nodes from DHT in batch, sending one message per responsible node.
+    /// The result of each vid is returned, a failed vid won't abort the others.
+    async fn storage_fetch_batch(&self, vids: Vec<Did>) -> Vec<(Did, Result<()>)>;
     /// append data to Data type virtual node
     async fn storage_append_data(&self, topic: &str, data: Encoded) -> Result<()>;
     /// append data to Data type virtual node uniquely
@@ -65,14 +80,29 @@ async fn handle_storage_fetch_act(
         }
         PeerRingAction::RemoteAction(next, dht_act) => {
             if let PeerRingRemoteAction::FindVNode(vid) = dht_act {
-                tracing::debug!(
-                    "storage_fetch send_message: SearchVNode({:?}) to {:?}",
-                    vid,
-                    next
-                );
-                transport
-                    .send_message(Message::SearchVNode(SearchVNode { vid }), next)
-                    .await?;
+                let mut res = Ok(());
+                for target in fallback_targets(&transport, next) {
+                    tracing::debug!(
+                        "storage_fetch send_message: SearchVNode({:?}) to {:?}",
+                        vid,
+                        target
+                    );
+                    res = transport
+                        .send_message(Message::SearchVNode(SearchVNode { vid }), target)
+                        .await
+                        .map(|_| ());
+                    match &res {
+                        Ok(()) => break,
+                        Err(e) => tracing::warn!("Failed to search {vid:?} on {target:?}: {e}"),
+                    }
+                }
+                // The last resort is the replica kept by this node.
+                if let Err(e) = res {
+                    let Some(v) = transport.dht.replica_get(vid).await else {
+                        return Err(e);
+                    };
+                    transport.dht.local_cache_put(v).await?;
+                }
             }
         }
         PeerRingAction::MultiActions(acts) => {
@@ -99,6 +129,9 @@ pub(super) async fn handle_storage_store_act(
                 .send_message(Message::OperateVNode(op), target)
                 .await?;
         }
+        PeerRingAction::RemoteAction(target, PeerRingRemoteAction::ReplicateVNode(vnode)) => {
+            send_replica(&transport, vnode, target).await?;
+        }
         PeerRingAction::MultiActions(acts) => {
             for act in acts {
                 handle_storage_store_act(transport.clone(), act).await?;
@@ -124,7 +157,17 @@ async fn handle_storage_search_act(
                 .send_report_message(ctx, Message::FoundVNode(FoundVNode { data: vec![v] }))
                 .await
         }
-        PeerRingAction::RemoteAction(next, _) => transport.reset_destination(ctx, next).await,
+        PeerRingAction::RemoteAction(next, _) => {
+            let mut res = Ok(());
+            for target in fallback_targets(&transport, next) {
+                res = transport.reset_destination(ctx, target).await;
+                match &res {
+                    Ok(()) => break,
+                    Err(e) => tracing::warn!("Failed to forward search to {target:?}: {e}"),
+                }
+            }
+            res
+        }
         PeerRingAction::MultiActions(acts) => {
             let jobs = acts.iter().map(|act| {
                 let transport_clone = transport.clone();
@@ -153,6 +196,9 @@ pub(super) async fn handle_storage_operate_act(
 ) -> Result<()> {
     match act {
         PeerRingAction::None => Ok(()),
+        PeerRingAction::RemoteAction(target, PeerRingRemoteAction::ReplicateVNode(vnode)) => {
+            send_replica(&transport, vnode.clone(), *target).await
+        }
         PeerRingAction::RemoteAction(next, _) => transport.reset_destination(ctx, *next).await,
         PeerRingAction::MultiActions(acts) => {
             let jobs = acts.iter().map(|act| {
@@ -172,6 +218,72 @@ pub(super) async fn handle_storage_operate_act(
     }
 }
 
+/// The node to search a vnode on, followed by the successors keeping replicas of it, which
+/// are tried in turn if the former ones are unreachable, see [PeerRing::replica_holders].
+fn fallback_targets(transport: &SwarmTransport, target: Did) -> Vec<Did> {
+    let mut targets = vec![target];
+    targets.extend(transport.dht.replica_holders(target).unwrap_or_default());
+    targets
+}
+
+/// Send a replica of vnode to a successor, see [PeerRing::with_replicas].
+async fn send_replica(transport: &SwarmTransport, vnode: VirtualNode, target: Did) -> Result<()> {
+    let msg = Message::ReplicateVNode(ReplicateVNode { data: vec![vnode] });
+    transport.send_message(msg, target).await?;
+    Ok(())
+}
+
+/// Send remote storage actions to the node found by [SwarmTransport::route],
+/// so that the lookup mode of swarm applies to them.
+async fn route_act(transport: &SwarmTransport, act: PeerRingAction) -> PeerRingAction {
+    let mut acts = vec![];
+    for act in flatten_actions(act) {
+        let act = match act {
+            PeerRingAction::RemoteAction(next, PeerRingRemoteAction::FindVNode(vid)) => {
+                let target = transport.route(vid, next).await;
+                PeerRingAction::RemoteAction(target, PeerRingRemoteAction::FindVNode(vid))
+            }
+            PeerRingAction::RemoteAction(next, PeerRingRemoteAction::FindVNodeForOperate(op)) => {
+                let target = match op.did() {
+                    Ok(vid) => transport.route(vid, next).await,
+                    Err(_) => next,
+                };
+                PeerRingAction::RemoteAction(target, PeerRingRemoteAction::FindVNodeForOperate(op))
+            }
+            act => act,
+        };
+        acts.push(act);
+    }
+    match acts.len() {
+        0 => PeerRingAction::None,
+        1 => acts.remove(0),
+        _ => PeerRingAction::MultiActions(acts),
+    }
+}
+
+/// Flatten nested [PeerRingAction::MultiActions] and drop [PeerRingAction::None].
+fn flatten_actions(act: PeerRingAction) -> Vec<PeerRingAction> {
+    match act {
+        PeerRingAction::None => vec![],
+        PeerRingAction::MultiActions(acts) => acts.into_iter().flat_map(flatten_actions).collect(),
+        act => vec![act],
+    }
+}
+
+/// Mark results of `vids` as failed after a batched message to `target` cannot be sent.
+fn fail_batch_results(
+    results: &mut [(Did, Result<()>)],
+    vids: &[Did],
+    target: Did,
+    reason: &Error,
+) {
+    for (vid, res) in results.iter_mut() {
+        if vids.contains(vid) && res.is_ok() {
+            *res = Err(Error::StorageBatchSend(target, reason.to_string()));
+        }
+    }
+}
+
 #[cfg_attr(feature = "wasm", async_trait(?Send))]
 #[cfg_attr(not(feature = "wasm"), async_trait)]
 impl ChordStorageInterfaceCacheChecker for Swarm {
@@ -189,6 +301,7 @@ impl<const REDUNDANT: u16> ChordStorageInterface<REDUNDANT> for Swarm {
     async fn storage_fetch(&self, vid: Did) -> Result<()> {
         // If peer found that data is on it's localstore, copy it to the cache
         let act = <PeerRing as ChordStorage<_, REDUNDANT>>::vnode_lookup(&self.dht, vid).await?;
+        let act = route_act(&self.transport, act).await;
         handle_storage_fetch_act(self.transport.clone(), act).await?;
         Ok(())
     }
@@ -197,14 +310,129 @@ impl<const REDUNDANT: u16> ChordStorageInterface<REDUNDANT> for Swarm {
     async fn storage_store(&self, vnode: VirtualNode) -> Result<()> {
         let op = VNodeOperation::Overwrite(vnode);
         let act = <PeerRing as ChordStorage<_, REDUNDANT>>::vnode_operate(&self.dht, op).await?;
+        let act = route_act(&self.transport, act).await;
         handle_storage_store_act(self.transport.clone(), act).await?;
         Ok(())
     }
 
+    /// Store VirtualNode, which expires after `ttl` from the time of the swarm clock.
+    async fn storage_store_with_ttl(&self, vnode: VirtualNode, ttl: Duration) -> Result<()> {
+        let vnode = vnode.with_ttl_by(ttl, self.transport.clock());
+        <Self as ChordStorageInterface<REDUNDANT>>::storage_store(self, vnode).await
+    }
+
+    /// Store VirtualNodes, the operations are grouped by the responsible node,
+    /// so that only one message is sent to each of them.
+    async fn storage_store_batch(&self, vnodes: Vec<VirtualNode>) -> Vec<(Did, Result<()>)> {
+        let mut results = Vec::with_capacity(vnodes.len());
+        let mut groups: HashMap<Did, Vec<VNodeOperation>> = HashMap::new();
+
+        for vnode in vnodes {
+            let vid = vnode.did;
+            let op = VNodeOperation::Overwrite(vnode);
+            let act = match <PeerRing as ChordStorage<_, REDUNDANT>>::vnode_operate(&self.dht, op)
+                .await
+            {
+                Ok(act) => act,
+                Err(e) => {
+                    results.push((vid, Err(e)));
+                    continue;
+                }
+            };
+
+            let mut res = Ok(());
+            for act in flatten_actions(route_act(&self.transport, act).await) {
+                match act {
+                    PeerRingAction::RemoteAction(
+                        target,
+                        PeerRingRemoteAction::FindVNodeForOperate(op),
+                    ) => groups.entry(target).or_default().push(op),
+                    PeerRingAction::RemoteAction(
+                        target,
+                        PeerRingRemoteAction::ReplicateVNode(vnode),
+                    ) => {
+                        if let Err(e) = send_replica(&self.transport, vnode, target).await {
+                            res = Err(e);
+                        }
+                    }
+                    act => res = Err(Error::PeerRingUnexpectedAction(act)),
+                }
+            }
+            results.push((vid, res));
+        }
+
+        for (target, ops) in groups {
+            let vids = ops.iter().map(|op| op.did()).collect::<Result<Vec<_>>>();
+            tracing::debug!(
+                "storage_store_batch send_message: {} ops to {:?}",
+                ops.len(),
+                target
+            );
+            let msg = Message::OperateVNodeBatch(OperateVNodeBatch { ops });
+            if let Err(e) = self.transport.send_message(msg, target).await {
+                fail_batch_results(&mut results, &vids.unwrap_or_default(), target, &e);
+            }
+        }
+
+        results
+    }
+
+    /// Fetch VirtualNodes, vnodes stored locally are copied to the cache,
+    /// the others are queried from remote nodes with one message per node.
+    async fn storage_fetch_batch(&self, vids: Vec<Did>) -> Vec<(Did, Result<()>)> {
+        let mut results = Vec::with_capacity(vids.len());
+        // Group of target -> (origin vid, searching vid).
+        let mut groups: HashMap<Did, Vec<(Did, Did)>> = HashMap::new();
+
+        for vid in vids {
+            let act = match <PeerRing as ChordStorage<_, REDUNDANT>>::vnode_lookup(&self.dht, vid)
+                .await
+            {
+                Ok(act) => act,
+                Err(e) => {
+                    results.push((vid, Err(e)));
+                    continue;
+                }
+            };
+
+            let mut res = Ok(());
+            for act in flatten_actions(route_act(&self.transport, act).await) {
+                match act {
+                    PeerRingAction::SomeVNode(v) => {
+                        if let Err(e) = self.dht.local_cache_put(v).await {
+                            res = Err(e);
+                        }
+                    }
+                    PeerRingAction::RemoteAction(target, PeerRingRemoteAction::FindVNode(rid)) => {
+                        groups.entry(target).or_default().push((vid, rid))
+                    }
+                    act => res = Err(Error::PeerRingUnexpectedAction(act)),
+                }
+            }
+            results.push((vid, res));
+        }
+
+        for (target, pairs) in groups {
+            let (origins, vids): (Vec<Did>, Vec<Did>) = pairs.into_iter().unzip();
+            tracing::debug!(
+                "storage_fetch_batch send_message: SearchVNodeBatch({:?}) to {:?}",
+                vids,
+                target
+            );
+            let msg = Message::SearchVNodeBatch(SearchVNodeBatch { vids });
+            if let Err(e) = self.transport.send_message(msg, target).await {
+                fail_batch_results(&mut results, &origins, target, &e);
+            }
+        }
+
+        results
+    }
+
     async fn storage_append_data(&self, topic: &str, data: Encoded) -> Result<()> {
         let vnode: VirtualNode = (topic.to_string(), data).try_into()?;
         let op = VNodeOperation::Extend(vnode);
         let act = <PeerRing as ChordStorage<_, REDUNDANT>>::vnode_operate(&self.dht, op).await?;
+        let act = route_act(&self.transport, act).await;
         handle_storage_store_act(self.transport.clone(), act).await?;
         Ok(())
     }
@@ -213,6 +441,7 @@ impl<const REDUNDANT: u16> ChordStorageInterface<REDUNDANT> for Swarm {
         let vnode: VirtualNode = (topic.to_string(), data).try_into()?;
         let op = VNodeOperation::Touch(vnode);
         let act = <PeerRing as ChordStorage<_, REDUNDANT>>::vnode_operate(&self.dht, op).await?;
+        let act = route_act(&self.transport, act).await;
         handle_storage_store_act(self.transport.clone(), act).await?;
         Ok(())
     }
@@ -232,6 +461,30 @@ impl HandleMsg<SearchVNode> for MessageHandler {
     }
 }
 
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+impl HandleMsg<SearchVNodeBatch> for MessageHandler {
+    /// Search VNodes stored locally and response them in one [FoundVNode].
+    /// The vids not stored on this node are skipped, so the origin gets partial results.
+    async fn handle(&self, ctx: &MessagePayload, msg: &SearchVNodeBatch) -> Result<()> {
+        let mut data = vec![];
+        for vid in msg.vids.iter() {
+            // For relay message, set redundant to 1
+            match <PeerRing as ChordStorage<_, 1>>::vnode_lookup(&self.dht, *vid).await {
+                Ok(PeerRingAction::SomeVNode(v)) => data.push(v),
+                Ok(_) => tracing::debug!("SearchVNodeBatch: {:?} is not found locally", vid),
+                Err(e) => tracing::error!("SearchVNodeBatch: failed on lookup {:?}: {:?}", vid, e),
+            }
+        }
+        if data.is_empty() {
+            return Ok(());
+        }
+        self.transport
+            .send_report_message(ctx, Message::FoundVNode(FoundVNode { data }))
+            .await
+    }
+}
+
 #[cfg_attr(feature = "wasm", async_trait(?Send))]
 #[cfg_attr(not(feature = "wasm"), async_trait)]
 impl HandleMsg<FoundVNode> for MessageHandler {
@@ -257,6 +510,27 @@ impl HandleMsg<VNodeOperation> for MessageHandler {
     }
 }
 
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+impl HandleMsg<OperateVNodeBatch> for MessageHandler {
+    /// Apply operations one by one, a failed operation won't abort the others.
+    /// Operations not belonging to this node are forwarded individually.
+    async fn handle(&self, _ctx: &MessagePayload, msg: &OperateVNodeBatch) -> Result<()> {
+        for op in msg.ops.iter().cloned() {
+            let vid = op.did();
+            // For relay message, set redundant to 1
+            let res = match <PeerRing as ChordStorage<_, 1>>::vnode_operate(&self.dht, op).await {
+                Ok(act) => handle_storage_store_act(self.transport.clone(), act).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = res {
+                tracing::error!("OperateVNodeBatch: failed on operate {:?}: {:?}", vid, e);
+            }
+        }
+        Ok(())
+    }
+}
+
 #[cfg_attr(feature = "wasm", async_trait(?Send))]
 #[cfg_attr(not(feature = "wasm"), async_trait)]
 impl HandleMsg<SyncVNodeWithSuccessor> for MessageHandler {
@@ -273,18 +547,90 @@ impl HandleMsg<SyncVNodeWithSuccessor> for MessageHandler {
     }
 }
 
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+impl HandleMsg<ReplicateVNode> for MessageHandler {
+    // received replicas from the node responsible for them
+    async fn handle(&self, ctx: &MessagePayload, msg: &ReplicateVNode) -> Result<()> {
+        let from = ctx.relay.origin_sender();
+        for data in msg.data.iter().cloned() {
+            self.dht.store_replica(from, data).await?;
+        }
+        Ok(())
+    }
+}
+
 #[cfg(not(feature = "wasm"))]
 #[cfg(test)]
 mod test {
+    use tokio::time::timeout;
+
     use super::*;
+    use crate::dht::SuccessorReader;
     use crate::ecc::tests::gen_ordered_keys;
+    use crate::ecc::SecretKey;
     use crate::message::Encoder;
     use crate::prelude::vnode::VNodeType;
+    use crate::session::SessionSk;
+    use crate::storage::MemStorage;
+    use crate::swarm::LookupMode;
+    use crate::swarm::SwarmBuilder;
     use crate::tests::default::assert_no_more_msg;
     use crate::tests::default::prepare_node;
     use crate::tests::default::wait_for_msgs;
+    use crate::tests::default::Node;
     use crate::tests::manually_establish_connection;
 
+    #[tokio::test]
+    async fn test_store_vnode_by_iterative_lookup() -> Result<()> {
+        let keys = gen_ordered_keys(3);
+        let node1 = {
+            let stun = "stun://stun.l.google.com:19302";
+            let storage = Box::new(MemStorage::new());
+            let session_sk = SessionSk::new_with_seckey(&keys[0]).unwrap();
+            let swarm = SwarmBuilder::new(0, stun, storage, session_sk)
+                .lookup_mode(LookupMode::Iterative)
+                .build();
+            Node::new(Arc::new(swarm))
+        };
+        let node2 = prepare_node(keys[1]).await;
+        let node3 = prepare_node(keys[2]).await;
+
+        manually_establish_connection(&node1.swarm, &node2.swarm).await;
+        wait_for_msgs([&node1, &node2]).await;
+        manually_establish_connection(&node2.swarm, &node3.swarm).await;
+        node1.swarm.stabilize_now().await?;
+        wait_for_msgs([&node1, &node2, &node3]).await;
+        assert_eq!(node1.dht().successors().min()?, node2.did());
+
+        // node3 is the successor of the vnode, which node1 finds by asking node2,
+        // then node1 sends the operation to node3 instead of letting node2 forward it.
+        let mut vnode: VirtualNode = "routed".to_string().try_into().unwrap();
+        vnode.did = node3.did();
+        <Swarm as ChordStorageInterface<1>>::storage_store(&node1.swarm, vnode.clone()).await?;
+
+        let mut asked = false;
+        while let Ok(Some(ev)) = timeout(Duration::from_secs(3), node2.listen_once()).await {
+            asked |= matches!(
+                ev.transaction.data()?,
+                Message::FindNextHopSend(x) if x.did == node3.did()
+            );
+        }
+        assert!(asked);
+
+        let mut operated = false;
+        while let Ok(Some(ev)) = timeout(Duration::from_secs(3), node3.listen_once()).await {
+            operated |= ev.transaction.destination == node3.did()
+                && matches!(ev.transaction.data()?, Message::OperateVNode(_));
+        }
+        assert!(operated);
+        assert_eq!(
+            node3.dht().storage.get(&node3.did().to_string()).await?,
+            Some(vnode)
+        );
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_store_vnode() -> Result<()> {
         let keys = gen_ordered_keys(2);
@@ -353,13 +699,89 @@ mod test {
             Some(VirtualNode {
                 did: vid,
                 data: vec![data.encode()?],
-                kind: VNodeType::Data
+                kind: VNodeType::Data,
+                expires_at_ms: None,
             })
         );
 
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_store_and_fetch_vnode_batch() -> Result<()> {
+        let keys = gen_ordered_keys(2);
+        let (key1, key2) = (keys[0], keys[1]);
+        let node1 = prepare_node(key1).await;
+        let node2 = prepare_node(key2).await;
+
+        manually_establish_connection(&node1.swarm, &node2.swarm).await;
+        wait_for_msgs([&node1, &node2]).await;
+        assert_no_more_msg([&node1, &node2]).await;
+
+        let vnodes: Vec<VirtualNode> = (0..16)
+            .map(|i| format!("batch data {i}").try_into().unwrap())
+            .collect();
+        let (remote, local): (Vec<_>, Vec<_>) = vnodes
+            .iter()
+            .partition(|v| v.did.in_range(node2.did(), node2.did(), node1.did()));
+
+        let results =
+            <Swarm as ChordStorageInterface<1>>::storage_store_batch(&node1.swarm, vnodes.clone())
+                .await;
+        assert_eq!(results.len(), vnodes.len());
+        assert!(results.iter().all(|(_, r)| r.is_ok()));
+
+        // All the remote vnodes are sent to node2 in one message.
+        if !remote.is_empty() {
+            let ev = node2.listen_once().await.unwrap();
+            assert!(matches!(
+                ev.transaction.data()?,
+                Message::OperateVNodeBatch(x) if x.ops.len() == remote.len()
+            ));
+        }
+        assert_no_more_msg([&node1, &node2]).await;
+        assert_eq!(
+            node1.dht().storage.count().await.unwrap(),
+            local.len() as u32
+        );
+        assert_eq!(
+            node2.dht().storage.count().await.unwrap(),
+            remote.len() as u32
+        );
+
+        // A missing vid won't fail the whole batch.
+        let missing: VirtualNode = "batch data missing".to_string().try_into().unwrap();
+        let mut vids: Vec<Did> = vnodes.iter().map(|v| v.did).collect();
+        vids.push(missing.did);
+
+        let results =
+            <Swarm as ChordStorageInterface<1>>::storage_fetch_batch(&node1.swarm, vids.clone())
+                .await;
+        assert_eq!(results.len(), vids.len());
+        assert!(results.iter().all(|(_, r)| r.is_ok()));
+
+        // The missing vid is always queried from node2, see `vnode_lookup`.
+        let ev = node2.listen_once().await.unwrap();
+        assert!(matches!(
+            ev.transaction.data()?,
+            Message::SearchVNodeBatch(x) if x.vids.len() == remote.len() + 1
+        ));
+        if !remote.is_empty() {
+            let ev = node1.listen_once().await.unwrap();
+            assert!(matches!(
+                ev.transaction.data()?,
+                Message::FoundVNode(x) if x.data.len() == remote.len()
+            ));
+        }
+
+        for vnode in vnodes.iter() {
+            assert!(node1.swarm.storage_check_cache(vnode.did).await.is_some());
+        }
+        assert!(node1.swarm.storage_check_cache(missing.did).await.is_none());
+
+        Ok(())
+    }
+
     #[cfg(not(feature = "redundant"))]
     #[tokio::test]
     async fn test_extend_data() -> Result<()> {
@@ -428,7 +850,8 @@ mod test {
             Some(VirtualNode {
                 did: vid,
                 data: vec!["111".to_string().encode()?, "222".to_string().encode()?],
-                kind: VNodeType::Data
+                kind: VNodeType::Data,
+                expires_at_ms: None,
             })
         );
 
@@ -460,10 +883,88 @@ mod test {
                     "222".to_string().encode()?,
                     "333".to_string().encode()?
                 ],
-                kind: VNodeType::Data
+                kind: VNodeType::Data,
+                expires_at_ms: None,
             })
         );
 
         Ok(())
     }
+
+    fn prepare_replicated_node(key: SecretKey, len: usize) -> Node {
+        let stun = "stun://stun.l.google.com:19302";
+        let storage = Box::new(MemStorage::new());
+        let session_sk = SessionSk::new_with_seckey(&key).unwrap();
+        let swarm = SwarmBuilder::new(0, stun, storage, session_sk)
+            .successor_list_length(len)
+            .build();
+        Node::new(Arc::new(swarm))
+    }
+
+    #[tokio::test]
+    async fn test_replicate_vnode() -> Result<()> {
+        let keys = gen_ordered_keys(2);
+        let node1 = prepare_replicated_node(keys[0], 2);
+        let node2 = prepare_replicated_node(keys[1], 2);
+
+        manually_establish_connection(&node1.swarm, &node2.swarm).await;
+        wait_for_msgs([&node1, &node2]).await;
+        assert_no_more_msg([&node1, &node2]).await;
+
+        let vnode: VirtualNode = "replicated".to_string().try_into().unwrap();
+        let vid = vnode.did;
+        <Swarm as ChordStorageInterface<1>>::storage_store(&node1.swarm, vnode.clone()).await?;
+        wait_for_msgs([&node1, &node2]).await;
+        assert_no_more_msg([&node1, &node2]).await;
+
+        let (primary, holder) = if vid.in_range(node1.did(), node1.did(), node2.did()) {
+            (&node2, &node1)
+        } else {
+            (&node1, &node2)
+        };
+
+        // The replica is kept apart from the storage of its successor.
+        assert_eq!(primary.dht().storage.count().await?, 1);
+        assert_eq!(primary.dht().replica_storage.count().await?, 0);
+        assert_eq!(holder.dht().storage.count().await?, 0);
+        assert_eq!(holder.dht().replica_storage.count().await?, 1);
+
+        // While the responsible node is alive, lookups are still sent to it.
+        let act = <PeerRing as ChordStorage<_, 1>>::vnode_lookup(&holder.dht(), vid).await?;
+        assert_eq!(
+            act,
+            PeerRingAction::RemoteAction(primary.did(), PeerRingRemoteAction::FindVNode(vid))
+        );
+
+        // Once it's gone, the successor answers lookups with the replica.
+        holder.dht().remove(primary.did())?;
+        let act = <PeerRing as ChordStorage<_, 1>>::vnode_lookup(&holder.dht(), vid).await?;
+        assert_eq!(act, PeerRingAction::SomeVNode(vnode.clone()));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reject_replica_from_non_predecessor() -> Result<()> {
+        let keys = gen_ordered_keys(3);
+        let node1 = prepare_replicated_node(keys[0], 2);
+        let node2 = prepare_replicated_node(keys[1], 2);
+        let node3 = prepare_replicated_node(keys[2], 2);
+
+        manually_establish_connection(&node1.swarm, &node2.swarm).await;
+        manually_establish_connection(&node2.swarm, &node3.swarm).await;
+        wait_for_msgs([&node1, &node2, &node3]).await;
+        assert_no_more_msg([&node1, &node2, &node3]).await;
+        assert_eq!(*node2.dht().lock_predecessor()?, Some(node1.did()));
+
+        // node3 is the successor of node2, it's not allowed to overwrite the replicas of node2.
+        let vnode: VirtualNode = "forged".to_string().try_into().unwrap();
+        assert!(matches!(
+            node2.dht().store_replica(node3.did(), vnode.clone()).await,
+            Err(Error::ReplicaRejected(vid, from)) if vid == vnode.did && from == node3.did()
+        ));
+        assert_eq!(node2.dht().replica_storage.count().await?, 0);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,148 @@
+//! Structured view of the handshake payloads exchanged out of band.
+
+use std::collections::HashMap;
+
+use crate::dht::Did;
+use crate::error::Error;
+use crate::error::Result;
+use crate::message::Decoder;
+use crate::message::Encoded;
+use crate::message::Encoder;
+use crate::message::Message;
+use crate::message::MessagePayload;
+use crate::message::MessageVerificationExt;
+
+/// Information of a verified offer or answer, created by [crate::swarm::Swarm::create_offer]
+/// or [crate::swarm::Swarm::answer_offer]. It helps applications to show what is going to be
+/// connected before accepting it.
+#[derive(Debug, Clone)]
+pub struct HandshakeInfo {
+    payload: MessagePayload,
+    sdp_type: String,
+    candidate_count: usize,
+    metadata: HashMap<String, String>,
+}
+
+impl HandshakeInfo {
+    /// Verify a handshake payload and parse its information.
+    pub fn from_payload(payload: MessagePayload) -> Result<Self> {
+        if !payload.verify() {
+            return Err(Error::VerifyMessageSignatureFailed(
+                payload.transaction.signer(),
+            ));
+        }
+        Self::from_verified(payload)
+    }
+
+    /// Parse the information of a handshake payload verified by the caller,
+    /// such as one checked by the clock of swarm.
+    pub(crate) fn from_verified(payload: MessagePayload) -> Result<Self> {
+        let (sdp, metadata) = match payload.transaction.data()? {
+            Message::ConnectNodeSend(msg) => (msg.sdp, msg.metadata),
+            Message::ConnectNodeReport(msg) => (msg.sdp, msg.metadata),
+            _ => {
+                return Err(Error::InvalidMessage(
+                    "Should be ConnectNodeSend or ConnectNodeReport".to_string(),
+                ))
+            }
+        };
+
+        let (sdp_type, candidates) = parse_sdp(&sdp)?;
+        let candidate_count = candidates.len();
+
+        Ok(Self {
+            payload,
+            sdp_type,
+            candidate_count,
+            metadata,
+        })
+    }
+
+    /// Did of the node creating the handshake.
+    pub fn did(&self) -> Did {
+        self.payload.transaction.signer()
+    }
+
+    /// Type of the session description, `offer` or `answer`.
+    pub fn sdp_type(&self) -> &str {
+        &self.sdp_type
+    }
+
+    /// Count of ICE candidates gathered in the session description.
+    pub fn candidate_count(&self) -> usize {
+        self.candidate_count
+    }
+
+    /// Time in ms when the handshake was signed.
+    pub fn created_at(&self) -> u128 {
+        self.payload.transaction.verification.ts_ms
+    }
+
+    /// Application level metadata of the node creating the handshake.
+    pub fn metadata(&self) -> &HashMap<String, String> {
+        &self.metadata
+    }
+
+    /// The verified handshake payload.
+    pub fn payload(&self) -> &MessagePayload {
+        &self.payload
+    }
+}
+
+impl Encoder for HandshakeInfo {
+    fn encode(&self) -> Result<Encoded> {
+        self.payload.encode()
+    }
+}
+
+impl Decoder for HandshakeInfo {
+    fn from_encoded(encoded: &Encoded) -> Result<Self> {
+        Self::from_payload(MessagePayload::from_encoded(encoded)?)
+    }
+}
+
+impl From<HandshakeInfo> for MessagePayload {
+    fn from(info: HandshakeInfo) -> Self {
+        info.payload
+    }
+}
+
+/// Parse the type and ICE candidates of a serialized session description,
+/// which is like `{"type": "offer", "sdp": "v=0..."}`.
+pub(crate) fn parse_sdp(sdp: &str) -> Result<(String, Vec<String>)> {
+    let desc: serde_json::Value = serde_json::from_str(sdp).map_err(Error::Deserialize)?;
+    let sdp_type = desc["type"].as_str().unwrap_or("unknown").to_string();
+    let candidates = desc["sdp"]
+        .as_str()
+        .map(|sdp| {
+            sdp.lines()
+                .filter_map(|l| l.strip_prefix("a="))
+                .filter(|l| l.starts_with("candidate:"))
+                .map(|l| l.to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok((sdp_type, candidates))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_sdp() {
+        let sdp = serde_json::json!({
+            "type": "offer",
+            "sdp": "v=0\r\na=candidate:1 1 udp 2130706431 192.168.1.2 50000 typ host\r\na=candidate:2 1 udp 1694498815 1.2.3.4 50001 typ srflx\r\na=end-of-candidates\r\n",
+        })
+        .to_string();
+        let (sdp_type, candidates) = parse_sdp(&sdp).unwrap();
+        assert_eq!(sdp_type, "offer");
+        assert_eq!(candidates, vec![
+            "candidate:1 1 udp 2130706431 192.168.1.2 50000 typ host",
+            "candidate:2 1 udp 1694498815 1.2.3.4 50001 typ srflx",
+        ]);
+
+        assert!(parse_sdp("not json").is_err());
+    }
+}
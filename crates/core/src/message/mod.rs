@@ -4,6 +4,10 @@ pub use encoder::Decoder;
 pub use encoder::Encoded;
 pub use encoder::Encoder;
 
+mod handshake;
+pub(crate) use handshake::parse_sdp;
+pub use handshake::HandshakeInfo;
+
 mod payload;
 pub use payload::decode_gzip_data;
 pub use payload::encode_data_gzip;
@@ -1,5 +1,6 @@
 #![warn(missing_docs)]
 
+use std::borrow::Cow;
 use std::io::Write;
 use std::sync::Arc;
 
@@ -19,6 +20,9 @@ use super::encoder::Encoder;
 use super::protocols::MessageRelay;
 use super::protocols::MessageVerification;
 use super::protocols::MessageVerificationExt;
+use crate::consts::COMPACT_PAYLOAD_TAG;
+use crate::consts::DEFAULT_RELAY_TTL;
+use crate::consts::PAYLOAD_VERSION;
 use crate::dht::Chord;
 use crate::dht::Did;
 use crate::dht::PeerRing;
@@ -26,6 +30,7 @@ use crate::dht::PeerRingAction;
 use crate::ecc::keccak256;
 use crate::error::Error;
 use crate::error::Result;
+use crate::metrics;
 use crate::session::SessionSk;
 
 /// Compresses the given data byte slice using the gzip algorithm with the specified compression level.
@@ -108,6 +113,36 @@ pub struct MessagePayload {
     pub verification: MessageVerification,
 }
 
+/// Fields of [MessagePayload] added after the first release, appended to its bincode.
+/// `bincode::deserialize` of earlier versions ignores trailing bytes, so they still decode the
+/// payload, while the payloads sent by them have no extension.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+struct PayloadExtension {
+    /// Version of the extension, see [PAYLOAD_VERSION].
+    version: u8,
+    /// Remaining count of hops, see [MessageRelay::ttl].
+    ttl: u8,
+}
+
+/// Compact form of [MessagePayload], in which a [Did] takes 20 bytes instead of 50.
+/// It's prefixed by [COMPACT_PAYLOAD_TAG] and [PAYLOAD_VERSION] on wire.
+#[derive(Deserialize, Serialize)]
+struct CompactPayload<'a> {
+    #[serde(with = "crate::dht::did::compact")]
+    destination: Did,
+    tx_id: uuid::Uuid,
+    data: Cow<'a, [u8]>,
+    tx_verification: MessageVerification,
+    #[serde(with = "crate::dht::did::compact::vec")]
+    path: Vec<Did>,
+    #[serde(with = "crate::dht::did::compact")]
+    next_hop: Did,
+    #[serde(with = "crate::dht::did::compact")]
+    relay_destination: Did,
+    ttl: u8,
+    verification: MessageVerification,
+}
+
 impl Transaction {
     /// Wrap data. Will serialize by [bincode::serialize]
     /// then sign [MessageVerification] by session_sk.
@@ -180,15 +215,73 @@ impl MessagePayload {
     }
 
     /// Deserializes a `MessagePayload` instance from the given binary data.
+    /// The ttl of a payload without extension, which is sent by an earlier version,
+    /// is [DEFAULT_RELAY_TTL].
     pub fn from_bincode(data: &[u8]) -> Result<Self> {
-        bincode::deserialize(data).map_err(Error::BincodeDeserialize)
+        Self::from_bincode_versioned(data).map(|(payload, _)| payload)
+    }
+
+    /// Like [MessagePayload::from_bincode], also returns the version of the extension,
+    /// which is 0 if the payload is sent by a version without extension.
+    pub fn from_bincode_versioned(data: &[u8]) -> Result<(Self, u8)> {
+        if let [COMPACT_PAYLOAD_TAG, version, rest @ ..] = data {
+            let compact: CompactPayload =
+                bincode::deserialize(rest).map_err(Error::BincodeDeserialize)?;
+            let payload = Self {
+                transaction: Transaction {
+                    destination: compact.destination,
+                    tx_id: compact.tx_id,
+                    data: compact.data.into_owned(),
+                    verification: compact.tx_verification,
+                },
+                relay: MessageRelay::new(compact.path, compact.next_hop, compact.relay_destination)
+                    .with_ttl(compact.ttl),
+                verification: compact.verification,
+            };
+            return Ok((payload, *version));
+        }
+
+        let mut rest = data;
+        let mut payload: Self =
+            bincode::deserialize_from(&mut rest).map_err(Error::BincodeDeserialize)?;
+        let mut version = 0;
+        if let Ok(ext) = bincode::deserialize::<PayloadExtension>(rest) {
+            payload.relay.ttl = ext.ttl;
+            version = ext.version;
+        }
+        Ok((payload, version))
     }
 
     /// Serializes the `MessagePayload` instance into binary data.
+    /// The fields known by every version come first, followed by a [PayloadExtension].
     pub fn to_bincode(&self) -> Result<Bytes> {
-        bincode::serialize(self)
-            .map(Bytes::from)
-            .map_err(Error::BincodeSerialize)
+        let mut data = bincode::serialize(self).map_err(Error::BincodeSerialize)?;
+        let ext = PayloadExtension {
+            version: PAYLOAD_VERSION,
+            ttl: self.relay.ttl,
+        };
+        bincode::serialize_into(&mut data, &ext).map_err(Error::BincodeSerialize)?;
+        Ok(data.into())
+    }
+
+    /// Serializes the `MessagePayload` instance into the compact form, which is smaller
+    /// but only decodable by the peers of [PAYLOAD_VERSION] or later.
+    /// [MessagePayload::from_bincode] detects the form by [COMPACT_PAYLOAD_TAG].
+    pub fn to_compact_bincode(&self) -> Result<Bytes> {
+        let compact = CompactPayload {
+            destination: self.transaction.destination,
+            tx_id: self.transaction.tx_id,
+            data: Cow::Borrowed(&self.transaction.data),
+            tx_verification: self.transaction.verification.clone(),
+            path: self.relay.path.clone(),
+            next_hop: self.relay.next_hop,
+            relay_destination: self.relay.destination,
+            ttl: self.relay.ttl,
+            verification: self.verification.clone(),
+        };
+        let mut data = vec![COMPACT_PAYLOAD_TAG, PAYLOAD_VERSION];
+        bincode::serialize_into(&mut data, &compact).map_err(Error::BincodeSerialize)?;
+        Ok(data.into())
     }
 }
 
@@ -243,6 +336,11 @@ pub trait PayloadSender {
     /// Used to check if destination is already connected when `infer_next_hop`
     fn is_connected(&self, did: Did) -> bool;
 
+    /// Count of hops the messages sent by this node can be relayed.
+    fn relay_ttl(&self) -> u8 {
+        DEFAULT_RELAY_TTL
+    }
+
     /// Send a message payload to a specified DID.
     async fn do_send_payload(&self, did: Did, payload: MessagePayload) -> Result<()>;
 
@@ -278,7 +376,8 @@ pub trait PayloadSender {
     where
         T: Serialize + Send,
     {
-        let payload = MessagePayload::new_send(msg, self.session_sk(), next_hop, destination)?;
+        let mut payload = MessagePayload::new_send(msg, self.session_sk(), next_hop, destination)?;
+        payload.relay.ttl = self.relay_ttl();
         let tx_id = payload.transaction.tx_id;
         self.send_payload(payload).await?;
         Ok(tx_id)
@@ -301,7 +400,10 @@ pub trait PayloadSender {
     /// Send a report message to a specified destination.
     async fn send_report_message<T>(&self, payload: &MessagePayload, msg: T) -> Result<()>
     where T: Serialize + Send {
-        let relay = payload.relay.report(self.dht().did)?;
+        let relay = payload
+            .relay
+            .report(self.dht().did)?
+            .with_ttl(self.relay_ttl());
 
         let transaction = Transaction::new(
             relay.destination,
@@ -325,7 +427,9 @@ pub trait PayloadSender {
     async fn forward_payload(&self, payload: &MessagePayload, next_hop: Option<Did>) -> Result<()> {
         let next_hop = self.infer_next_hop(payload.relay.destination, next_hop)?;
         let relay = payload.relay.forward(self.dht().did, next_hop)?;
-        self.forward_by_relay(payload, relay).await
+        self.forward_by_relay(payload, relay).await?;
+        metrics::MESSAGES_RELAYED.inc();
+        Ok(())
     }
 
     /// Reset the destination to a secp DID.
@@ -381,6 +485,68 @@ pub mod test {
         assert!(payload.verify());
     }
 
+    #[test]
+    fn test_verify_expiry_boundary() {
+        use std::time::Duration;
+        use std::time::UNIX_EPOCH;
+
+        use crate::clock::MockClock;
+
+        let payload = new_test_payload(SecretKey::random().address().into());
+        let verification = payload.verification();
+        let expires_at = verification.ts_ms + verification.ttl_ms as u128;
+
+        let clock = MockClock::new(UNIX_EPOCH + Duration::from_millis(expires_at as u64));
+        assert!(!payload.is_expired_with(&clock));
+        assert!(payload.verify_with(&clock));
+
+        clock.advance(Duration::from_millis(1));
+        assert!(payload.is_expired_with(&clock));
+        assert!(!payload.verify_with(&clock));
+    }
+
+    #[test]
+    fn test_payload_extension() {
+        let payload = new_test_payload(SecretKey::random().address().into());
+        let mut relayed = payload.clone();
+        relayed.relay.ttl = 3;
+
+        // The extension round trips.
+        let data = relayed.to_bincode().unwrap();
+        assert_eq!(MessagePayload::from_bincode(&data).unwrap().relay.ttl, 3);
+
+        // Earlier versions decode the fields they know and ignore the extension.
+        let legacy: MessagePayload = bincode::deserialize(&data).unwrap();
+        assert_eq!(legacy.transaction, payload.transaction);
+        assert_eq!(legacy.relay.path, payload.relay.path);
+
+        // Payloads of earlier versions have no extension.
+        let legacy_data = bincode::serialize(&relayed).unwrap();
+        assert_eq!(legacy_data.len() + 2, data.len());
+        let decoded = MessagePayload::from_bincode(&legacy_data).unwrap();
+        assert_eq!(decoded.relay.ttl, DEFAULT_RELAY_TTL);
+        assert_eq!(decoded.transaction, payload.transaction);
+    }
+
+    #[test]
+    fn test_compact_payload() {
+        let mut payload = new_test_payload(SecretKey::random().address().into());
+        payload.relay.ttl = 3;
+
+        let legacy = payload.to_bincode().unwrap();
+        let compact = payload.to_compact_bincode().unwrap();
+        assert_eq!(legacy[0], 42);
+        assert_eq!(compact[..2], [COMPACT_PAYLOAD_TAG, PAYLOAD_VERSION]);
+        // Each of the four Dids is 30 bytes smaller, while the tag takes one more byte than
+        // the extension.
+        assert_eq!(legacy.len() - compact.len(), 30 * 4 - 1);
+
+        let (decoded, version) = MessagePayload::from_bincode_versioned(&compact).unwrap();
+        assert_eq!(decoded, payload);
+        assert_eq!(version, PAYLOAD_VERSION);
+        assert!(decoded.verify());
+    }
+
     #[test]
     fn test_message_payload_from_auto() {
         let next_hop = SecretKey::random().address().into();
@@ -4,6 +4,7 @@ use itertools::izip;
 use serde::Deserialize;
 use serde::Serialize;
 
+use crate::consts::DEFAULT_RELAY_TTL;
 use crate::dht::Did;
 use crate::error::Error;
 use crate::error::Result;
@@ -26,20 +27,40 @@ pub struct MessageRelay {
     /// The destination of the message.
     /// It may help the handler to find out `next_hop` in some situations.
     pub destination: Did,
+
+    /// Remaining count of hops the message can be forwarded. It's decreased by each relay,
+    /// and a message with zero ttl is dropped unless it reaches the destination, so that a
+    /// routing loop cannot keep it alive forever.
+    /// It's not a part of the fields known by earlier versions, but carried by the extension
+    /// of [MessagePayload](crate::message::MessagePayload) on wire.
+    #[serde(skip, default = "default_ttl")]
+    pub ttl: u8,
+}
+
+fn default_ttl() -> u8 {
+    DEFAULT_RELAY_TTL
 }
 
 impl MessageRelay {
-    /// Create a new `MessageRelay`.
+    /// Create a new `MessageRelay` with [DEFAULT_RELAY_TTL].
     pub fn new(path: Vec<Did>, next_hop: Did, destination: Did) -> Self {
         Self {
             path,
             next_hop,
             destination,
+            ttl: DEFAULT_RELAY_TTL,
         }
     }
 
+    /// Set the count of hops the message can be forwarded.
+    pub fn with_ttl(mut self, ttl: u8) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
     /// Validate relay, then create a new `MessageRelay` that have `current` did in the end of path.
     /// The new relay will use `next_hop` as `next_hop` and `self.destination` as `destination`.
+    /// Returns [Error::RelayTtlExpired] if the message cannot be forwarded any more.
     pub fn forward(&self, current: Did, next_hop: Did) -> Result<Self> {
         self.validate(current)?;
 
@@ -47,6 +68,10 @@ impl MessageRelay {
             return Err(Error::InvalidNextHop);
         }
 
+        if self.ttl == 0 {
+            return Err(Error::RelayTtlExpired(self.destination));
+        }
+
         let mut path = self.path.clone();
         path.push(current);
 
@@ -54,12 +79,14 @@ impl MessageRelay {
             path,
             next_hop,
             destination: self.destination,
+            ttl: self.ttl - 1,
         })
     }
 
     /// Validate relay, then create a new `MessageRelay` that used to report the message.
     /// The new relay will use `self.path[self.path.len() - 1]` as `next_hop` and `self.sender()` as `destination`.
-    /// In the new relay, the path will be cleared and only have `current` did.
+    /// In the new relay, the path will be cleared and only have `current` did, and the ttl
+    /// is reset to [DEFAULT_RELAY_TTL].
     pub fn report(&self, current: Did) -> Result<Self> {
         self.validate(current)?;
 
@@ -71,6 +98,7 @@ impl MessageRelay {
             path: vec![current],
             next_hop: self.path[self.path.len() - 1],
             destination: self.origin_sender(),
+            ttl: DEFAULT_RELAY_TTL,
         })
     }
 
@@ -168,6 +196,23 @@ where T: PartialEq + std::fmt::Debug {
 mod test {
     use super::*;
 
+    #[test]
+    fn test_relay_ttl() {
+        let [a, b, c, d] = [1u32, 2, 3, 4].map(Did::from);
+
+        let relay = MessageRelay::new(vec![a], b, d).with_ttl(1);
+        let relay = relay.forward(b, c).unwrap();
+        assert_eq!(relay.ttl, 0);
+        assert_eq!(relay.path, vec![a, b]);
+        assert!(matches!(
+            relay.forward(c, d),
+            Err(Error::RelayTtlExpired(did)) if did == d
+        ));
+
+        // The report starts a new journey.
+        assert_eq!(relay.report(c).unwrap().ttl, DEFAULT_RELAY_TTL);
+    }
+
     #[test]
     #[rustfmt::skip]
     fn test_has_infinite_loop() {
@@ -5,10 +5,14 @@
 use serde::Deserialize;
 use serde::Serialize;
 
+use crate::clock::Clock;
+use crate::clock::SystemClock;
 use crate::consts::DEFAULT_TTL_MS;
 use crate::consts::MAX_TTL_MS;
 use crate::consts::TS_OFFSET_TOLERANCE_MS;
 use crate::dht::Did;
+use crate::ecc::signers;
+use crate::ecc::PublicKey;
 use crate::error::Result;
 use crate::session::Session;
 use crate::session::SessionSk;
@@ -55,15 +59,26 @@ impl MessageVerification {
 
     /// Verify a MessageVerification
     pub fn verify(&self, data: &[u8]) -> bool {
+        self.verify_with(data, &SystemClock)
+    }
+
+    /// Verify a MessageVerification, checking session expiry at the time of `clock`.
+    pub fn verify_with(&self, data: &[u8], clock: &dyn Clock) -> bool {
         let msg = pack_msg(data, self.ts_ms, self.ttl_ms);
 
         self.session
-            .verify(&msg, &self.sig)
+            .verify_with(&msg, &self.sig, clock)
             .map_err(|e| {
                 tracing::warn!("MessageVerification verify failed: {:?}", e);
             })
             .is_ok()
     }
+
+    /// Recover the public key of the [SessionSk] that signed the data.
+    pub fn session_pubkey(&self, data: &[u8]) -> Result<PublicKey<33>> {
+        let msg = pack_msg(data, self.ts_ms, self.ttl_ms);
+        signers::secp256k1::recover(&msg, &self.sig)
+    }
 }
 
 /// This trait helps a struct with `MessageVerification` field to `verify` itself.
@@ -77,11 +92,16 @@ pub trait MessageVerificationExt {
 
     /// Checks whether the message is expired.
     fn is_expired(&self) -> bool {
+        self.is_expired_with(&SystemClock)
+    }
+
+    /// Checks whether the message is expired at the time of `clock`.
+    fn is_expired_with(&self, clock: &dyn Clock) -> bool {
         if self.verification().ttl_ms > MAX_TTL_MS {
             return false;
         }
 
-        let now = get_epoch_ms();
+        let now = clock.now_ms();
 
         if self.verification().ts_ms - TS_OFFSET_TOLERANCE_MS > now {
             return false;
@@ -92,7 +112,13 @@ pub trait MessageVerificationExt {
 
     /// Verifies that the message is not expired and that the signature is valid.
     fn verify(&self) -> bool {
-        if self.is_expired() {
+        self.verify_with(&SystemClock)
+    }
+
+    /// Verifies that the message is not expired at the time of `clock`
+    /// and that the signature is valid.
+    fn verify_with(&self, clock: &dyn Clock) -> bool {
+        if self.is_expired_with(clock) {
             tracing::warn!("message expired");
             return false;
         }
@@ -102,11 +128,17 @@ pub trait MessageVerificationExt {
             return false;
         };
 
-        self.verification().verify(&data)
+        self.verification().verify_with(&data, clock)
     }
 
     /// Get signer did from verification.
     fn signer(&self) -> Did {
         self.verification().session.account_did()
     }
+
+    /// Get public key of the signer's session, which can be used to encrypt data to the signer.
+    fn session_pubkey(&self) -> Result<PublicKey<33>> {
+        let data = self.verification_data()?;
+        self.verification().session_pubkey(&data)
+    }
 }
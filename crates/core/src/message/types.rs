@@ -3,7 +3,12 @@
 //! Most of the messages follow the Ping/Pong pattern, where there is a one-to-one correspondence between them,
 //! such as xxxSend and xxxReport messages.
 
+use std::collections::HashMap;
+
+use serde::de::SeqAccess;
+use serde::de::Visitor;
 use serde::Deserialize;
+use serde::Deserializer;
 use serde::Serialize;
 
 use crate::chunk::Chunk;
@@ -20,20 +25,164 @@ pub trait Then {
 }
 
 /// MessageType use to ask for connection, send to remote with transport_uuid and handshake_info.
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Serialize, Clone)]
 pub struct ConnectNodeSend {
     /// sdp offer of webrtc
     pub sdp: String,
     /// The network_id is used to distinguish different networks.
     /// Use 1 for main network.
     pub network_id: u32,
+    /// Application level metadata of sender, such as the services it provides.
+    /// It's a trailing field, see [next_trailing_field].
+    pub metadata: HashMap<String, String>,
 }
 
 /// MessageType report to origin with own transport_uuid and handshake_info.
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Serialize, Clone)]
 pub struct ConnectNodeReport {
     /// sdp answer of webrtc
     pub sdp: String,
+    /// Application level metadata of sender, such as the services it provides.
+    /// It's a trailing field, see [next_trailing_field].
+    pub metadata: HashMap<String, String>,
+}
+
+/// Get the next field of a message in a binary format, which is added by a later version
+/// as the last field of the message.
+///
+/// bincode ignores `#[serde(default)]`, so the message sent by an earlier version, whose bytes
+/// end before the field, fails to decode unless the field is defaulted here. In turn, the
+/// earlier versions ignore the trailing bytes of the field, since `bincode::deserialize`
+/// allows trailing bytes.
+pub(crate) fn next_trailing_field<'de, A, T>(seq: &mut A) -> T
+where
+    A: SeqAccess<'de>,
+    T: Deserialize<'de> + Default,
+{
+    seq.next_element().ok().flatten().unwrap_or_default()
+}
+
+impl<'de> Deserialize<'de> for ConnectNodeSend {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where D: Deserializer<'de> {
+        #[derive(Deserialize)]
+        struct Fields {
+            sdp: String,
+            network_id: u32,
+            #[serde(default)]
+            metadata: HashMap<String, String>,
+        }
+
+        struct FieldsVisitor;
+        impl<'de> Visitor<'de> for FieldsVisitor {
+            type Value = Fields;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("struct ConnectNodeSend")
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> std::result::Result<Fields, A::Error>
+            where A: SeqAccess<'de> {
+                let sdp = seq
+                    .next_element()?
+                    .ok_or_else(|| serde::de::Error::invalid_length(0, &self))?;
+                let network_id = seq
+                    .next_element()?
+                    .ok_or_else(|| serde::de::Error::invalid_length(1, &self))?;
+                let metadata = next_trailing_field(&mut seq);
+                Ok(Fields {
+                    sdp,
+                    network_id,
+                    metadata,
+                })
+            }
+        }
+
+        let fields = if deserializer.is_human_readable() {
+            Fields::deserialize(deserializer)?
+        } else {
+            deserializer.deserialize_struct(
+                "ConnectNodeSend",
+                &["sdp", "network_id", "metadata"],
+                FieldsVisitor,
+            )?
+        };
+        Ok(Self {
+            sdp: fields.sdp,
+            network_id: fields.network_id,
+            metadata: fields.metadata,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for ConnectNodeReport {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where D: Deserializer<'de> {
+        #[derive(Deserialize)]
+        struct Fields {
+            sdp: String,
+            #[serde(default)]
+            metadata: HashMap<String, String>,
+        }
+
+        struct FieldsVisitor;
+        impl<'de> Visitor<'de> for FieldsVisitor {
+            type Value = Fields;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("struct ConnectNodeReport")
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> std::result::Result<Fields, A::Error>
+            where A: SeqAccess<'de> {
+                let sdp = seq
+                    .next_element()?
+                    .ok_or_else(|| serde::de::Error::invalid_length(0, &self))?;
+                let metadata = next_trailing_field(&mut seq);
+                Ok(Fields { sdp, metadata })
+            }
+        }
+
+        let fields = if deserializer.is_human_readable() {
+            Fields::deserialize(deserializer)?
+        } else {
+            deserializer.deserialize_struct(
+                "ConnectNodeReport",
+                &["sdp", "metadata"],
+                FieldsVisitor,
+            )?
+        };
+        Ok(Self {
+            sdp: fields.sdp,
+            metadata: fields.metadata,
+        })
+    }
+}
+
+/// MessageType report to origin that the offer is rejected, so that origin can try other nodes.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ConnectNodeReject {
+    /// Why the offer is rejected.
+    pub reason: ConnectRejectReason,
+}
+
+/// Reason of [ConnectNodeReject].
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConnectRejectReason {
+    /// Remote node has reached its connection limit.
+    TooManyConnections(usize),
+    /// Remote node denies connections from the origin, see [crate::swarm::ConnectionGater].
+    Denied,
+}
+
+impl std::fmt::Display for ConnectRejectReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::TooManyConnections(max) => write!(f, "too many connections, limit is {max}"),
+            Self::Denied => write!(f, "connection denied"),
+        }
+    }
 }
 
 /// MessageType use to find successor in a chord ring.
@@ -59,6 +208,26 @@ pub struct FindSuccessorReport {
     pub handler: FindSuccessorReportHandler,
 }
 
+/// MessageType of asking a node for the next hop towards the successor of a did.
+/// Unlike [FindSuccessorSend], it's answered by the receiver instead of being forwarded,
+/// so that the origin controls each hop of an iterative lookup.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct FindNextHopSend {
+    /// did of target
+    pub did: Did,
+}
+
+/// MessageType use to report origin node the next hop of a lookup.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct FindNextHopReport {
+    /// did of target
+    pub did: Did,
+    /// successor of target if found is true, else the closest predecessor known by the reporter
+    pub next_hop: Did,
+    /// whether next_hop is the successor of target
+    pub found: bool,
+}
+
 /// MessageType use notify the successor about the predecessor inferred by current node.
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct NotifyPredecessorSend {
@@ -148,6 +317,22 @@ pub struct FoundVNode {
     pub data: Vec<VirtualNode>,
 }
 
+/// MessageType use to search virtual nodes in batch.
+/// All the vids are expected to be stored on the receiver.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SearchVNodeBatch {
+    /// The virtual ids of searching targets
+    pub vids: Vec<Did>,
+}
+
+/// MessageType use to operate virtual nodes in batch.
+/// All the operations are expected to be applied on the receiver.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct OperateVNodeBatch {
+    /// Operations of virtual nodes
+    pub ops: Vec<VNodeOperation>,
+}
+
 /// MessageType after `FindSuccessorSend` and syncing data.
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct SyncVNodeWithSuccessor {
@@ -155,10 +340,78 @@ pub struct SyncVNodeWithSuccessor {
     pub data: Vec<VirtualNode>,
 }
 
+/// MessageType of keeping replicas of virtual nodes, sent by the node responsible for them.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ReplicateVNode {
+    /// Data of virtual nodes to replicate.
+    pub data: Vec<VirtualNode>,
+}
+
+/// MessageType to notify a peer that the sender is leaving.
+/// The receiver should remove the sender from DHT and close the connection.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct LeaveDht {
+    /// did of the leaving node
+    pub did: Did,
+}
+
+/// MessageType to notify a peer that the sender is leaving, handing off what the peer should
+/// take over. It's sent instead of [LeaveDht] to the peers known to decode it, see
+/// [crate::consts::PAYLOAD_VERSION].
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct LeaveDhtHandoff {
+    /// did of the leaving node
+    pub did: Did,
+    /// Successor of the leaving node, told to its predecessor to fill the gap on the ring.
+    pub successor: Option<Did>,
+    /// Virtual nodes stored by the leaving node, handed off to its successor.
+    pub data: Vec<VirtualNode>,
+}
+
+/// MessageType of asking the sender of an interrupted chunked message, after reconnecting,
+/// to send the chunks not received yet.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ChunkResumeRequest {
+    /// id of the chunked message
+    pub id: uuid::Uuid,
+    /// count of chunks of the message
+    pub total: usize,
+    /// indices of received chunks
+    pub received: Vec<usize>,
+}
+
+/// Response of ChunkResumeRequest
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ChunkResumeAck {
+    /// id of the chunked message
+    pub id: uuid::Uuid,
+    /// false if the message is no longer kept by sender, it cannot be resumed
+    pub found: bool,
+    /// count of chunks sent again
+    pub resent: usize,
+}
+
 /// MessageType use to customize message, will be handle by `custom_message` method.
 #[derive(Deserialize, Serialize, Clone)]
 pub struct CustomMessage(pub Vec<u8>);
 
+/// MessageType of a [CustomMessage] which the destination acknowledges with [Ack].
+/// It's relayed like [CustomMessage], see [crate::swarm::Swarm::send_reliable].
+#[derive(Deserialize, Serialize, Clone)]
+pub struct ReliableMessage(pub Vec<u8>);
+
+/// Acknowledgement of a [ReliableMessage], reported back by its destination.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Ack {
+    /// tx_id of the acknowledged message
+    pub tx_id: uuid::Uuid,
+}
+
+/// MessageType of a [CustomMessage] encrypted to the session key of its destination.
+/// It's relayed like [CustomMessage] and can only be decrypted by the destination.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct EncryptedMessage(pub Vec<u8>);
+
 /// MessageType enum Report contain FindSuccessorSend.
 #[derive(Debug, Deserialize, Serialize, Clone)]
 #[non_exhaustive]
@@ -213,6 +466,32 @@ pub enum Message {
     QueryForTopoInfoReport(QueryForTopoInfoReport),
     /// A chunk that can be deserialized to a payload.
     Chunk(Chunk),
+    /// Custom messages encrypted to the destination.
+    EncryptedMessage(EncryptedMessage),
+    /// Remote message of notifying a node is leaving.
+    LeaveDht(LeaveDht),
+    /// Response of ConnectNodeSend when the offer is rejected.
+    ConnectNodeReject(ConnectNodeReject),
+    /// Remote message of search virtual nodes in batch.
+    SearchVNodeBatch(SearchVNodeBatch),
+    /// Remote message of operations of virtual nodes in batch.
+    OperateVNodeBatch(OperateVNodeBatch),
+    /// Direct message of resuming an interrupted chunked message.
+    ChunkResumeRequest(ChunkResumeRequest),
+    /// Response of ChunkResumeRequest
+    ChunkResumeAck(ChunkResumeAck),
+    /// Custom messages acknowledged by the destination.
+    ReliableMessage(ReliableMessage),
+    /// Response of ReliableMessage
+    Ack(Ack),
+    /// Remote message of keeping replicas of virtual nodes.
+    ReplicateVNode(ReplicateVNode),
+    /// Remote message of asking the next hop of an iterative lookup.
+    FindNextHopSend(FindNextHopSend),
+    /// Response of FindNextHopSend
+    FindNextHopReport(FindNextHopReport),
+    /// Direct message of leaving the ring, with the handoff to the receiver.
+    LeaveDhtHandoff(LeaveDhtHandoff),
 }
 
 impl std::fmt::Display for Message {
@@ -226,6 +505,42 @@ impl Message {
     pub fn custom(msg: &[u8]) -> Result<Message> {
         Ok(Message::CustomMessage(CustomMessage(msg.to_vec())))
     }
+
+    /// Priority of sending the message. Messages maintaining connections, transfers and DHT topology
+    /// are [Priority::Control], others are [Priority::App].
+    pub fn priority(&self) -> Priority {
+        match self {
+            Message::ConnectNodeSend(_)
+            | Message::ConnectNodeReport(_)
+            | Message::ConnectNodeReject(_)
+            | Message::FindSuccessorSend(_)
+            | Message::FindSuccessorReport(_)
+            | Message::FindNextHopSend(_)
+            | Message::FindNextHopReport(_)
+            | Message::NotifyPredecessorSend(_)
+            | Message::NotifyPredecessorReport(_)
+            | Message::QueryForTopoInfoSend(_)
+            | Message::QueryForTopoInfoReport(_)
+            | Message::LeaveDht(_)
+            | Message::LeaveDhtHandoff(_)
+            | Message::ChunkResumeRequest(_)
+            | Message::ChunkResumeAck(_) => Priority::Control,
+            _ => Priority::App,
+        }
+    }
+}
+
+/// Priority class of outgoing messages. Messages of higher priority are sent first
+/// when there are messages waiting for the same connection.
+/// It's derived from the message type rather than carried on the wire, so a message
+/// which cannot be recognized is treated as [Priority::App].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Priority {
+    /// Messages maintaining connections and DHT topology.
+    Control,
+    /// Messages of applications, such as custom messages and storage operations.
+    #[default]
+    App,
 }
 
 impl std::fmt::Debug for CustomMessage {
@@ -235,3 +550,73 @@ impl std::fmt::Debug for CustomMessage {
             .finish()
     }
 }
+
+impl std::fmt::Debug for EncryptedMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EncryptedMessage")
+            .field("size", &self.0.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handshake_metadata_is_trailing() {
+        let metadata = HashMap::from([("service".to_string(), "image".to_string())]);
+
+        // Message::ConnectNodeSend and Message::ConnectNodeReport of earlier versions,
+        // which are variant index followed by the fields without metadata.
+        let legacy_send = bincode::serialize(&(0u32, "offer", 1u32)).unwrap();
+        let legacy_report = bincode::serialize(&(1u32, "answer")).unwrap();
+
+        let msg: Message = bincode::deserialize(&legacy_send).unwrap();
+        let Message::ConnectNodeSend(msg) = msg else {
+            panic!("Should be ConnectNodeSend");
+        };
+        assert_eq!((msg.sdp.as_str(), msg.network_id), ("offer", 1));
+        assert!(msg.metadata.is_empty());
+        let msg: Message = bincode::deserialize(&legacy_report).unwrap();
+        let Message::ConnectNodeReport(msg) = msg else {
+            panic!("Should be ConnectNodeReport");
+        };
+        assert_eq!(msg.sdp, "answer");
+        assert!(msg.metadata.is_empty());
+
+        // Earlier versions decode the fields they know and ignore the metadata.
+        let send = bincode::serialize(&Message::ConnectNodeSend(ConnectNodeSend {
+            sdp: "offer".to_string(),
+            network_id: 1,
+            metadata: metadata.clone(),
+        }))
+        .unwrap();
+        let report = bincode::serialize(&Message::ConnectNodeReport(ConnectNodeReport {
+            sdp: "answer".to_string(),
+            metadata: metadata.clone(),
+        }))
+        .unwrap();
+        let legacy: (u32, String, u32) = bincode::deserialize(&send).unwrap();
+        assert_eq!(legacy, (0, "offer".to_string(), 1));
+        let legacy: (u32, String) = bincode::deserialize(&report).unwrap();
+        assert_eq!(legacy, (1, "answer".to_string()));
+
+        // Metadata round trips in both binary and human readable formats.
+        let msg: Message = bincode::deserialize(&send).unwrap();
+        let Message::ConnectNodeSend(msg) = msg else {
+            panic!("Should be ConnectNodeSend");
+        };
+        assert_eq!(msg.metadata, metadata);
+        let msg: Message = bincode::deserialize(&report).unwrap();
+        let Message::ConnectNodeReport(msg) = msg else {
+            panic!("Should be ConnectNodeReport");
+        };
+        assert_eq!(msg.metadata, metadata);
+        let json = serde_json::to_string(&msg).unwrap();
+        let msg: ConnectNodeReport = serde_json::from_str(&json).unwrap();
+        assert_eq!(msg.metadata, metadata);
+        let msg: ConnectNodeReport = serde_json::from_str(r#"{"sdp":"answer"}"#).unwrap();
+        assert!(msg.metadata.is_empty());
+    }
+}
@@ -0,0 +1,385 @@
+#![warn(missing_docs)]
+//! Lightweight metrics of a node, rendered in Prometheus text format.
+//!
+//! The metrics are process wide statics, so they can be recorded anywhere without
+//! threading a registry through. Use [render] to export all of them.
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::AtomicI64;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+
+/// Count of messages relayed to the next hop.
+pub static MESSAGES_RELAYED: Counter = Counter::new(
+    "rings_messages_relayed_total",
+    "Count of messages relayed to the next hop.",
+);
+
+/// Count of messages addressed to this node and delivered without any connection.
+pub static MESSAGES_LOOPED_BACK: Counter = Counter::new(
+    "rings_messages_looped_back_total",
+    "Count of messages addressed to this node and delivered without any connection.",
+);
+
+/// Count of custom messages handled by backend.
+pub static CUSTOM_MESSAGES_HANDLED: Counter = Counter::new(
+    "rings_custom_messages_handled_total",
+    "Count of custom messages handled by backend.",
+);
+
+/// Count of chunked messages failed to be reassembled.
+pub static CHUNK_REASSEMBLY_FAILURES: Counter = Counter::new(
+    "rings_chunk_reassembly_failures_total",
+    "Count of chunked messages failed to be reassembled.",
+);
+
+/// Count of connections closed for being idle.
+pub static IDLE_CONNECTIONS_EVICTED: Counter = Counter::new(
+    "rings_idle_connections_evicted_total",
+    "Count of connections closed for being idle.",
+);
+
+/// Count of connected peers.
+pub static ACTIVE_CONNECTIONS: Gauge =
+    Gauge::new("rings_active_connections", "Count of connected peers.");
+
+/// Latency in milliseconds of http requests proxied to local services.
+pub static HTTP_PROXY_LATENCY_MS: Histogram = Histogram::new(
+    "rings_http_proxy_latency_ms",
+    "Latency in milliseconds of http requests proxied to local services.",
+);
+
+/// Count of proxied http requests answered from cache.
+pub static HTTP_CACHE_HITS: Counter = Counter::new(
+    "rings_http_cache_hits_total",
+    "Count of proxied http requests answered from cache.",
+);
+
+/// Count of cacheable proxied http requests not found in cache.
+pub static HTTP_CACHE_MISSES: Counter = Counter::new(
+    "rings_http_cache_misses_total",
+    "Count of cacheable proxied http requests not found in cache.",
+);
+
+/// Count of proxied http requests rejected because too many requests are in flight.
+pub static HTTP_REQUESTS_SHED: Counter = Counter::new(
+    "rings_http_requests_shed_total",
+    "Count of proxied http requests rejected because too many requests are in flight.",
+);
+
+/// Count of proxied http requests rejected because their origin exceeds the rate limit.
+pub static HTTP_REQUESTS_RATE_LIMITED: Counter = Counter::new(
+    "rings_http_requests_rate_limited_total",
+    "Count of proxied http requests rejected because their origin exceeds the rate limit.",
+);
+
+/// Count of upstreams of services marked unhealthy.
+pub static UNHEALTHY_UPSTREAMS: Gauge = Gauge::new(
+    "rings_unhealthy_upstreams",
+    "Count of upstreams of services marked unhealthy.",
+);
+
+/// Health of each upstream of services, 1 for healthy and 0 for unhealthy.
+pub static UPSTREAM_HEALTHY: GaugeVec = GaugeVec::new(
+    "rings_upstream_healthy",
+    "Health of each upstream of services, 1 for healthy and 0 for unhealthy.",
+    &["service", "upstream"],
+);
+
+/// Render all metrics in Prometheus text format.
+pub fn render() -> String {
+    let mut out = String::new();
+    MESSAGES_RELAYED.render(&mut out);
+    MESSAGES_LOOPED_BACK.render(&mut out);
+    CUSTOM_MESSAGES_HANDLED.render(&mut out);
+    CHUNK_REASSEMBLY_FAILURES.render(&mut out);
+    IDLE_CONNECTIONS_EVICTED.render(&mut out);
+    ACTIVE_CONNECTIONS.render(&mut out);
+    HTTP_PROXY_LATENCY_MS.render(&mut out);
+    HTTP_CACHE_HITS.render(&mut out);
+    HTTP_CACHE_MISSES.render(&mut out);
+    HTTP_REQUESTS_SHED.render(&mut out);
+    HTTP_REQUESTS_RATE_LIMITED.render(&mut out);
+    UNHEALTHY_UPSTREAMS.render(&mut out);
+    UPSTREAM_HEALTHY.render(&mut out);
+    out
+}
+
+/// A monotonically increasing counter.
+pub struct Counter {
+    name: &'static str,
+    help: &'static str,
+    value: AtomicU64,
+}
+
+impl Counter {
+    /// Create a counter with name and help text.
+    pub const fn new(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            value: AtomicU64::new(0),
+        }
+    }
+
+    /// Increase the counter by one.
+    pub fn inc(&self) {
+        self.inc_by(1)
+    }
+
+    /// Increase the counter by `n`.
+    pub fn inc_by(&self, n: u64) {
+        self.value.fetch_add(n, Ordering::Relaxed);
+    }
+
+    /// Get current value.
+    pub fn get(&self) -> u64 {
+        self.value.load(Ordering::Relaxed)
+    }
+
+    fn render(&self, out: &mut String) {
+        let _ = writeln!(out, "# HELP {} {}", self.name, self.help);
+        let _ = writeln!(out, "# TYPE {} counter", self.name);
+        let _ = writeln!(out, "{} {}", self.name, self.get());
+    }
+}
+
+/// A value that can go up and down.
+pub struct Gauge {
+    name: &'static str,
+    help: &'static str,
+    value: AtomicI64,
+}
+
+impl Gauge {
+    /// Create a gauge with name and help text.
+    pub const fn new(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            value: AtomicI64::new(0),
+        }
+    }
+
+    /// Set current value.
+    pub fn set(&self, value: i64) {
+        self.value.store(value, Ordering::Relaxed);
+    }
+
+    /// Increase current value by one.
+    pub fn inc(&self) {
+        self.value.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Decrease current value by one.
+    pub fn dec(&self) {
+        self.value.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Get current value.
+    pub fn get(&self) -> i64 {
+        self.value.load(Ordering::Relaxed)
+    }
+
+    fn render(&self, out: &mut String) {
+        let _ = writeln!(out, "# HELP {} {}", self.name, self.help);
+        let _ = writeln!(out, "# TYPE {} gauge", self.name);
+        let _ = writeln!(out, "{} {}", self.name, self.get());
+    }
+}
+
+/// Gauges of the same name, distinguished by values of labels.
+pub struct GaugeVec {
+    name: &'static str,
+    help: &'static str,
+    labels: &'static [&'static str],
+    values: Mutex<BTreeMap<Vec<String>, i64>>,
+}
+
+impl GaugeVec {
+    /// Create gauges with name, help text and names of labels.
+    pub const fn new(
+        name: &'static str,
+        help: &'static str,
+        labels: &'static [&'static str],
+    ) -> Self {
+        Self {
+            name,
+            help,
+            labels,
+            values: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Set value of the gauge with `label_values`, which are in the order of labels.
+    pub fn set(&self, label_values: &[&str], value: i64) {
+        let key = label_values.iter().map(|v| v.to_string()).collect();
+        self.lock().insert(key, value);
+    }
+
+    /// Remove the gauge with `label_values`, so that it's not rendered any more.
+    pub fn remove(&self, label_values: &[&str]) {
+        let key: Vec<String> = label_values.iter().map(|v| v.to_string()).collect();
+        self.lock().remove(&key);
+    }
+
+    /// Get value of the gauge with `label_values`.
+    pub fn get(&self, label_values: &[&str]) -> Option<i64> {
+        let key: Vec<String> = label_values.iter().map(|v| v.to_string()).collect();
+        self.lock().get(&key).copied()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<Vec<String>, i64>> {
+        self.values.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn render(&self, out: &mut String) {
+        let _ = writeln!(out, "# HELP {} {}", self.name, self.help);
+        let _ = writeln!(out, "# TYPE {} gauge", self.name);
+        for (values, value) in self.lock().iter() {
+            let labels = self
+                .labels
+                .iter()
+                .zip(values)
+                .map(|(label, v)| format!("{}=\"{}\"", label, v.replace('"', "\\\"")))
+                .collect::<Vec<_>>()
+                .join(",");
+            let _ = writeln!(out, "{}{{{}}} {}", self.name, labels, value);
+        }
+    }
+}
+
+/// Upper bounds of [Histogram] buckets.
+const HISTOGRAM_BOUNDS: [u64; 11] = [5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10000];
+
+/// A histogram of observed values, with fixed buckets of [HISTOGRAM_BOUNDS].
+pub struct Histogram {
+    name: &'static str,
+    help: &'static str,
+    buckets: [AtomicU64; HISTOGRAM_BOUNDS.len()],
+    sum: AtomicU64,
+    count: AtomicU64,
+}
+
+impl Histogram {
+    /// Create a histogram with name and help text.
+    pub const fn new(name: &'static str, help: &'static str) -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const ZERO: AtomicU64 = AtomicU64::new(0);
+        Self {
+            name,
+            help,
+            buckets: [ZERO; HISTOGRAM_BOUNDS.len()],
+            sum: AtomicU64::new(0),
+            count: AtomicU64::new(0),
+        }
+    }
+
+    /// Record an observed value.
+    pub fn observe(&self, value: u64) {
+        if let Some(i) = HISTOGRAM_BOUNDS.iter().position(|b| value <= *b) {
+            self.buckets[i].fetch_add(1, Ordering::Relaxed);
+        }
+        self.sum.fetch_add(value, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Get count of observed values.
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    fn render(&self, out: &mut String) {
+        let _ = writeln!(out, "# HELP {} {}", self.name, self.help);
+        let _ = writeln!(out, "# TYPE {} histogram", self.name);
+        // Prometheus buckets are cumulative.
+        let mut cumulative = 0;
+        for (bound, bucket) in HISTOGRAM_BOUNDS.iter().zip(self.buckets.iter()) {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let _ = writeln!(
+                out,
+                "{}_bucket{{le=\"{}\"}} {}",
+                self.name, bound, cumulative
+            );
+        }
+        let count = self.count();
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", self.name, count);
+        let _ = writeln!(
+            out,
+            "{}_sum {}",
+            self.name,
+            self.sum.load(Ordering::Relaxed)
+        );
+        let _ = writeln!(out, "{}_count {}", self.name, count);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_counter_and_gauge() {
+        let counter = Counter::new("test_total", "Test counter.");
+        counter.inc();
+        counter.inc_by(2);
+        let gauge = Gauge::new("test_gauge", "Test gauge.");
+        gauge.set(-1);
+        gauge.inc();
+        gauge.dec();
+
+        let mut out = String::new();
+        counter.render(&mut out);
+        gauge.render(&mut out);
+        assert_eq!(
+            out,
+            "# HELP test_total Test counter.\n\
+             # TYPE test_total counter\n\
+             test_total 3\n\
+             # HELP test_gauge Test gauge.\n\
+             # TYPE test_gauge gauge\n\
+             test_gauge -1\n"
+        );
+    }
+
+    #[test]
+    fn test_render_gauge_vec() {
+        let gauges = GaugeVec::new("test_up", "Test gauges.", &["service", "upstream"]);
+        gauges.set(&["a", "127.0.0.1:8001"], 1);
+        gauges.set(&["a", "127.0.0.1:8002"], 0);
+        gauges.set(&["b", "127.0.0.1:8001"], 1);
+        gauges.remove(&["b", "127.0.0.1:8001"]);
+        assert_eq!(gauges.get(&["a", "127.0.0.1:8002"]), Some(0));
+        assert_eq!(gauges.get(&["b", "127.0.0.1:8001"]), None);
+
+        let mut out = String::new();
+        gauges.render(&mut out);
+        assert_eq!(
+            out,
+            "# HELP test_up Test gauges.\n\
+             # TYPE test_up gauge\n\
+             test_up{service=\"a\",upstream=\"127.0.0.1:8001\"} 1\n\
+             test_up{service=\"a\",upstream=\"127.0.0.1:8002\"} 0\n"
+        );
+    }
+
+    #[test]
+    fn test_render_histogram() {
+        let histogram = Histogram::new("test_ms", "Test histogram.");
+        histogram.observe(3);
+        histogram.observe(42);
+        histogram.observe(20000);
+
+        let mut out = String::new();
+        histogram.render(&mut out);
+        assert!(out.contains("# TYPE test_ms histogram\n"));
+        assert!(out.contains("test_ms_bucket{le=\"5\"} 1\n"));
+        assert!(out.contains("test_ms_bucket{le=\"25\"} 1\n"));
+        assert!(out.contains("test_ms_bucket{le=\"50\"} 2\n"));
+        assert!(out.contains("test_ms_bucket{le=\"10000\"} 2\n"));
+        assert!(out.contains("test_ms_bucket{le=\"+Inf\"} 3\n"));
+        assert!(out.contains("test_ms_sum 20045\n"));
+        assert!(out.contains("test_ms_count 3\n"));
+    }
+}
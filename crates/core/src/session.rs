@@ -72,11 +72,14 @@
 //! See [SessionSk] and [SessionSkBuilder] for details.
 
 use std::str::FromStr;
+use std::time::Duration;
 
 use rings_derive::wasm_export;
 use serde::Deserialize;
 use serde::Serialize;
 
+use crate::clock::Clock;
+use crate::clock::SystemClock;
 use crate::consts::DEFAULT_SESSION_TTL_MS;
 use crate::dht::Did;
 use crate::ecc::keccak256;
@@ -241,6 +244,10 @@ impl SessionSkBuilder {
 
     /// Build the [SessionSk].
     pub fn build(self) -> Result<SessionSk> {
+        if self.ttl_ms == 0 {
+            return Err(Error::InvalidSessionTtl);
+        }
+
         let account = Account::try_from((self.account_entity, self.account_type))?;
         let session = Session {
             session_id: self.sk.address().into(),
@@ -259,6 +266,14 @@ impl SessionSkBuilder {
     }
 }
 
+impl SessionSkBuilder {
+    /// Set the lifetime of session, e.g. days for long-lived server nodes,
+    /// or hours for browser sessions. It should be greater than zero.
+    pub fn session_ttl(self, ttl: Duration) -> Self {
+        self.set_ttl(ttl.as_millis() as u64)
+    }
+}
+
 impl Session {
     /// Pack the session into a string for verification or public key recovery.
     pub fn pack(&self) -> Vec<u8> {
@@ -267,15 +282,29 @@ impl Session {
             .to_vec()
     }
 
+    /// Get the timestamp in milliseconds when session expires.
+    pub fn expires_at_ms(&self) -> u128 {
+        self.ts_ms + self.ttl_ms as u128
+    }
+
     /// Check session is expired or not.
     pub fn is_expired(&self) -> bool {
-        let now = utils::get_epoch_ms();
-        now > self.ts_ms + self.ttl_ms as u128
+        self.is_expired_with(&SystemClock)
+    }
+
+    /// Check session is expired or not at the time of `clock`.
+    pub fn is_expired_with(&self, clock: &dyn Clock) -> bool {
+        clock.now_ms() > self.expires_at_ms()
     }
 
     /// Verify session.
     pub fn verify_self(&self) -> Result<()> {
-        if self.is_expired() {
+        self.verify_self_with(&SystemClock)
+    }
+
+    /// Verify session, checking expiry at the time of `clock`.
+    pub fn verify_self_with(&self, clock: &dyn Clock) -> Result<()> {
+        if self.is_expired_with(clock) {
             return Err(Error::SessionExpired);
         }
 
@@ -302,7 +331,12 @@ impl Session {
 
     /// Verify message.
     pub fn verify(&self, msg: &[u8], sig: impl AsRef<[u8]>) -> Result<()> {
-        self.verify_self()?;
+        self.verify_with(msg, sig, &SystemClock)
+    }
+
+    /// Verify message, checking session expiry at the time of `clock`.
+    pub fn verify_with(&self, msg: &[u8], sig: impl AsRef<[u8]>, clock: &dyn Clock) -> Result<()> {
+        self.verify_self_with(clock)?;
         if !signers::secp256k1::verify(msg, &self.session_id, sig) {
             return Err(Error::VerifySignatureFailed);
         }
@@ -331,6 +365,11 @@ impl Session {
             Account::Secp256r1(ref pk) => pk.address().into(),
         }
     }
+
+    /// Get the did of session public key, which identifies the session.
+    pub fn session_id(&self) -> Did {
+        self.session_id
+    }
 }
 
 impl SessionSk {
@@ -365,6 +404,11 @@ impl SessionSk {
         self.session.account_did()
     }
 
+    /// Decrypt data that was encrypted to the public key of session.
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        ecies::decrypt(&self.sk.ser(), data).map_err(Error::MessageDecryptionFailed)
+    }
+
     /// Dump session_sk to string, allowing user to save it in a config file.
     /// It can be restored using `SessionSk::from_str`.
     pub fn dump(&self) -> Result<String> {
@@ -394,6 +438,74 @@ mod test {
         assert_eq!(key.pubkey(), pubkey);
     }
 
+    #[test]
+    pub fn test_decrypt_with_recovered_session_pubkey() {
+        let key = SecretKey::random();
+        let sm = SessionSk::new_with_seckey(&key).unwrap();
+        let data = "hello".as_bytes();
+        let verification = crate::message::MessageVerification::new(data, &sm).unwrap();
+        let pubkey = verification.session_pubkey(data).unwrap();
+        assert_eq!(Did::from(pubkey.address()), sm.session().session_id);
+
+        let encrypted = pubkey.encrypt(data).unwrap();
+        assert_ne!(encrypted, data);
+        assert_eq!(sm.decrypt(&encrypted).unwrap(), data);
+    }
+
+    #[test]
+    pub fn test_session_ttl() {
+        let key = SecretKey::random();
+        let account_entity = Did::from(key.address()).to_string();
+        let build = |ttl| {
+            let builder = SessionSkBuilder::new(account_entity.clone(), "secp256k1".to_string())
+                .session_ttl(ttl);
+            let sig = key.sign(&builder.unsigned_proof());
+            builder.set_session_sig(sig.to_vec()).build()
+        };
+
+        let sm = build(Duration::from_secs(3 * 24 * 3600)).unwrap();
+        let session = sm.session();
+        assert_eq!(
+            session.expires_at_ms() - session.ts_ms,
+            3 * 24 * 3600 * 1000
+        );
+
+        assert!(matches!(
+            build(Duration::ZERO),
+            Err(Error::InvalidSessionTtl)
+        ));
+    }
+
+    #[test]
+    pub fn test_session_expiry_boundary() {
+        use std::time::UNIX_EPOCH;
+
+        use crate::clock::MockClock;
+
+        let key = SecretKey::random();
+        let account_entity = Did::from(key.address()).to_string();
+        let builder = SessionSkBuilder::new(account_entity, "secp256k1".to_string())
+            .session_ttl(Duration::from_secs(60));
+        let sig = key.sign(&builder.unsigned_proof());
+        let session = builder
+            .set_session_sig(sig.to_vec())
+            .build()
+            .unwrap()
+            .session();
+
+        let clock =
+            MockClock::new(UNIX_EPOCH + Duration::from_millis(session.expires_at_ms() as u64));
+        assert!(!session.is_expired_with(&clock));
+        assert!(session.verify_self_with(&clock).is_ok());
+
+        clock.advance(Duration::from_millis(1));
+        assert!(session.is_expired_with(&clock));
+        assert!(matches!(
+            session.verify_self_with(&clock),
+            Err(Error::SessionExpired)
+        ));
+    }
+
     #[test]
     pub fn test_dump_restore() {
         let key = SecretKey::random();
@@ -76,4 +76,20 @@ mod tests {
         store.put(&addr, &"value 2".to_string()).await.unwrap();
         assert_eq!(store.get(&addr).await.unwrap(), Some("value 2".into()));
     }
+
+    #[tokio::test]
+    async fn memstorage_scan_by_prefix() {
+        let store = MemStorage::new();
+        store.put("presence/a", &"a".to_string()).await.unwrap();
+        store.put("presence/b", &"b".to_string()).await.unwrap();
+        store.put("service/a", &"c".to_string()).await.unwrap();
+
+        let mut found = store.scan("presence/").await.unwrap();
+        found.sort();
+        assert_eq!(found, [
+            ("presence/a".to_string(), "a".to_string()),
+            ("presence/b".to_string(), "b".to_string())
+        ]);
+        assert!(store.scan("none/").await.unwrap().is_empty());
+    }
 }
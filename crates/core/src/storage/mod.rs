@@ -23,6 +23,18 @@ pub trait KvStorageInterface<V> {
 
     async fn get_all(&self) -> Result<Vec<(String, V)>>;
 
+    /// Get all entries whose key starts with `prefix`.
+    /// The default implementation filters [KvStorageInterface::get_all],
+    /// storages supporting ordered keys should override it.
+    async fn scan(&self, prefix: &str) -> Result<Vec<(String, V)>> {
+        Ok(self
+            .get_all()
+            .await?
+            .into_iter()
+            .filter(|(k, _)| k.starts_with(prefix))
+            .collect())
+    }
+
     /// Remove an `entry` by `key`.
     async fn remove(&self, key: &str) -> Result<()>;
 
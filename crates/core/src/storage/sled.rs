@@ -74,6 +74,19 @@ where V: Serialize + DeserializeOwned + Sync
             .collect_vec())
     }
 
+    async fn scan(&self, prefix: &str) -> Result<Vec<(String, V)>> {
+        let iter = self.db.scan_prefix(prefix.as_bytes());
+        Ok(iter
+            .flatten()
+            .flat_map(|(k, v)| {
+                Some((
+                    std::str::from_utf8(k.as_ref()).ok()?.to_string(),
+                    bincode::deserialize(v.as_ref()).ok()?,
+                ))
+            })
+            .collect_vec())
+    }
+
     async fn remove(&self, key: &str) -> Result<()> {
         self.db
             .remove(key.to_string().as_bytes())
@@ -2,18 +2,48 @@
 //! This module provider [SwarmBuilder] and it's interface for
 //! [Swarm]
 
+use std::collections::HashMap;
+#[cfg(not(feature = "wasm"))]
+use std::path::Path;
 use std::sync::Arc;
 use std::sync::RwLock;
+use std::time::Duration;
 
+use rings_transport::core::transport::DataChannelConfig;
+use rings_transport::ice_server::IceServer;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::clock::SharedClock;
+use crate::consts::DEFAULT_DEDUP_CACHE_SIZE;
+use crate::consts::DEFAULT_LOOKUP_TIMEOUT_MS;
+use crate::consts::DEFAULT_RELAY_TTL;
+use crate::consts::DEFAULT_STABILIZE_INTERVAL_MS;
+use crate::dht::Did;
 use crate::dht::PeerRing;
 use crate::dht::VNodeStorage;
+use crate::error::Error;
+use crate::error::Result;
 use crate::measure::MeasureImpl;
 use crate::session::SessionSk;
 use crate::swarm::callback::SharedSwarmCallback;
 use crate::swarm::callback::SwarmCallback;
+use crate::swarm::gater::SharedConnectionGater;
+use crate::swarm::keepalive::KeepAliveConfig;
+use crate::swarm::lookup::LookupMode;
+use crate::swarm::reconnect::ReconnectPolicy;
 use crate::swarm::transport::SwarmTransport;
 use crate::swarm::Swarm;
 
+/// A seed peer to join the network through, see [SwarmBuilder::bootstrap_nodes].
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct BootstrapEntry {
+    /// Did of the seed peer.
+    pub did: Did,
+    /// Signaling endpoint of the seed peer, where offer and answer are exchanged.
+    pub endpoint: String,
+}
+
 struct DefaultCallback;
 impl SwarmCallback for DefaultCallback {}
 
@@ -23,11 +53,31 @@ pub struct SwarmBuilder {
     ice_servers: String,
     external_address: Option<String>,
     dht_succ_max: u8,
+    dht_replicas: usize,
     dht_storage: VNodeStorage,
     session_sk: SessionSk,
     session_ttl: Option<usize>,
     measure: Option<MeasureImpl>,
     callback: Option<SharedSwarmCallback>,
+    max_connections: Option<usize>,
+    keepalive: Option<KeepAliveConfig>,
+    relay_fallback: bool,
+    data_channel: DataChannelConfig,
+    dedup_cache_size: usize,
+    handshake_replay_window: Option<Duration>,
+    metadata: HashMap<String, String>,
+    chunk_reassembly_timeout: Option<Duration>,
+    max_message_size: Option<usize>,
+    relay_ttl: u8,
+    clock: Option<SharedClock>,
+    stabilize_interval: Duration,
+    bootstrap_nodes: Vec<BootstrapEntry>,
+    idle_timeout: Option<Duration>,
+    reconnect: Option<ReconnectPolicy>,
+    connection_gater: Option<SharedConnectionGater>,
+    ice_gathering_timeout: Option<Duration>,
+    lookup_mode: LookupMode,
+    lookup_timeout: Duration,
 }
 
 impl SwarmBuilder {
@@ -43,12 +93,61 @@ impl SwarmBuilder {
             ice_servers: ice_servers.to_string(),
             external_address: None,
             dht_succ_max: 3,
+            dht_replicas: 1,
             dht_storage,
             session_sk,
             session_ttl: None,
             measure: None,
             callback: None,
+            max_connections: None,
+            keepalive: None,
+            relay_fallback: false,
+            data_channel: DataChannelConfig::default(),
+            dedup_cache_size: DEFAULT_DEDUP_CACHE_SIZE,
+            handshake_replay_window: None,
+            metadata: HashMap::new(),
+            chunk_reassembly_timeout: None,
+            max_message_size: None,
+            relay_ttl: DEFAULT_RELAY_TTL,
+            clock: None,
+            stabilize_interval: Duration::from_millis(DEFAULT_STABILIZE_INTERVAL_MS),
+            bootstrap_nodes: vec![],
+            idle_timeout: None,
+            reconnect: None,
+            connection_gater: None,
+            ice_gathering_timeout: None,
+            lookup_mode: LookupMode::default(),
+            lookup_timeout: Duration::from_millis(DEFAULT_LOOKUP_TIMEOUT_MS),
+        }
+    }
+
+    /// Sets up ice servers from environment variable `key`, in the same `;` separated format
+    /// as [SwarmBuilder::new]. The ice servers passed to `new` are kept if it's not set.
+    /// Returns error listing the invalid urls.
+    #[cfg(not(feature = "wasm"))]
+    pub fn ice_servers_from_env(mut self, key: &str) -> Result<Self> {
+        if let Ok(ice_servers) = std::env::var(key) {
+            IceServer::vec_from_str(&ice_servers).map_err(|e| Error::Transport(e.into()))?;
+            self.ice_servers = ice_servers;
         }
+        Ok(self)
+    }
+
+    /// Sets up ice servers from a file, with one url per line or separated by `;`.
+    /// Empty lines and lines starting with `#` are ignored.
+    /// Returns error listing the invalid urls.
+    #[cfg(not(feature = "wasm"))]
+    pub fn ice_servers_from_file(mut self, path: impl AsRef<Path>) -> Result<Self> {
+        let content = std::fs::read_to_string(path).map_err(Error::IOError)?;
+        let ice_servers = content
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .collect::<Vec<_>>()
+            .join(";");
+        IceServer::vec_from_str(&ice_servers).map_err(|e| Error::Transport(e.into()))?;
+        self.ice_servers = ice_servers;
+        Ok(self)
     }
 
     /// Sets up the maximum length of successors in the DHT.
@@ -57,6 +156,18 @@ impl SwarmBuilder {
         self
     }
 
+    /// Sets up the length of successor list, which is also the replication factor of DHT storage.
+    /// Each vnode is replicated to the first `len - 1` successors of the node responsible for
+    /// it, so that it survives up to `len - 1` simultaneous failures, and lookups are answered
+    /// by replicas if the responsible node is unreachable.
+    /// The length is capped to 255, vnodes are not replicated without this option.
+    pub fn successor_list_length(mut self, len: usize) -> Self {
+        let len = len.clamp(1, u8::MAX as usize);
+        self.dht_succ_max = len as u8;
+        self.dht_replicas = len;
+        self
+    }
+
     /// Sets up the external address for swarm transport.
     /// This will be used to configure the transport to listen for WebRTC connections in "HOST" mode.
     pub fn external_address(mut self, external_address: String) -> Self {
@@ -82,34 +193,237 @@ impl SwarmBuilder {
         self
     }
 
+    /// Limit the count of connections. Once the limit is reached, offers from remote
+    /// peers will be rejected, so that they can try other nodes.
+    /// Connections created by this node itself are not limited.
+    pub fn max_connections(mut self, n: usize) -> Self {
+        self.max_connections = Some(n);
+        self
+    }
+
+    /// Sets up the interval between stabilization rounds, see [Swarm::stabilize_now].
+    /// A short interval makes the ring converge faster after nodes join or leave, at the cost
+    /// of more messages to successors and predecessor. A long interval saves traffic, but stale
+    /// successors and fingers are kept longer, so lookups may fail or take extra hops.
+    pub fn stabilize_interval(mut self, interval: Duration) -> Self {
+        self.stabilize_interval = interval;
+        self
+    }
+
+    /// Enable keepalive on connections. A tiny ping is sent to each connected peer every
+    /// `interval`, and peers missing pongs for `miss_threshold` intervals are disconnected.
+    /// Idle data channels may be silently dropped by NAT without it.
+    pub fn keepalive(mut self, interval: Duration, miss_threshold: u32) -> Self {
+        self.keepalive = Some(KeepAliveConfig {
+            interval,
+            miss_threshold,
+        });
+        self
+    }
+
+    /// Close connections without any message sent or received for longer than `timeout`,
+    /// unless the peer is a successor, predecessor or finger of this node. It keeps peers
+    /// dialed once during a lookup from holding connections forever.
+    /// The check runs with [KeepAlive](super::KeepAlive), even if pings are not enabled.
+    /// A zero timeout is invalid and ignored with an error logged.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets up how [Swarm::lookup] walks the ring. [LookupMode::Recursive] is the default.
+    /// With [LookupMode::Iterative], this node queries each hop itself, so a dead hop fails
+    /// the lookup after one `lookup_timeout` instead of stalling it somewhere in the ring.
+    pub fn lookup_mode(mut self, mode: LookupMode) -> Self {
+        self.lookup_mode = mode;
+        self
+    }
+
+    /// Sets up the timeout of a recursive lookup, or of each hop of an iterative lookup.
+    pub fn lookup_timeout(mut self, timeout: Duration) -> Self {
+        self.lookup_timeout = timeout;
+        self
+    }
+
+    /// Retry connections that failed on ICE with TURN candidates only, for peers both behind
+    /// symmetric NATs. The failed peers are redialed in the next stabilization, and use TURN
+    /// candidates only for a while. At least one TURN server should be configured in
+    /// `ice_servers`, otherwise this option is ignored.
+    pub fn relay_fallback(mut self, enable: bool) -> Self {
+        self.relay_fallback = enable;
+        self
+    }
+
+    /// Sets up reliability of data channels, which are reliable and ordered by default.
+    /// Peers should use the same config. An invalid config is ignored with an error logged.
+    pub fn data_channel(mut self, config: DataChannelConfig) -> Self {
+        self.data_channel = config;
+        self
+    }
+
+    /// Sets up the time to wait for ICE gathering when creating an offer or answer, default to
+    /// [DEFAULT_ICE_GATHERING_TIMEOUT](rings_transport::core::transport::DEFAULT_ICE_GATHERING_TIMEOUT).
+    /// When it's reached, the handshake proceeds with the candidates gathered so far,
+    /// so a slow STUN server or a browser never completing gathering doesn't stall it.
+    pub fn ice_gathering_timeout(mut self, timeout: Duration) -> Self {
+        self.ice_gathering_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets up the count of recently seen tx_ids kept to drop duplicated inbound messages.
+    /// Zero disables deduplication.
+    pub fn dedup_cache_size(mut self, size: usize) -> Self {
+        self.dedup_cache_size = size;
+        self
+    }
+
+    /// Sets up the window of handshake replay protection. Offers and answers signed
+    /// earlier than it are rejected, so are the ones seen before. A manual offer/answer
+    /// exchange must be completed in it, default to 10 minutes.
+    pub fn handshake_replay_window(mut self, window: Duration) -> Self {
+        self.handshake_replay_window = Some(window);
+        self
+    }
+
+    /// Sets up the metadata advertised to peers on handshake, such as the services this
+    /// node provides. It's signed along with the offer or answer.
+    pub fn metadata(mut self, metadata: HashMap<String, String>) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Sets up the timeout of chunked message reassembly. Large messages not completed
+    /// in it, such as the sender disconnected in the middle, are dropped by stabilization.
+    pub fn chunk_reassembly_timeout(mut self, timeout: Duration) -> Self {
+        self.chunk_reassembly_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets up the size limit of a message sent on a data channel, default to the typical
+    /// SCTP limit [crate::consts::DEFAULT_MAX_MESSAGE_SIZE]. Larger messages are split into
+    /// chunks automatically, up to [crate::consts::TRANSPORT_MAX_SIZE] in total.
+    pub fn max_message_size(mut self, size: usize) -> Self {
+        self.max_message_size = Some(size);
+        self
+    }
+
+    /// Sets up the count of hops messages sent by this node can be relayed, default to
+    /// [DEFAULT_RELAY_TTL]. Messages are dropped when it runs out before they reach the
+    /// destination, which stops a routing loop. It should be no less than log2 of the
+    /// count of nodes in the ring, plus a few hops for stale routes.
+    pub fn relay_ttl(mut self, ttl: u8) -> Self {
+        self.relay_ttl = ttl;
+        self
+    }
+
+    /// Sets up the source of the current time used to check expiry of messages and
+    /// handshakes, default to the wall clock. Tests can drive it with [crate::clock::MockClock].
+    pub fn clock(mut self, clock: SharedClock) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Sets up the seed peers to dial on start, the node joins the ring via the first
+    /// one that connects. Swarm only keeps the list, since the handshake is exchanged
+    /// out of band through the signaling endpoint, see `Processor::bootstrap` of rings-node.
+    pub fn bootstrap_nodes(mut self, nodes: Vec<BootstrapEntry>) -> Self {
+        self.bootstrap_nodes = nodes;
+        self
+    }
+
+    /// Reconnect bootstrap nodes, successors, predecessor and fingers after they are lost,
+    /// with jittered exponential backoff by `policy`. The attempts are made by
+    /// [Reconnector](super::Reconnector), see [Swarm::reconnect_states] for their progress.
+    pub fn reconnect(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect = Some(policy);
+        self
+    }
+
+    /// Ask `gater` before answering each offer, offers denied by it are rejected.
+    /// See [ConnectionGater](super::ConnectionGater).
+    pub fn connection_gater(mut self, gater: SharedConnectionGater) -> Self {
+        self.connection_gater = Some(gater);
+        self
+    }
+
     /// Try build for `Swarm`.
     pub fn build(self) -> Swarm {
         let dht_did = self.session_sk.account_did();
 
-        let dht = Arc::new(PeerRing::new_with_storage(
-            dht_did,
-            self.dht_succ_max,
-            self.dht_storage,
-        ));
+        let mut dht = PeerRing::new_with_storage(dht_did, self.dht_succ_max, self.dht_storage)
+            .with_replicas(self.dht_replicas);
+        if let Some(clock) = &self.clock {
+            dht = dht.with_clock(clock.clone());
+        }
+        let dht = Arc::new(dht);
 
         let callback = RwLock::new(
             self.callback
                 .unwrap_or_else(|| Arc::new(DefaultCallback {})),
         );
 
-        let transport = Arc::new(SwarmTransport::new(
+        let mut transport = SwarmTransport::new(
             self.network_id,
             &self.ice_servers,
             self.external_address,
             self.session_sk,
             dht.clone(),
             self.measure,
-        ));
+            self.max_connections,
+        );
+        transport.set_dedup_cache_size(self.dedup_cache_size);
+        transport.set_metadata(self.metadata);
+        if let Some(timeout) = self.chunk_reassembly_timeout {
+            transport.set_chunk_reassembly_timeout(timeout);
+        }
+        if let Some(size) = self.max_message_size {
+            transport.set_max_message_size(size);
+        }
+        if let Some(clock) = self.clock {
+            transport.set_clock(clock);
+        }
+        transport.set_relay_ttl(self.relay_ttl);
+        transport.set_lookup(self.lookup_mode, self.lookup_timeout);
+        if let Some(window) = self.handshake_replay_window {
+            transport.set_handshake_replay_window(window);
+        }
+        if self.relay_fallback {
+            if let Err(e) = transport.enable_relay_fallback() {
+                tracing::error!("Failed to enable relay fallback: {e:?}");
+            }
+        }
+        if let Err(e) = transport.set_data_channel_config(self.data_channel) {
+            tracing::error!("Failed to set data channel config: {e:?}");
+        }
+        if let Some(timeout) = self.ice_gathering_timeout {
+            transport.set_ice_gathering_timeout(timeout);
+        }
+        if let Some(policy) = self.reconnect {
+            let bootstrap_dids: Vec<Did> = self.bootstrap_nodes.iter().map(|n| n.did).collect();
+            transport.set_reconnect(policy, &bootstrap_dids);
+        }
+        if let Some(gater) = self.connection_gater {
+            transport.set_connection_gater(gater);
+        }
+        let transport = Arc::new(transport);
+
+        // A zero timeout would make keepalive run in a busy loop.
+        let idle_timeout = match self.idle_timeout {
+            Some(timeout) if timeout.is_zero() => {
+                tracing::error!("Idle timeout should be greater than zero, ignored");
+                None
+            }
+            timeout => timeout,
+        };
 
         Swarm {
             dht,
             transport,
             callback,
+            keepalive: self.keepalive,
+            stabilize_interval: self.stabilize_interval,
+            bootstrap_nodes: self.bootstrap_nodes,
+            idle_timeout,
         }
     }
 }
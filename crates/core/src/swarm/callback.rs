@@ -1,21 +1,26 @@
+use std::panic::AssertUnwindSafe;
 use std::str::FromStr;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::time::Duration;
 
+use async_recursion::async_recursion;
 use async_trait::async_trait;
-use futures::lock::Mutex as FuturesMutex;
+use futures::FutureExt;
 use rings_transport::core::callback::TransportCallback;
 use rings_transport::core::transport::WebrtcConnectionState;
 
-use crate::chunk::ChunkList;
-use crate::chunk::ChunkManager;
-use crate::consts::TRANSPORT_MTU;
 use crate::dht::Did;
+use crate::error::Error;
 use crate::message::HandleMsg;
 use crate::message::Message;
 use crate::message::MessageHandler;
 use crate::message::MessagePayload;
 use crate::message::MessageVerificationExt;
+use crate::metrics;
 use crate::swarm::transport::SwarmTransport;
+use crate::utils::get_epoch_ms;
 
 type CallbackError = Box<dyn std::error::Error>;
 
@@ -61,12 +66,109 @@ pub trait SwarmCallback {
     }
 }
 
+/// A [SwarmCallback] dispatching to multiple callbacks, so that an application can register
+/// its own handlers alongside others, such as the backend of node.
+///
+/// Callbacks are invoked one by one in the order they were added. Every callback is invoked
+/// even if a former one returns an error or panics. After all of them are invoked, the first
+/// error, if any, is returned. For [SwarmCallback::on_validate], that means the message is
+/// rejected if any of the callbacks rejects it.
+#[derive(Default, Clone)]
+pub struct CompositeCallback {
+    callbacks: Vec<SharedSwarmCallback>,
+}
+
+impl CompositeCallback {
+    /// Create a [CompositeCallback] with the provided callbacks.
+    pub fn new(callbacks: Vec<SharedSwarmCallback>) -> Self {
+        Self { callbacks }
+    }
+
+    /// Append a callback, it will be invoked after the existing ones.
+    pub fn push(&mut self, callback: SharedSwarmCallback) {
+        self.callbacks.push(callback)
+    }
+
+    /// Append a callback, it will be invoked after the existing ones.
+    pub fn with(mut self, callback: SharedSwarmCallback) -> Self {
+        self.push(callback);
+        self
+    }
+
+    /// Count of callbacks.
+    pub fn len(&self) -> usize {
+        self.callbacks.len()
+    }
+
+    /// Whether there is no callback.
+    pub fn is_empty(&self) -> bool {
+        self.callbacks.is_empty()
+    }
+
+    /// Run `f` on every callback, isolating errors and panics of each other.
+    async fn dispatch<'a, F, Fut>(&'a self, method: &str, f: F) -> Result<(), CallbackError>
+    where
+        F: Fn(&'a SharedSwarmCallback) -> Fut,
+        Fut: std::future::Future<Output = Result<(), CallbackError>> + 'a,
+    {
+        // CallbackError is not Send, keep its message across awaits instead.
+        let mut first_err: Option<String> = None;
+        for (i, callback) in self.callbacks.iter().enumerate() {
+            let err = match AssertUnwindSafe(f(callback)).catch_unwind().await {
+                Ok(Ok(())) => continue,
+                Ok(Err(e)) => e.to_string(),
+                Err(_) => "panicked".to_string(),
+            };
+            tracing::error!("Callback {i} failed in {method}: {err}");
+            first_err.get_or_insert(err);
+        }
+        match first_err {
+            Some(e) => Err(e.into()),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+impl SwarmCallback for CompositeCallback {
+    async fn on_validate(&self, payload: &MessagePayload) -> Result<(), CallbackError> {
+        self.dispatch("on_validate", |cb| cb.on_validate(payload))
+            .await
+    }
+
+    async fn on_inbound(&self, payload: &MessagePayload) -> Result<(), CallbackError> {
+        self.dispatch("on_inbound", |cb| cb.on_inbound(payload))
+            .await
+    }
+
+    async fn on_event(&self, event: &SwarmEvent) -> Result<(), CallbackError> {
+        self.dispatch("on_event", |cb| cb.on_event(event)).await
+    }
+}
+
+/// Counts a message being handled until it's dropped, even if the handler panics or
+/// its future is dropped.
+struct Inflight<'a>(&'a AtomicUsize);
+
+impl<'a> Inflight<'a> {
+    fn new(count: &'a AtomicUsize) -> Self {
+        count.fetch_add(1, Ordering::SeqCst);
+        Self(count)
+    }
+}
+
+impl Drop for Inflight<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
 /// [InnerSwarmCallback] wraps [SharedSwarmCallback] with inner handling for a specific connection.
 pub struct InnerSwarmCallback {
     transport: Arc<SwarmTransport>,
     message_handler: MessageHandler,
     callback: SharedSwarmCallback,
-    chunk_list: FuturesMutex<ChunkList<TRANSPORT_MTU>>,
 }
 
 impl InnerSwarmCallback {
@@ -77,10 +179,77 @@ impl InnerSwarmCallback {
             transport,
             message_handler,
             callback,
-            chunk_list: Default::default(),
         }
     }
 
+    /// Update [metrics::ACTIVE_CONNECTIONS] with count of connected peers.
+    fn record_active_connections(&self) {
+        let connected = self
+            .transport
+            .get_connections()
+            .iter()
+            .filter(|(_, conn)| conn.webrtc_connection_state() == WebrtcConnectionState::Connected)
+            .count();
+        metrics::ACTIVE_CONNECTIONS.set(connected as i64);
+    }
+
+    /// Verify and handle a message from transport.
+    /// The message completed by a chunk is handled recursively.
+    #[cfg_attr(feature = "wasm", async_recursion(?Send))]
+    #[cfg_attr(not(feature = "wasm"), async_recursion)]
+    async fn handle_message(&self, cid: &str, msg: &[u8]) -> Result<(), CallbackError> {
+        let (payload, version) = MessagePayload::from_bincode_versioned(msg)?;
+        // The signer is claimed by the message and not trusted, it helps to diagnose
+        // man-in-the-middle or clock-skew issues.
+        let clock = self.transport.clock();
+        let bad_signer = if !payload.verify_with(clock) {
+            Some(payload.signer())
+        } else if !payload.transaction.verify_with(clock) {
+            Some(payload.transaction.signer())
+        } else {
+            None
+        };
+        if let Some(signer) = bad_signer {
+            tracing::error!(
+                "Cannot verify msg from {signer} or it's expired: {:?}",
+                payload
+            );
+            return Err(Error::VerifyMessageSignatureFailed(signer).into());
+        }
+        if let Ok(peer) = Did::from_str(cid) {
+            self.transport.record_peer_version(peer, version);
+        }
+        // A message which cannot be relayed any more may be looping, drop it before handling.
+        let relay = &payload.relay;
+        if relay.ttl == 0 && relay.destination != self.transport.dht.did {
+            tracing::warn!(
+                "Drop message {} to {} with expired ttl, path: {:?}",
+                payload.transaction.tx_id,
+                relay.destination,
+                relay.path
+            );
+            return Ok(());
+        }
+        self.transport.record_session_pubkey(&payload.transaction);
+        self.callback.on_validate(&payload).await?;
+
+        let _inflight = Inflight::new(&self.transport.inflight);
+        self.handle_payload(cid, &payload).await
+    }
+
+    /// Handle a payload created by this node and addressed to itself, see [super::Swarm::send_message].
+    /// It's never serialized or sent on a connection, so verification is skipped.
+    pub async fn handle_local_payload(
+        &self,
+        payload: &MessagePayload,
+    ) -> Result<(), CallbackError> {
+        self.callback.on_validate(payload).await?;
+
+        let cid = self.transport.dht.did.to_string();
+        let _inflight = Inflight::new(&self.transport.inflight);
+        self.handle_payload(&cid, payload).await
+    }
+
     async fn handle_payload(
         &self,
         cid: &str,
@@ -107,7 +276,18 @@ impl InnerSwarmCallback {
                 self.message_handler.handle(payload, msg).await
             }
             Message::OperateVNode(ref msg) => self.message_handler.handle(payload, msg).await,
+            Message::SearchVNodeBatch(ref msg) => self.message_handler.handle(payload, msg).await,
+            Message::OperateVNodeBatch(ref msg) => self.message_handler.handle(payload, msg).await,
+            Message::ReplicateVNode(ref msg) => self.message_handler.handle(payload, msg).await,
+            Message::FindNextHopSend(ref msg) => self.message_handler.handle(payload, msg).await,
+            Message::FindNextHopReport(ref msg) => self.message_handler.handle(payload, msg).await,
             Message::CustomMessage(ref msg) => self.message_handler.handle(payload, msg).await,
+            Message::EncryptedMessage(ref msg) => self.message_handler.handle(payload, msg).await,
+            Message::ReliableMessage(ref msg) => self.message_handler.handle(payload, msg).await,
+            Message::Ack(ref msg) => self.message_handler.handle(payload, msg).await,
+            Message::LeaveDht(ref msg) => self.message_handler.handle(payload, msg).await,
+            Message::LeaveDhtHandoff(ref msg) => self.message_handler.handle(payload, msg).await,
+            Message::ConnectNodeReject(ref msg) => self.message_handler.handle(payload, msg).await,
             Message::QueryForTopoInfoSend(ref msg) => {
                 self.message_handler.handle(payload, msg).await
             }
@@ -115,10 +295,20 @@ impl InnerSwarmCallback {
                 self.message_handler.handle(payload, msg).await
             }
             Message::Chunk(ref msg) => {
-                if let Some(data) = self.chunk_list.lock().await.handle(msg.clone()) {
-                    return self.on_message(cid, &data).await;
+                let from = payload.transaction.signer();
+                match self.transport.handle_chunk(msg.clone(), from).await {
+                    Ok(Some(data)) => return self.handle_message(cid, &data).await,
+                    Ok(None) => Ok(()),
+                    Err(e) => Err(e),
                 }
-                Ok(())
+            }
+            Message::ChunkResumeRequest(ref msg) => {
+                let from = payload.transaction.signer();
+                self.transport.resume_chunks(from, msg).await
+            }
+            Message::ChunkResumeAck(ref msg) => {
+                let from = payload.transaction.signer();
+                self.transport.handle_chunk_resume_ack(from, msg).await
             }
         }
         .unwrap_or_else(|e| {
@@ -126,6 +316,10 @@ impl InnerSwarmCallback {
         });
 
         if payload.transaction.destination == self.transport.dht.did {
+            if !self.transport.mark_seen(payload.transaction.tx_id) {
+                tracing::debug!("Drop duplicated message {}", payload.transaction.tx_id);
+                return Ok(());
+            }
             self.callback.on_inbound(payload).await?;
         }
 
@@ -137,13 +331,40 @@ impl InnerSwarmCallback {
 #[cfg_attr(not(feature = "wasm"), async_trait)]
 impl TransportCallback for InnerSwarmCallback {
     async fn on_message(&self, cid: &str, msg: &[u8]) -> Result<(), CallbackError> {
-        let payload = MessagePayload::from_bincode(msg)?;
-        if !(payload.verify() && payload.transaction.verify()) {
-            tracing::error!("Cannot verify msg or it's expired: {:?}", payload);
-            return Err("Cannot verify msg or it's expired".into());
+        if self.transport.is_shutting_down() {
+            tracing::debug!("Swarm is shutting down, drop message from {cid}");
+            return Ok(());
         }
-        self.callback.on_validate(&payload).await?;
-        self.handle_payload(cid, &payload).await
+
+        if let Ok(did) = Did::from_str(cid) {
+            self.transport.record_peer_recv(did, msg.len());
+        }
+        self.handle_message(cid, msg).await
+    }
+
+    async fn on_ping(&self, cid: &str, nonce: u64) -> Result<(), CallbackError> {
+        let Ok(did) = Did::from_str(cid) else {
+            tracing::warn!("on_ping parse did failed: {}", cid);
+            return Ok(());
+        };
+        let Some(conn) = self.transport.get_connection(did) else {
+            return Ok(());
+        };
+        conn.send_pong(nonce).await?;
+        Ok(())
+    }
+
+    async fn on_pong(&self, cid: &str, nonce: u64) -> Result<(), CallbackError> {
+        let Ok(did) = Did::from_str(cid) else {
+            tracing::warn!("on_pong parse did failed: {}", cid);
+            return Ok(());
+        };
+        // The nonce of ping is the time it was sent.
+        let rtt = get_epoch_ms().saturating_sub(nonce as u128);
+        self.transport
+            .record_latency(did, Duration::from_millis(rtt as u64));
+        self.transport.record_pong(did);
+        Ok(())
     }
 
     async fn on_peer_connection_state_change(
@@ -160,10 +381,13 @@ impl TransportCallback for InnerSwarmCallback {
             WebrtcConnectionState::Failed
             | WebrtcConnectionState::Disconnected
             | WebrtcConnectionState::Closed => {
+                // Check importance of peer before it's removed from dht.
+                self.transport.reconnects.watch(did, &self.transport.dht);
                 self.message_handler.leave_dht(did).await?;
             }
             _ => {}
         };
+        self.record_active_connections();
 
         // Should use the `on_data_channel_open` function to notify the Connected state.
         // It prevents users from blocking the channel creation while
@@ -187,6 +411,14 @@ impl TransportCallback for InnerSwarmCallback {
         };
 
         self.message_handler.join_dht(did).await?;
+        self.transport.reconnects.connected(did);
+        self.transport.record_pong(did);
+        self.transport.reset_peer_stats(did);
+        self.record_active_connections();
+
+        if let Err(e) = self.transport.request_chunk_resume(did).await {
+            tracing::warn!("Failed to resume chunked messages from {did}: {e:?}");
+        }
 
         // Notify Connected state here instead of on_peer_connection_state_change.
         // It prevents users from blocking the channel creation while
@@ -199,3 +431,20 @@ impl TransportCallback for InnerSwarmCallback {
             .await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inflight_released_on_panic() {
+        let count = AtomicUsize::new(0);
+        let panicked = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            let _inflight = Inflight::new(&count);
+            assert_eq!(count.load(Ordering::SeqCst), 1);
+            panic!("handler panicked");
+        }));
+        assert!(panicked.is_err());
+        assert_eq!(count.load(Ordering::SeqCst), 0);
+    }
+}
@@ -0,0 +1,45 @@
+//! Deduplication of inbound messages.
+
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// A bounded cache of recently seen tx_ids. The same message may arrive more than once
+/// via different DHT paths, it should only be handled once.
+/// When the cache is full, the earliest seen tx_id is evicted.
+pub(crate) struct SeenTxs {
+    capacity: usize,
+    inner: Mutex<(HashSet<uuid::Uuid>, VecDeque<uuid::Uuid>)>,
+}
+
+impl SeenTxs {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::new((HashSet::new(), VecDeque::new())),
+        }
+    }
+
+    /// Record a tx_id. Returns false if it was seen before.
+    pub fn insert(&self, tx_id: uuid::Uuid) -> bool {
+        if self.capacity == 0 {
+            return true;
+        }
+
+        let Ok(mut guard) = self.inner.lock() else {
+            return true;
+        };
+        let (set, queue) = &mut *guard;
+
+        if !set.insert(tx_id) {
+            return false;
+        }
+        queue.push_back(tx_id);
+        if queue.len() > self.capacity {
+            if let Some(evicted) = queue.pop_front() {
+                set.remove(&evicted);
+            }
+        }
+        true
+    }
+}
@@ -0,0 +1,31 @@
+//! Admission control of inbound connections.
+//!
+//! A [ConnectionGater] registered by [SwarmBuilder::connection_gater](super::SwarmBuilder::connection_gater)
+//! is asked before an offer is answered, with the verified signer and its handshake. A denied
+//! offer is answered by a signed [ConnectNodeReject](crate::message::ConnectNodeReject), so no
+//! connection is created for it. It can be used to implement allowlists, blocklists or
+//! rate-based admission.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::dht::Did;
+use crate::message::HandshakeInfo;
+
+/// Shared [ConnectionGater] trait object.
+#[cfg(feature = "wasm")]
+pub type SharedConnectionGater = Arc<dyn ConnectionGater>;
+
+/// Shared [ConnectionGater] trait object.
+#[cfg(not(feature = "wasm"))]
+pub type SharedConnectionGater = Arc<dyn ConnectionGater + Send + Sync>;
+
+/// Decide whether to accept inbound connections.
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+pub trait ConnectionGater {
+    /// Invoked with the signer of an offer and its handshake before the offer is answered.
+    /// Returns false to reject the connection.
+    async fn allow_inbound(&self, did: Did, info: &HandshakeInfo) -> bool;
+}
@@ -0,0 +1,89 @@
+//! Keepalive runs daemons to ping connected peers periodically.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::swarm::Swarm;
+
+/// Configuration of keepalive, see [SwarmBuilder::keepalive](super::SwarmBuilder::keepalive).
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct KeepAliveConfig {
+    /// Interval of pings.
+    pub interval: Duration,
+    /// Count of missed pongs before a peer is treated as disconnected.
+    pub miss_threshold: u32,
+}
+
+/// The keepalive runner.
+#[derive(Clone)]
+pub struct KeepAlive {
+    swarm: Arc<Swarm>,
+    interval: Duration,
+}
+
+impl KeepAlive {
+    /// Create a new keepalive runner.
+    /// Returns None if neither keepalive nor idle timeout is enabled on the swarm.
+    pub fn new(swarm: Arc<Swarm>) -> Option<Self> {
+        let interval = match (swarm.keepalive, swarm.idle_timeout) {
+            (Some(config), _) => config.interval,
+            // Check twice per timeout, so that idle connections are closed in time.
+            (None, Some(idle_timeout)) => idle_timeout / 2,
+            (None, None) => return None,
+        };
+        Some(Self { swarm, interval })
+    }
+}
+
+#[cfg(not(feature = "wasm"))]
+mod keepalive {
+    use futures::future::FutureExt;
+    use futures::pin_mut;
+    use futures::select;
+    use futures_timer::Delay;
+
+    use super::*;
+
+    impl KeepAlive {
+        /// Run keepalive in a loop.
+        pub async fn wait(self: Arc<Self>) {
+            loop {
+                let timeout = Delay::new(self.interval).fuse();
+                pin_mut!(timeout);
+                select! {
+                    _ = timeout => self
+                        .swarm
+                        .keepalive()
+                        .await
+                        .unwrap_or_else(|e| tracing::error!("failed to keepalive {:?}", e)),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "wasm")]
+mod keepalive {
+    use wasm_bindgen_futures::spawn_local;
+
+    use super::*;
+    use crate::poll;
+
+    impl KeepAlive {
+        /// Run keepalive in a loop.
+        pub async fn wait(self: Arc<Self>) {
+            let caller = Arc::clone(&self);
+            let func = move || {
+                let caller = caller.clone();
+                spawn_local(Box::pin(async move {
+                    caller
+                        .swarm
+                        .keepalive()
+                        .await
+                        .unwrap_or_else(|e| tracing::error!("failed to keepalive {:?}", e));
+                }))
+            };
+            poll!(func, self.interval.as_millis().try_into().unwrap());
+        }
+    }
+}
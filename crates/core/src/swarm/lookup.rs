@@ -0,0 +1,243 @@
+//! Lookup of the successor of a did, see [Swarm::lookup].
+//!
+//! In [LookupMode::Recursive], the request is forwarded hop by hop through the ring until it
+//! meets the successor, and only the report of the last node is seen by the origin.
+//! In [LookupMode::Iterative], the origin asks each hop for the next one itself, so that a
+//! slow or dead hop is detected by its own timeout, and the time of each hop is known.
+
+use std::time::Duration;
+
+use futures::future::select;
+use futures::future::Either;
+
+use crate::consts::MAX_LOOKUP_HOPS;
+use crate::dht::Chord;
+use crate::dht::Did;
+use crate::dht::PeerRingAction;
+use crate::error::Error;
+use crate::error::Result;
+use crate::message::FindNextHopSend;
+use crate::message::FindSuccessorReportHandler;
+use crate::message::FindSuccessorSend;
+use crate::message::FindSuccessorThen;
+use crate::message::Message;
+use crate::message::MessagePayload;
+use crate::message::MessageRelay;
+use crate::message::PayloadSender;
+use crate::message::Transaction;
+use crate::swarm::callback::InnerSwarmCallback;
+use crate::swarm::transport::SwarmTransport;
+use crate::swarm::Swarm;
+use crate::utils::get_epoch_ms;
+use crate::utils::sleep;
+
+/// How [Swarm::lookup] walks the ring, see [SwarmBuilder::lookup_mode](super::SwarmBuilder::lookup_mode).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LookupMode {
+    /// Each hop forwards the request to the next one, and the last one reports to the origin.
+    /// It takes the fewest messages, but a lost request is only detected by the overall timeout.
+    #[default]
+    Recursive,
+    /// The origin asks each hop for the next one, with a timeout for each hop.
+    Iterative,
+}
+
+/// A node queried during a lookup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LookupHop {
+    /// Did of the queried node. In [LookupMode::Recursive] it's the first hop of the request.
+    pub peer: Did,
+    /// Time from sending the request to receiving the report.
+    pub elapsed: Duration,
+}
+
+/// Result of [Swarm::lookup].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lookup {
+    /// Successor of the did looked up.
+    pub successor: Did,
+    /// Nodes queried in order. It's empty if the successor is known locally.
+    pub hops: Vec<LookupHop>,
+}
+
+/// Report of a lookup request, delivered to the waiting [Swarm::lookup].
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct LookupStep {
+    /// Successor of the target if found, otherwise the next node to ask.
+    pub next_hop: Did,
+    /// Whether next_hop is the successor of the target.
+    pub found: bool,
+}
+
+impl Swarm {
+    /// Find the successor of `did` in the ring, in the mode set by
+    /// [SwarmBuilder::lookup_mode](super::SwarmBuilder::lookup_mode).
+    /// The returned hops can be used to profile the lookup.
+    pub async fn lookup(&self, did: Did) -> Result<Lookup> {
+        self.transport.lookup(did).await
+    }
+}
+
+impl SwarmTransport {
+    /// Find the successor of `did` in the ring, see [Swarm::lookup].
+    pub async fn lookup(&self, did: Did) -> Result<Lookup> {
+        if self.is_shutting_down() {
+            return Err(Error::SwarmShuttingDown);
+        }
+        let next = match self.dht.find_successor(did)? {
+            PeerRingAction::Some(successor) => {
+                return Ok(Lookup {
+                    successor,
+                    hops: vec![],
+                })
+            }
+            PeerRingAction::RemoteAction(next, _) => next,
+            act => return Err(Error::PeerRingUnexpectedAction(act)),
+        };
+        if next == self.dht.did {
+            return Err(Error::LookupNotFinished { did, hops: 0 });
+        }
+
+        match self.lookup_mode {
+            LookupMode::Recursive => self.lookup_recursive(did, next).await,
+            LookupMode::Iterative => self.lookup_iterative(did, next).await,
+        }
+    }
+
+    /// The node to send a request about `did` to, where `next` is the next hop found locally.
+    /// In [LookupMode::Recursive], it's `next`, which forwards the request until it meets the
+    /// successor of `did`, so the request itself is the lookup. In [LookupMode::Iterative],
+    /// it's the successor found by [SwarmTransport::lookup], or `next` if the lookup fails.
+    pub(crate) async fn route(&self, did: Did, next: Did) -> Did {
+        if self.lookup_mode == LookupMode::Recursive {
+            return next;
+        }
+        match self.lookup(did).await {
+            Ok(lookup) if lookup.successor != self.dht.did => lookup.successor,
+            Ok(_) => next,
+            Err(e) => {
+                tracing::warn!("Failed to look up {did}, send to {next} instead: {e}");
+                next
+            }
+        }
+    }
+
+    /// Look up the successor of `did` and connect to it if it's not connected yet.
+    pub(crate) async fn connect_successor(
+        &self,
+        did: Did,
+        callback: InnerSwarmCallback,
+    ) -> Result<()> {
+        let successor = self.lookup(did).await?.successor;
+        if successor == self.dht.did || self.get_connection(successor).is_some() {
+            return Ok(());
+        }
+        let offer_msg = self.prepare_connection_offer(successor, callback).await?;
+        self.send_message(Message::ConnectNodeSend(offer_msg), successor)
+            .await?;
+        Ok(())
+    }
+
+    async fn lookup_recursive(&self, did: Did, next: Did) -> Result<Lookup> {
+        let msg = Message::FindSuccessorSend(FindSuccessorSend {
+            did,
+            strict: false,
+            then: FindSuccessorThen::Report(FindSuccessorReportHandler::None),
+        });
+        let start = get_epoch_ms();
+        let step = self
+            .lookup_request(msg, next, None)
+            .await?
+            .ok_or(Error::LookupTimeout { did, peer: next })?;
+        Ok(Lookup {
+            successor: step.next_hop,
+            hops: vec![LookupHop {
+                peer: next,
+                elapsed: elapsed_since(start),
+            }],
+        })
+    }
+
+    async fn lookup_iterative(&self, did: Did, mut next: Did) -> Result<Lookup> {
+        let mut hops = vec![];
+        while hops.len() < MAX_LOOKUP_HOPS {
+            let msg = Message::FindNextHopSend(FindNextHopSend { did });
+            let start = get_epoch_ms();
+            let step = self
+                .lookup_request(msg, next, Some(next))
+                .await?
+                .ok_or(Error::LookupTimeout { did, peer: next })?;
+            hops.push(LookupHop {
+                peer: next,
+                elapsed: elapsed_since(start),
+            });
+            tracing::debug!(
+                "Lookup of {did}: hop {} {next} -> {}",
+                hops.len(),
+                step.next_hop
+            );
+
+            if step.found {
+                return Ok(Lookup {
+                    successor: step.next_hop,
+                    hops,
+                });
+            }
+            // The hop knows no node closer to the target than itself.
+            if step.next_hop == next {
+                break;
+            }
+            next = step.next_hop;
+        }
+        Err(Error::LookupNotFinished {
+            did,
+            hops: hops.len(),
+        })
+    }
+
+    /// Send a lookup request to `destination`, and wait for its report in the lookup timeout.
+    /// Returns None on timeout.
+    async fn lookup_request(
+        &self,
+        msg: Message,
+        destination: Did,
+        reporter: Option<Did>,
+    ) -> Result<Option<LookupStep>> {
+        let session_sk = self.session_sk();
+        let tx_id = uuid::Uuid::new_v4();
+        let transaction = Transaction::new(destination, tx_id, msg, session_sk)?;
+        let mut reported = self.expect_lookup(tx_id, reporter);
+        let _pending = PendingLookup {
+            transport: self,
+            tx_id,
+        };
+
+        let next_hop = self.infer_next_hop(destination, None)?;
+        let relay = MessageRelay::new(vec![session_sk.account_did()], next_hop, destination)
+            .with_ttl(self.relay_ttl());
+        let payload = MessagePayload::new(transaction, session_sk, relay)?;
+        self.send_payload(payload).await?;
+
+        let step = match select(&mut reported, Box::pin(sleep(self.lookup_timeout))).await {
+            Either::Left((Ok(step), _)) => Some(step),
+            _ => None,
+        };
+        Ok(step)
+    }
+}
+
+/// Forget a lookup request when it's finished, failed or dropped while waiting.
+struct PendingLookup<'a> {
+    transport: &'a SwarmTransport,
+    tx_id: uuid::Uuid,
+}
+
+impl Drop for PendingLookup<'_> {
+    fn drop(&mut self) {
+        self.transport.forget_lookup(self.tx_id);
+    }
+}
+
+fn elapsed_since(start: u128) -> Duration {
+    Duration::from_millis(get_epoch_ms().saturating_sub(start) as u64)
+}
@@ -5,27 +5,88 @@
 mod builder;
 /// Callback interface for swarm
 pub mod callback;
+mod dedup;
+mod gater;
+mod keepalive;
+mod lookup;
+mod priority;
+mod pubkeys;
+mod reconnect;
+mod reliable;
+mod resume;
+mod stats;
 pub(crate) mod transport;
 
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::sync::RwLock;
+use std::time::Duration;
 
+pub use builder::BootstrapEntry;
 pub use builder::SwarmBuilder;
+use futures::future::select;
+use futures::future::Either;
+pub use gater::ConnectionGater;
+pub use gater::SharedConnectionGater;
+pub use keepalive::KeepAlive;
+pub use lookup::Lookup;
+pub use lookup::LookupHop;
+pub use lookup::LookupMode;
+pub use reconnect::BootstrapDialer;
+pub use reconnect::ReconnectPolicy;
+pub use reconnect::ReconnectState;
+pub use reconnect::Reconnector;
+pub use reconnect::SharedBootstrapDialer;
+pub use reliable::AckPolicy;
+use rings_transport::core::transport::ConnectionInterface;
+pub use rings_transport::core::transport::ConnectionStats;
+pub use rings_transport::core::transport::DataChannelConfig;
+use rings_transport::core::transport::WebrtcConnectionState;
+use rings_transport::ice_server::IceServer;
+pub use stats::PeerStats;
+pub use stats::TrafficStats;
+pub use transport::DataChannel;
 
 use self::callback::InnerSwarmCallback;
+use self::callback::SwarmEvent;
+use crate::consts::LEAVE_TIMEOUT_MS;
+use crate::consts::PAYLOAD_VERSION;
+use crate::dht::vnode::VirtualNode;
 use crate::dht::Did;
 use crate::dht::PeerRing;
 use crate::dht::Stabilizer;
+use crate::dht::SuccessorReader;
+use crate::dht::TopoInfo;
 use crate::error::Error;
 use crate::error::Result;
 use crate::inspect::ConnectionInspect;
 use crate::inspect::SwarmInspect;
+use crate::message::ConnectNodeReject;
+use crate::message::ConnectRejectReason;
+use crate::message::CustomMessage;
+use crate::message::EncryptedMessage;
+use crate::message::LeaveDht;
+use crate::message::LeaveDhtHandoff;
 use crate::message::Message;
 use crate::message::MessagePayload;
 use crate::message::MessageVerificationExt;
 use crate::message::PayloadSender;
+use crate::metrics;
+use crate::session::Session;
 use crate::swarm::callback::SharedSwarmCallback;
+use crate::swarm::keepalive::KeepAliveConfig;
 use crate::swarm::transport::SwarmTransport;
+use crate::utils::get_epoch_ms;
+use crate::utils::sleep;
+
+/// Data handed off to neighbours when leaving the ring, see [Swarm::leave].
+struct Handoff {
+    successor: Did,
+    predecessor: Option<Did>,
+    data: Vec<VirtualNode>,
+}
 
 /// The transport and dht management.
 pub struct Swarm {
@@ -34,6 +95,10 @@ pub struct Swarm {
     /// Swarm tansport.
     pub(crate) transport: Arc<SwarmTransport>,
     callback: RwLock<SharedSwarmCallback>,
+    keepalive: Option<KeepAliveConfig>,
+    stabilize_interval: Duration,
+    bootstrap_nodes: Vec<BootstrapEntry>,
+    idle_timeout: Option<Duration>,
 }
 
 impl Swarm {
@@ -42,6 +107,11 @@ impl Swarm {
         self.dht.did
     }
 
+    /// Get the [Session] which signs messages of self.
+    pub fn session(&self) -> Session {
+        self.transport.session_sk().session()
+    }
+
     /// Get DHT(Distributed Hash Table) of self.
     pub fn dht(&self) -> Arc<PeerRing> {
         self.dht.clone()
@@ -76,15 +146,197 @@ impl Swarm {
 
     /// Create [Stabilizer] for swarm.
     pub fn stabilizer(&self) -> Stabilizer {
-        Stabilizer::new(self.transport.clone())
+        let stabilizer = Stabilizer::new(self.transport.clone());
+        match self.callback() {
+            Ok(callback) => stabilizer.with_callback(callback),
+            Err(_) => stabilizer,
+        }
+    }
+
+    /// Get the interval between stabilization rounds, see [SwarmBuilder::stabilize_interval].
+    pub fn stabilize_interval(&self) -> Duration {
+        self.stabilize_interval
+    }
+
+    /// Get the seed peers to join the network through, see [SwarmBuilder::bootstrap_nodes].
+    pub fn bootstrap_nodes(&self) -> &[BootstrapEntry] {
+        &self.bootstrap_nodes
+    }
+
+    /// Run a stabilization round immediately, without waiting for the interval.
+    /// It's useful when topology is known to be changed, or in tests.
+    pub async fn stabilize_now(&self) -> Result<()> {
+        self.stabilizer().stabilize().await
+    }
+
+    /// Close connections idle for longer than [SwarmBuilder::idle_timeout], with a
+    /// [SwarmEvent::ConnectionStateChange] of `Closed` state. Successors, predecessor and
+    /// fingers are kept. Returns the count of closed connections.
+    pub async fn evict_idle_connections(&self) -> Result<usize> {
+        let Some(idle_timeout) = self.idle_timeout else {
+            return Ok(0);
+        };
+
+        let TopoInfo {
+            successors,
+            predecessor,
+        } = TopoInfo::try_from(self.dht.as_ref())?;
+        let mut protected: HashSet<Did> = successors.into_iter().chain(predecessor).collect();
+        protected.extend(self.dht.lock_finger()?.list().iter().flatten());
+
+        let now = get_epoch_ms();
+        let mut evicted = 0;
+        for peer in self.transport.get_connection_ids() {
+            if protected.contains(&peer) {
+                continue;
+            }
+            let Some(last_active) = self.transport.last_active(peer) else {
+                continue;
+            };
+            if now.saturating_sub(last_active) <= idle_timeout.as_millis() {
+                continue;
+            }
+
+            tracing::info!("Close connection to {peer} for being idle");
+            if let Err(e) = self.transport.disconnect(peer).await {
+                tracing::error!("Failed on close connection {peer}: {e:?}");
+                continue;
+            }
+            evicted += 1;
+            metrics::IDLE_CONNECTIONS_EVICTED.inc();
+
+            let event = SwarmEvent::ConnectionStateChange {
+                peer,
+                state: WebrtcConnectionState::Closed,
+            };
+            if let Err(e) = self.callback()?.on_event(&event).await {
+                tracing::error!("Failed to handle event {event:?}: {e:?}");
+            }
+        }
+        Ok(evicted)
+    }
+
+    /// Run keepalive once. Idle connections are closed if [SwarmBuilder::idle_timeout] is set.
+    /// Each connected peer is pinged, and the ones missing pongs for
+    /// `miss_threshold` intervals are disconnected with a [SwarmEvent::ConnectionStateChange]
+    /// of `Failed` state. Pings are skipped if keepalive is not enabled.
+    pub async fn keepalive(&self) -> Result<()> {
+        self.evict_idle_connections().await?;
+        self.transport
+            .prune_pongs(&self.transport.get_connection_ids());
+        let Some(config) = self.keepalive else {
+            return Ok(());
+        };
+        let max_silence = config.interval.as_millis() * config.miss_threshold as u128;
+        let now = get_epoch_ms();
+
+        for (peer, conn) in self.transport.get_connections() {
+            if conn.connection.webrtc_connection_state() != WebrtcConnectionState::Connected {
+                continue;
+            }
+
+            if now.saturating_sub(self.transport.last_pong(peer)) > max_silence {
+                tracing::warn!(
+                    "Peer {peer} missed {} keepalive pongs",
+                    config.miss_threshold
+                );
+                self.transport.reconnects.watch(peer, &self.dht);
+                if let Err(e) = self.transport.disconnect(peer).await {
+                    tracing::error!("Failed on close connection {peer}: {e:?}");
+                }
+
+                let event = SwarmEvent::ConnectionStateChange {
+                    peer,
+                    state: WebrtcConnectionState::Failed,
+                };
+                if let Err(e) = self.callback()?.on_event(&event).await {
+                    tracing::error!("Failed to handle event {event:?}: {e:?}");
+                }
+                continue;
+            }
+
+            // Use the sending time as nonce, so that the round-trip time can be measured by pong.
+            if let Err(e) = conn.send_ping(now as u64).await {
+                tracing::debug!("Failed to ping {peer}: {e:?}");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the round-trip time to a connected peer, measured by the last keepalive ping.
+    /// Returns None if keepalive is not enabled or no pong is received yet.
+    pub async fn peer_latency(&self, did: Did) -> Option<Duration> {
+        self.transport.latency(did)
+    }
+
+    /// Get the traffic statistics of a peer. The current counters are reset on reconnection,
+    /// while the total counters are kept. Returns None if no traffic with the peer is recorded.
+    pub async fn peer_stats(&self, did: Did) -> Option<PeerStats> {
+        self.transport.peer_stats(did)
+    }
+
+    /// Get the webrtc stats of the connection to a peer, such as the current rtt and bytes
+    /// transferred on the selected candidate pair, which help to diagnose flaky connections.
+    /// Return None if the connection has no candidate pair yet.
+    pub async fn connection_stats(&self, did: Did) -> Result<Option<ConnectionStats>> {
+        let conn = self
+            .transport
+            .get_connection(did)
+            .ok_or(Error::SwarmMissDidInTable(did))?;
+        Ok(conn.connection_stats().await)
+    }
+
+    /// Get the progress of a large message being received, as the ratio of received chunks.
+    /// Returns None if no chunk of it is pending, including the message is completed.
+    pub async fn chunk_progress(&self, id: uuid::Uuid) -> Option<f32> {
+        self.transport.chunk_progress(id).await
+    }
+
+    /// Get the metadata a connected peer advertised on handshake.
+    /// Returns None if the peer sent no metadata.
+    pub async fn peer_metadata(&self, did: Did) -> Option<HashMap<String, String>> {
+        self.transport.peer_metadata(did)
     }
 
     /// Disconnect a connection. There are three steps:
     /// 1) remove from DHT;
     /// 2) remove from Transport;
     /// 3) close the connection;
+    ///
+    /// A [SwarmEvent::ConnectionStateChange] with `Closed` state will be emitted after that,
+    /// since closing locally is not always reported by the underlying transport.
+    /// Returns [Error::PeerNotFound] if the peer is not connected.
     pub async fn disconnect(&self, peer: Did) -> Result<()> {
-        self.transport.disconnect(peer).await
+        if self.transport.get_connection(peer).is_none() {
+            return Err(Error::PeerNotFound(peer));
+        }
+        self.transport.reconnects.dismiss(peer);
+        self.transport.disconnect(peer).await?;
+
+        let event = SwarmEvent::ConnectionStateChange {
+            peer,
+            state: WebrtcConnectionState::Closed,
+        };
+        if let Err(e) = self.callback()?.on_event(&event).await {
+            tracing::error!("Failed to handle event {event:?}: {e:?}");
+        }
+
+        Ok(())
+    }
+
+    /// Replace the ice servers without restart, such as rotating TURN credentials before they
+    /// expire. Established connections keep running, only new candidate gathering uses the
+    /// new servers. Returns error if relay fallback is enabled but no TURN server is given.
+    pub async fn update_ice_servers(&self, ice_servers: Vec<IceServer>) -> Result<()> {
+        self.transport.update_ice_servers(ice_servers).await
+    }
+
+    /// Dump full SDP and candidates of handshakes in debug logs, for debugging interop in the
+    /// field. It's disabled by default since SDP may contain IP addresses, and can also be
+    /// enabled by setting environment variable [DEBUG_SDP_ENV](crate::consts::DEBUG_SDP_ENV).
+    pub fn set_debug_sdp(&self, enable: bool) {
+        self.transport.set_debug_sdp(enable)
     }
 
     /// Connect a given Did. If the did is already connected, return directly,
@@ -94,15 +346,237 @@ impl Swarm {
         if peer == self.did() {
             return Err(Error::ShouldNotConnectSelf);
         }
+        if self.transport.is_shutting_down() {
+            return Err(Error::SwarmShuttingDown);
+        }
         self.transport.connect(peer, self.inner_callback()?).await
     }
 
+    /// Wait for the data channel of the connection to peer to be open.
+    /// Returns false if the peer is not connected, or the connection is not ready in time,
+    /// in which case it's closed.
+    pub async fn wait_connected(&self, peer: Did) -> bool {
+        self.transport
+            .get_and_check_connection(peer)
+            .await
+            .is_some()
+    }
+
     /// Send [Message] to peer.
     pub async fn send_message(&self, msg: Message, destination: Did) -> Result<uuid::Uuid> {
+        if self.transport.is_shutting_down() {
+            return Err(Error::SwarmShuttingDown);
+        }
+        let is_custom = matches!(
+            msg,
+            Message::CustomMessage(_) | Message::EncryptedMessage(_) | Message::ReliableMessage(_)
+        );
+        if is_custom && destination == self.did() {
+            return self.send_loopback(msg).await;
+        }
         self.transport.send_message(msg, destination).await
     }
 
-    /// List peers and their connection status.
+    /// Open an additional data channel of label on the connection to a peer, so that a stream
+    /// of messages, such as a bulk transfer, doesn't block the others. Labels of the default
+    /// channels are reserved. Send on it by [Swarm::send_message_on].
+    pub async fn open_channel(
+        &self,
+        peer: Did,
+        label: &str,
+        config: DataChannelConfig,
+    ) -> Result<Arc<DataChannel>> {
+        let conn = self
+            .transport
+            .get_connection(peer)
+            .ok_or(Error::SwarmMissDidInTable(peer))?;
+        conn.open_channel(label, config).await
+    }
+
+    /// Send [Message] to destination on the data channel of label, which is opened on the
+    /// connection to the next hop by either peer, see [Swarm::open_channel].
+    pub async fn send_message_on(
+        &self,
+        label: &str,
+        msg: Message,
+        destination: Did,
+    ) -> Result<uuid::Uuid> {
+        if self.transport.is_shutting_down() {
+            return Err(Error::SwarmShuttingDown);
+        }
+        self.transport
+            .send_message_on(label, msg, destination)
+            .await
+    }
+
+    /// Deliver a message addressed to this node to its callback directly,
+    /// without serializing it or sending it on any connection.
+    /// Like messages sent to remote nodes, errors of handling it are not returned.
+    async fn send_loopback(&self, msg: Message) -> Result<uuid::Uuid> {
+        let did = self.did();
+        let payload = MessagePayload::new_send(msg, self.transport.session_sk(), did, did)?;
+        let tx_id = payload.transaction.tx_id;
+        metrics::MESSAGES_LOOPED_BACK.inc();
+        if let Err(e) = self.inner_callback()?.handle_local_payload(&payload).await {
+            tracing::error!("Failed to handle loopback message {tx_id}: {e}");
+        }
+        Ok(tx_id)
+    }
+
+    /// Gracefully shut down the swarm. There are three steps:
+    /// 1) stop handling inbound messages and refuse to send new messages;
+    /// 2) wait for the messages being handled, so that their reports can be flushed;
+    /// 3) send [LeaveDht] to each connected peer as a goodbye, then close the connection.
+    ///
+    /// Connections that cannot say goodbye before `timeout` are closed directly, so are the
+    /// connections to peers of earlier versions, which cannot decode [LeaveDht].
+    /// Returns the count of these force-closed connections.
+    pub async fn shutdown(&self, timeout: Duration) -> usize {
+        let deadline = self.drain(timeout).await;
+        self.say_goodbye(deadline, None).await
+    }
+
+    /// Leave the ring gracefully, so that the data stored by this node is not orphaned.
+    /// It works like [Swarm::shutdown], except that the goodbye messages, [LeaveDhtHandoff],
+    /// also:
+    /// 1) hand off the local virtual nodes to the successor, which stores them after removing
+    ///    this node from its DHT;
+    /// 2) tell the predecessor about the successor, so that the gap is filled without waiting
+    ///    for stabilization.
+    ///
+    /// Virtual nodes are removed locally once handed off, and kept if that fails or the
+    /// successor is of an earlier version that cannot decode [LeaveDhtHandoff].
+    /// Returns the count of connections closed without a goodbye.
+    pub async fn leave(&self) -> usize {
+        let deadline = self.drain(Duration::from_millis(LEAVE_TIMEOUT_MS)).await;
+        let handoff = match self.handoff().await {
+            Ok(handoff) => handoff,
+            Err(e) => {
+                tracing::error!("Failed to prepare handoff: {e:?}");
+                None
+            }
+        };
+        self.say_goodbye(deadline, handoff).await
+    }
+
+    /// Stop handling inbound messages, and wait for the ones being handled until `timeout`.
+    /// Returns the deadline in ms.
+    async fn drain(&self, timeout: Duration) -> u128 {
+        self.transport.start_shutdown();
+
+        let deadline = get_epoch_ms() + timeout.as_millis();
+        while self.transport.inflight.load(Ordering::SeqCst) > 0 && get_epoch_ms() < deadline {
+            sleep(Duration::from_millis(50)).await;
+        }
+        deadline
+    }
+
+    /// Collect the data to hand off when leaving the ring.
+    /// Returns None if there is no successor to take it.
+    async fn handoff(&self) -> Result<Option<Handoff>> {
+        let successors = self.dht.successors();
+        if successors.is_empty()? {
+            return Ok(None);
+        }
+        let successor = successors.min()?;
+        let predecessor = *self.dht.lock_predecessor()?;
+        let data = self
+            .dht
+            .storage
+            .get_all()
+            .await?
+            .into_iter()
+            .map(|(_, vnode)| vnode)
+            .filter(|vnode| !self.dht.is_expired(vnode))
+            .collect();
+        Ok(Some(Handoff {
+            successor,
+            predecessor,
+            data,
+        }))
+    }
+
+    /// Send [LeaveDht], or [LeaveDhtHandoff] with the handoff to it, to each connected peer
+    /// before `deadline`, then close the connection.
+    /// Peers of earlier versions cannot decode either of them, their connections are closed
+    /// without a goodbye, and the data is kept for them.
+    /// Returns the count of connections failed to say goodbye.
+    async fn say_goodbye(&self, deadline: u128, mut handoff: Option<Handoff>) -> usize {
+        let mut force_closed = 0;
+        for peer in self.transport.get_connection_ids() {
+            if self.transport.peer_version(peer) < PAYLOAD_VERSION {
+                if let Err(e) = self.transport.disconnect(peer).await {
+                    tracing::error!("Failed on close connection {peer}: {e:?}");
+                }
+                continue;
+            }
+
+            let mut handed_off = vec![];
+            let mut msg = Message::LeaveDht(LeaveDht { did: self.did() });
+            if let Some(handoff) = handoff.as_mut() {
+                if peer == handoff.successor {
+                    let data = std::mem::take(&mut handoff.data);
+                    handed_off = data.iter().map(|vnode| vnode.did).collect();
+                    msg = Message::LeaveDhtHandoff(LeaveDhtHandoff {
+                        did: self.did(),
+                        successor: None,
+                        data,
+                    });
+                } else if Some(peer) == handoff.predecessor {
+                    msg = Message::LeaveDhtHandoff(LeaveDhtHandoff {
+                        did: self.did(),
+                        successor: Some(handoff.successor),
+                        data: vec![],
+                    });
+                }
+            }
+
+            let remaining = deadline.saturating_sub(get_epoch_ms()) as u64;
+            let goodbye = self.transport.send_direct_message(msg, peer);
+            let wait = sleep(Duration::from_millis(remaining));
+
+            match select(Box::pin(goodbye), Box::pin(wait)).await {
+                Either::Left((Ok(_), _)) => {
+                    for vid in handed_off {
+                        if let Err(e) = self.dht.storage.remove(&vid.to_string()).await {
+                            tracing::error!("Failed to remove handed off vnode {vid}: {e:?}");
+                        }
+                    }
+                }
+                Either::Left((Err(e), _)) => {
+                    tracing::warn!("Failed to say goodbye to {peer}: {e:?}");
+                    force_closed += 1;
+                }
+                Either::Right(_) => {
+                    tracing::warn!("Timeout on saying goodbye to {peer}");
+                    force_closed += 1;
+                }
+            }
+
+            if let Err(e) = self.transport.disconnect(peer).await {
+                tracing::error!("Failed on close connection {peer}: {e:?}");
+            }
+        }
+
+        force_closed
+    }
+
+    /// Send data to peer as an [EncryptedMessage], which only the destination can decrypt.
+    /// The destination should have sent a message to us before, so that its session key is known.
+    pub async fn send_encrypted_message(&self, msg: &[u8], destination: Did) -> Result<uuid::Uuid> {
+        let encrypted = self.transport.encrypt_for(destination, msg)?;
+        self.send_message(Message::EncryptedMessage(encrypted), destination)
+            .await
+    }
+
+    /// Decrypt an [EncryptedMessage] sent to us.
+    pub fn decrypt_message(&self, msg: &EncryptedMessage) -> Result<CustomMessage> {
+        let data = self.transport.session_sk().decrypt(&msg.0)?;
+        Ok(CustomMessage(data))
+    }
+
+    /// List peers and their connection status. The webrtc stats of connections are not
+    /// collected, see [Swarm::connection_stats].
     pub fn peers(&self) -> Vec<ConnectionInspect> {
         self.transport
             .get_connections()
@@ -110,6 +584,14 @@ impl Swarm {
             .map(|(did, c)| ConnectionInspect {
                 did: did.to_string(),
                 state: format!("{:?}", c.webrtc_connection_state()),
+                ice_state: format!("{:?}", c.webrtc_ice_connection_state()),
+                data_channel_ready: c.is_data_channel_open(),
+                latency_ms: self
+                    .transport
+                    .latency(*did)
+                    .map(|rtt| rtt.as_millis() as u64),
+                stats: self.transport.peer_stats(*did),
+                connection_stats: None,
             })
             .collect()
     }
@@ -143,10 +625,17 @@ impl Swarm {
 
     /// Answer the offer of remote connection. This function will verify the answer payload and
     /// will wrap the answer inside a payload with verification.
+    /// If the offer is denied by [ConnectionGater], a signed [ConnectNodeReject] is returned
+    /// instead, which fails [Swarm::accept_answer] of the remote. The offer is dropped with
+    /// [Error::InboundConnectionDenied] if the remote is not known to decode the reject,
+    /// see [SwarmTransport::peer_version].
     pub async fn answer_offer(&self, offer_payload: MessagePayload) -> Result<MessagePayload> {
-        if !offer_payload.verify() {
-            return Err(Error::VerifySignatureFailed);
+        if !offer_payload.verify_with(self.transport.clock()) {
+            let signer = offer_payload.transaction.signer();
+            tracing::warn!("Cannot verify offer signature of {signer}");
+            return Err(Error::VerifyMessageSignatureFailed(signer));
         }
+        self.transport.check_handshake_replay(&offer_payload)?;
 
         let Message::ConnectNodeSend(msg) = offer_payload.transaction.data()? else {
             return Err(Error::InvalidMessage(
@@ -155,6 +644,25 @@ impl Swarm {
         };
 
         let peer = offer_payload.transaction.signer();
+        match self.transport.check_inbound(&offer_payload).await {
+            Ok(()) => {}
+            // Peers of earlier versions cannot decode the reject, the offer is dropped for them.
+            Err(Error::InboundConnectionDenied(_))
+                if self.transport.peer_version(peer) >= PAYLOAD_VERSION =>
+            {
+                let reject = ConnectNodeReject {
+                    reason: ConnectRejectReason::Denied,
+                };
+                return MessagePayload::new_send(
+                    Message::ConnectNodeReject(reject),
+                    self.transport.session_sk(),
+                    peer,
+                    peer,
+                );
+            }
+            Err(e) => return Err(e),
+        }
+
         let answer_msg = self
             .transport
             .answer_remote_connection(peer, self.inner_callback()?, &msg)
@@ -175,17 +683,29 @@ impl Swarm {
     /// Accept the answer of remote connection. This function will verify the answer payload and
     /// will return its did with the connection.
     pub async fn accept_answer(&self, answer_payload: MessagePayload) -> Result<()> {
-        if !answer_payload.verify() {
-            return Err(Error::VerifySignatureFailed);
+        if !answer_payload.verify_with(self.transport.clock()) {
+            let signer = answer_payload.transaction.signer();
+            tracing::warn!("Cannot verify answer signature of {signer}");
+            return Err(Error::VerifyMessageSignatureFailed(signer));
         }
+        self.transport.check_handshake_replay(&answer_payload)?;
 
-        let Message::ConnectNodeReport(ref msg) = answer_payload.transaction.data()? else {
-            return Err(Error::InvalidMessage(
-                "Should be ConnectNodeReport".to_string(),
-            ));
+        let peer = answer_payload.transaction.signer();
+        let msg = match answer_payload.transaction.data()? {
+            Message::ConnectNodeReport(msg) => msg,
+            Message::ConnectNodeReject(reject) => {
+                if self.transport.get_connection(peer).is_some() {
+                    self.transport.disconnect(peer).await?;
+                }
+                return Err(Error::ConnectionRejected(reject.reason));
+            }
+            _ => {
+                return Err(Error::InvalidMessage(
+                    "Should be ConnectNodeReport".to_string(),
+                ))
+            }
         };
 
-        let peer = answer_payload.transaction.signer();
-        self.transport.accept_remote_connection(peer, msg).await
+        self.transport.accept_remote_connection(peer, &msg).await
     }
 }
@@ -0,0 +1,157 @@
+//! Prioritized sending of messages on a connection.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::task::Context;
+use std::task::Poll;
+use std::task::Waker;
+
+use crate::message::Priority;
+
+/// Serializes sending on a connection. When the connection is busy, waiting senders of
+/// [Priority::Control] go before those of [Priority::App], so that DHT maintenance is not
+/// stuck behind large application messages. Senders of the same priority are not ordered.
+#[derive(Default)]
+pub(crate) struct SendQueue {
+    state: Arc<Mutex<QueueState>>,
+}
+
+#[derive(Default)]
+struct QueueState {
+    sending: bool,
+    control_waiting: usize,
+    wakers: Vec<Waker>,
+}
+
+impl QueueState {
+    fn wake_all(&mut self) {
+        for waker in self.wakers.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+/// Permission to send on the connection, released on drop.
+pub(crate) struct SendPermit {
+    state: Arc<Mutex<QueueState>>,
+}
+
+/// Future of [SendQueue::acquire].
+pub(crate) struct Acquire {
+    state: Arc<Mutex<QueueState>>,
+    priority: Priority,
+    waiting: bool,
+}
+
+impl SendQueue {
+    /// Wait for the turn to send a message of priority.
+    pub fn acquire(&self, priority: Priority) -> Acquire {
+        Acquire {
+            state: self.state.clone(),
+            priority,
+            waiting: false,
+        }
+    }
+}
+
+impl Future for Acquire {
+    type Output = SendPermit;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let mut state = this.state.lock().unwrap_or_else(|e| e.into_inner());
+
+        let is_control = this.priority == Priority::Control;
+        let others_first = !is_control && state.control_waiting > 0;
+        if !state.sending && !others_first {
+            state.sending = true;
+            if this.waiting && is_control {
+                state.control_waiting -= 1;
+            }
+            this.waiting = false;
+            return Poll::Ready(SendPermit {
+                state: this.state.clone(),
+            });
+        }
+
+        if !this.waiting && is_control {
+            state.control_waiting += 1;
+        }
+        this.waiting = true;
+        state.wakers.push(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for Acquire {
+    fn drop(&mut self) {
+        if self.waiting && self.priority == Priority::Control {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            state.control_waiting -= 1;
+            // App senders may be waiting for this one.
+            state.wake_all();
+        }
+    }
+}
+
+impl Drop for SendPermit {
+    fn drop(&mut self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.sending = false;
+        state.wake_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::task::Wake;
+
+    use super::*;
+
+    struct Noop;
+
+    impl Wake for Noop {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    fn poll(fut: &mut Acquire) -> Option<SendPermit> {
+        let waker = Waker::from(Arc::new(Noop));
+        match Pin::new(fut).poll(&mut Context::from_waker(&waker)) {
+            Poll::Ready(permit) => Some(permit),
+            Poll::Pending => None,
+        }
+    }
+
+    #[test]
+    fn test_control_goes_first() {
+        let queue = SendQueue::default();
+
+        let permit = poll(&mut queue.acquire(Priority::App)).unwrap();
+        let mut app = queue.acquire(Priority::App);
+        let mut control = queue.acquire(Priority::Control);
+        assert!(poll(&mut app).is_none());
+        assert!(poll(&mut control).is_none());
+
+        drop(permit);
+        assert!(poll(&mut app).is_none());
+        let permit = poll(&mut control).unwrap();
+        assert!(poll(&mut app).is_none());
+
+        drop(permit);
+        assert!(poll(&mut app).is_some());
+    }
+
+    #[test]
+    fn test_cancelled_control_unblocks_app() {
+        let queue = SendQueue::default();
+
+        let permit = poll(&mut queue.acquire(Priority::App)).unwrap();
+        let mut control = queue.acquire(Priority::Control);
+        assert!(poll(&mut control).is_none());
+        drop(permit);
+        drop(control);
+        assert!(poll(&mut queue.acquire(Priority::App)).is_some());
+    }
+}
@@ -0,0 +1,138 @@
+//! Session public keys of remote peers.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::dht::Did;
+use crate::ecc::PublicKey;
+use crate::error::Result;
+
+struct Entry {
+    session_id: Did,
+    pubkey: PublicKey<33>,
+    used: u64,
+}
+
+/// A bounded cache of session public keys of peers, used to encrypt data to them.
+/// The key is recovered from a signature only when a peer starts a new session.
+/// When the cache is full, the least recently used peer is evicted.
+pub(crate) struct SessionPubkeys {
+    capacity: usize,
+    inner: Mutex<(u64, HashMap<Did, Entry>)>,
+}
+
+impl SessionPubkeys {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::new((0, HashMap::new())),
+        }
+    }
+
+    /// Record the session of peer. `recover` is called to get the public key of session
+    /// only if it differs from the recorded one.
+    pub fn record(
+        &self,
+        peer: Did,
+        session_id: Did,
+        recover: impl FnOnce() -> Result<PublicKey<33>>,
+    ) -> Result<()> {
+        if self.capacity == 0 {
+            return Ok(());
+        }
+
+        let Ok(mut guard) = self.inner.lock() else {
+            return Ok(());
+        };
+        let (tick, peers) = &mut *guard;
+        *tick += 1;
+
+        if let Some(entry) = peers.get_mut(&peer) {
+            if entry.session_id == session_id {
+                entry.used = *tick;
+                return Ok(());
+            }
+        }
+
+        let pubkey = recover()?;
+        peers.insert(peer, Entry {
+            session_id,
+            pubkey,
+            used: *tick,
+        });
+        if peers.len() > self.capacity {
+            let evicted = peers
+                .iter()
+                .min_by_key(|(_, entry)| entry.used)
+                .map(|(did, _)| *did);
+            if let Some(did) = evicted {
+                peers.remove(&did);
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the session public key of peer.
+    pub fn get(&self, peer: Did) -> Option<PublicKey<33>> {
+        let mut guard = self.inner.lock().ok()?;
+        let (tick, peers) = &mut *guard;
+        *tick += 1;
+        let entry = peers.get_mut(&peer)?;
+        entry.used = *tick;
+        Some(entry.pubkey)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+    use crate::ecc::SecretKey;
+
+    #[test]
+    fn test_recover_once_per_session() {
+        let cache = SessionPubkeys::new(2);
+        let peer = SecretKey::random().address().into();
+        let (sk1, sk2) = (SecretKey::random(), SecretKey::random());
+        let recovered = Cell::new(0);
+        let record = |sk: &SecretKey| {
+            cache.record(peer, sk.address().into(), || {
+                recovered.set(recovered.get() + 1);
+                Ok(sk.pubkey())
+            })
+        };
+
+        record(&sk1).unwrap();
+        record(&sk1).unwrap();
+        assert_eq!(recovered.get(), 1);
+        assert_eq!(cache.get(peer), Some(sk1.pubkey()));
+
+        // A new session of peer replaces the old one.
+        record(&sk2).unwrap();
+        assert_eq!(recovered.get(), 2);
+        assert_eq!(cache.get(peer), Some(sk2.pubkey()));
+    }
+
+    #[test]
+    fn test_evict_least_recently_used() {
+        let cache = SessionPubkeys::new(2);
+        let keys = [
+            SecretKey::random(),
+            SecretKey::random(),
+            SecretKey::random(),
+        ];
+        let did = |sk: &SecretKey| -> Did { sk.address().into() };
+        let record = |sk: &SecretKey| cache.record(did(sk), did(sk), || Ok(sk.pubkey()));
+
+        record(&keys[0]).unwrap();
+        record(&keys[1]).unwrap();
+        // keys[0] is used after keys[1], so keys[1] is evicted.
+        assert!(cache.get(did(&keys[0])).is_some());
+        record(&keys[2]).unwrap();
+
+        assert!(cache.get(did(&keys[0])).is_some());
+        assert!(cache.get(did(&keys[1])).is_none());
+        assert!(cache.get(did(&keys[2])).is_some());
+    }
+}
@@ -0,0 +1,353 @@
+//! Reconnection of important peers, such as bootstrap nodes and fingers, after they disconnect.
+//!
+//! When a connection is closed or failed, the peer is watched if it's important at that moment,
+//! and [Reconnector] dials it with exponential backoff. The delay of each attempt is doubled
+//! from [ReconnectPolicy::initial_backoff] up to [ReconnectPolicy::max_backoff], and jittered
+//! to a random point in its upper half, so that peers losing a common node don't dial it at once.
+//! Peers disconnected by [Swarm::disconnect] are not reconnected.
+//!
+//! A lost peer is dialed by [Swarm::connect], which signals over dht. A bootstrap node may be
+//! the only connection of this node though, so it's dialed through its signaling endpoint by
+//! the [BootstrapDialer] of [Reconnector] if any.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use dashmap::DashMap;
+use dashmap::DashSet;
+use rand::Rng;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::consts::DEFAULT_RECONNECT_INITIAL_BACKOFF_MS;
+use crate::consts::DEFAULT_RECONNECT_MAX_BACKOFF_MS;
+use crate::consts::RECONNECT_CHECK_INTERVAL_MS;
+use crate::dht::Did;
+use crate::dht::PeerRing;
+use crate::dht::TopoInfo;
+use crate::error::Error;
+use crate::swarm::BootstrapEntry;
+use crate::swarm::Swarm;
+use crate::utils::get_epoch_ms;
+
+/// Shared [BootstrapDialer] trait object.
+#[cfg(feature = "wasm")]
+pub type SharedBootstrapDialer = Arc<dyn BootstrapDialer>;
+
+/// Shared [BootstrapDialer] trait object.
+#[cfg(not(feature = "wasm"))]
+pub type SharedBootstrapDialer = Arc<dyn BootstrapDialer + Send + Sync>;
+
+/// Dial bootstrap nodes through their signaling endpoints, such as by http in rings-node.
+#[cfg_attr(feature = "wasm", async_trait(?Send))]
+#[cfg_attr(not(feature = "wasm"), async_trait)]
+pub trait BootstrapDialer {
+    /// Connect to the bootstrap node through its endpoint. Returns false if failed.
+    async fn dial(&self, node: &BootstrapEntry) -> bool;
+}
+
+/// Policy of reconnecting important peers,
+/// see [SwarmBuilder::reconnect](super::SwarmBuilder::reconnect).
+#[derive(Debug, Clone, Copy)]
+pub struct ReconnectPolicy {
+    /// Delay before the first attempt.
+    pub initial_backoff: Duration,
+    /// Cap of the delay between attempts.
+    pub max_backoff: Duration,
+    /// Count of attempts before giving up, retry forever if not provided.
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_millis(DEFAULT_RECONNECT_INITIAL_BACKOFF_MS),
+            max_backoff: Duration::from_millis(DEFAULT_RECONNECT_MAX_BACKOFF_MS),
+            max_attempts: None,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Delay before attempt `attempts`, counted from zero, with jitter applied.
+    pub fn backoff(&self, attempts: u32) -> Duration {
+        let delay = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempts))
+            .min(self.max_backoff);
+        let half = delay / 2;
+        let jitter = rand::thread_rng().gen_range(0..=half.as_millis() as u64);
+        half + Duration::from_millis(jitter)
+    }
+}
+
+/// Retry state of a peer being reconnected.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReconnectState {
+    /// The peer to reconnect.
+    pub peer: Did,
+    /// Count of attempts made so far.
+    pub attempts: u32,
+    /// Time in ms of the next attempt.
+    pub next_attempt_at_ms: u128,
+}
+
+/// Peers watched for reconnection, held by transport so that connection callbacks can reach it.
+#[derive(Default)]
+pub(crate) struct Reconnects {
+    policy: Option<ReconnectPolicy>,
+    bootstrap_nodes: HashSet<Did>,
+    states: DashMap<Did, ReconnectState>,
+    dismissed: DashSet<Did>,
+}
+
+impl Reconnects {
+    pub fn new(policy: ReconnectPolicy, bootstrap_nodes: impl IntoIterator<Item = Did>) -> Self {
+        Self {
+            policy: Some(policy),
+            bootstrap_nodes: bootstrap_nodes.into_iter().collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Whether the peer is a bootstrap node, successor, predecessor or finger of dht.
+    fn is_important(&self, peer: Did, dht: &PeerRing) -> bool {
+        if self.bootstrap_nodes.contains(&peer) {
+            return true;
+        }
+        let Ok(TopoInfo {
+            successors,
+            predecessor,
+        }) = TopoInfo::try_from(dht)
+        else {
+            return false;
+        };
+        if successors.contains(&peer) || predecessor == Some(peer) {
+            return true;
+        }
+        dht.lock_finger()
+            .map(|finger| finger.list().iter().flatten().any(|d| *d == peer))
+            .unwrap_or(false)
+    }
+
+    /// Schedule reconnecting a lost peer if it's important. It should be called before the peer
+    /// is removed from dht. A peer already scheduled keeps its state.
+    pub fn watch(&self, peer: Did, dht: &PeerRing) {
+        let Some(policy) = self.policy else {
+            return;
+        };
+        if self.dismissed.contains(&peer) || !self.is_important(peer, dht) {
+            return;
+        }
+        self.states.entry(peer).or_insert_with(|| {
+            tracing::info!("Watch {peer} for reconnection");
+            ReconnectState {
+                peer,
+                attempts: 0,
+                next_attempt_at_ms: get_epoch_ms() + policy.backoff(0).as_millis(),
+            }
+        });
+    }
+
+    /// Stop reconnecting a peer disconnected deliberately, until it's connected again.
+    pub fn dismiss(&self, peer: Did) {
+        self.dismissed.insert(peer);
+        self.states.remove(&peer);
+    }
+
+    /// Forget a peer once it's connected.
+    pub fn connected(&self, peer: Did) {
+        self.dismissed.remove(&peer);
+        self.states.remove(&peer);
+    }
+
+    /// Peers due to an attempt at `now_ms`. Their next attempts are scheduled by backoff,
+    /// and the ones running out of attempts are dropped.
+    pub fn take_due(&self, now_ms: u128) -> Vec<Did> {
+        let Some(policy) = self.policy else {
+            return vec![];
+        };
+        let mut due = vec![];
+        self.states.retain(|peer, state| {
+            if state.next_attempt_at_ms > now_ms {
+                return true;
+            }
+            if policy
+                .max_attempts
+                .map_or(false, |max| state.attempts >= max)
+            {
+                tracing::warn!(
+                    "Give up reconnecting {peer} after {} attempts",
+                    state.attempts
+                );
+                return false;
+            }
+            due.push(*peer);
+            state.attempts += 1;
+            state.next_attempt_at_ms = now_ms + policy.backoff(state.attempts).as_millis();
+            true
+        });
+        due
+    }
+
+    /// Retry states of watched peers.
+    pub fn states(&self) -> Vec<ReconnectState> {
+        self.states.iter().map(|s| *s.value()).collect()
+    }
+}
+
+impl Swarm {
+    /// Dial the watched peers due to reconnect. Peers connected already are forgotten.
+    /// Bootstrap nodes are dialed by `dialer` if provided, or over dht otherwise.
+    pub async fn reconnect_due(&self, dialer: Option<&SharedBootstrapDialer>) {
+        for peer in self.transport.reconnects.take_due(get_epoch_ms()) {
+            let connection = self.transport.get_connection(peer);
+            if connection.map_or(false, |c| c.is_data_channel_open()) {
+                self.transport.reconnects.connected(peer);
+                continue;
+            }
+            tracing::info!("Reconnect {peer}");
+            let bootstrap = self.bootstrap_nodes.iter().find(|n| n.did == peer);
+            if let (Some(node), Some(dialer)) = (bootstrap, dialer) {
+                if !dialer.dial(node).await {
+                    tracing::debug!("Failed to reconnect {peer} at {}", node.endpoint);
+                }
+                continue;
+            }
+            match self.connect(peer).await {
+                Ok(()) => {}
+                Err(Error::AlreadyConnected) => self.transport.reconnects.connected(peer),
+                Err(e) => tracing::debug!("Failed to reconnect {peer}: {e:?}"),
+            }
+        }
+    }
+
+    /// Retry state of each peer being reconnected,
+    /// see [SwarmBuilder::reconnect](super::SwarmBuilder::reconnect).
+    pub fn reconnect_states(&self) -> Vec<ReconnectState> {
+        self.transport.reconnects.states()
+    }
+}
+
+/// The reconnect supervisor, dialing lost important peers by [ReconnectPolicy].
+#[derive(Clone)]
+pub struct Reconnector {
+    swarm: Arc<Swarm>,
+    interval: Duration,
+    dialer: Option<SharedBootstrapDialer>,
+}
+
+impl Reconnector {
+    /// Create a new reconnect supervisor.
+    /// Returns None if reconnection is not enabled on the swarm.
+    pub fn new(swarm: Arc<Swarm>) -> Option<Self> {
+        swarm.transport.reconnects.policy?;
+        Some(Self {
+            swarm,
+            interval: Duration::from_millis(RECONNECT_CHECK_INTERVAL_MS),
+            dialer: None,
+        })
+    }
+
+    /// Dial lost bootstrap nodes through their endpoints by `dialer`, instead of over dht.
+    pub fn bootstrap_dialer(mut self, dialer: SharedBootstrapDialer) -> Self {
+        self.dialer = Some(dialer);
+        self
+    }
+}
+
+#[cfg(not(feature = "wasm"))]
+mod reconnector {
+    use futures_timer::Delay;
+
+    use super::*;
+
+    impl Reconnector {
+        /// Run reconnection in a loop.
+        pub async fn wait(self: Arc<Self>) {
+            loop {
+                Delay::new(self.interval).await;
+                self.swarm.reconnect_due(self.dialer.as_ref()).await;
+            }
+        }
+    }
+}
+
+#[cfg(feature = "wasm")]
+mod reconnector {
+    use wasm_bindgen_futures::spawn_local;
+
+    use super::*;
+    use crate::poll;
+
+    impl Reconnector {
+        /// Run reconnection in a loop.
+        pub async fn wait(self: Arc<Self>) {
+            let caller = Arc::clone(&self);
+            let func = move || {
+                let caller = caller.clone();
+                spawn_local(Box::pin(async move {
+                    caller.swarm.reconnect_due(caller.dialer.as_ref()).await;
+                }))
+            };
+            poll!(func, self.interval.as_millis().try_into().unwrap());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::storage::MemStorage;
+
+    fn policy() -> ReconnectPolicy {
+        ReconnectPolicy {
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(1000),
+            max_attempts: Some(2),
+        }
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = policy();
+        for (attempts, cap) in [(0, 100), (1, 200), (3, 800), (4, 1000), (40, 1000)] {
+            let delay = policy.backoff(attempts).as_millis();
+            assert!(delay >= cap / 2 && delay <= cap, "{attempts}: {delay}");
+        }
+    }
+
+    #[test]
+    fn test_watch_important_peers() {
+        let did = Did::from_str("0x11E807fcc88dD319270493fB2e822e388Fe36ab0").unwrap();
+        let bootstrap = Did::from_str("0x999999cf1046e68e36E1aA2E0E07105eDDD1f08E").unwrap();
+        let other = Did::from_str("0x0000000000000000000000000000000000000001").unwrap();
+        let dht = PeerRing::new_with_storage(did, 3, Box::new(MemStorage::new()));
+        let reconnects = Reconnects::new(policy(), [bootstrap]);
+
+        reconnects.watch(other, &dht);
+        reconnects.watch(bootstrap, &dht);
+        assert_eq!(reconnects.states().len(), 1);
+        assert!(reconnects.take_due(0).is_empty());
+
+        // Attempts are made until max_attempts is reached.
+        let far = get_epoch_ms() + 1_000_000;
+        assert_eq!(reconnects.take_due(far), vec![bootstrap]);
+        assert_eq!(reconnects.states()[0].attempts, 1);
+        assert_eq!(reconnects.take_due(far * 2), vec![bootstrap]);
+        assert!(reconnects.take_due(far * 4).is_empty());
+        assert!(reconnects.states().is_empty());
+
+        // Dismissed peers are not watched until connected again.
+        reconnects.dismiss(bootstrap);
+        reconnects.watch(bootstrap, &dht);
+        assert!(reconnects.states().is_empty());
+        reconnects.connected(bootstrap);
+        reconnects.watch(bootstrap, &dht);
+        assert_eq!(reconnects.states().len(), 1);
+    }
+}
@@ -0,0 +1,119 @@
+//! Reliable delivery of custom messages, by waiting for the [Ack] of the destination
+//! and resending on timeout.
+//!
+//! The delivery is at least once. A message resent after its ack is lost arrives again
+//! with the same tx_id, and is dropped by the deduplication of the destination, as long as
+//! the first one is still in its window. It's acked again anyway.
+
+use std::time::Duration;
+
+use futures::future::select;
+use futures::future::Either;
+
+use crate::consts::DEFAULT_ACK_MAX_ATTEMPTS;
+use crate::consts::DEFAULT_ACK_TIMEOUT_MS;
+use crate::dht::Did;
+use crate::error::Error;
+use crate::error::Result;
+use crate::message::Message;
+use crate::message::MessagePayload;
+use crate::message::MessageRelay;
+use crate::message::PayloadSender;
+use crate::message::ReliableMessage;
+use crate::message::Transaction;
+use crate::swarm::transport::SwarmTransport;
+use crate::swarm::Swarm;
+use crate::utils::sleep;
+
+/// Policy of resending a reliable message, see [Swarm::send_reliable].
+#[derive(Debug, Clone, Copy)]
+pub struct AckPolicy {
+    /// Time to wait for the ack of each attempt.
+    pub timeout: Duration,
+    /// Count of attempts, including the first one.
+    pub max_attempts: u32,
+}
+
+impl Default for AckPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_millis(DEFAULT_ACK_TIMEOUT_MS),
+            max_attempts: DEFAULT_ACK_MAX_ATTEMPTS,
+        }
+    }
+}
+
+impl Swarm {
+    /// Send data to destination as a [ReliableMessage], and wait for its ack.
+    /// It's resent with the same tx_id if the ack doesn't arrive in `policy.timeout`,
+    /// until `policy.max_attempts` is reached, then [Error::MessageNotAcked] is returned.
+    pub async fn send_reliable(
+        &self,
+        msg: &[u8],
+        destination: Did,
+        policy: AckPolicy,
+    ) -> Result<uuid::Uuid> {
+        if self.transport.is_shutting_down() {
+            return Err(Error::SwarmShuttingDown);
+        }
+        let msg = Message::ReliableMessage(ReliableMessage(msg.to_vec()));
+        if destination == self.did() {
+            return self.send_loopback(msg).await;
+        }
+
+        let session_sk = self.transport.session_sk();
+        let tx_id = uuid::Uuid::new_v4();
+        let transaction = Transaction::new(destination, tx_id, msg, session_sk)?;
+        let mut acked = self.transport.expect_ack(tx_id, destination);
+        let _pending = PendingAck {
+            transport: &self.transport,
+            tx_id,
+        };
+
+        for attempt in 1..=policy.max_attempts {
+            // The next hop may change between attempts.
+            let sent = self
+                .transport
+                .infer_next_hop(destination, None)
+                .and_then(|next_hop| {
+                    let relay =
+                        MessageRelay::new(vec![session_sk.account_did()], next_hop, destination)
+                            .with_ttl(self.transport.relay_ttl());
+                    MessagePayload::new(transaction.clone(), session_sk, relay)
+                });
+            match sent {
+                Ok(payload) => {
+                    if let Err(e) = self.transport.send_payload(payload).await {
+                        tracing::warn!("Failed to send reliable message {tx_id}: {e}");
+                    }
+                }
+                Err(e) => tracing::warn!("Failed to send reliable message {tx_id}: {e}"),
+            }
+
+            match select(&mut acked, Box::pin(sleep(policy.timeout))).await {
+                Either::Left((Ok(()), _)) => return Ok(tx_id),
+                Either::Left((Err(_), _)) => break,
+                Either::Right(_) => {
+                    tracing::debug!("Reliable message {tx_id} is not acked in attempt {attempt}")
+                }
+            }
+        }
+
+        Err(Error::MessageNotAcked {
+            tx_id,
+            attempts: policy.max_attempts,
+        })
+    }
+}
+
+/// Forget a reliable message when it's acked, given up or dropped while waiting.
+struct PendingAck<'a> {
+    transport: &'a SwarmTransport,
+    tx_id: uuid::Uuid,
+}
+
+impl Drop for PendingAck<'_> {
+    fn drop(&mut self) {
+        self.transport.forget_ack(self.tx_id);
+    }
+}
@@ -0,0 +1,92 @@
+//! Cache of sent chunked messages, for resuming interrupted transfers.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use crate::chunk::Chunk;
+use crate::dht::Did;
+
+/// Chunks of recently sent large messages. When a peer reconnects in the middle of a
+/// transfer, it asks for the chunks it missed, which are sent again from here.
+/// When the cache exceeds its capacity in bytes, the earliest sent message is evicted.
+pub(crate) struct SentChunks {
+    capacity: usize,
+    inner: Mutex<(VecDeque<(Did, Vec<Chunk>)>, usize)>,
+}
+
+impl SentChunks {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::new((VecDeque::new(), 0)),
+        }
+    }
+
+    /// Remember chunks of a message sent to peer.
+    pub fn insert(&self, peer: Did, chunks: Vec<Chunk>) {
+        let size: usize = chunks.iter().map(|c| c.data.len()).sum();
+        if chunks.is_empty() || size > self.capacity {
+            return;
+        }
+
+        let Ok(mut guard) = self.inner.lock() else {
+            return;
+        };
+        let (queue, total) = &mut *guard;
+        queue.push_back((peer, chunks));
+        *total += size;
+        while *total > self.capacity {
+            let Some((_, evicted)) = queue.pop_front() else {
+                break;
+            };
+            *total -= evicted.iter().map(|c| c.data.len()).sum::<usize>();
+        }
+    }
+
+    /// Get chunks of message `id` sent to peer.
+    pub fn get(&self, peer: Did, id: uuid::Uuid) -> Option<Vec<Chunk>> {
+        let guard = self.inner.lock().ok()?;
+        guard
+            .0
+            .iter()
+            .find(|(p, chunks)| *p == peer && chunks[0].meta.id == id)
+            .map(|(_, chunks)| chunks.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::ChunkList;
+    use crate::ecc::SecretKey;
+
+    #[test]
+    fn test_sent_chunks() {
+        let peer: Did = SecretKey::random().address().into();
+        let other: Did = SecretKey::random().address().into();
+        let cache = SentChunks::new(64);
+
+        let data = "a".repeat(40).into();
+        let chunks1: Vec<Chunk> = ChunkList::<16>::from(&data).into();
+        let id1 = chunks1[0].meta.id;
+        cache.insert(peer, chunks1);
+        assert_eq!(cache.get(peer, id1).unwrap().len(), 3);
+        assert!(cache.get(other, id1).is_none());
+
+        // Evict the earliest one when exceeding capacity
+        let data = "b".repeat(40).into();
+        let chunks2: Vec<Chunk> = ChunkList::<16>::from(&data).into();
+        let id2 = chunks2[0].meta.id;
+        cache.insert(peer, chunks2);
+        assert!(cache.get(peer, id1).is_none());
+        assert!(cache.get(peer, id2).is_some());
+
+        // Larger than capacity
+        let data = "c".repeat(80).into();
+        let chunks3: Vec<Chunk> = ChunkList::<16>::from(&data).into();
+        let id3 = chunks3[0].meta.id;
+        cache.insert(peer, chunks3);
+        assert!(cache.get(peer, id3).is_none());
+        assert!(cache.get(peer, id2).is_some());
+    }
+}
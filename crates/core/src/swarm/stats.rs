@@ -0,0 +1,144 @@
+//! Traffic statistics of peers.
+
+use std::time::Duration;
+
+use dashmap::DashMap;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::dht::Did;
+use crate::utils::get_epoch_ms;
+
+/// Counters of traffic between this node and a peer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrafficStats {
+    /// Bytes sent to the peer.
+    pub bytes_sent: u64,
+    /// Bytes received from the peer.
+    pub bytes_recv: u64,
+    /// Count of messages sent to the peer. Each chunk of a large message is counted.
+    pub messages_sent: u64,
+    /// Count of messages received from the peer. Each chunk of a large message is counted.
+    pub messages_recv: u64,
+}
+
+/// Traffic statistics of a peer, see [Swarm::peer_stats](super::Swarm::peer_stats).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerStats {
+    /// Traffic of the current connection, reset on reconnection.
+    pub current: TrafficStats,
+    /// Traffic of all connections to the peer.
+    pub total: TrafficStats,
+    /// Time since the current connection is established.
+    pub uptime: Duration,
+}
+
+#[derive(Default)]
+struct PeerStatsRecord {
+    current: TrafficStats,
+    total: TrafficStats,
+    connected_at_ms: u128,
+    last_active_ms: u128,
+}
+
+/// Traffic statistics of all peers.
+#[derive(Default)]
+pub(crate) struct PeerStatsTable {
+    inner: DashMap<Did, PeerStatsRecord>,
+}
+
+impl PeerStatsTable {
+    /// Start a new connection to peer, the current counters are reset.
+    pub fn reset(&self, peer: Did) {
+        let mut record = self.inner.entry(peer).or_default();
+        record.current = TrafficStats::default();
+        record.connected_at_ms = get_epoch_ms();
+        record.last_active_ms = record.connected_at_ms;
+    }
+
+    /// Record a message of `bytes` sent to peer.
+    pub fn record_sent(&self, peer: Did, bytes: usize) {
+        let mut entry = self.inner.entry(peer).or_default();
+        let record = &mut *entry;
+        for stats in [&mut record.current, &mut record.total] {
+            stats.bytes_sent += bytes as u64;
+            stats.messages_sent += 1;
+        }
+        record.last_active_ms = get_epoch_ms();
+    }
+
+    /// Record a message of `bytes` received from peer.
+    pub fn record_recv(&self, peer: Did, bytes: usize) {
+        let mut entry = self.inner.entry(peer).or_default();
+        let record = &mut *entry;
+        for stats in [&mut record.current, &mut record.total] {
+            stats.bytes_recv += bytes as u64;
+            stats.messages_recv += 1;
+        }
+        record.last_active_ms = get_epoch_ms();
+    }
+
+    /// Get the time in ms of the last message sent to or received from peer.
+    /// Keepalive pings are not counted.
+    pub fn last_active_ms(&self, peer: Did) -> Option<u128> {
+        self.inner.get(&peer).map(|r| r.last_active_ms)
+    }
+
+    /// Get statistics of peer. Returns None if no traffic is recorded.
+    pub fn get(&self, peer: Did) -> Option<PeerStats> {
+        let record = self.inner.get(&peer)?;
+        let uptime = match record.connected_at_ms {
+            0 => Duration::ZERO,
+            t => Duration::from_millis(get_epoch_ms().saturating_sub(t) as u64),
+        };
+        Some(PeerStats {
+            current: record.current,
+            total: record.total,
+            uptime,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecc::SecretKey;
+
+    #[test]
+    fn test_peer_stats_reset_keeps_total() {
+        let table = PeerStatsTable::default();
+        let peer: Did = SecretKey::random().address().into();
+        assert!(table.get(peer).is_none());
+        assert!(table.last_active_ms(peer).is_none());
+
+        table.reset(peer);
+        assert!(table.last_active_ms(peer).unwrap() > 0);
+        table.record_sent(peer, 10);
+        table.record_recv(peer, 20);
+        table.record_recv(peer, 5);
+        let stats = table.get(peer).unwrap();
+        assert_eq!(stats.current, TrafficStats {
+            bytes_sent: 10,
+            bytes_recv: 25,
+            messages_sent: 1,
+            messages_recv: 2,
+        });
+        assert_eq!(stats.current, stats.total);
+
+        // Reconnected
+        table.reset(peer);
+        table.record_sent(peer, 7);
+        let stats = table.get(peer).unwrap();
+        assert_eq!(stats.current, TrafficStats {
+            bytes_sent: 7,
+            messages_sent: 1,
+            ..Default::default()
+        });
+        assert_eq!(stats.total, TrafficStats {
+            bytes_sent: 17,
+            bytes_recv: 25,
+            messages_sent: 2,
+            messages_recv: 2,
+        });
+    }
+}
@@ -1,27 +1,69 @@
+use std::collections::HashMap;
 use std::str::FromStr;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
 use bytes::Bytes;
+use dashmap::DashMap;
+use dashmap::DashSet;
+use futures::channel::oneshot;
+use futures::lock::Mutex as FuturesMutex;
 use rings_transport::connection_ref::ConnectionRef;
-#[cfg(feature = "dummy")]
+#[cfg(all(feature = "dummy", not(feature = "test-util")))]
 pub use rings_transport::connections::DummyConnection as ConnectionOwner;
-#[cfg(feature = "dummy")]
+#[cfg(all(feature = "dummy", not(feature = "test-util")))]
 pub use rings_transport::connections::DummyTransport as Transport;
+#[cfg(feature = "test-util")]
+pub use rings_transport::connections::MemoryConnection as ConnectionOwner;
+#[cfg(feature = "test-util")]
+pub use rings_transport::connections::MemoryTransport as Transport;
 #[cfg(feature = "wasm")]
 pub use rings_transport::connections::WebSysWebrtcConnection as ConnectionOwner;
 #[cfg(feature = "wasm")]
 pub use rings_transport::connections::WebSysWebrtcTransport as Transport;
-#[cfg(all(not(feature = "wasm"), not(feature = "dummy")))]
+#[cfg(all(
+    not(feature = "wasm"),
+    not(feature = "dummy"),
+    not(feature = "test-util")
+))]
 use rings_transport::connections::WebrtcConnection as ConnectionOwner;
-#[cfg(all(not(feature = "wasm"), not(feature = "dummy")))]
+#[cfg(all(
+    not(feature = "wasm"),
+    not(feature = "dummy"),
+    not(feature = "test-util")
+))]
 use rings_transport::connections::WebrtcTransport as Transport;
 use rings_transport::core::transport::ConnectionInterface;
+use rings_transport::core::transport::ConnectionStats;
+use rings_transport::core::transport::DataChannelConfig;
 use rings_transport::core::transport::TransportInterface;
 use rings_transport::core::transport::TransportMessage;
 use rings_transport::core::transport::WebrtcConnectionState;
+use rings_transport::core::transport::WebrtcIceConnectionState;
+use rings_transport::ice_server::IceServer;
 
+use crate::chunk::Chunk;
 use crate::chunk::ChunkList;
+use crate::chunk::ChunkManager;
+use crate::clock::Clock;
+use crate::clock::SharedClock;
+use crate::clock::SystemClock;
+use crate::consts::CHUNK_PAYLOAD_OVERHEAD;
+use crate::consts::DEBUG_SDP_ENV;
+use crate::consts::DEFAULT_CHUNK_REASSEMBLY_TIMEOUT_MS;
+use crate::consts::DEFAULT_CHUNK_RESUME_CACHE_BYTES;
+use crate::consts::DEFAULT_DEDUP_CACHE_SIZE;
+use crate::consts::DEFAULT_HANDSHAKE_REPLAY_WINDOW_MS;
+use crate::consts::DEFAULT_LOOKUP_TIMEOUT_MS;
+use crate::consts::DEFAULT_MAX_MESSAGE_SIZE;
+use crate::consts::DEFAULT_RELAY_TTL;
+use crate::consts::DEFAULT_SESSION_PUBKEYS_CACHE_SIZE;
+use crate::consts::MIN_CHUNK_DATA_SIZE;
+use crate::consts::PAYLOAD_VERSION;
 use crate::consts::TRANSPORT_MAX_SIZE;
 use crate::consts::TRANSPORT_MTU;
 use crate::dht::Did;
@@ -30,13 +72,35 @@ use crate::dht::PeerRing;
 use crate::error::Error;
 use crate::error::Result;
 use crate::measure::MeasureImpl;
+use crate::message::parse_sdp;
+use crate::message::Ack;
+use crate::message::ChunkResumeAck;
+use crate::message::ChunkResumeRequest;
 use crate::message::ConnectNodeReport;
 use crate::message::ConnectNodeSend;
+use crate::message::EncryptedMessage;
+use crate::message::HandshakeInfo;
 use crate::message::Message;
 use crate::message::MessagePayload;
+use crate::message::MessageVerificationExt;
 use crate::message::PayloadSender;
+use crate::message::Priority;
+use crate::message::Transaction;
+use crate::metrics;
 use crate::session::SessionSk;
 use crate::swarm::callback::InnerSwarmCallback;
+use crate::swarm::dedup::SeenTxs;
+use crate::swarm::gater::SharedConnectionGater;
+use crate::swarm::lookup::LookupMode;
+use crate::swarm::lookup::LookupStep;
+use crate::swarm::priority::SendQueue;
+use crate::swarm::pubkeys::SessionPubkeys;
+use crate::swarm::reconnect::ReconnectPolicy;
+use crate::swarm::reconnect::Reconnects;
+use crate::swarm::resume::SentChunks;
+use crate::swarm::stats::PeerStats;
+use crate::swarm::stats::PeerStatsTable;
+use crate::utils::get_epoch_ms;
 
 pub struct SwarmTransport {
     pub(crate) network_id: u32,
@@ -45,8 +109,69 @@ pub struct SwarmTransport {
     pub(crate) dht: Arc<PeerRing>,
     #[allow(dead_code)]
     measure: Option<MeasureImpl>,
+    /// Session public keys of remote peers, learned from their signed transactions.
+    session_pubkeys: SessionPubkeys,
+    /// Set when swarm is shutting down, inbound messages will be dropped.
+    shutting_down: AtomicBool,
+    /// Dump full SDP of handshakes in debug logs, which may contain IP addresses.
+    debug_sdp: AtomicBool,
+    /// Count of hops the messages sent by this node can be relayed.
+    relay_ttl: u8,
+    /// Count of inbound messages being handled.
+    pub(crate) inflight: AtomicUsize,
+    /// Maximum count of connections, remote offers will be rejected once it's reached.
+    max_connections: Option<usize>,
+    /// Time in ms of the last keepalive pong received from each peer.
+    pub(crate) last_pongs: DashMap<Did, u128>,
+    /// Round-trip time to each peer, measured by keepalive ping/pong.
+    latencies: DashMap<Did, Duration>,
+    /// Recently seen tx_ids of inbound messages.
+    seen_txs: SeenTxs,
+    /// Handshake payloads signed earlier than this window are rejected.
+    handshake_replay_window_ms: u64,
+    /// Recently seen tx_ids of handshake payloads, used as nonces against replay.
+    handshake_nonces: SeenTxs,
+    /// Metadata of this node, sent to peers on handshake.
+    metadata: HashMap<String, String>,
+    /// Metadata of remote peers, received on handshake.
+    peer_metadata: DashMap<Did, HashMap<String, String>>,
+    /// Payload versions of remote peers, learned from the payloads they sent.
+    peer_versions: DashMap<Did, u8>,
+    /// Chunks of large messages waiting for reassembly.
+    chunk_list: FuturesMutex<ChunkList<TRANSPORT_MTU>>,
+    /// Incomplete chunked messages created earlier than this are dropped.
+    chunk_reassembly_timeout_ms: u64,
+    /// Senders of chunked messages being reassembled.
+    chunk_senders: DashMap<uuid::Uuid, Did>,
+    /// Chunks of recently sent large messages, kept for resuming interrupted transfers.
+    pub(crate) sent_chunks: SentChunks,
+    /// Messages larger than this are split into chunks before sending.
+    max_message_size: usize,
+    /// Source of the current time, used to check expiry of inbound messages and handshakes.
+    clock: SharedClock,
+    /// Traffic statistics of each peer.
+    peer_stats: PeerStatsTable,
+    /// Prioritized queues of sending to each peer.
+    send_queues: DashMap<Did, Arc<SendQueue>>,
+    /// Reliable messages waiting for [Ack], with their destinations.
+    pub(crate) pending_acks: DashMap<uuid::Uuid, (Did, oneshot::Sender<()>)>,
+    /// Lookup requests waiting for reports, with the peers expected to report.
+    pub(crate) pending_lookups: DashMap<uuid::Uuid, (Option<Did>, oneshot::Sender<LookupStep>)>,
+    /// How lookups walk the ring, see [SwarmTransport::lookup].
+    pub(crate) lookup_mode: LookupMode,
+    /// Timeout of a recursive lookup, or of each hop of an iterative lookup.
+    pub(crate) lookup_timeout: Duration,
+    /// Dids whose successors are waiting to be looked up and connected by stabilization.
+    connect_lookups: DashSet<Did>,
+    /// Important peers being reconnected after they are lost.
+    pub(crate) reconnects: Reconnects,
+    /// Admission control of inbound connections.
+    connection_gater: Option<SharedConnectionGater>,
 }
 
+/// An additional data channel of connection, see [SwarmConnection::open_channel].
+pub type DataChannel = <ConnectionOwner as ConnectionInterface>::DataChannel;
+
 #[derive(Clone)]
 pub struct SwarmConnection {
     peer: Did,
@@ -61,6 +186,7 @@ impl SwarmTransport {
         session_sk: SessionSk,
         dht: Arc<PeerRing>,
         measure: Option<MeasureImpl>,
+        max_connections: Option<usize>,
     ) -> Self {
         Self {
             network_id,
@@ -68,7 +194,476 @@ impl SwarmTransport {
             session_sk,
             dht,
             measure,
+            session_pubkeys: SessionPubkeys::new(DEFAULT_SESSION_PUBKEYS_CACHE_SIZE),
+            shutting_down: AtomicBool::new(false),
+            debug_sdp: AtomicBool::new(debug_sdp_from_env()),
+            relay_ttl: DEFAULT_RELAY_TTL,
+            inflight: AtomicUsize::new(0),
+            max_connections,
+            last_pongs: DashMap::new(),
+            latencies: DashMap::new(),
+            seen_txs: SeenTxs::new(DEFAULT_DEDUP_CACHE_SIZE),
+            handshake_replay_window_ms: DEFAULT_HANDSHAKE_REPLAY_WINDOW_MS,
+            handshake_nonces: SeenTxs::new(DEFAULT_DEDUP_CACHE_SIZE),
+            metadata: HashMap::new(),
+            peer_metadata: DashMap::new(),
+            peer_versions: DashMap::new(),
+            chunk_list: Default::default(),
+            chunk_reassembly_timeout_ms: DEFAULT_CHUNK_REASSEMBLY_TIMEOUT_MS,
+            chunk_senders: DashMap::new(),
+            sent_chunks: SentChunks::new(DEFAULT_CHUNK_RESUME_CACHE_BYTES),
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            clock: Arc::new(SystemClock),
+            peer_stats: PeerStatsTable::default(),
+            send_queues: DashMap::new(),
+            pending_acks: DashMap::new(),
+            pending_lookups: DashMap::new(),
+            lookup_mode: LookupMode::default(),
+            lookup_timeout: Duration::from_millis(DEFAULT_LOOKUP_TIMEOUT_MS),
+            connect_lookups: DashSet::new(),
+            reconnects: Reconnects::default(),
+            connection_gater: None,
+        }
+    }
+
+    /// Set the count of recently seen tx_ids kept for deduplication. Zero disables deduplication.
+    pub fn set_dedup_cache_size(&mut self, size: usize) {
+        self.seen_txs = SeenTxs::new(size);
+    }
+
+    /// Record the tx_id of an inbound message. Returns false if it's a duplicate.
+    pub fn mark_seen(&self, tx_id: uuid::Uuid) -> bool {
+        self.seen_txs.insert(tx_id)
+    }
+
+    /// Set the window of handshake replay protection.
+    pub fn set_handshake_replay_window(&mut self, window: Duration) {
+        self.handshake_replay_window_ms = window.as_millis() as u64;
+    }
+
+    /// Reject a handshake payload if it's signed out of the replay window, or its tx_id,
+    /// which is a random nonce bound to the signature, has been seen before.
+    pub fn check_handshake_replay(&self, payload: &MessagePayload) -> Result<()> {
+        let tx = &payload.transaction;
+        let signer = tx.signer();
+        let age = self.clock.now_ms().saturating_sub(tx.verification.ts_ms);
+        if age > self.handshake_replay_window_ms as u128 {
+            return Err(Error::HandshakeExpired(signer));
+        }
+        if !self.handshake_nonces.insert(tx.tx_id) {
+            return Err(Error::HandshakeReplayed(signer));
+        }
+        Ok(())
+    }
+
+    /// Set how lookups walk the ring, and the timeout of them, see [SwarmTransport::lookup].
+    pub fn set_lookup(&mut self, mode: LookupMode, timeout: Duration) {
+        self.lookup_mode = mode;
+        self.lookup_timeout = timeout;
+    }
+
+    /// Set the timeout of chunked message reassembly.
+    pub fn set_chunk_reassembly_timeout(&mut self, timeout: Duration) {
+        self.chunk_reassembly_timeout_ms = timeout.as_millis() as u64;
+    }
+
+    /// Reconnect lost peers by policy, if they are bootstrap nodes or in dht.
+    pub fn set_reconnect(&mut self, policy: ReconnectPolicy, bootstrap_nodes: &[Did]) {
+        self.reconnects = Reconnects::new(policy, bootstrap_nodes.iter().copied());
+    }
+
+    /// Ask `gater` before answering offers of remote peers.
+    pub fn set_connection_gater(&mut self, gater: SharedConnectionGater) {
+        self.connection_gater = Some(gater);
+    }
+
+    /// Check a verified offer payload with the connection gater, if any.
+    pub async fn check_inbound(&self, payload: &MessagePayload) -> Result<()> {
+        let Some(gater) = &self.connection_gater else {
+            return Ok(());
+        };
+        let info = HandshakeInfo::from_verified(payload.clone())?;
+        let did = info.did();
+        if !gater.allow_inbound(did, &info).await {
+            tracing::info!("Inbound connection from {did} is denied");
+            return Err(Error::InboundConnectionDenied(did));
+        }
+        Ok(())
+    }
+
+    /// Set the source of the current time.
+    pub fn set_clock(&mut self, clock: SharedClock) {
+        self.clock = clock;
+    }
+
+    /// Get the source of the current time.
+    pub fn clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
+    /// Set the size limit of a message sent on a data channel. It's raised to leave room
+    /// for [MIN_CHUNK_DATA_SIZE] bytes of data in each chunk, otherwise a large message
+    /// would be split into too many chunks to reassemble.
+    pub fn set_max_message_size(&mut self, size: usize) {
+        self.max_message_size = size.max(CHUNK_PAYLOAD_OVERHEAD + MIN_CHUNK_DATA_SIZE);
+    }
+
+    /// Get the size limit of a message sent on a data channel.
+    pub fn max_message_size(&self) -> usize {
+        self.max_message_size
+    }
+
+    /// Buffer a chunk of large message sent by peer `from`.
+    /// Returns the message once all its chunks arrived.
+    pub async fn handle_chunk(&self, chunk: Chunk, from: Did) -> Result<Option<Bytes>> {
+        let id = chunk.meta.id;
+        self.chunk_senders.insert(id, from);
+        let result = self.chunk_list.lock().await.handle(chunk);
+        if result.is_err() {
+            metrics::CHUNK_REASSEMBLY_FAILURES.inc();
+        }
+        if !matches!(result, Ok(None)) {
+            self.chunk_senders.remove(&id);
+        }
+        result
+    }
+
+    /// Ask peer to send the missing chunks of the messages it was sending when the connection
+    /// was interrupted. It should be called once the peer is reconnected.
+    pub async fn request_chunk_resume(&self, peer: Did) -> Result<()> {
+        let ids: Vec<uuid::Uuid> = self
+            .chunk_senders
+            .iter()
+            .filter(|e| *e.value() == peer)
+            .map(|e| *e.key())
+            .collect();
+
+        for id in ids {
+            let Some((received, total)) = self.chunk_list.lock().await.received(id) else {
+                self.chunk_senders.remove(&id);
+                continue;
+            };
+            tracing::info!(
+                "Resume chunked message {id} from {peer}, received {}/{total}",
+                received.len()
+            );
+            let req = ChunkResumeRequest {
+                id,
+                total,
+                received,
+            };
+            self.send_direct_message(Message::ChunkResumeRequest(req), peer)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Send the chunks requested by [ChunkResumeRequest] again, then respond [ChunkResumeAck].
+    pub async fn resume_chunks(&self, peer: Did, req: &ChunkResumeRequest) -> Result<()> {
+        let chunks = self
+            .sent_chunks
+            .get(peer, req.id)
+            .filter(|chunks| chunks.len() == req.total);
+
+        let ack = match chunks {
+            Some(chunks) => {
+                let missing: Vec<Chunk> = chunks
+                    .into_iter()
+                    .filter(|c| !req.received.contains(&c.chunk[0]))
+                    .collect();
+                let resent = missing.len();
+                tracing::info!(
+                    "Resume chunked message {} to {peer}, {resent} chunks",
+                    req.id
+                );
+                self.send_chunks(peer, missing, Priority::App, None).await?;
+                ChunkResumeAck {
+                    id: req.id,
+                    found: true,
+                    resent,
+                }
+            }
+            None => ChunkResumeAck {
+                id: req.id,
+                found: false,
+                resent: 0,
+            },
+        };
+        self.send_direct_message(Message::ChunkResumeAck(ack), peer)
+            .await?;
+        Ok(())
+    }
+
+    /// Send chunks of a large message to a connected peer, on the data channel of label if given.
+    async fn send_chunks(
+        &self,
+        did: Did,
+        chunks: Vec<Chunk>,
+        priority: Priority,
+        label: Option<&str>,
+    ) -> Result<()> {
+        let conn = self
+            .get_and_check_connection(did)
+            .await
+            .ok_or(Error::SwarmMissDidInTable(did))?;
+        let queue = self.send_queues.entry(did).or_default().clone();
+
+        for chunk in chunks {
+            let data = MessagePayload::new_send(Message::Chunk(chunk), &self.session_sk, did, did)?
+                .to_bincode()?;
+            let sent = data.len();
+            match label {
+                // A labeled channel has its own buffer, so it doesn't wait for the others.
+                Some(label) => conn.send_data_on(label, data).await?,
+                None => {
+                    // Acquire per chunk, so that control messages can be sent between chunks.
+                    let _permit = queue.acquire(priority).await;
+                    conn.send_data(data).await?;
+                }
+            }
+            self.peer_stats.record_sent(did, sent);
+        }
+        Ok(())
+    }
+
+    /// Send a message to destination on the data channel of label to its next hop, which is
+    /// opened by either peer, see [SwarmConnection::open_channel]. Relays forward it on their
+    /// default channels.
+    pub async fn send_message_on(
+        &self,
+        label: &str,
+        msg: Message,
+        destination: Did,
+    ) -> Result<uuid::Uuid> {
+        let next_hop = self.infer_next_hop(destination, None)?;
+        let mut payload = MessagePayload::new_send(msg, &self.session_sk, next_hop, destination)?;
+        payload.relay.ttl = self.relay_ttl;
+        let tx_id = payload.transaction.tx_id;
+
+        let conn = self
+            .get_and_check_connection(next_hop)
+            .await
+            .ok_or(Error::SwarmMissDidInTable(next_hop))?;
+        let data = self.encode_payload(next_hop, &payload)?;
+        if data.len() > self.max_message_size {
+            let chunk_size = self.max_message_size - CHUNK_PAYLOAD_OVERHEAD;
+            let chunks: Vec<Chunk> = ChunkList::<TRANSPORT_MTU>::split(&data, chunk_size).into();
+            self.sent_chunks.insert(next_hop, chunks.clone());
+            self.send_chunks(next_hop, chunks, Priority::App, Some(label))
+                .await?;
+        } else {
+            let sent = data.len();
+            conn.send_data_on(label, data).await?;
+            self.peer_stats.record_sent(next_hop, sent);
+        }
+        Ok(tx_id)
+    }
+
+    /// Serialize a payload to be sent to a connected peer, in the compact form if the peer
+    /// is known to decode it.
+    fn encode_payload(&self, did: Did, payload: &MessagePayload) -> Result<Bytes> {
+        // Check the size before serializing, so that an oversized payload is not encoded in vain.
+        // The compact form is never larger.
+        let size = bincode::serialized_size(payload).map_err(Error::BincodeSerialize)? as usize;
+        if size > TRANSPORT_MAX_SIZE {
+            tracing::error!("Message is too large: {:?}", payload);
+            return Err(Error::MessageTooLarge(size));
+        }
+        if self.peer_version(did) >= PAYLOAD_VERSION {
+            payload.to_compact_bincode()
+        } else {
+            payload.to_bincode()
+        }
+    }
+
+    /// Wait for the [Ack] of message `tx_id` sent to destination.
+    /// The receiver is cancelled if [SwarmTransport::forget_ack] is called.
+    pub(crate) fn expect_ack(&self, tx_id: uuid::Uuid, destination: Did) -> oneshot::Receiver<()> {
+        let (tx, rx) = oneshot::channel();
+        self.pending_acks.insert(tx_id, (destination, tx));
+        rx
+    }
+
+    /// Stop waiting for the [Ack] of message `tx_id`.
+    pub(crate) fn forget_ack(&self, tx_id: uuid::Uuid) {
+        self.pending_acks.remove(&tx_id);
+    }
+
+    /// Wake up the sender waiting for the ack. Acks not from the destination are ignored.
+    pub fn handle_ack(&self, peer: Did, ack: &Ack) {
+        match self
+            .pending_acks
+            .remove_if(&ack.tx_id, |_, (destination, _)| *destination == peer)
+        {
+            // The sender may have given up.
+            Some((_, (_, tx))) => {
+                let _ = tx.send(());
+            }
+            None => tracing::debug!("Ignore ack of {} from {peer}", ack.tx_id),
+        }
+    }
+
+    /// Wait for the report of lookup request `tx_id`. If `reporter` is given, only its report
+    /// is accepted.
+    pub(crate) fn expect_lookup(
+        &self,
+        tx_id: uuid::Uuid,
+        reporter: Option<Did>,
+    ) -> oneshot::Receiver<LookupStep> {
+        let (tx, rx) = oneshot::channel();
+        self.pending_lookups.insert(tx_id, (reporter, tx));
+        rx
+    }
+
+    /// Stop waiting for the report of lookup request `tx_id`.
+    pub(crate) fn forget_lookup(&self, tx_id: uuid::Uuid) {
+        self.pending_lookups.remove(&tx_id);
+    }
+
+    /// Look up the successor of `did` and connect to it in the next stabilization, instead of
+    /// waiting for the lookup in a message handler, which would block the connection carrying
+    /// the reports of the lookup.
+    pub(crate) fn defer_connect_lookup(&self, did: Did) {
+        self.connect_lookups.insert(did);
+    }
+
+    /// Take the dids deferred by [SwarmTransport::defer_connect_lookup].
+    pub(crate) fn take_connect_lookups(&self) -> Vec<Did> {
+        let dids: Vec<Did> = self.connect_lookups.iter().map(|d| *d).collect();
+        for did in dids.iter() {
+            self.connect_lookups.remove(did);
+        }
+        dids
+    }
+
+    /// Wake up the lookup waiting for the report. Reports not from the expected peer are ignored.
+    pub(crate) fn handle_lookup_step(&self, peer: Did, tx_id: uuid::Uuid, step: LookupStep) {
+        match self.pending_lookups.remove_if(&tx_id, |_, (reporter, _)| {
+            reporter.map_or(true, |r| r == peer)
+        }) {
+            // The lookup may have timed out.
+            Some((_, (_, tx))) => {
+                let _ = tx.send(step);
+            }
+            None => tracing::debug!("Ignore lookup report of {tx_id} from {peer}"),
+        }
+    }
+
+    /// Drop the chunks of a message which cannot be resumed by its sender.
+    pub async fn handle_chunk_resume_ack(&self, peer: Did, ack: &ChunkResumeAck) -> Result<()> {
+        if ack.found {
+            return Ok(());
+        }
+        if self
+            .chunk_senders
+            .remove_if(&ack.id, |_, from| *from == peer)
+            .is_none()
+        {
+            return Ok(());
+        }
+        tracing::warn!("Chunked message {} cannot be resumed by {peer}", ack.id);
+        self.chunk_list.lock().await.remove(ack.id);
+        metrics::CHUNK_REASSEMBLY_FAILURES.inc();
+        Ok(())
+    }
+
+    /// Get the ratio of received chunks of a large message being reassembled.
+    pub async fn chunk_progress(&self, id: uuid::Uuid) -> Option<f32> {
+        self.chunk_list.lock().await.progress(id)
+    }
+
+    /// Drop chunked messages not completed in the reassembly timeout.
+    /// Returns [Error::ChunkIncomplete] for each of them.
+    pub async fn remove_incomplete_chunks(&self) -> Vec<Error> {
+        let errors = self
+            .chunk_list
+            .lock()
+            .await
+            .remove_incomplete(self.chunk_reassembly_timeout_ms);
+        for e in errors.iter() {
+            if let Error::ChunkIncomplete { id, .. } = e {
+                self.chunk_senders.remove(id);
+            }
         }
+        metrics::CHUNK_REASSEMBLY_FAILURES.inc_by(errors.len() as u64);
+        errors
+    }
+
+    /// Set the metadata sent to peers on handshake.
+    pub fn set_metadata(&mut self, metadata: HashMap<String, String>) {
+        self.metadata = metadata;
+    }
+
+    /// Get the metadata a peer sent on handshake.
+    pub fn peer_metadata(&self, peer: Did) -> Option<HashMap<String, String>> {
+        self.peer_metadata.get(&peer).map(|v| v.clone())
+    }
+
+    fn record_peer_metadata(&self, peer: Did, metadata: &HashMap<String, String>) {
+        if metadata.is_empty() {
+            self.peer_metadata.remove(&peer);
+        } else {
+            self.peer_metadata.insert(peer, metadata.clone());
+        }
+    }
+
+    /// Record the payload version of a peer, learned from a payload it sent.
+    pub(crate) fn record_peer_version(&self, peer: Did, version: u8) {
+        self.peer_versions.insert(peer, version);
+    }
+
+    /// Get the payload version of a peer, see [crate::consts::PAYLOAD_VERSION].
+    /// It's 0 if the peer has sent nothing yet, or is of a version without payload extension.
+    pub fn peer_version(&self, peer: Did) -> u8 {
+        self.peer_versions
+            .get(&peer)
+            .map(|v| *v)
+            .unwrap_or_default()
+    }
+
+    /// Let connections failed on ICE retry with TURN relay only.
+    /// Returns error if no TURN server is configured.
+    pub fn enable_relay_fallback(&mut self) -> Result<()> {
+        self.transport
+            .enable_relay_fallback()
+            .map_err(Error::Transport)
+    }
+
+    /// Take the peers whose ICE failed with relay fallback enabled, to redial them with
+    /// TURN candidates only. Each of them is returned once.
+    pub fn take_relay_redials(&self) -> Vec<Did> {
+        self.transport
+            .take_relay_redials()
+            .into_iter()
+            .filter_map(|cid| Did::from_str(&cid).ok())
+            .collect()
+    }
+
+    /// Set reliability of data channels of new connections.
+    /// Returns error if the config is invalid.
+    pub fn set_data_channel_config(&mut self, config: DataChannelConfig) -> Result<()> {
+        self.transport
+            .set_data_channel_config(config)
+            .map_err(Error::Transport)
+    }
+
+    /// Set the count of hops the messages sent by this node can be relayed.
+    pub fn set_relay_ttl(&mut self, ttl: u8) {
+        self.relay_ttl = ttl;
+    }
+
+    /// Set the time to wait for ICE gathering of new connections.
+    pub fn set_ice_gathering_timeout(&mut self, timeout: Duration) {
+        self.transport.set_ice_gathering_timeout(timeout)
+    }
+
+    /// Replace the ice servers of new connections and existing connections.
+    /// Returns error if relay fallback is enabled but no TURN server is given.
+    pub async fn update_ice_servers(&self, ice_servers: Vec<IceServer>) -> Result<()> {
+        self.transport
+            .update_ice_servers(ice_servers)
+            .await
+            .map_err(Error::Transport)
     }
 
     /// Create new connection that will be handled by swarm.
@@ -126,6 +721,11 @@ impl SwarmTransport {
     /// 3) close the connection;
     pub async fn disconnect(&self, peer: Did) -> Result<()> {
         tracing::info!("removing {peer} from DHT");
+        self.last_pongs.remove(&peer);
+        self.latencies.remove(&peer);
+        self.peer_metadata.remove(&peer);
+        self.peer_versions.remove(&peer);
+        self.send_queues.remove(&peer);
         self.dht.remove(peer)?;
         self.transport
             .close_connection(&peer.to_string())
@@ -187,9 +787,11 @@ impl SwarmTransport {
 
         let offer = conn.webrtc_create_offer().await.map_err(Error::Transport)?;
         let offer_str = serde_json::to_string(&offer).map_err(|_| Error::SerializeToString)?;
+        self.log_sdp(peer, true, &offer_str);
         let offer_msg = ConnectNodeSend {
             sdp: offer_str,
             network_id: self.network_id,
+            metadata: self.metadata.clone(),
         };
 
         Ok(offer_msg)
@@ -203,6 +805,13 @@ impl SwarmTransport {
         offer_msg: &ConnectNodeSend,
     ) -> Result<ConnectNodeReport> {
         let offer = serde_json::from_str(&offer_msg.sdp).map_err(Error::Deserialize)?;
+        self.log_sdp(peer, false, &offer_msg.sdp);
+
+        if let Some(max) = self.max_connections {
+            if self.get_connection(peer).is_none() && self.transport.connection_ids().len() >= max {
+                return Err(Error::TooManyConnections(max));
+            }
+        }
 
         if let Some(swarm_conn) = self.get_connection(peer) {
             // Solve the scenario of creating offers simultaneously.
@@ -238,11 +847,128 @@ impl SwarmTransport {
             .await
             .map_err(Error::Transport)?;
         let answer_str = serde_json::to_string(&answer).map_err(|_| Error::SerializeToString)?;
-        let answer_msg = ConnectNodeReport { sdp: answer_str };
+        self.log_sdp(peer, true, &answer_str);
+        let answer_msg = ConnectNodeReport {
+            sdp: answer_str,
+            metadata: self.metadata.clone(),
+        };
+        self.record_peer_metadata(peer, &offer_msg.metadata);
 
         Ok(answer_msg)
     }
 
+    /// Stop handling inbound messages.
+    pub fn start_shutdown(&self) {
+        self.shutting_down.store(true, Ordering::SeqCst);
+    }
+
+    /// Check if swarm is shutting down.
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::SeqCst)
+    }
+
+    /// Dump full SDP of handshakes in debug logs or not. Only the type and count of candidates
+    /// are logged by default, since SDP may contain IP addresses.
+    pub fn set_debug_sdp(&self, enable: bool) {
+        self.debug_sdp.store(enable, Ordering::SeqCst);
+    }
+
+    /// Log a session description exchanged with a peer at debug level, for debugging interop.
+    fn log_sdp(&self, peer: Did, local: bool, sdp: &str) {
+        if !tracing::enabled!(tracing::Level::DEBUG) {
+            return;
+        }
+        let side = if local { "local" } else { "remote" };
+        let (sdp_type, candidates) = match parse_sdp(sdp) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                tracing::debug!(%peer, side, "Failed to parse sdp: {e}");
+                return;
+            }
+        };
+        if self.debug_sdp.load(Ordering::SeqCst) {
+            tracing::debug!(%peer, side, sdp_type, ?candidates, sdp, "SDP exchanged");
+        } else {
+            tracing::debug!(
+                %peer,
+                side,
+                sdp_type,
+                candidates = candidates.len(),
+                "SDP exchanged, set {DEBUG_SDP_ENV} to dump it"
+            );
+        }
+    }
+
+    /// Remember the session public key of the origin sender of a verified transaction.
+    /// It's recovered from the signature only when the sender starts a new session.
+    pub fn record_session_pubkey(&self, tx: &Transaction) {
+        let session_id = tx.verification().session.session_id();
+        if let Err(e) = self
+            .session_pubkeys
+            .record(tx.signer(), session_id, || tx.session_pubkey())
+        {
+            tracing::warn!("Failed to recover session pubkey: {e:?}");
+        }
+    }
+
+    /// Encrypt data to the session of a peer. The peer should have sent a message to us before.
+    pub fn encrypt_for(&self, peer: Did, data: &[u8]) -> Result<EncryptedMessage> {
+        let pubkey = self
+            .session_pubkeys
+            .get(peer)
+            .ok_or(Error::SessionPubkeyNotFound(peer))?;
+        Ok(EncryptedMessage(pubkey.encrypt(data)?))
+    }
+
+    /// Remember the time of a keepalive pong from a peer.
+    pub fn record_pong(&self, peer: Did) {
+        self.last_pongs.insert(peer, get_epoch_ms());
+    }
+
+    /// Remember the round-trip time to a peer.
+    pub fn record_latency(&self, peer: Did, rtt: Duration) {
+        self.latencies.insert(peer, rtt);
+    }
+
+    /// Get the last measured round-trip time to a peer.
+    pub fn latency(&self, peer: Did) -> Option<Duration> {
+        self.latencies.get(&peer).map(|v| *v)
+    }
+
+    /// Reset traffic statistics of the current connection to a peer, when it's (re)connected.
+    pub fn reset_peer_stats(&self, peer: Did) {
+        self.peer_stats.reset(peer);
+    }
+
+    /// Get the time in ms of the last message exchanged with a peer, see [PeerStatsTable::last_active_ms].
+    pub fn last_active(&self, peer: Did) -> Option<u128> {
+        self.peer_stats.last_active_ms(peer)
+    }
+
+    /// Remember a message of `bytes` received from a peer.
+    pub fn record_peer_recv(&self, peer: Did, bytes: usize) {
+        self.peer_stats.record_recv(peer, bytes);
+    }
+
+    /// Get the traffic statistics of a peer.
+    pub fn peer_stats(&self, peer: Did) -> Option<PeerStats> {
+        self.peer_stats.get(peer)
+    }
+
+    /// Forget pongs and round-trip times of peers not in `connected`, such as the ones whose
+    /// connections failed without [SwarmTransport::disconnect].
+    pub fn prune_pongs(&self, connected: &[Did]) {
+        self.last_pongs.retain(|peer, _| connected.contains(peer));
+        self.latencies.retain(|peer, _| connected.contains(peer));
+    }
+
+    /// Get the time of the last keepalive pong from a peer.
+    /// If no pong is received yet, the current time is recorded and returned,
+    /// so that a new connection has a full window to answer.
+    pub fn last_pong(&self, peer: Did) -> u128 {
+        *self.last_pongs.entry(peer).or_insert_with(get_epoch_ms)
+    }
+
     /// Accept the answer of remote connection.
     pub async fn accept_remote_connection(
         &self,
@@ -250,6 +976,7 @@ impl SwarmTransport {
         answer_msg: &ConnectNodeReport,
     ) -> Result<()> {
         let answer = serde_json::from_str(&answer_msg.sdp).map_err(Error::Deserialize)?;
+        self.log_sdp(peer, false, &answer_msg.sdp);
 
         let conn = self
             .transport
@@ -258,6 +985,7 @@ impl SwarmTransport {
         conn.webrtc_accept_answer(answer)
             .await
             .map_err(Error::Transport)?;
+        self.record_peer_metadata(peer, &answer_msg.metadata);
 
         Ok(())
     }
@@ -271,9 +999,57 @@ impl SwarmConnection {
             .map_err(|e| e.into())
     }
 
+    /// Send data on the data channel of label, see [SwarmConnection::open_channel].
+    pub async fn send_data_on(&self, label: &str, data: Bytes) -> Result<()> {
+        self.connection
+            .send_message_on(label, TransportMessage::Custom(data.to_vec()))
+            .await
+            .map_err(|e| e.into())
+    }
+
+    /// Open an additional data channel of label on the connection, so that a stream of
+    /// messages, such as a bulk transfer, doesn't block the others. The channel is shared by
+    /// both peers, and messages received on it are handled like the others.
+    pub async fn open_channel(
+        &self,
+        label: &str,
+        config: DataChannelConfig,
+    ) -> Result<Arc<DataChannel>> {
+        self.connection
+            .open_channel(label, config)
+            .await
+            .map_err(|e| e.into())
+    }
+
+    pub async fn send_ping(&self, nonce: u64) -> Result<()> {
+        self.connection
+            .send_message(TransportMessage::Ping(nonce))
+            .await
+            .map_err(|e| e.into())
+    }
+
+    pub async fn send_pong(&self, nonce: u64) -> Result<()> {
+        self.connection
+            .send_message(TransportMessage::Pong(nonce))
+            .await
+            .map_err(|e| e.into())
+    }
+
     pub fn webrtc_connection_state(&self) -> WebrtcConnectionState {
         self.connection.webrtc_connection_state()
     }
+
+    pub fn webrtc_ice_connection_state(&self) -> WebrtcIceConnectionState {
+        self.connection.webrtc_ice_connection_state()
+    }
+
+    pub fn is_data_channel_open(&self) -> bool {
+        self.connection.is_data_channel_open()
+    }
+
+    pub async fn connection_stats(&self) -> Option<ConnectionStats> {
+        self.connection.connection_stats().await
+    }
 }
 
 #[cfg_attr(feature = "wasm", async_trait(?Send))]
@@ -294,6 +1070,10 @@ impl PayloadSender for SwarmTransport {
         conn.webrtc_connection_state() == WebrtcConnectionState::Connected
     }
 
+    fn relay_ttl(&self) -> u8 {
+        self.relay_ttl
+    }
+
     async fn do_send_payload(&self, did: Did, payload: MessagePayload) -> Result<()> {
         let conn = self
             .get_and_check_connection(did)
@@ -306,23 +1086,26 @@ impl PayloadSender for SwarmTransport {
             payload.relay.next_hop,
         );
 
-        let data = payload.to_bincode()?;
-        if data.len() > TRANSPORT_MAX_SIZE {
-            tracing::error!("Message is too large: {:?}", payload);
-            return Err(Error::MessageTooLarge(data.len()));
-        }
+        let data = self.encode_payload(did, &payload)?;
 
-        let result = if data.len() > TRANSPORT_MTU {
-            let chunks = ChunkList::<TRANSPORT_MTU>::from(&data);
-            for chunk in chunks {
-                let data =
-                    MessagePayload::new_send(Message::Chunk(chunk), &self.session_sk, did, did)?
-                        .to_bincode()?;
-                conn.send_data(data).await?;
-            }
-            Ok(())
+        let priority = payload
+            .transaction
+            .data::<Message>()
+            .map(|msg| msg.priority())
+            .unwrap_or_default();
+
+        let result = if data.len() > self.max_message_size {
+            let chunk_size = self.max_message_size - CHUNK_PAYLOAD_OVERHEAD;
+            let chunks: Vec<Chunk> = ChunkList::<TRANSPORT_MTU>::split(&data, chunk_size).into();
+            self.sent_chunks.insert(did, chunks.clone());
+            self.send_chunks(did, chunks, priority, None).await
         } else {
-            conn.send_data(data).await
+            let sent = data.len();
+            let queue = self.send_queues.entry(did).or_default().clone();
+            let _permit = queue.acquire(priority).await;
+            conn.send_data(data)
+                .await
+                .map(|_| self.peer_stats.record_sent(did, sent))
         };
 
         tracing::debug!(
@@ -348,3 +1131,11 @@ impl From<SwarmConnection> for Did {
         conn.peer
     }
 }
+
+/// Check if dumping full SDP is enabled by environment variable [DEBUG_SDP_ENV].
+fn debug_sdp_from_env() -> bool {
+    matches!(
+        std::env::var(DEBUG_SDP_ENV).as_deref(),
+        Ok("1") | Ok("true")
+    )
+}
@@ -0,0 +1,72 @@
+//! Utilities of testing swarms in process, enabled by feature `test-util`.
+//!
+//! With this feature, swarms are connected by [MemoryTransport](rings_transport::connections::MemoryTransport)
+//! instead of WebRTC. There is no SDP or ICE, and messages are delivered in order, so tests of
+//! message routing and DHT behave deterministically without network.
+
+use std::time::Duration;
+
+use crate::dht::SuccessorReader;
+use crate::ecc::SecretKey;
+use crate::session::SessionSk;
+use crate::storage::MemStorage;
+use crate::swarm::Swarm;
+use crate::swarm::SwarmBuilder;
+
+/// Create a swarm of a random key with memory storage.
+pub fn new_swarm() -> Swarm {
+    let session_sk = SessionSk::new_with_seckey(&SecretKey::random()).expect("create session sk");
+    SwarmBuilder::new(0, "", Box::new(MemStorage::new()), session_sk).build()
+}
+
+/// Handshake between two swarms directly, and wait until they join the DHT of each other.
+pub async fn connect(swarm1: &Swarm, swarm2: &Swarm) {
+    let offer = swarm1
+        .create_offer(swarm2.did())
+        .await
+        .expect("create offer");
+    let answer = swarm2.answer_offer(offer).await.expect("answer offer");
+    swarm1.accept_answer(answer).await.expect("accept answer");
+
+    for _ in 0..100 {
+        if joined(swarm1, swarm2) && joined(swarm2, swarm1) {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("{} and {} are not joined", swarm1.did(), swarm2.did());
+}
+
+/// Create two swarms connected with each other.
+pub async fn connect_pair() -> (Swarm, Swarm) {
+    let swarm1 = new_swarm();
+    let swarm2 = new_swarm();
+    connect(&swarm1, &swarm2).await;
+    (swarm1, swarm2)
+}
+
+fn joined(swarm: &Swarm, peer: &Swarm) -> bool {
+    swarm
+        .dht()
+        .successors()
+        .list()
+        .map(|list| list.contains(&peer.did()))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_connect_pair() {
+        let (swarm1, swarm2) = connect_pair().await;
+        assert!(swarm1.transport.get_connection(swarm2.did()).is_some());
+        assert!(swarm2.transport.get_connection(swarm1.did()).is_some());
+
+        // A third swarm joins the ring through swarm2.
+        let swarm3 = new_swarm();
+        connect(&swarm2, &swarm3).await;
+        assert!(joined(&swarm2, &swarm3));
+    }
+}
@@ -1,11 +1,48 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use futures::FutureExt;
+use rings_transport::core::transport::ConnectionInterface;
+use rings_transport::core::transport::DataChannelConfig;
 use rings_transport::core::transport::WebrtcConnectionState;
+use rings_transport::ice_server::IceServer;
 
+use crate::clock::MockClock;
+use crate::consts::PAYLOAD_VERSION;
+use crate::dht::vnode::VirtualNode;
+use crate::dht::Did;
+use crate::dht::SuccessorReader;
 use crate::ecc::tests::gen_ordered_keys;
 use crate::ecc::SecretKey;
+use crate::message::ConnectRejectReason;
+use crate::message::Decoder;
+use crate::message::Encoder;
+use crate::message::HandleMsg;
+use crate::message::HandshakeInfo;
+use crate::message::Message;
+use crate::message::MessageHandler;
+use crate::message::MessagePayload;
+use crate::message::PayloadSender;
+use crate::session::SessionSk;
+use crate::storage::MemStorage;
+use crate::swarm::callback::SwarmCallback;
+use crate::swarm::BootstrapDialer;
+use crate::swarm::BootstrapEntry;
+use crate::swarm::ConnectionGater;
+use crate::swarm::KeepAlive;
+use crate::swarm::LookupMode;
+use crate::swarm::ReconnectPolicy;
+use crate::swarm::SharedBootstrapDialer;
+use crate::swarm::Swarm;
+use crate::swarm::SwarmBuilder;
 use crate::tests::default::assert_no_more_msg;
 use crate::tests::default::prepare_node;
 use crate::tests::default::wait_for_msgs;
+use crate::tests::default::Node;
 use crate::tests::manually_establish_connection;
+use crate::utils::get_epoch_ms;
 
 #[tokio::test]
 async fn test_handshake_on_both_sides_ordered() {
@@ -127,3 +164,591 @@ async fn test_handshake_on_both_sides(key1: SecretKey, key2: SecretKey, key3: Se
         WebrtcConnectionState::Connected,
     )
 }
+
+#[tokio::test]
+async fn test_shutdown_says_goodbye_to_peers() {
+    let keys = gen_ordered_keys(2);
+    let node1 = prepare_node(keys[0]).await;
+    let node2 = prepare_node(keys[1]).await;
+
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    wait_for_msgs([&node1, &node2]).await;
+    assert_no_more_msg([&node1, &node2]).await;
+
+    let force_closed = node1.swarm.shutdown(Duration::from_secs(3)).await;
+    assert_eq!(force_closed, 0);
+    node1.assert_transports(vec![]);
+
+    wait_for_msgs([&node1, &node2]).await;
+    node2.assert_transports(vec![]);
+    assert!(node2.dht().successors().is_empty().unwrap());
+
+    assert!(matches!(
+        node1.swarm.connect(node2.did()).await,
+        Err(crate::error::Error::SwarmShuttingDown)
+    ));
+}
+
+#[tokio::test]
+async fn test_leave_hands_off_vnodes() {
+    let keys = gen_ordered_keys(2);
+    let node1 = prepare_node(keys[0]).await;
+    let node2 = prepare_node(keys[1]).await;
+
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    wait_for_msgs([&node1, &node2]).await;
+    assert_no_more_msg([&node1, &node2]).await;
+
+    let vnode: VirtualNode = "leaving".to_string().try_into().unwrap();
+    let vid = vnode.did.to_string();
+    node1.dht().storage.put(&vid, &vnode).await.unwrap();
+
+    let force_closed = node1.swarm.leave().await;
+    assert_eq!(force_closed, 0);
+    node1.assert_transports(vec![]);
+    assert!(node1.dht().storage.get(&vid).await.unwrap().is_none());
+
+    wait_for_msgs([&node1, &node2]).await;
+    node2.assert_transports(vec![]);
+    assert!(node2.dht().successors().is_empty().unwrap());
+    assert_eq!(node2.dht().storage.get(&vid).await.unwrap(), Some(vnode));
+}
+
+#[tokio::test]
+async fn test_leave_keeps_vnodes_for_legacy_peer() {
+    let keys = gen_ordered_keys(2);
+    let node1 = prepare_node(keys[0]).await;
+    let node2 = prepare_node(keys[1]).await;
+
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    wait_for_msgs([&node1, &node2]).await;
+    assert_no_more_msg([&node1, &node2]).await;
+    assert_eq!(
+        node1.swarm.transport.peer_version(node2.did()),
+        PAYLOAD_VERSION
+    );
+
+    // Pretend node2 is of an earlier version, which cannot decode the goodbye or handoff.
+    node1.swarm.transport.record_peer_version(node2.did(), 0);
+
+    let vnode: VirtualNode = "leaving".to_string().try_into().unwrap();
+    let vid = vnode.did.to_string();
+    node1.dht().storage.put(&vid, &vnode).await.unwrap();
+
+    let force_closed = node1.swarm.leave().await;
+    assert_eq!(force_closed, 0);
+    node1.assert_transports(vec![]);
+    assert_eq!(node1.dht().storage.get(&vid).await.unwrap(), Some(vnode));
+
+    // The connection is closed without a goodbye.
+    assert_no_more_msg([&node2]).await;
+    assert!(node2.dht().storage.get(&vid).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_lookup_modes() {
+    let node1 = {
+        let stun = "stun://stun.l.google.com:19302";
+        let storage = Box::new(MemStorage::new());
+        let session_sk = SessionSk::new_with_seckey(&SecretKey::random()).unwrap();
+        let swarm = SwarmBuilder::new(0, stun, storage, session_sk)
+            .lookup_mode(LookupMode::Iterative)
+            .build();
+        Node::new(Arc::new(swarm))
+    };
+    let node2 = prepare_node(SecretKey::random()).await;
+
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    wait_for_msgs([&node1, &node2]).await;
+
+    // Known locally, no hop is needed.
+    let lookup = node1.swarm.lookup(node2.did()).await.unwrap();
+    assert_eq!(lookup.successor, node2.did());
+    assert!(lookup.hops.is_empty());
+
+    // Iterative, node1 asks node2, who reports node1 as the successor.
+    let lookup = node1
+        .swarm
+        .lookup(node2.did() + Did::from(1u32))
+        .await
+        .unwrap();
+    assert_eq!(lookup.successor, node1.did());
+    assert_eq!(lookup.hops.len(), 1);
+    assert_eq!(lookup.hops[0].peer, node2.did());
+
+    // Recursive, node2 sends the request to node1, who reports node2 as the successor.
+    let lookup = node2
+        .swarm
+        .lookup(node1.did() + Did::from(1u32))
+        .await
+        .unwrap();
+    assert_eq!(lookup.successor, node2.did());
+    assert_eq!(lookup.hops.len(), 1);
+    assert_eq!(lookup.hops[0].peer, node1.did());
+}
+
+#[tokio::test]
+async fn test_lookup_dropped_is_forgotten() {
+    let node1 = {
+        let stun = "stun://stun.l.google.com:19302";
+        let storage = Box::new(MemStorage::new());
+        let session_sk = SessionSk::new_with_seckey(&SecretKey::random()).unwrap();
+        let swarm = SwarmBuilder::new(0, stun, storage, session_sk)
+            .lookup_mode(LookupMode::Iterative)
+            .build();
+        Node::new(Arc::new(swarm))
+    };
+    let node2 = prepare_node(SecretKey::random()).await;
+
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    wait_for_msgs([&node1, &node2]).await;
+
+    // The lookup is waiting for the report of node2 when it's dropped.
+    let lookup = node1.swarm.lookup(node2.did() + Did::from(1u32));
+    assert!(lookup.now_or_never().is_none());
+    assert!(node1.swarm.transport.pending_lookups.is_empty());
+}
+
+fn prepare_keepalive_node(interval: Duration, miss_threshold: u32) -> Node {
+    let stun = "stun://stun.l.google.com:19302";
+    let storage = Box::new(MemStorage::new());
+    let session_sk = SessionSk::new_with_seckey(&SecretKey::random()).unwrap();
+    let swarm = SwarmBuilder::new(0, stun, storage, session_sk)
+        .keepalive(interval, miss_threshold)
+        .build();
+    Node::new(Arc::new(swarm))
+}
+
+#[tokio::test]
+async fn test_keepalive_measures_latency() {
+    let node1 = prepare_keepalive_node(Duration::from_millis(100), 3);
+    let node2 = prepare_node(SecretKey::random()).await;
+
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    wait_for_msgs([&node1, &node2]).await;
+    assert!(node1.swarm.peer_latency(node2.did()).await.is_none());
+
+    node1.swarm.keepalive().await.unwrap();
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    assert!(node1.swarm.peer_latency(node2.did()).await.is_some());
+    assert!(node1.swarm.transport.get_connection(node2.did()).is_some());
+}
+
+#[tokio::test]
+async fn test_keepalive_disconnects_silent_peer() {
+    let node1 = prepare_keepalive_node(Duration::from_millis(100), 1);
+    let node2 = prepare_node(SecretKey::random()).await;
+
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    wait_for_msgs([&node1, &node2]).await;
+
+    // node2 has not answered pings since long ago.
+    node1.swarm.transport.last_pongs.insert(node2.did(), 0);
+    node1.swarm.keepalive().await.unwrap();
+    assert!(node1.swarm.transport.get_connection(node2.did()).is_none());
+    assert!(!node1.swarm.transport.last_pongs.contains_key(&node2.did()));
+}
+
+#[tokio::test]
+async fn test_keepalive_prunes_disconnected_peers() {
+    let node1 = prepare_keepalive_node(Duration::from_millis(100), 3);
+    let node2 = prepare_node(SecretKey::random()).await;
+    let gone: Did = SecretKey::random().address().into();
+
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    wait_for_msgs([&node1, &node2]).await;
+
+    // The connection to gone peer failed without being disconnected by swarm.
+    node1.swarm.transport.record_pong(gone);
+    node1
+        .swarm
+        .transport
+        .record_latency(gone, Duration::from_millis(1));
+
+    node1.swarm.keepalive().await.unwrap();
+    assert!(!node1.swarm.transport.last_pongs.contains_key(&gone));
+    assert!(node1.swarm.peer_latency(gone).await.is_none());
+    assert!(node1.swarm.transport.last_pongs.contains_key(&node2.did()));
+}
+
+#[tokio::test]
+async fn test_zero_idle_timeout_ignored() {
+    let stun = "stun://stun.l.google.com:19302";
+    let storage = Box::new(MemStorage::new());
+    let session_sk = SessionSk::new_with_seckey(&SecretKey::random()).unwrap();
+    let swarm = SwarmBuilder::new(0, stun, storage, session_sk)
+        .idle_timeout(Duration::ZERO)
+        .build();
+    let node1 = Node::new(Arc::new(swarm));
+    let node2 = prepare_node(SecretKey::random()).await;
+
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    wait_for_msgs([&node1, &node2]).await;
+
+    // No keepalive runner with a zero interval, and no connection is taken as idle.
+    assert!(KeepAlive::new(node1.swarm.clone()).is_none());
+    assert_eq!(node1.swarm.evict_idle_connections().await.unwrap(), 0);
+    assert!(node1.swarm.transport.get_connection(node2.did()).is_some());
+}
+
+#[tokio::test]
+async fn test_update_ice_servers() {
+    let node1 = prepare_node(SecretKey::random()).await;
+    let node2 = prepare_node(SecretKey::random()).await;
+    let node3 = prepare_node(SecretKey::random()).await;
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    wait_for_msgs([&node1, &node2]).await;
+
+    let ice_servers = IceServer::vec_from_str("stun://stun1.l.google.com:19302").unwrap();
+    node1.swarm.update_ice_servers(ice_servers).await.unwrap();
+
+    // The established connection is not interrupted, and new connections use the new servers.
+    let conn = node1.swarm.transport.get_connection(node2.did()).unwrap();
+    assert_eq!(
+        conn.connection.webrtc_connection_state(),
+        WebrtcConnectionState::Connected
+    );
+    manually_establish_connection(&node1.swarm, &node3.swarm).await;
+    wait_for_msgs([&node1, &node2, &node3]).await;
+
+    // Relay fallback requires a TURN server in the new servers as well.
+    let node4 = {
+        let ice_servers = "stun://stun.l.google.com:19302;turn://ethereum.org:9090";
+        let storage = Box::new(MemStorage::new());
+        let session_sk = SessionSk::new_with_seckey(&SecretKey::random()).unwrap();
+        let swarm = SwarmBuilder::new(0, ice_servers, storage, session_sk)
+            .relay_fallback(true)
+            .build();
+        Node::new(Arc::new(swarm))
+    };
+    let stun_only = IceServer::vec_from_str("stun://stun.l.google.com:19302").unwrap();
+    assert!(node4.swarm.update_ice_servers(stun_only).await.is_err());
+}
+
+#[tokio::test]
+async fn test_max_connections_rejects_offer() {
+    let keys = gen_ordered_keys(3);
+    let node1 = prepare_node(keys[0]).await;
+    let node2 = prepare_node(keys[1]).await;
+    let node3 = {
+        let stun = "stun://stun.l.google.com:19302";
+        let storage = Box::new(MemStorage::new());
+        let session_sk = SessionSk::new_with_seckey(&keys[2]).unwrap();
+        let swarm = SwarmBuilder::new(0, stun, storage, session_sk)
+            .max_connections(1)
+            .build();
+        Node::new(Arc::new(swarm))
+    };
+
+    manually_establish_connection(&node1.swarm, &node3.swarm).await;
+    wait_for_msgs([&node1, &node2, &node3]).await;
+
+    let offer = node2.swarm.create_offer(node3.did()).await.unwrap();
+    assert!(matches!(
+        node3.swarm.answer_offer(offer).await,
+        Err(crate::error::Error::TooManyConnections(1))
+    ));
+    node3.assert_transports(vec![node1.did()]);
+}
+
+struct DenyGater(Did);
+
+#[async_trait::async_trait]
+impl ConnectionGater for DenyGater {
+    async fn allow_inbound(&self, did: Did, info: &HandshakeInfo) -> bool {
+        assert_eq!(info.did(), did);
+        did != self.0
+    }
+}
+
+#[tokio::test]
+async fn test_connection_gater_rejects_offer() {
+    let node1 = prepare_node(SecretKey::random()).await;
+    let node2 = prepare_node(SecretKey::random()).await;
+    let node3 = {
+        let stun = "stun://stun.l.google.com:19302";
+        let storage = Box::new(MemStorage::new());
+        let session_sk = SessionSk::new_with_seckey(&SecretKey::random()).unwrap();
+        let swarm = SwarmBuilder::new(0, stun, storage, session_sk)
+            .connection_gater(Arc::new(DenyGater(node1.did())))
+            .build();
+        Node::new(Arc::new(swarm))
+    };
+
+    // The offer is dropped if node1 is not known to decode the reject.
+    let offer = node1.swarm.create_offer(node3.did()).await.unwrap();
+    assert!(matches!(
+        node3.swarm.answer_offer(offer).await,
+        Err(crate::error::Error::InboundConnectionDenied(did)) if did == node1.did()
+    ));
+    assert!(node3.swarm.transport.get_connection(node1.did()).is_none());
+    node1.swarm.disconnect(node3.did()).await.unwrap();
+
+    node3
+        .swarm
+        .transport
+        .record_peer_version(node1.did(), PAYLOAD_VERSION);
+    let offer = node1.swarm.create_offer(node3.did()).await.unwrap();
+    let reject = node3.swarm.answer_offer(offer).await.unwrap();
+    assert_eq!(reject.transaction.destination, node1.did());
+    assert!(matches!(
+        node1.swarm.accept_answer(reject).await,
+        Err(crate::error::Error::ConnectionRejected(
+            ConnectRejectReason::Denied
+        ))
+    ));
+    assert!(node1.swarm.transport.get_connection(node3.did()).is_none());
+    assert!(node3.swarm.transport.get_connection(node1.did()).is_none());
+
+    manually_establish_connection(&node2.swarm, &node3.swarm).await;
+    node3.assert_transports(vec![node2.did()]);
+}
+
+#[tokio::test]
+async fn test_replayed_offer_is_rejected() {
+    let node1 = prepare_node(SecretKey::random()).await;
+    let node2 = prepare_node(SecretKey::random()).await;
+
+    let offer = node1.swarm.create_offer(node2.did()).await.unwrap();
+    node2.swarm.answer_offer(offer.clone()).await.unwrap();
+
+    let replayed = node2.swarm.answer_offer(offer).await;
+    assert!(matches!(
+        replayed,
+        Err(crate::error::Error::HandshakeReplayed(did)) if did == node1.did()
+    ));
+}
+
+struct NoopCallback;
+impl SwarmCallback for NoopCallback {}
+
+#[tokio::test]
+async fn test_relayed_offer_is_replay_checked() {
+    let node1 = prepare_node(SecretKey::random()).await;
+    let node2 = prepare_node(SecretKey::random()).await;
+
+    let offer = node1.swarm.create_offer(node2.did()).await.unwrap();
+    let Message::ConnectNodeSend(msg) = offer.transaction.data().unwrap() else {
+        panic!("Should be ConnectNodeSend");
+    };
+    node2.swarm.answer_offer(offer.clone()).await.unwrap();
+
+    // The same offer is replayed to node2 through DHT.
+    let handler = MessageHandler::new(node2.swarm.transport.clone(), Arc::new(NoopCallback));
+    let replayed = handler.handle(&offer, &msg).await;
+    assert!(matches!(
+        replayed,
+        Err(crate::error::Error::HandshakeReplayed(did)) if did == node1.did()
+    ));
+}
+
+#[tokio::test]
+async fn test_relayed_offer_out_of_window_is_rejected() {
+    let node1 = prepare_node(SecretKey::random()).await;
+    let clock = Arc::new(MockClock::new(SystemTime::now()));
+    let node2 = {
+        let stun = "stun://stun.l.google.com:19302";
+        let storage = Box::new(MemStorage::new());
+        let session_sk = SessionSk::new_with_seckey(&SecretKey::random()).unwrap();
+        let swarm = SwarmBuilder::new(0, stun, storage, session_sk)
+            .handshake_replay_window(Duration::from_secs(1))
+            .clock(clock.clone())
+            .build();
+        Node::new(Arc::new(swarm))
+    };
+
+    let offer = node1.swarm.create_offer(node2.did()).await.unwrap();
+    let Message::ConnectNodeSend(msg) = offer.transaction.data().unwrap() else {
+        panic!("Should be ConnectNodeSend");
+    };
+    clock.advance(Duration::from_secs(2));
+
+    let handler = MessageHandler::new(node2.swarm.transport.clone(), Arc::new(NoopCallback));
+    let expired = handler.handle(&offer, &msg).await;
+    assert!(matches!(
+        expired,
+        Err(crate::error::Error::HandshakeExpired(did)) if did == node1.did()
+    ));
+}
+
+#[tokio::test]
+async fn test_peer_metadata_exchanged_on_handshake() {
+    let node1 = {
+        let stun = "stun://stun.l.google.com:19302";
+        let storage = Box::new(MemStorage::new());
+        let session_sk = SessionSk::new_with_seckey(&SecretKey::random()).unwrap();
+        let metadata = HashMap::from([("service".to_string(), "image".to_string())]);
+        let swarm = SwarmBuilder::new(0, stun, storage, session_sk)
+            .metadata(metadata)
+            .build();
+        Node::new(Arc::new(swarm))
+    };
+    let node2 = prepare_node(SecretKey::random()).await;
+
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+
+    let metadata = node2.swarm.peer_metadata(node1.did()).await.unwrap();
+    assert_eq!(metadata.get("service").unwrap(), "image");
+    assert!(node1.swarm.peer_metadata(node2.did()).await.is_none());
+}
+
+#[tokio::test]
+async fn test_handshake_info() {
+    let node1 = prepare_node(SecretKey::random()).await;
+    let node2 = prepare_node(SecretKey::random()).await;
+
+    let offer = node1.swarm.create_offer(node2.did()).await.unwrap();
+    let info = HandshakeInfo::from_payload(offer.clone()).unwrap();
+    assert_eq!(info.did(), node1.did());
+    assert_eq!(info.sdp_type(), "offer");
+    assert_eq!(info.created_at(), offer.transaction.verification.ts_ms);
+    assert!(info.created_at() <= get_epoch_ms());
+
+    let decoded = HandshakeInfo::from_encoded(&info.encode().unwrap()).unwrap();
+    assert_eq!(decoded.did(), node1.did());
+    assert_eq!(decoded.candidate_count(), info.candidate_count());
+
+    let answer = node2.swarm.answer_offer(offer).await.unwrap();
+    let info = HandshakeInfo::from_payload(answer.clone()).unwrap();
+    assert_eq!(info.did(), node2.did());
+    assert_eq!(info.sdp_type(), "answer");
+
+    // The payload is expired at the time of a clock far in the future.
+    let clock = MockClock::new(SystemTime::now());
+    assert!(HandshakeInfo::from_payload_with(answer.clone(), &clock).is_ok());
+    clock.advance(Duration::from_secs(365 * 24 * 60 * 60));
+    assert!(HandshakeInfo::from_payload_with(answer, &clock).is_err());
+
+    let not_handshake = MessagePayload::new_send(
+        Message::custom(b"hello").unwrap(),
+        node1.swarm.transport.session_sk(),
+        node2.did(),
+        node2.did(),
+    )
+    .unwrap();
+    assert!(HandshakeInfo::from_payload(not_handshake).is_err());
+}
+
+#[tokio::test]
+async fn test_send_message_on_labeled_channel() {
+    let node1 = prepare_node(SecretKey::random()).await;
+    let node2 = prepare_node(SecretKey::random()).await;
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    wait_for_msgs([&node1, &node2]).await;
+
+    let config = DataChannelConfig::default();
+    let channel = node1
+        .swarm
+        .open_channel(node2.did(), "bulk", config)
+        .await
+        .unwrap();
+    assert_eq!(channel.label(), "bulk");
+    assert!(node1
+        .swarm
+        .open_channel(node2.did(), "bulk", config)
+        .await
+        .is_err());
+    assert!(node1
+        .swarm
+        .open_channel(node2.did(), "rings_data_channel_0", config)
+        .await
+        .is_err());
+
+    // The channel opened by node1 is usable by node2 as well.
+    let msg = Message::custom(b"bulk data").unwrap();
+    let tx_id = node2
+        .swarm
+        .send_message_on("bulk", msg, node1.did())
+        .await
+        .unwrap();
+    let msg = Message::custom(b"control").unwrap();
+    assert!(node2
+        .swarm
+        .send_message_on("control", msg, node1.did())
+        .await
+        .is_err());
+
+    let received = tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            let payload = node1.listen_once().await.unwrap();
+            if let Ok(Message::CustomMessage(msg)) = payload.transaction.data() {
+                return (payload.transaction.tx_id, msg);
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(received.0, tx_id);
+    assert_eq!(received.1 .0, b"bulk data");
+}
+
+/// Dial by handshaking with the bootstrap node directly, as signaling through its endpoint.
+struct EndpointDialer {
+    local: Arc<Swarm>,
+    bootstrap: Arc<Swarm>,
+}
+
+#[async_trait::async_trait]
+impl BootstrapDialer for EndpointDialer {
+    async fn dial(&self, node: &BootstrapEntry) -> bool {
+        assert_eq!(node.did, self.bootstrap.did());
+        let Ok(offer) = self.local.create_offer(node.did).await else {
+            return false;
+        };
+        let Ok(answer) = self.bootstrap.answer_offer(offer).await else {
+            return false;
+        };
+        self.local.accept_answer(answer).await.is_ok()
+    }
+}
+
+#[tokio::test]
+async fn test_reconnect_bootstrap_node_via_endpoint() {
+    let node2 = prepare_node(SecretKey::random()).await;
+    let node1 = {
+        let stun = "stun://stun.l.google.com:19302";
+        let storage = Box::new(MemStorage::new());
+        let session_sk = SessionSk::new_with_seckey(&SecretKey::random()).unwrap();
+        let swarm = SwarmBuilder::new(0, stun, storage, session_sk)
+            .bootstrap_nodes(vec![BootstrapEntry {
+                did: node2.did(),
+                endpoint: "http://127.0.0.1:50000".to_string(),
+            }])
+            .reconnect(ReconnectPolicy {
+                initial_backoff: Duration::from_millis(100),
+                max_backoff: Duration::from_millis(200),
+                max_attempts: None,
+            })
+            .build();
+        Node::new(Arc::new(swarm))
+    };
+
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    wait_for_msgs([&node1, &node2]).await;
+    assert!(node1.swarm.reconnect_states().is_empty());
+
+    // The bootstrap node drops node1, which is watched by node1 for reconnection.
+    node2.swarm.disconnect(node1.did()).await.unwrap();
+    wait_for_msgs([&node1, &node2]).await;
+    node1.assert_transports(vec![]);
+    let states = node1.swarm.reconnect_states();
+    assert_eq!(states.len(), 1);
+    assert_eq!(states[0].peer, node2.did());
+
+    // It was the only connection, so it can't be dialed over dht.
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    node1.swarm.reconnect_due(None).await;
+    node1.assert_transports(vec![]);
+    assert_eq!(node1.swarm.reconnect_states()[0].attempts, 1);
+
+    let dialer: SharedBootstrapDialer = Arc::new(EndpointDialer {
+        local: node1.swarm.clone(),
+        bootstrap: node2.swarm.clone(),
+    });
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    node1.swarm.reconnect_due(Some(&dialer)).await;
+    wait_for_msgs([&node1, &node2]).await;
+    node1.assert_transports(vec![node2.did()]);
+    node2.assert_transports(vec![node1.did()]);
+    assert!(node1.swarm.reconnect_states().is_empty());
+}
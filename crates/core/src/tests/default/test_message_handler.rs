@@ -1,21 +1,49 @@
 use std::str::FromStr;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
 
+use async_trait::async_trait;
+use rings_transport::core::callback::TransportCallback;
 use rings_transport::core::transport::WebrtcConnectionState;
 use tokio::time::sleep;
 use tokio::time::Duration;
 
+use crate::chunk::ChunkList;
+use crate::consts::CHUNK_PAYLOAD_OVERHEAD;
+use crate::consts::DEFAULT_MAX_MESSAGE_SIZE;
+use crate::consts::MIN_CHUNK_DATA_SIZE;
+use crate::consts::TRANSPORT_MAX_SIZE;
+use crate::consts::TRANSPORT_MTU;
 use crate::dht::successor::SuccessorReader;
 use crate::dht::vnode::VirtualNode;
 use crate::ecc::tests::gen_ordered_keys;
 use crate::ecc::SecretKey;
+use crate::error::Error;
 use crate::error::Result;
 use crate::message;
+use crate::message::Ack;
 use crate::message::Encoder;
 use crate::message::FindSuccessorReportHandler;
 use crate::message::FindSuccessorThen;
 use crate::message::Message;
+use crate::message::MessageHandler;
+use crate::message::MessagePayload;
+use crate::message::MessageVerificationExt;
+use crate::message::PayloadSender;
+use crate::metrics;
 use crate::prelude::vnode::VNodeOperation;
+use crate::session::SessionSk;
+use crate::storage::MemStorage;
+use crate::swarm::callback::CompositeCallback;
+use crate::swarm::callback::InnerSwarmCallback;
+use crate::swarm::callback::SharedSwarmCallback;
+use crate::swarm::callback::SwarmCallback;
+use crate::swarm::AckPolicy;
+use crate::swarm::SwarmBuilder;
 use crate::tests::default::prepare_node;
+use crate::tests::default::Node;
 use crate::tests::manually_establish_connection;
 
 #[tokio::test]
@@ -400,3 +428,537 @@ async fn test_handle_storage() -> Result<()> {
     assert_eq!(data.data[0].clone().decode::<String>().unwrap(), message);
     Ok(())
 }
+
+#[tokio::test]
+async fn test_duplicated_message_only_inbound_once() -> Result<()> {
+    struct InboundCounter(AtomicUsize);
+
+    #[async_trait]
+    impl SwarmCallback for InboundCounter {
+        async fn on_inbound(
+            &self,
+            _payload: &MessagePayload,
+        ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    let node1 = prepare_node(SecretKey::random()).await;
+    let node2 = prepare_node(SecretKey::random()).await;
+
+    let counter = Arc::new(InboundCounter(AtomicUsize::new(0)));
+    let callback = InnerSwarmCallback::new(node2.swarm.transport.clone(), counter.clone());
+
+    let payload = MessagePayload::new_send(
+        Message::custom(b"hello")?,
+        node1.swarm.transport.session_sk(),
+        node2.did(),
+        node2.did(),
+    )?;
+    let data = payload.to_bincode()?;
+
+    // The same payload arrives twice, e.g. via different DHT paths.
+    let cid = node1.did().to_string();
+    callback.on_message(&cid, &data).await.unwrap();
+    callback.on_message(&cid, &data).await.unwrap();
+
+    assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_drop_message_with_expired_ttl() -> Result<()> {
+    struct ValidateCounter(AtomicUsize);
+
+    #[async_trait]
+    impl SwarmCallback for ValidateCounter {
+        async fn on_validate(
+            &self,
+            _payload: &MessagePayload,
+        ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    let node1 = prepare_node(SecretKey::random()).await;
+    let node2 = prepare_node(SecretKey::random()).await;
+    let node3 = prepare_node(SecretKey::random()).await;
+
+    let counter = Arc::new(ValidateCounter(AtomicUsize::new(0)));
+    let callback = InnerSwarmCallback::new(node2.swarm.transport.clone(), counter.clone());
+    let cid = node1.did().to_string();
+
+    // A message which is still on its way to node3 cannot be relayed by node2 any more.
+    let mut payload = MessagePayload::new_send(
+        Message::custom(b"hello")?,
+        node1.swarm.transport.session_sk(),
+        node2.did(),
+        node3.did(),
+    )?;
+    payload.relay.ttl = 0;
+    let data = payload.to_bincode()?;
+    callback.on_message(&cid, &data).await.unwrap();
+    assert_eq!(counter.0.load(Ordering::SeqCst), 0);
+
+    // The ttl doesn't matter once the message reaches its destination.
+    let mut payload = MessagePayload::new_send(
+        Message::custom(b"hello")?,
+        node1.swarm.transport.session_sk(),
+        node2.did(),
+        node2.did(),
+    )?;
+    payload.relay.ttl = 0;
+    let data = payload.to_bincode()?;
+    callback.on_message(&cid, &data).await.unwrap();
+    assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_encrypt_for_relayed_destination() -> Result<()> {
+    struct NoopCallback;
+    impl SwarmCallback for NoopCallback {}
+
+    let keys = gen_ordered_keys(3);
+    let node1 = prepare_node(keys[0]).await;
+    let node2 = prepare_node(keys[1]).await;
+    let node3 = prepare_node(keys[2]).await;
+
+    // node1 <-> node2 <-> node3, messages between node1 and node3 are relayed by node2.
+    manually_establish_connection(&node3.swarm, &node2.swarm).await;
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    sleep(Duration::from_secs(3)).await;
+
+    // node1 learns the session of node3 from a message sent by node3.
+    node3
+        .swarm
+        .send_message(Message::custom(b"hello")?, node1.did())
+        .await?;
+    sleep(Duration::from_secs(3)).await;
+
+    let handler = MessageHandler::new(node1.swarm.transport.clone(), Arc::new(NoopCallback));
+    let encrypted = handler.encrypt_for(node3.did(), b"secret")?;
+    assert!(node2.swarm.decrypt_message(&encrypted).is_err());
+
+    node1
+        .swarm
+        .send_message(Message::EncryptedMessage(encrypted), node3.did())
+        .await?;
+
+    let received = tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            let payload = node3.listen_once().await.unwrap();
+            if let Ok(Message::EncryptedMessage(msg)) = payload.transaction.data() {
+                return msg;
+            }
+        }
+    })
+    .await
+    .expect("node3 should receive the encrypted message");
+
+    assert_eq!(node3.swarm.decrypt_message(&received)?.0, b"secret");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_composite_callback_isolates_failures() -> Result<()> {
+    struct Failing;
+
+    #[async_trait]
+    impl SwarmCallback for Failing {
+        async fn on_inbound(
+            &self,
+            _payload: &MessagePayload,
+        ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+            Err("failing callback".into())
+        }
+    }
+
+    struct Panicking;
+
+    #[async_trait]
+    impl SwarmCallback for Panicking {
+        async fn on_inbound(
+            &self,
+            _payload: &MessagePayload,
+        ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+            panic!("panicking callback")
+        }
+    }
+
+    struct InboundCounter(AtomicUsize);
+
+    #[async_trait]
+    impl SwarmCallback for InboundCounter {
+        async fn on_inbound(
+            &self,
+            _payload: &MessagePayload,
+        ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    let node1 = prepare_node(SecretKey::random()).await;
+    let node2 = prepare_node(SecretKey::random()).await;
+
+    let counter = Arc::new(InboundCounter(AtomicUsize::new(0)));
+    let callbacks: Vec<SharedSwarmCallback> = vec![Arc::new(Failing), Arc::new(Panicking)];
+    let composite = CompositeCallback::new(callbacks).with(counter.clone());
+
+    let payload = MessagePayload::new_send(
+        Message::custom(b"hello")?,
+        node1.swarm.transport.session_sk(),
+        node2.did(),
+        node2.did(),
+    )?;
+
+    let err = composite.on_inbound(&payload).await.unwrap_err();
+    assert_eq!(err.to_string(), "failing callback");
+    assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+    Ok(())
+}
+
+/// Drop the chunks of large messages but the first one while lossy, as if the connection is
+/// interrupted in the middle of a transfer, and record the custom messages received.
+#[derive(Default)]
+struct LossyChunks {
+    lossy: AtomicBool,
+    received: std::sync::Mutex<Vec<Vec<u8>>>,
+}
+
+#[async_trait]
+impl SwarmCallback for LossyChunks {
+    async fn on_validate(
+        &self,
+        payload: &MessagePayload,
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        if let Ok(Message::Chunk(chunk)) = payload.transaction.data() {
+            if self.lossy.load(Ordering::SeqCst) && chunk.chunk[0] > 0 {
+                return Err("drop chunk".into());
+            }
+        }
+        Ok(())
+    }
+
+    async fn on_inbound(
+        &self,
+        payload: &MessagePayload,
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        if let Ok(Message::CustomMessage(msg)) = payload.transaction.data() {
+            self.received.lock().unwrap().push(msg.0);
+        }
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_resume_interrupted_chunks() -> Result<()> {
+    let node1 = prepare_node(SecretKey::random()).await;
+    let node2 = prepare_node(SecretKey::random()).await;
+    let receiver = Arc::new(LossyChunks::default());
+    node2.swarm.set_callback(receiver.clone())?;
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    sleep(Duration::from_secs(3)).await;
+
+    // Only the first chunk arrives before the connection is interrupted.
+    receiver.lossy.store(true, Ordering::SeqCst);
+    let data = vec![7u8; DEFAULT_MAX_MESSAGE_SIZE * 2];
+    node1
+        .swarm
+        .send_message(Message::custom(&data)?, node2.did())
+        .await?;
+    sleep(Duration::from_secs(1)).await;
+    assert!(receiver.received.lock().unwrap().is_empty());
+
+    node1.swarm.disconnect(node2.did()).await?;
+    // The closing may have been reported to node2 already.
+    let _ = node2.swarm.disconnect(node1.did()).await;
+    receiver.lossy.store(false, Ordering::SeqCst);
+
+    // Once reconnected, node2 asks for the missing chunks, and node1 sends them again.
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    let received = tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            if let Some(msg) = receiver.received.lock().unwrap().first() {
+                return msg.clone();
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await
+    .expect("node2 should receive the resumed message");
+    assert_eq!(received, data);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_chunk_messages_over_max_size() -> Result<()> {
+    let storage = Box::new(MemStorage::new());
+    let session_sk = SessionSk::new_with_seckey(&SecretKey::random()).unwrap();
+    // Raised to leave room for the least data of a chunk.
+    let swarm = SwarmBuilder::new(0, "stun://stun.l.google.com:19302", storage, session_sk)
+        .max_message_size(1)
+        .build();
+    let node1 = Node::new(Arc::new(swarm));
+    let node2 = prepare_node(SecretKey::random()).await;
+    let limit = node1.swarm.transport.max_message_size();
+    assert_eq!(limit, CHUNK_PAYLOAD_OVERHEAD + MIN_CHUNK_DATA_SIZE);
+
+    // A chunk of the least data fits in the limit after wrapped by a signed payload.
+    let chunk = ChunkList::<TRANSPORT_MTU>::split(
+        &vec![7u8; MIN_CHUNK_DATA_SIZE].into(),
+        MIN_CHUNK_DATA_SIZE,
+    )
+    .to_vec()
+    .remove(0);
+    let wrapped = MessagePayload::new_send(
+        Message::Chunk(chunk),
+        node1.swarm.transport.session_sk(),
+        node2.did(),
+        node2.did(),
+    )?
+    .to_bincode()?;
+    assert!(wrapped.len() <= limit);
+
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    sleep(Duration::from_secs(3)).await;
+    let before = node1.swarm.peer_stats(node2.did()).await.unwrap().current;
+
+    let data = vec![7u8; 40 * 1024];
+    let payload = MessagePayload::new_send(
+        Message::custom(&data)?,
+        node1.swarm.transport.session_sk(),
+        node2.did(),
+        node2.did(),
+    )?;
+    node1.swarm.transport.send_payload(payload).await?;
+
+    let received = tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            let payload = node2.listen_once().await.unwrap();
+            if let Ok(Message::CustomMessage(msg)) = payload.transaction.data() {
+                return msg;
+            }
+        }
+    })
+    .await
+    .expect("node2 should receive the chunked message");
+    assert_eq!(received.0, data);
+
+    // The message is split into chunks of the least data, each sent within the limit.
+    let after = node1.swarm.peer_stats(node2.did()).await.unwrap().current;
+    let messages = after.messages_sent - before.messages_sent;
+    let bytes = after.bytes_sent - before.bytes_sent;
+    let expected = (data.len() / MIN_CHUNK_DATA_SIZE) as u64;
+    assert!((expected..=expected + 1).contains(&messages));
+    assert!(bytes <= messages * limit as u64);
+
+    // A message over the total limit is rejected before sending.
+    let payload = MessagePayload::new_send(
+        Message::custom(&vec![0u8; TRANSPORT_MAX_SIZE])?,
+        node1.swarm.transport.session_sk(),
+        node2.did(),
+        node2.did(),
+    )?;
+    assert!(matches!(
+        node1.swarm.transport.send_payload(payload).await,
+        Err(Error::MessageTooLarge(size)) if size > TRANSPORT_MAX_SIZE
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_send_message_to_self_loops_back() -> Result<()> {
+    // The node is not connected to anyone, sending to itself never touches a connection.
+    let node = prepare_node(SecretKey::random()).await;
+    let looped_back = metrics::MESSAGES_LOOPED_BACK.get();
+
+    let tx_id = node
+        .swarm
+        .send_message(Message::custom(b"hello myself")?, node.did())
+        .await?;
+
+    let payload = node.listen_once().await.unwrap();
+    assert_eq!(payload.transaction.tx_id, tx_id);
+    assert_eq!(payload.transaction.signer(), node.did());
+    let Message::CustomMessage(msg) = payload.transaction.data()? else {
+        panic!("unexpected message");
+    };
+    assert_eq!(msg.0, b"hello myself");
+    assert!(metrics::MESSAGES_LOOPED_BACK.get() > looped_back);
+    assert!(node.swarm.peer_stats(node.did()).await.is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_send_reliable_message() -> Result<()> {
+    let node1 = prepare_node(SecretKey::random()).await;
+    let node2 = prepare_node(SecretKey::random()).await;
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    sleep(Duration::from_secs(3)).await;
+
+    let tx_id = node1
+        .swarm
+        .send_reliable(b"hello", node2.did(), AckPolicy::default())
+        .await?;
+
+    let received = tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            let payload = node2.listen_once().await.unwrap();
+            if let Ok(Message::ReliableMessage(msg)) = payload.transaction.data() {
+                return (payload.transaction.tx_id, msg);
+            }
+        }
+    })
+    .await
+    .expect("node2 should receive the reliable message");
+    assert_eq!(received.0, tx_id);
+    assert_eq!(received.1 .0, b"hello");
+
+    // A message to a destination without any route is never acked.
+    let policy = AckPolicy {
+        timeout: Duration::from_millis(500),
+        max_attempts: 2,
+    };
+    let unknown = SecretKey::random().address().into();
+    let err = node1
+        .swarm
+        .send_reliable(b"hello", unknown, policy)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::MessageNotAcked { attempts: 2, .. }));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ignore_ack_from_other_than_destination() -> Result<()> {
+    let node1 = Arc::new(prepare_node(SecretKey::random()).await);
+    let node2 = prepare_node(SecretKey::random()).await;
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    sleep(Duration::from_secs(3)).await;
+
+    // The destination is unreachable, while node2 forges its ack.
+    let destination = SecretKey::random().address().into();
+    let policy = AckPolicy {
+        timeout: Duration::from_secs(3),
+        max_attempts: 1,
+    };
+    let sending = tokio::spawn({
+        let node1 = node1.clone();
+        async move {
+            node1
+                .swarm
+                .send_reliable(b"hello", destination, policy)
+                .await
+        }
+    });
+    let tx_id = tokio::time::timeout(Duration::from_secs(3), async {
+        loop {
+            if let Some(entry) = node1.swarm.transport.pending_acks.iter().next() {
+                return *entry.key();
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("node1 should wait for the ack");
+
+    node2
+        .swarm
+        .send_message(Message::Ack(Ack { tx_id }), node1.did())
+        .await?;
+    tokio::time::timeout(Duration::from_secs(3), async {
+        loop {
+            let payload = node1.listen_once().await.unwrap();
+            if let Ok(Message::Ack(ack)) = payload.transaction.data() {
+                assert_eq!(ack.tx_id, tx_id);
+                return;
+            }
+        }
+    })
+    .await
+    .expect("node1 should receive the forged ack");
+
+    let err = sending.await.unwrap().unwrap_err();
+    assert!(matches!(err, Error::MessageNotAcked { attempts: 1, .. }));
+    assert!(node1.swarm.transport.pending_acks.is_empty());
+    Ok(())
+}
+
+/// Reject the first [Ack] received, as if it's lost.
+#[derive(Default)]
+struct DropFirstAck {
+    dropped: AtomicBool,
+}
+
+#[async_trait]
+impl SwarmCallback for DropFirstAck {
+    async fn on_validate(
+        &self,
+        payload: &MessagePayload,
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        if let Ok(Message::Ack(_)) = payload.transaction.data() {
+            if !self.dropped.swap(true, Ordering::SeqCst) {
+                return Err("drop the first ack".into());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_resend_reliable_message_after_lost_ack() -> Result<()> {
+    let node1 = prepare_node(SecretKey::random()).await;
+    let node2 = prepare_node(SecretKey::random()).await;
+    manually_establish_connection(&node1.swarm, &node2.swarm).await;
+    sleep(Duration::from_secs(3)).await;
+
+    let dropper = Arc::new(DropFirstAck::default());
+    node1.swarm.set_callback(dropper.clone())?;
+    let policy = AckPolicy {
+        timeout: Duration::from_secs(1),
+        max_attempts: 3,
+    };
+    let tx_id = node1
+        .swarm
+        .send_reliable(b"hello", node2.did(), policy)
+        .await?;
+    assert!(dropper.dropped.load(Ordering::SeqCst));
+    assert!(node1.swarm.transport.pending_acks.is_empty());
+
+    // The message is resent with the same tx_id, and acked again.
+    let received = tokio::time::timeout(Duration::from_secs(5), async {
+        let mut received = 0;
+        while received < 2 {
+            let payload = node2.listen_once().await.unwrap();
+            if let Ok(Message::ReliableMessage(msg)) = payload.transaction.data() {
+                assert_eq!(payload.transaction.tx_id, tx_id);
+                assert_eq!(msg.0, b"hello");
+                received += 1;
+            }
+        }
+        received
+    })
+    .await
+    .expect("node2 should receive the reliable message twice");
+    assert_eq!(received, 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_forget_ack_of_dropped_reliable_message() -> Result<()> {
+    let node1 = prepare_node(SecretKey::random()).await;
+    let destination = SecretKey::random().address().into();
+
+    let sending = node1
+        .swarm
+        .send_reliable(b"hello", destination, AckPolicy::default());
+    let dropped = tokio::time::timeout(Duration::from_millis(500), sending).await;
+    assert!(dropped.is_err());
+    assert!(node1.swarm.transport.pending_acks.is_empty());
+    Ok(())
+}
@@ -5,6 +5,21 @@ pub fn get_epoch_ms() -> u128 {
     Utc::now().timestamp_millis() as u128
 }
 
+/// Sleep for a duration, without blocking the executor.
+#[cfg(not(feature = "wasm"))]
+pub async fn sleep(duration: std::time::Duration) {
+    futures_timer::Delay::new(duration).await
+}
+
+/// Sleep for a duration, without blocking the executor.
+#[cfg(feature = "wasm")]
+pub async fn sleep(duration: std::time::Duration) {
+    let millis = duration.as_millis().try_into().unwrap_or(i32::MAX);
+    if let Err(e) = js_utils::window_sleep(millis).await {
+        tracing::error!("Failed to sleep: {:?}", e);
+    }
+}
+
 #[cfg(feature = "wasm")]
 /// Toolset for wasm
 pub mod js_value {
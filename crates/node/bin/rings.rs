@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::path::Path;
 use std::str::FromStr;
 use std::sync::Arc;
@@ -225,7 +226,7 @@ impl SessionArgs {
         let key_did: Did = key.address().into();
 
         let ssk_builder = SessionSkBuilder::new(key_did.to_string(), "secp256k1".to_string())
-            .set_ttl(self.ttl * 1000);
+            .session_ttl(Duration::from_secs(self.ttl));
         let unsigned_proof = ssk_builder.unsigned_proof();
 
         let sig = key.sign(&unsigned_proof).to_vec();
@@ -362,6 +363,9 @@ struct SendCustomMessageCommand {
     to_did: String,
     message_type: u16,
     data: String,
+
+    #[arg(long, help = "encrypt data to the destination")]
+    encrypt: bool,
 }
 
 #[derive(Subcommand, Debug)]
@@ -413,8 +417,13 @@ async fn daemon_run(args: RunCommand) -> anyhow::Result<()> {
         c.internal_api_port = internal_api_port;
     }
 
+    if let Some(dir) = &c.receive_dir {
+        c.receive_dir = Some(expand_home(dir)?.to_string_lossy().into_owned());
+    }
+
     let pc = ProcessorConfig::try_from(c.clone())?;
     let bc = BackendConfig::from(c.clone());
+    bc.validate()?;
 
     let (data_storage, measure_storage) = if let Some(storage_path) = args.storage_path {
         let storage_path = Path::new(&storage_path);
@@ -440,27 +449,44 @@ async fn daemon_run(args: RunCommand) -> anyhow::Result<()> {
 
     let measure = PeriodicMeasure::new(per_measure_storage);
 
-    let processor = Arc::new(
-        ProcessorBuilder::from_config(&pc)?
-            .storage(per_data_storage)
-            .measure(measure)
-            .build()?,
-    );
+    let mut processor_builder = ProcessorBuilder::from_config(&pc)?
+        .storage(per_data_storage)
+        .measure(measure)
+        .bootstrap_nodes(c.bootstrap_nodes.clone())
+        .prefer_ipv6(c.prefer_ipv6)
+        .send_dir(c.send_dir.as_deref().map(expand_home).transpose()?);
+    if let Some(reconnect) = c.reconnect {
+        processor_builder = processor_builder.reconnect(reconnect.into());
+    }
+    let processor = Arc::new(processor_builder.build()?);
     println!("Did: {}", processor.swarm.did());
     let backend_behaviour = BackendBehaviour::new(bc).await?;
     let backend_service_names = backend_behaviour.service_names();
     let provider = Arc::new(Provider::from_processor(processor.clone()));
-    let backend = Arc::new(Backend::new(provider, Box::new(backend_behaviour)));
+    let backend = Arc::new(Backend::new(
+        provider,
+        Box::new(backend_behaviour),
+        HashMap::new(),
+    ));
     processor.swarm.set_callback(backend).unwrap();
 
     let processor_clone1 = processor.clone();
     let processor_clone2 = processor.clone();
-    let _ = futures::join!(
-        processor.listen(),
-        service_loop_register(&processor, backend_service_names),
-        run_internal_api(c.internal_api_port, processor_clone2),
-        run_external_api(c.external_api_addr, processor_clone1),
-    );
+    tokio::select! {
+        _ = async {
+            futures::join!(
+                processor.listen(),
+                bootstrap(&processor),
+                service_loop_register(&processor, backend_service_names),
+                run_internal_api(c.internal_api_port, processor_clone2),
+                run_external_api(c.external_api_addr, processor_clone1),
+            )
+        } => {}
+        _ = tokio::signal::ctrl_c() => {
+            let force_closed = processor.leave().await;
+            tracing::info!("Left the ring, {force_closed} connections are force-closed");
+        }
+    }
 
     Ok(())
 }
@@ -584,7 +610,7 @@ async fn main() -> anyhow::Result<()> {
             args.client_args
                 .new_client()
                 .await?
-                .send_custom_message(args.to_did.as_str(), args.data.as_str())
+                .send_custom_message(args.to_did.as_str(), args.data.as_str(), args.encrypt)
                 .await?
                 .display();
             Ok(())
@@ -643,6 +669,14 @@ async fn register_services(processor: &Processor, names: Vec<String>) -> anyhow:
     Ok(())
 }
 
+async fn bootstrap(processor: &Processor) {
+    match processor.bootstrap().await {
+        Ok(Some(seed)) => println!("Joined network via {}", seed.did),
+        Ok(None) => {}
+        Err(e) => tracing::error!("bootstrap error: {}", e),
+    }
+}
+
 async fn service_loop_register(processor: &Processor, names: Vec<String>) {
     loop {
         let timeout = Delay::new(Duration::from_secs(30)).fuse();
@@ -70,7 +70,7 @@ impl BackendBehaviour {
         match msg {
             BackendMessage::ServiceMessage(m) => {
                 if let Some(func) = &self.get_handler("ServiceMessage") {
-                    let m = js_value::serialize(m)?;
+                    let m = js_value::serialize(&m.clone().decompress()?)?;
                     let cb = js_func::of4::<BackendBehaviour, Provider, JsValue, JsValue>(func);
                     cb(self.clone(), provider.clone(), ctx, m).await?;
                 }
@@ -89,6 +89,50 @@ impl BackendBehaviour {
                     cb(self.clone(), provider.clone(), ctx, m).await?;
                 }
             }
+            BackendMessage::Bytes { .. } => {
+                if let Some(func) = &self.get_handler("Bytes") {
+                    let m = js_value::serialize(msg)?;
+                    let cb = js_func::of4::<BackendBehaviour, Provider, JsValue, JsValue>(func);
+                    cb(self.clone(), provider.clone(), ctx, m).await?;
+                }
+            }
+            // Ping is answered by Backend before reaching behaviour.
+            BackendMessage::Ping { .. } => {}
+            BackendMessage::Pong { .. } => {
+                if let Some(func) = &self.get_handler("Pong") {
+                    let m = js_value::serialize(msg)?;
+                    let cb = js_func::of4::<BackendBehaviour, Provider, JsValue, JsValue>(func);
+                    cb(self.clone(), provider.clone(), ctx, m).await?;
+                }
+            }
+            BackendMessage::WebSocket(m) => {
+                if let Some(func) = &self.get_handler("WebSocket") {
+                    let m = js_value::serialize(m)?;
+                    let cb = js_func::of4::<BackendBehaviour, Provider, JsValue, JsValue>(func);
+                    cb(self.clone(), provider.clone(), ctx, m).await?;
+                }
+            }
+            BackendMessage::FileChunk { .. } => {
+                if let Some(func) = &self.get_handler("FileChunk") {
+                    let m = js_value::serialize(msg)?;
+                    let cb = js_func::of4::<BackendBehaviour, Provider, JsValue, JsValue>(func);
+                    cb(self.clone(), provider.clone(), ctx, m).await?;
+                }
+            }
+            BackendMessage::Protocol { .. } => {
+                if let Some(func) = &self.get_handler("Protocol") {
+                    let m = js_value::serialize(msg)?;
+                    let cb = js_func::of4::<BackendBehaviour, Provider, JsValue, JsValue>(func);
+                    cb(self.clone(), provider.clone(), ctx, m).await?;
+                }
+            }
+            BackendMessage::ProtocolUnsupported { .. } => {
+                if let Some(func) = &self.get_handler("ProtocolUnsupported") {
+                    let m = js_value::serialize(msg)?;
+                    let cb = js_func::of4::<BackendBehaviour, Provider, JsValue, JsValue>(func);
+                    cb(self.clone(), provider.clone(), ctx, m).await?;
+                }
+            }
             #[cfg(feature = "snark")]
             BackendMessage::SNARKTaskMessage(m) => {
                 if let Some(func) = &self.get_handler("SNARKTaskMessage") {
@@ -108,6 +108,7 @@ impl FFIBackendBehaviourWithRuntime {
                 handle_backend_message!(self, provider, extension_message_handler, payload, m)
             }
             BackendMessage::ServiceMessage(m) => {
+                let m = m.clone().decompress()?;
                 handle_backend_message!(self, provider, service_message_handler, payload, m)
             }
             _ => (),
@@ -4,15 +4,23 @@
 #[cfg(feature = "snark")]
 pub mod snark;
 pub mod types;
+use std::collections::HashMap;
 use std::result::Result;
 use std::sync::Arc;
 
 use async_trait::async_trait;
+use rings_core::dht::Did;
 use rings_core::message::CustomMessage;
 use rings_core::message::Message;
 use rings_core::message::MessagePayload;
+use rings_core::message::MessageVerificationExt;
+use rings_core::message::ReliableMessage;
+use rings_core::metrics;
 use rings_core::swarm::callback::SwarmCallback;
+use rings_core::swarm::callback::SwarmEvent;
 use rings_derive::wasm_export;
+use rings_rpc::method::Method;
+use rings_transport::core::transport::WebrtcConnectionState;
 
 use crate::backend::types::BackendMessage;
 use crate::backend::types::MessageHandler;
@@ -32,16 +40,65 @@ type HandlerTrait = dyn MessageHandler<BackendMessage> + Send + Sync;
 #[cfg(feature = "browser")]
 type HandlerTrait = dyn MessageHandler<BackendMessage>;
 
+/// Handler of [BackendMessage::Bytes] payloads published under a topic.
+#[cfg(feature = "node")]
+pub type TopicHandler =
+    Arc<dyn Fn(Vec<u8>) -> Result<(), Box<dyn std::error::Error>> + Send + Sync>;
+/// Handler of [BackendMessage::Bytes] payloads published under a topic.
+#[cfg(feature = "browser")]
+pub type TopicHandler = Arc<dyn Fn(Vec<u8>) -> Result<(), Box<dyn std::error::Error>>>;
+
+/// Handler of [BackendMessage::Protocol] payloads of a kind, with the Did of sender.
+#[cfg(feature = "node")]
+pub type ProtocolHandler =
+    Arc<dyn Fn(Did, Vec<u8>) -> Result<(), Box<dyn std::error::Error>> + Send + Sync>;
+/// Handler of [BackendMessage::Protocol] payloads of a kind, with the Did of sender.
+#[cfg(feature = "browser")]
+pub type ProtocolHandler = Arc<dyn Fn(Did, Vec<u8>) -> Result<(), Box<dyn std::error::Error>>>;
+
 /// Backend handle custom messages from Swarm
 pub struct Backend {
     provider: Arc<Provider>,
     handler: Box<HandlerTrait>,
+    topic_handlers: HashMap<String, TopicHandler>,
+    protocol_handlers: HashMap<String, ProtocolHandler>,
 }
 
 impl Backend {
-    /// Create a new backend instance with Provider and Handler functions
-    pub fn new(provider: Arc<Provider>, handler: Box<HandlerTrait>) -> Self {
-        Self { provider, handler }
+    /// Create a new backend instance with Provider and Handler functions.
+    /// [BackendMessage::Bytes] payloads are dispatched to the handler of `topic_handlers`
+    /// registered with the same topic.
+    pub fn new(
+        provider: Arc<Provider>,
+        handler: Box<HandlerTrait>,
+        topic_handlers: HashMap<String, TopicHandler>,
+    ) -> Self {
+        Self {
+            provider,
+            handler,
+            topic_handlers,
+            protocol_handlers: HashMap::new(),
+        }
+    }
+
+    /// Dispatch [BackendMessage::Protocol] payloads of kind to handler, so that downstream
+    /// crates can add protocols without changing [BackendMessage]. Payloads of kinds without
+    /// a handler are answered with [BackendMessage::ProtocolUnsupported].
+    pub fn with_protocol_handler(mut self, kind: impl ToString, handler: ProtocolHandler) -> Self {
+        self.protocol_handlers.insert(kind.to_string(), handler);
+        self
+    }
+
+    /// Request ids of http requests sent by the handler and still waiting for response,
+    /// see [MessageHandler::pending_requests].
+    pub fn pending_requests(&self) -> Vec<String> {
+        self.handler.pending_requests()
+    }
+
+    /// Stop waiting for the response of request `rid`, its caller gets a cancelled error.
+    /// Returns false if the request is not pending. See [MessageHandler::cancel_request].
+    pub fn cancel_request(&self, rid: &str) -> bool {
+        self.handler.cancel_request(rid)
     }
 
     async fn on_backend_message(
@@ -49,9 +106,52 @@ impl Backend {
         payload: &MessagePayload,
         msg: &BackendMessage,
     ) -> Result<(), Box<dyn std::error::Error>> {
+        match msg {
+            BackendMessage::Ping { nonce, sent_at_ms } => {
+                let pong = BackendMessage::Pong {
+                    nonce: *nonce,
+                    sent_at_ms: *sent_at_ms,
+                };
+                self.reply(payload, pong).await?;
+            }
+            BackendMessage::Bytes { topic, data } => match self.topic_handlers.get(topic) {
+                Some(topic_handler) => topic_handler(data.clone())?,
+                None => tracing::debug!("no handler registered for topic {topic}"),
+            },
+            BackendMessage::Protocol { kind, data } => match self.protocol_handlers.get(kind) {
+                Some(protocol_handler) => {
+                    protocol_handler(payload.transaction.signer(), data.clone())?
+                }
+                None => {
+                    tracing::debug!("no handler registered for protocol {kind}");
+                    let kind = kind.clone();
+                    self.reply(payload, BackendMessage::ProtocolUnsupported { kind })
+                        .await?;
+                }
+            },
+            _ => {}
+        }
         let provider = self.provider.clone();
         self.handler.handle_message(provider, payload, msg).await
     }
+
+    /// Answer the signer of payload with msg, such as a [BackendMessage::Pong] echoed to a ping,
+    /// so that the sender can compute round-trip time from the returned `sent_at_ms`.
+    async fn reply(
+        &self,
+        payload: &MessagePayload,
+        msg: BackendMessage,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let peer_did = payload.transaction.signer();
+        let params = msg.into_send_backend_message_request(peer_did)?;
+        self.provider
+            .request_internal(
+                Method::SendBackendMessage.to_string(),
+                serde_json::to_value(params)?,
+            )
+            .await?;
+        Ok(())
+    }
 }
 
 /// This struct is used to simulate `impl T`
@@ -96,6 +196,18 @@ impl MessageHandler<BackendMessage> for BackendMessageHandlerDynObj {
         self.handle_message(provider.clone(), ctx, msg).await?;
         Ok(())
     }
+
+    async fn on_peer_disconnected(&self, peer: Did) {
+        self.inner.on_peer_disconnected(peer).await
+    }
+
+    fn pending_requests(&self) -> Vec<String> {
+        self.inner.pending_requests()
+    }
+
+    fn cancel_request(&self, rid: &str) -> bool {
+        self.inner.cancel_request(rid)
+    }
 }
 
 #[cfg_attr(feature = "browser", async_trait(?Send))]
@@ -104,15 +216,42 @@ impl SwarmCallback for Backend {
     async fn on_inbound(&self, payload: &MessagePayload) -> Result<(), Box<dyn std::error::Error>> {
         let data: Message = payload.transaction.data()?;
 
-        let Message::CustomMessage(CustomMessage(msg)) = data else {
-            return Ok(());
+        let msg = match data {
+            Message::CustomMessage(CustomMessage(msg)) => msg,
+            Message::ReliableMessage(ReliableMessage(msg)) => msg,
+            Message::EncryptedMessage(ref encrypted) => {
+                let CustomMessage(msg) = self.provider.decrypt_message(encrypted)?;
+                msg
+            }
+            _ => return Ok(()),
         };
 
-        let backend_msg = bincode::deserialize(&msg)?;
+        let backend_msg = BackendMessage::from_bytes(&msg).map_err(|e| {
+            tracing::warn!(
+                "Failed to decode backend message of {} bytes from {:?}: {e}",
+                msg.len(),
+                payload.transaction.signer()
+            );
+            e
+        })?;
         tracing::debug!("backend_message received: {backend_msg:?}");
 
         self.on_backend_message(payload, &backend_msg).await?;
+        metrics::CUSTOM_MESSAGES_HANDLED.inc();
+
+        Ok(())
+    }
 
+    async fn on_event(&self, event: &SwarmEvent) -> Result<(), Box<dyn std::error::Error>> {
+        if let SwarmEvent::ConnectionStateChange { peer, state } = event {
+            // Disconnected is transient, the connection may recover from it.
+            if matches!(
+                state,
+                WebrtcConnectionState::Failed | WebrtcConnectionState::Closed
+            ) {
+                self.handler.on_peer_disconnected(*peer).await;
+            }
+        }
         Ok(())
     }
 }
@@ -115,12 +115,7 @@ impl Extension {
         match path {
             Path::Local(path) => loader::load_from_fs(path.to_string()).await,
             Path::Remote(path) => {
-                let data: String = reqwest::get(path)
-                    .await
-                    .map_err(|e| Error::HttpRequestError(e.to_string()))?
-                    .text()
-                    .await
-                    .map_err(|e| Error::HttpRequestError(e.to_string()))?;
+                let data: String = reqwest::get(path).await?.error_for_status()?.text().await?;
                 loader::load(data).await
             }
         }
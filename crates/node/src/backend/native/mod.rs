@@ -14,34 +14,265 @@
 pub mod extension;
 pub mod service;
 
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::net::SocketAddr;
 use std::result::Result;
 use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
+use rings_core::dht::Did;
 use rings_core::message::MessagePayload;
 use rings_core::message::MessageVerificationExt;
+use serde::Deserialize;
+use serde::Serialize;
 
 use crate::backend::native::extension::Extension;
 use crate::backend::native::extension::ExtensionConfig;
+use crate::backend::native::extension::Path;
+use crate::backend::native::service::HttpExecutor;
+use crate::backend::native::service::RateLimitConfig;
 use crate::backend::native::service::ServiceConfig;
 use crate::backend::native::service::ServiceProvider;
 use crate::backend::types::BackendMessage;
+use crate::backend::types::HttpRequest;
+use crate::backend::types::HttpResponse;
 use crate::backend::types::MessageHandler;
+use crate::consts::DEFAULT_MAX_RECEIVE_BYTES;
 use crate::error::Error;
 use crate::provider::Provider;
+use crate::transfer::FileReceiver;
 
 /// BackendConfig including services config and extension config
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
 pub struct BackendConfig {
     /// Config of services
+    #[serde(default)]
     pub services: Vec<ServiceConfig>,
     /// Config of extensions
+    #[serde(default)]
     pub extensions: ExtensionConfig,
+    /// Maximum count of http requests forwarded to services concurrently, unlimited if not provided
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrent_requests: Option<usize>,
+    /// Dids allowed to use services, open to all if not provided
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_origins: Option<Vec<Did>>,
+    /// Rate limit of http requests from each origin, unlimited if not provided
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<RateLimitConfig>,
+    /// Directory files sent by other nodes are written to, receiving is disabled if not provided
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub receive_dir: Option<String>,
+    /// Max size of a file sent by other nodes, larger ones are rejected,
+    /// [DEFAULT_MAX_RECEIVE_BYTES] if not provided
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_receive_bytes: Option<u64>,
+}
+
+impl BackendConfig {
+    /// Load config from a `.toml`, `.yaml`/`.yml` or `.json` file, detected by its extension.
+    /// Parse errors are reported with line and column. The config is not validated,
+    /// see [BackendConfig::validate].
+    pub fn from_path(path: impl AsRef<std::path::Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| Error::OpenFileError(format!("{}: {e}", path.display())))?;
+        let ext = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase());
+
+        let (location, reason) = match ext.as_deref() {
+            Some("toml") => match toml::from_str(&content) {
+                Ok(config) => return Ok(config),
+                Err(e) => (e.line_col().map(|(l, c)| (l + 1, c + 1)), e.to_string()),
+            },
+            Some("yaml") | Some("yml") => match serde_yaml::from_str(&content) {
+                Ok(config) => return Ok(config),
+                Err(e) => (e.location().map(|l| (l.line(), l.column())), e.to_string()),
+            },
+            Some("json") => match serde_json::from_str(&content) {
+                Ok(config) => return Ok(config),
+                Err(e) => (
+                    (e.line() > 0).then(|| (e.line(), e.column())),
+                    e.to_string(),
+                ),
+            },
+            _ => {
+                return Err(Error::InvalidBackendConfig(format!(
+                    "{} is not a toml, yaml or json file",
+                    path.display()
+                )))
+            }
+        };
+
+        Err(Error::InvalidBackendConfig(match location {
+            Some((line, column)) => format!("{}:{line}:{column}: {reason}", path.display()),
+            None => format!("{}: {reason}", path.display()),
+        }))
+    }
+
+    /// Check the config without starting any service, so that a node can fail fast at startup.
+    pub fn validate(&self) -> Result<(), Error> {
+        let mut names = HashSet::new();
+        for service in &self.services {
+            match (service.addr, &service.unix_socket) {
+                (Some(_), Some(_)) => {
+                    return Err(Error::InvalidBackendConfig(format!(
+                        "service {} sets both addr and unix_socket",
+                        service.name
+                    )))
+                }
+                (None, None) if service.upstreams.is_empty() => {
+                    return Err(Error::InvalidBackendConfig(format!(
+                        "service {} sets neither addr, unix_socket nor upstreams",
+                        service.name
+                    )))
+                }
+                (Some(_), _) | (_, Some(_)) if !service.upstreams.is_empty() => {
+                    return Err(Error::InvalidBackendConfig(format!(
+                        "service {} sets upstreams with addr or unix_socket",
+                        service.name
+                    )))
+                }
+                (Some(addr), None) if addr.port() == 0 => {
+                    return Err(Error::InvalidBackendConfig(format!(
+                        "port of service {} is zero",
+                        service.name
+                    )))
+                }
+                _ => {}
+            }
+            if service.upstreams.iter().any(|u| u.addr.port() == 0) {
+                return Err(Error::InvalidBackendConfig(format!(
+                    "port of an upstream of service {} is zero",
+                    service.name
+                )));
+            }
+            match &service.health_check {
+                Some(_) if service.upstreams.is_empty() => {
+                    return Err(Error::InvalidBackendConfig(format!(
+                        "service {} sets health_check without upstreams",
+                        service.name
+                    )));
+                }
+                Some(check) if check.interval_secs == 0 => {
+                    return Err(Error::InvalidBackendConfig(format!(
+                        "health check interval of service {} is zero",
+                        service.name
+                    )));
+                }
+                _ => {}
+            }
+            if service.channel_buffer == 0 {
+                return Err(Error::InvalidBackendConfig(format!(
+                    "channel_buffer of service {} is zero",
+                    service.name
+                )));
+            }
+            if service.http2_prior_knowledge && service.http1_only {
+                return Err(Error::InvalidBackendConfig(format!(
+                    "service {} sets both http2_prior_knowledge and http1_only",
+                    service.name
+                )));
+            }
+            if !names.insert(service.name.to_ascii_lowercase()) {
+                return Err(Error::InvalidBackendConfig(format!(
+                    "service {} is duplicated",
+                    service.name
+                )));
+            }
+        }
+        if self.max_concurrent_requests == Some(0) {
+            return Err(Error::InvalidBackendConfig(
+                "max_concurrent_requests is zero".to_string(),
+            ));
+        }
+        if let Some(limit) = self.rate_limit {
+            if !(limit.requests_per_second.is_finite() && limit.requests_per_second > 0.0) {
+                return Err(Error::InvalidBackendConfig(
+                    "rate_limit requires a positive requests_per_second".to_string(),
+                ));
+            }
+            if limit.burst == 0 {
+                return Err(Error::InvalidBackendConfig(
+                    "rate_limit requires a non-zero burst".to_string(),
+                ));
+            }
+        }
+        if self.max_receive_bytes == Some(0) {
+            return Err(Error::InvalidBackendConfig(
+                "max_receive_bytes is zero".to_string(),
+            ));
+        }
+        if let Some(dir) = &self.receive_dir {
+            if !std::path::Path::new(dir).is_dir() {
+                return Err(Error::InvalidBackendConfig(format!(
+                    "receive_dir {dir} is not a directory"
+                )));
+            }
+        }
+        for path in &self.extensions.paths {
+            if let Path::Local(path) = path {
+                if !std::path::Path::new(path).is_file() {
+                    return Err(Error::InvalidBackendConfig(format!(
+                        "extension {path} is not a file"
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// List suspicious settings which are valid but may be a mistake.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = vec![];
+        for service in &self.services {
+            let Some(addr) = service.addr else {
+                continue;
+            };
+            let ip = addr.ip();
+            if ip.is_unspecified() {
+                warnings.push(format!(
+                    "service {} proxies to unspecified address {}",
+                    service.name, addr
+                ));
+            } else if !is_local_ip(&ip) {
+                warnings.push(format!(
+                    "service {} proxies to public address {}, it will be exposed to the network",
+                    service.name, addr
+                ));
+            }
+        }
+        for path in &self.extensions.paths {
+            if let Path::Remote(url) = path {
+                warnings.push(format!("extension is loaded from remote {url}"));
+            }
+        }
+        warnings
+    }
+}
+
+/// Loopback, private and link local addresses are treated as local.
+fn is_local_ip(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_loopback() || ip.is_private() || ip.is_link_local(),
+        IpAddr::V6(ip) => {
+            let first = ip.segments()[0];
+            // Unique local fc00::/7 and link local fe80::/10
+            ip.is_loopback() || (first & 0xfe00) == 0xfc00 || (first & 0xffc0) == 0xfe80
+        }
+    }
 }
 
 /// BackendBehaviour is a Context holder of backend message handler
 pub struct BackendBehaviour {
     server: ServiceProvider,
     extension: Extension,
+    receiver: Option<FileReceiver>,
 }
 
 #[cfg_attr(feature = "browser", async_trait(?Send))]
@@ -55,17 +286,56 @@ impl MessageHandler<BackendMessage> for BackendBehaviour {
     ) -> Result<(), Box<dyn std::error::Error>> {
         self.handle_backend_message(provider, payload, msg).await
     }
+
+    async fn on_peer_disconnected(&self, peer: Did) {
+        self.server.on_peer_disconnected(peer)
+    }
+
+    /// See [ServiceProvider::pending_requests].
+    fn pending_requests(&self) -> Vec<String> {
+        self.server.pending_requests()
+    }
+
+    /// See [ServiceProvider::cancel_request].
+    fn cancel_request(&self, rid: &str) -> bool {
+        self.server.cancel_request(rid)
+    }
 }
 
 impl BackendBehaviour {
     /// Create a new BackendBehaviour instance with config
+    /// Suspicious settings of config are logged as warnings, see [BackendConfig::warnings].
+    /// Services with `preconnect` set are connected before returning, and health checks of
+    /// upstreams are started in background.
     pub async fn new(config: BackendConfig) -> Result<Self, Error> {
+        for warning in config.warnings() {
+            tracing::warn!("Backend config: {warning}");
+        }
+        let server = ServiceProvider::new(config.services)
+            .max_concurrent_requests(config.max_concurrent_requests)
+            .allowed_origins(config.allowed_origins)
+            .rate_limit(config.rate_limit);
+        server.preconnect().await;
+        server.start_health_checks();
+        let max_receive_bytes = config
+            .max_receive_bytes
+            .unwrap_or(DEFAULT_MAX_RECEIVE_BYTES);
         Ok(Self {
-            server: ServiceProvider::new(config.services),
+            server,
             extension: Extension::new(&config.extensions).await?,
+            receiver: config
+                .receive_dir
+                .map(|dir| FileReceiver::new(dir).max_file_bytes(max_receive_bytes)),
         })
     }
 
+    /// Send http requests to service by executor instead of its pooled http client,
+    /// such as a mock of service in tests, see [ServiceProvider::http_executor].
+    pub fn http_executor(mut self, service: &str, executor: impl HttpExecutor + 'static) -> Self {
+        self.server = self.server.http_executor(service, executor);
+        self
+    }
+
     /// List service names
     pub fn service_names(&self) -> Vec<String> {
         self.server
@@ -75,6 +345,25 @@ impl BackendBehaviour {
             .collect()
     }
 
+    /// Send a http request to the service of a remote node and wait for its response,
+    /// see [ServiceProvider::request_with_timeout].
+    pub async fn request_with_timeout(
+        &self,
+        provider: Arc<Provider>,
+        destination: Did,
+        req: HttpRequest,
+        timeout: Duration,
+    ) -> Result<HttpResponse, Error> {
+        self.server
+            .request_with_timeout(provider, destination, req, timeout)
+            .await
+    }
+
+    /// Health of each upstream of services, see [ServiceProvider::upstream_health].
+    pub fn upstream_health(&self) -> HashMap<String, Vec<(SocketAddr, bool)>> {
+        self.server.upstream_health()
+    }
+
     async fn handle_backend_message(
         &self,
         provider: Arc<Provider>,
@@ -88,12 +377,774 @@ impl BackendBehaviour {
             BackendMessage::ServiceMessage(data) => {
                 self.server.handle_message(provider, payload, data).await
             }
+            BackendMessage::WebSocket(data) => self
+                .server
+                .handle_websocket_message(provider, payload, data)
+                .await
+                .map_err(|e| e.into()),
             BackendMessage::PlainText(text) => {
                 let peer_did = payload.transaction.signer();
                 tracing::info!("BackendMessage from {peer_did:?} PlainText: {text:?}");
                 Ok(())
             }
+            BackendMessage::FileChunk {
+                tid,
+                name,
+                offset,
+                total,
+                data,
+            } => {
+                let peer_did = payload.transaction.signer();
+                let len = data.len();
+                tracing::debug!(
+                    "BackendMessage from {peer_did:?} FileChunk: {name} of transfer {tid}, \
+                     {len} bytes at {offset}/{total}"
+                );
+                let Some(receiver) = &self.receiver else {
+                    tracing::warn!("Drop file {name} from {peer_did:?}: receive_dir is not set");
+                    return Ok(());
+                };
+                if let Some(path) = receiver
+                    .receive(peer_did, tid, name, *offset, *total, data)
+                    .await?
+                {
+                    tracing::info!("Received file {} from {peer_did:?}", path.display());
+                }
+                Ok(())
+            }
+            BackendMessage::Pong { nonce, .. } => {
+                let peer_did = payload.transaction.signer();
+                let rtt = msg.rtt_ms().unwrap_or_default();
+                tracing::info!("BackendMessage from {peer_did:?} Pong: nonce {nonce}, rtt {rtt}ms");
+                Ok(())
+            }
+            BackendMessage::ProtocolUnsupported { kind } => {
+                let peer_did = payload.transaction.signer();
+                tracing::warn!("BackendMessage from {peer_did:?} ProtocolUnsupported: {kind}");
+                Ok(())
+            }
             _ => Ok(()),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use rings_core::ecc::SecretKey;
+    use rings_core::message::Message;
+    use rings_core::session::SessionSk;
+    use rings_core::swarm::callback::SwarmCallback;
+    use rings_core::swarm::callback::SwarmEvent;
+    use rings_transport::core::transport::WebrtcConnectionState;
+
+    use super::*;
+    use crate::backend::native::service::BodyReceiver;
+    use crate::backend::native::service::HealthCheck;
+    use crate::backend::native::service::LbStrategy;
+    use crate::backend::native::service::Upstream;
+    use crate::backend::types::ServiceMessage;
+    use crate::backend::types::WireFormat;
+    use crate::backend::Backend;
+    use crate::processor::Processor;
+    use crate::tests::native::prepare_processor;
+
+    fn service(name: &str, addr: &str) -> ServiceConfig {
+        ServiceConfig {
+            name: name.to_string(),
+            addr: Some(addr.parse().unwrap()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_validate_backend_config() {
+        let config = BackendConfig {
+            services: vec![
+                service("a", "127.0.0.1:8080"),
+                service("b", "192.168.1.2:8080"),
+            ],
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+        assert!(config.warnings().is_empty());
+
+        let config = BackendConfig {
+            services: vec![service("a", "127.0.0.1:0")],
+            ..Default::default()
+        };
+        assert!(matches!(
+            config.validate(),
+            Err(Error::InvalidBackendConfig(_))
+        ));
+
+        let config = BackendConfig {
+            services: vec![service("a", "127.0.0.1:80"), service("A", "127.0.0.1:81")],
+            ..Default::default()
+        };
+        assert!(matches!(
+            config.validate(),
+            Err(Error::InvalidBackendConfig(_))
+        ));
+
+        let mut both = service("a", "127.0.0.1:80");
+        both.unix_socket = Some("/var/run/a.sock".to_string());
+        let mut socket_only = service("b", "127.0.0.1:80");
+        socket_only.addr = None;
+        socket_only.unix_socket = Some("/var/run/b.sock".to_string());
+        let config = BackendConfig {
+            services: vec![socket_only.clone()],
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+        let mut h2_and_h1 = service("a", "127.0.0.1:80");
+        h2_and_h1.http2_prior_knowledge = true;
+        h2_and_h1.http1_only = true;
+        let config = BackendConfig {
+            services: vec![h2_and_h1],
+            ..Default::default()
+        };
+        assert!(matches!(
+            config.validate(),
+            Err(Error::InvalidBackendConfig(_))
+        ));
+
+        let mut unbuffered = service("a", "127.0.0.1:80");
+        unbuffered.channel_buffer = 0;
+        let config = BackendConfig {
+            services: vec![unbuffered],
+            ..Default::default()
+        };
+        assert!(matches!(
+            config.validate(),
+            Err(Error::InvalidBackendConfig(_))
+        ));
+
+        let config = BackendConfig {
+            services: vec![both],
+            ..Default::default()
+        };
+        assert!(matches!(
+            config.validate(),
+            Err(Error::InvalidBackendConfig(_))
+        ));
+
+        let mut balanced = service("a", "127.0.0.1:80");
+        balanced.addr = None;
+        balanced.upstreams = vec![Upstream {
+            addr: "127.0.0.1:81".parse().unwrap(),
+        }];
+        balanced.strategy = LbStrategy::LeastConnections;
+        let config = BackendConfig {
+            services: vec![balanced.clone()],
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+        let mut unbalanced = service("b", "127.0.0.1:80");
+        unbalanced.health_check = Some(HealthCheck {
+            path: "/health".to_string(),
+            expected_status: 200,
+            interval_secs: 10,
+        });
+        let config = BackendConfig {
+            services: vec![unbalanced],
+            ..Default::default()
+        };
+        assert!(matches!(
+            config.validate(),
+            Err(Error::InvalidBackendConfig(_))
+        ));
+        balanced.addr = Some("127.0.0.1:80".parse().unwrap());
+        let config = BackendConfig {
+            services: vec![balanced],
+            ..Default::default()
+        };
+        assert!(matches!(
+            config.validate(),
+            Err(Error::InvalidBackendConfig(_))
+        ));
+
+        let config = BackendConfig {
+            rate_limit: Some(RateLimitConfig {
+                requests_per_second: 0.0,
+                burst: 10,
+            }),
+            ..Default::default()
+        };
+        assert!(matches!(
+            config.validate(),
+            Err(Error::InvalidBackendConfig(_))
+        ));
+
+        let config = BackendConfig {
+            max_concurrent_requests: Some(0),
+            ..Default::default()
+        };
+        assert!(matches!(
+            config.validate(),
+            Err(Error::InvalidBackendConfig(_))
+        ));
+
+        let config = BackendConfig {
+            max_receive_bytes: Some(0),
+            ..Default::default()
+        };
+        assert!(matches!(
+            config.validate(),
+            Err(Error::InvalidBackendConfig(_))
+        ));
+
+        let config = BackendConfig {
+            extensions: ExtensionConfig {
+                paths: vec![Path::Local("/not/exist.wasm".to_string())],
+            },
+            ..Default::default()
+        };
+        assert!(matches!(
+            config.validate(),
+            Err(Error::InvalidBackendConfig(_))
+        ));
+    }
+
+    #[test]
+    fn test_backend_config_from_path() {
+        let dir = std::env::temp_dir().join(format!(
+            "rings-{}",
+            rings_core::prelude::uuid::Uuid::new_v4()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("backend.toml");
+        let content = r#"
+            max_concurrent_requests = 8
+
+            [[services]]
+            name = "a"
+            addr = "127.0.0.1:8080"
+        "#;
+        std::fs::write(&path, content).unwrap();
+        let config = BackendConfig::from_path(&path).unwrap();
+        assert_eq!(config.services.len(), 1);
+        assert_eq!(
+            config.services[0].addr,
+            Some("127.0.0.1:8080".parse().unwrap())
+        );
+        assert_eq!(config.max_concurrent_requests, Some(8));
+        assert!(config.validate().is_ok());
+
+        let files = [
+            ("backend.yaml", serde_yaml::to_string(&config).unwrap()),
+            ("backend.json", serde_json::to_string(&config).unwrap()),
+        ];
+        for (name, content) in files {
+            let path = dir.join(name);
+            std::fs::write(&path, content).unwrap();
+            assert_eq!(BackendConfig::from_path(&path).unwrap(), config, "{name}");
+        }
+
+        let path = dir.join("broken.yaml");
+        std::fs::write(&path, "services:\n  - name: [\n").unwrap();
+        let Err(Error::InvalidBackendConfig(reason)) = BackendConfig::from_path(&path) else {
+            panic!("broken yaml should be rejected");
+        };
+        assert!(
+            reason.starts_with(&format!("{}:", path.display())),
+            "{reason}"
+        );
+
+        let path = dir.join("backend.ini");
+        std::fs::write(&path, "").unwrap();
+        assert!(matches!(
+            BackendConfig::from_path(&path),
+            Err(Error::InvalidBackendConfig(_))
+        ));
+        assert!(matches!(
+            BackendConfig::from_path(dir.join("missing.toml")),
+            Err(Error::OpenFileError(_))
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_backend_config_warnings() {
+        let config = BackendConfig {
+            services: vec![service("a", "8.8.8.8:80"), service("b", "0.0.0.0:80")],
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+        assert_eq!(config.warnings().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_requests_of_failed_peer() {
+        // A service accepting connections but never responding.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut streams = vec![];
+            while let Ok((stream, _)) = listener.accept().await {
+                streams.push(stream);
+            }
+        });
+        let config = BackendConfig {
+            services: vec![service("test", &addr.to_string())],
+            ..Default::default()
+        };
+        let behaviour = BackendBehaviour::new(config).await.unwrap();
+        let processor = Arc::new(prepare_processor().await);
+        let provider = Arc::new(Provider::from_processor(processor.clone()));
+        let backend = Backend::new(provider, Box::new(behaviour), HashMap::new());
+
+        let peer_sk = SessionSk::new_with_seckey(&SecretKey::random()).unwrap();
+        let peer = peer_sk.account_did();
+        let req = HttpRequest::builder("test").rid("1").build().unwrap();
+        let msg = BackendMessage::from(ServiceMessage::HttpRequest(req))
+            .to_bytes(WireFormat::Bincode)
+            .unwrap();
+        let payload = MessagePayload::new_send(
+            Message::custom(&msg).unwrap(),
+            &peer_sk,
+            processor.did(),
+            processor.did(),
+        )
+        .unwrap();
+        let event = |state| SwarmEvent::ConnectionStateChange { peer, state };
+
+        let handled = backend.on_inbound(&payload);
+        tokio::pin!(handled);
+        // The connection may recover from Disconnected, so the request goes on.
+        let disconnected = async {
+            backend
+                .on_event(&event(WebrtcConnectionState::Disconnected))
+                .await
+                .unwrap();
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        };
+        tokio::select! {
+            _ = &mut handled => panic!("request should be in flight"),
+            _ = disconnected => {}
+        }
+
+        let failed = backend.on_event(&event(WebrtcConnectionState::Failed));
+        let (handled, failed) = tokio::time::timeout(Duration::from_secs(5), async {
+            tokio::join!(&mut handled, failed)
+        })
+        .await
+        .unwrap();
+        failed.unwrap();
+        let err = handled.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::Cancelled)
+        ));
+    }
+
+    /// Executor answering every request without a service, recording the requests.
+    #[derive(Default, Clone)]
+    struct MockExecutor {
+        requests: Arc<std::sync::Mutex<Vec<HttpRequest>>>,
+        /// Requests are held until a permit is added to the gate, if provided.
+        gate: Option<Arc<tokio::sync::Semaphore>>,
+    }
+
+    #[async_trait]
+    impl HttpExecutor for MockExecutor {
+        async fn execute(
+            &self,
+            _addr: SocketAddr,
+            req: &HttpRequest,
+            _body: Option<BodyReceiver>,
+            _cancel: tokio_util::sync::CancellationToken,
+        ) -> crate::error::Result<HttpResponse> {
+            self.requests.lock().unwrap().push(req.clone());
+            if let Some(gate) = &self.gate {
+                gate.acquire().await.unwrap().forget();
+            }
+            Ok(HttpResponse {
+                rid: req.rid.clone(),
+                status: 200,
+                headers: vec![],
+                body: Some(Bytes::from_static(b"mocked")),
+            })
+        }
+    }
+
+    /// Handler recording the http responses received.
+    #[derive(Default, Clone)]
+    struct ResponseRecorder {
+        responses: Arc<std::sync::Mutex<Vec<HttpResponse>>>,
+    }
+
+    #[async_trait]
+    impl MessageHandler<BackendMessage> for ResponseRecorder {
+        async fn handle_message(
+            &self,
+            _provider: Arc<Provider>,
+            _ctx: &MessagePayload,
+            msg: &BackendMessage,
+        ) -> Result<(), Box<dyn std::error::Error>> {
+            if let BackendMessage::ServiceMessage(ServiceMessage::HttpResponse(resp)) = msg {
+                self.responses.lock().unwrap().push(resp.clone());
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_custom_message_with_http_executor() {
+        let config = BackendConfig {
+            services: vec![service("test", "127.0.0.1:9")],
+            ..Default::default()
+        };
+        let executor = MockExecutor::default();
+        let behaviour = BackendBehaviour::new(config)
+            .await
+            .unwrap()
+            .http_executor("test", executor.clone());
+        let recorder = ResponseRecorder::default();
+        let processor = Arc::new(prepare_processor().await);
+        let provider = Provider::from_processor(processor.clone());
+        provider
+            .set_backend_callback((behaviour, recorder.clone()))
+            .unwrap();
+
+        // The request is sent to the node itself, handled by its backend, and answered back.
+        let req = HttpRequest::builder("test")
+            .path("/hello")
+            .rid("1")
+            .build()
+            .unwrap();
+        processor
+            .send_backend_message(processor.did(), ServiceMessage::HttpRequest(req).into())
+            .await
+            .unwrap();
+        let resp = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                if let Some(resp) = recorder.responses.lock().unwrap().first() {
+                    return resp.clone();
+                }
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(resp.rid, Some("1".to_string()));
+        assert_eq!(resp.status, 200);
+        assert_eq!(resp.body, Some(Bytes::from_static(b"mocked")));
+
+        let requests = executor.requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].path, "/hello");
+    }
+
+    async fn wait_for_response(recorder: &ResponseRecorder, rid: &str) -> HttpResponse {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let responses = recorder.responses.lock().unwrap().clone();
+                if let Some(resp) = responses
+                    .into_iter()
+                    .find(|r| r.rid.as_deref() == Some(rid))
+                {
+                    return resp;
+                }
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_shed_requests_over_max_concurrent_requests() {
+        let config = BackendConfig {
+            services: vec![service("test", "127.0.0.1:9")],
+            max_concurrent_requests: Some(1),
+            ..Default::default()
+        };
+        let gate = Arc::new(tokio::sync::Semaphore::new(0));
+        let executor = MockExecutor {
+            gate: Some(gate.clone()),
+            ..Default::default()
+        };
+        let behaviour = BackendBehaviour::new(config)
+            .await
+            .unwrap()
+            .http_executor("test", executor.clone());
+        let recorder = ResponseRecorder::default();
+        let processor = Arc::new(prepare_processor().await);
+        let provider = Provider::from_processor(processor.clone());
+        provider
+            .set_backend_callback((behaviour, recorder.clone()))
+            .unwrap();
+
+        let request = |rid: &str| -> BackendMessage {
+            let req = HttpRequest::builder("test").rid(rid).build().unwrap();
+            ServiceMessage::HttpRequest(req).into()
+        };
+        processor
+            .send_backend_message(processor.did(), request("1"))
+            .await
+            .unwrap();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while executor.requests.lock().unwrap().is_empty() {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        // The first request holds the only permit, so the second one is shed.
+        processor
+            .send_backend_message(processor.did(), request("2"))
+            .await
+            .unwrap();
+        let resp = wait_for_response(&recorder, "2").await;
+        assert_eq!(resp.status, 503);
+        assert!(resp
+            .headers
+            .iter()
+            .any(|(k, _)| k.eq_ignore_ascii_case("retry-after")));
+        assert_eq!(executor.requests.lock().unwrap().len(), 1);
+
+        // Once the first request is done, the permit is released for further requests.
+        gate.add_permits(1);
+        let resp = wait_for_response(&recorder, "1").await;
+        assert_eq!(resp.status, 200);
+        gate.add_permits(1);
+        processor
+            .send_backend_message(processor.did(), request("3"))
+            .await
+            .unwrap();
+        let resp = wait_for_response(&recorder, "3").await;
+        assert_eq!(resp.status, 200);
+        assert_eq!(executor.requests.lock().unwrap().len(), 2);
+    }
+
+    /// Handler recording all the backend messages received.
+    #[derive(Default, Clone)]
+    struct MessageRecorder {
+        msgs: Arc<std::sync::Mutex<Vec<BackendMessage>>>,
+    }
+
+    #[async_trait]
+    impl MessageHandler<BackendMessage> for MessageRecorder {
+        async fn handle_message(
+            &self,
+            _provider: Arc<Provider>,
+            _ctx: &MessagePayload,
+            msg: &BackendMessage,
+        ) -> Result<(), Box<dyn std::error::Error>> {
+            self.msgs.lock().unwrap().push(msg.clone());
+            Ok(())
+        }
+    }
+
+    async fn wait_for_messages(recorder: &MessageRecorder, count: usize) -> Vec<BackendMessage> {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let msgs = recorder.msgs.lock().unwrap().clone();
+                if msgs.len() >= count {
+                    return msgs;
+                }
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await
+        .unwrap()
+    }
+
+    async fn prepare_backend(
+        recorder: &MessageRecorder,
+        topic_handlers: HashMap<String, crate::backend::TopicHandler>,
+    ) -> Arc<Processor> {
+        let processor = Arc::new(prepare_processor().await);
+        let provider = Arc::new(Provider::from_processor(processor.clone()));
+        let backend = Backend::new(provider, Box::new(recorder.clone()), topic_handlers);
+        processor.swarm.set_callback(Arc::new(backend)).unwrap();
+        processor
+    }
+
+    #[tokio::test]
+    async fn test_ping_answered_with_pong() {
+        let recorder = MessageRecorder::default();
+        let processor = prepare_backend(&recorder, HashMap::new()).await;
+
+        // The ping is sent to the node itself, so both the ping and its pong are recorded.
+        let ping = BackendMessage::ping(42);
+        let BackendMessage::Ping { sent_at_ms, .. } = ping else {
+            unreachable!()
+        };
+        processor
+            .send_backend_message(processor.did(), ping)
+            .await
+            .unwrap();
+        let msgs = wait_for_messages(&recorder, 2).await;
+        let pong = msgs
+            .iter()
+            .find(|m| matches!(m, BackendMessage::Pong { .. }))
+            .unwrap();
+        let BackendMessage::Pong {
+            nonce,
+            sent_at_ms: echoed,
+        } = pong
+        else {
+            unreachable!()
+        };
+        assert_eq!(*nonce, 42);
+        assert_eq!(*echoed, sent_at_ms);
+        assert!(pong.rtt_ms().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_bytes_dispatched_to_topic_handler() {
+        let received = Arc::new(std::sync::Mutex::new(vec![]));
+        let chat: crate::backend::TopicHandler = {
+            let received = received.clone();
+            Arc::new(move |data| {
+                received.lock().unwrap().push(data);
+                Ok(())
+            })
+        };
+        let topic_handlers = HashMap::from([("chat".to_string(), chat)]);
+        let recorder = MessageRecorder::default();
+        let processor = prepare_backend(&recorder, topic_handlers).await;
+
+        for topic in ["chat", "unknown"] {
+            let msg = BackendMessage::Bytes {
+                topic: topic.to_string(),
+                data: b"hello".to_vec(),
+            };
+            processor
+                .send_backend_message(processor.did(), msg)
+                .await
+                .unwrap();
+        }
+
+        // Payloads of topics without a handler are still passed to the message handler.
+        let msgs = wait_for_messages(&recorder, 2).await;
+        assert_eq!(msgs.len(), 2);
+        assert_eq!(*received.lock().unwrap(), vec![b"hello".to_vec()]);
+    }
+
+    /// Handler with requests pending until cancelled.
+    #[derive(Default)]
+    struct PendingHandler {
+        rids: std::sync::Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl MessageHandler<BackendMessage> for PendingHandler {
+        async fn handle_message(
+            &self,
+            _provider: Arc<Provider>,
+            _ctx: &MessagePayload,
+            _msg: &BackendMessage,
+        ) -> Result<(), Box<dyn std::error::Error>> {
+            Ok(())
+        }
+
+        fn pending_requests(&self) -> Vec<String> {
+            self.rids.lock().unwrap().clone()
+        }
+
+        fn cancel_request(&self, rid: &str) -> bool {
+            let mut rids = self.rids.lock().unwrap();
+            let len = rids.len();
+            rids.retain(|r| r != rid);
+            rids.len() != len
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cancel_request_through_backend() {
+        let config = BackendConfig {
+            ..Default::default()
+        };
+        let behaviour = BackendBehaviour::new(config).await.unwrap();
+        let pending = PendingHandler::default();
+        pending.rids.lock().unwrap().push("1".to_string());
+        let processor = Arc::new(prepare_processor().await);
+        let provider = Arc::new(Provider::from_processor(processor));
+        let backend = Backend::new(provider, Box::new((behaviour, pending)), HashMap::new());
+
+        // Handlers combined in a tuple are reached through the backend.
+        assert_eq!(backend.pending_requests(), vec!["1".to_string()]);
+        assert!(backend.cancel_request("1"));
+        assert!(backend.pending_requests().is_empty());
+        assert!(!backend.cancel_request("1"));
+    }
+
+    #[tokio::test]
+    async fn test_http_request_to_self_loops_back() {
+        let config = BackendConfig {
+            services: vec![service("test", "127.0.0.1:9")],
+            ..Default::default()
+        };
+        let executor = MockExecutor::default();
+        let behaviour = BackendBehaviour::new(config)
+            .await
+            .unwrap()
+            .http_executor("test", executor.clone());
+        let recorder = ResponseRecorder::default();
+        let processor = Arc::new(prepare_processor().await);
+        let provider = Provider::from_processor(processor.clone());
+        provider
+            .set_backend_callback((behaviour, recorder.clone()))
+            .unwrap();
+        let looped_back = rings_core::metrics::MESSAGES_LOOPED_BACK.get();
+
+        // The node is not connected to anyone, both the request and its response loop back.
+        let req = HttpRequest::builder("test").rid("1").build().unwrap();
+        processor
+            .send_backend_message(processor.did(), ServiceMessage::HttpRequest(req).into())
+            .await
+            .unwrap();
+        let resp = wait_for_response(&recorder, "1").await;
+        assert_eq!(resp.status, 200);
+        assert_eq!(executor.requests.lock().unwrap().len(), 1);
+        assert!(processor.swarm.peers().is_empty());
+        assert!(rings_core::metrics::MESSAGES_LOOPED_BACK.get() >= looped_back + 2);
+    }
+
+    #[tokio::test]
+    async fn test_protocol_dispatched_to_handler() {
+        let received = Arc::new(std::sync::Mutex::new(vec![]));
+        let chat: crate::backend::ProtocolHandler = {
+            let received = received.clone();
+            Arc::new(move |did, data| {
+                received.lock().unwrap().push((did, data));
+                Ok(())
+            })
+        };
+        let recorder = MessageRecorder::default();
+        let processor = Arc::new(prepare_processor().await);
+        let provider = Arc::new(Provider::from_processor(processor.clone()));
+        let backend = Backend::new(provider, Box::new(recorder.clone()), HashMap::new())
+            .with_protocol_handler("chat/1", chat);
+        processor.swarm.set_callback(Arc::new(backend)).unwrap();
+
+        for kind in ["chat/1", "unknown/1"] {
+            let msg = BackendMessage::Protocol {
+                kind: kind.to_string(),
+                data: b"hello".to_vec(),
+            };
+            processor
+                .send_backend_message(processor.did(), msg)
+                .await
+                .unwrap();
+        }
+
+        // Both messages are recorded, and the one of unknown kind is answered as unsupported.
+        let msgs = wait_for_messages(&recorder, 3).await;
+        let unsupported: Vec<_> = msgs
+            .iter()
+            .filter_map(|m| match m {
+                BackendMessage::ProtocolUnsupported { kind } => Some(kind.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(unsupported, vec!["unknown/1"]);
+        let received = received.lock().unwrap().clone();
+        assert_eq!(received, vec![(processor.did(), b"hello".to_vec())]);
+    }
+}
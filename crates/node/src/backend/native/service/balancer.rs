@@ -0,0 +1,452 @@
+//! Load balancing of http requests among multiple upstreams of a service, and active
+//! health checks of the upstreams.
+
+use std::net::SocketAddr;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU32;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::Weak;
+use std::time::Duration;
+use std::time::Instant;
+
+use rings_core::metrics;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::consts::DEFAULT_HEALTH_CHECK_INTERVAL_SECS;
+use crate::consts::HEALTH_CHECK_TIMEOUT_SECS;
+use crate::consts::UPSTREAM_MAX_FAILURES;
+use crate::consts::UPSTREAM_RETRY_INTERVAL_SECS;
+
+const RETRY_INTERVAL: Duration = Duration::from_secs(UPSTREAM_RETRY_INTERVAL_SECS);
+
+/// An upstream of a service, see `upstreams` of [super::ServiceConfig].
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Upstream {
+    /// address of the upstream
+    pub addr: SocketAddr,
+}
+
+/// Strategy of picking an upstream for each http request.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LbStrategy {
+    /// Take turns.
+    #[default]
+    RoundRobin,
+    /// Pick the one with the fewest requests in flight.
+    LeastConnections,
+}
+
+/// Active health check of upstreams, see `health_check` of [super::ServiceConfig].
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct HealthCheck {
+    /// path to send `GET` requests to, such as `/health`
+    pub path: String,
+
+    /// status of the response from a healthy upstream
+    #[serde(default = "default_expected_status")]
+    pub expected_status: u16,
+
+    /// seconds between checks
+    #[serde(default = "default_interval_secs")]
+    pub interval_secs: u64,
+}
+
+fn default_expected_status() -> u16 {
+    200
+}
+
+fn default_interval_secs() -> u64 {
+    DEFAULT_HEALTH_CHECK_INTERVAL_SECS
+}
+
+struct UpstreamState {
+    /// Lowercase name of the service.
+    service: String,
+    addr: SocketAddr,
+    active: AtomicUsize,
+    failures: AtomicU32,
+    /// When the upstream was marked unhealthy, or last failed a retry.
+    unhealthy_since: Mutex<Option<Instant>>,
+    /// Set while a request is retrying the unhealthy upstream.
+    probing: AtomicBool,
+}
+
+impl UpstreamState {
+    /// An unhealthy upstream gets a single request as a check once in
+    /// [UPSTREAM_RETRY_INTERVAL_SECS], unless it's `probed` by health checks, which decide
+    /// when it's healthy again.
+    fn is_available(&self, now: Instant, probed: bool) -> bool {
+        match *self
+            .unhealthy_since
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+        {
+            Some(since) => {
+                !probed
+                    && now.duration_since(since) >= RETRY_INTERVAL
+                    && !self.probing.load(Ordering::SeqCst)
+            }
+            None => true,
+        }
+    }
+
+    fn is_healthy(&self) -> bool {
+        self.unhealthy_since
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_none()
+    }
+
+    /// Record health in [metrics::UPSTREAM_HEALTHY].
+    fn record_health(&self, healthy: bool) {
+        let addr = self.addr.to_string();
+        metrics::UPSTREAM_HEALTHY.set(&[&self.service, &addr], healthy as i64);
+    }
+
+    fn set_healthy(&self, healthy: bool) {
+        let mut since = self
+            .unhealthy_since
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        self.record_health(healthy);
+        if healthy {
+            self.failures.store(0, Ordering::SeqCst);
+            if since.take().is_some() {
+                tracing::info!("Upstream {} is healthy again", self.addr);
+                metrics::UNHEALTHY_UPSTREAMS.dec();
+            }
+        } else {
+            if since.is_none() {
+                tracing::warn!("Upstream {} is unhealthy", self.addr);
+                metrics::UNHEALTHY_UPSTREAMS.inc();
+            }
+            *since = Some(Instant::now());
+        }
+    }
+}
+
+impl Drop for UpstreamState {
+    fn drop(&mut self) {
+        if !self.is_healthy() {
+            metrics::UNHEALTHY_UPSTREAMS.dec();
+        }
+        let addr = self.addr.to_string();
+        metrics::UPSTREAM_HEALTHY.remove(&[&self.service, &addr]);
+    }
+}
+
+/// Upstreams of a service and the state of picking among them.
+/// An upstream is marked unhealthy after [UPSTREAM_MAX_FAILURES] consecutive connection
+/// failures, and skipped until a retry, which is a single request let through once in
+/// [UPSTREAM_RETRY_INTERVAL_SECS], connects. With health checks, it's marked by their results instead, see [spawn_health_check].
+pub(crate) struct Balancer {
+    strategy: LbStrategy,
+    upstreams: Vec<Arc<UpstreamState>>,
+    next: AtomicUsize,
+    probed: bool,
+}
+
+/// An upstream picked for a request. Its count of requests in flight is decreased on drop.
+pub(crate) struct Picked {
+    state: Arc<UpstreamState>,
+    /// Whether the request is the retry of an unhealthy upstream.
+    probe: bool,
+}
+
+impl Picked {
+    /// Address of the upstream.
+    pub fn addr(&self) -> SocketAddr {
+        self.state.addr
+    }
+
+    /// Record whether the request connected to the upstream.
+    pub fn report(&self, connected: bool) {
+        if connected {
+            self.state.set_healthy(true);
+            return;
+        }
+        let failures = self.state.failures.fetch_add(1, Ordering::SeqCst) + 1;
+        if failures >= UPSTREAM_MAX_FAILURES || !self.state.is_healthy() {
+            self.state.set_healthy(false);
+        }
+    }
+}
+
+impl Drop for Picked {
+    fn drop(&mut self) {
+        self.state.active.fetch_sub(1, Ordering::SeqCst);
+        if self.probe {
+            self.state.probing.store(false, Ordering::SeqCst);
+        }
+    }
+}
+
+impl Balancer {
+    /// Create a balancer among upstreams of the service, whose health is recorded in
+    /// [metrics::UPSTREAM_HEALTHY] with the lowercase name of service.
+    pub fn new(service: &str, strategy: LbStrategy, upstreams: &[Upstream]) -> Self {
+        Self {
+            strategy,
+            upstreams: upstreams
+                .iter()
+                .map(|u| {
+                    let state = UpstreamState {
+                        service: service.to_string(),
+                        addr: u.addr,
+                        active: AtomicUsize::new(0),
+                        failures: AtomicU32::new(0),
+                        unhealthy_since: Mutex::new(None),
+                        probing: AtomicBool::new(false),
+                    };
+                    state.record_health(true);
+                    Arc::new(state)
+                })
+                .collect(),
+            next: AtomicUsize::new(0),
+            probed: false,
+        }
+    }
+
+    /// Leave recovery of unhealthy upstreams to health checks, instead of retrying by requests.
+    pub fn probed(mut self) -> Self {
+        self.probed = true;
+        self
+    }
+
+    /// Pick an available upstream not in `tried`. Returns None if there is none.
+    pub fn pick(&self, tried: &[SocketAddr]) -> Option<Picked> {
+        let now = Instant::now();
+        let mut skipped = vec![];
+        loop {
+            let available = |u: &&Arc<UpstreamState>| {
+                !tried.contains(&u.addr)
+                    && !skipped.contains(&u.addr)
+                    && u.is_available(now, self.probed)
+            };
+
+            let state = match self.strategy {
+                LbStrategy::RoundRobin => {
+                    let start =
+                        self.next.fetch_add(1, Ordering::SeqCst) % self.upstreams.len().max(1);
+                    self.upstreams[start..]
+                        .iter()
+                        .chain(&self.upstreams[..start])
+                        .find(available)
+                }
+                LbStrategy::LeastConnections => self
+                    .upstreams
+                    .iter()
+                    .filter(available)
+                    .min_by_key(|u| u.active.load(Ordering::SeqCst)),
+            }?
+            .clone();
+
+            // Another request may have taken the retry of the unhealthy upstream meanwhile.
+            let probe = !state.is_healthy();
+            if probe && state.probing.swap(true, Ordering::SeqCst) {
+                skipped.push(state.addr);
+                continue;
+            }
+
+            state.active.fetch_add(1, Ordering::SeqCst);
+            return Some(Picked { state, probe });
+        }
+    }
+
+    /// Mark health of the upstream of addr.
+    pub fn set_health(&self, addr: SocketAddr, healthy: bool) {
+        for upstream in self.upstreams.iter().filter(|u| u.addr == addr) {
+            upstream.set_healthy(healthy);
+        }
+    }
+
+    /// Health of each upstream.
+    pub fn health(&self) -> Vec<(SocketAddr, bool)> {
+        self.upstreams
+            .iter()
+            .map(|u| (u.addr, u.is_healthy()))
+            .collect()
+    }
+}
+
+/// Check if the upstream of addr answers the health check in time with the expected status.
+async fn probe(client: &reqwest::Client, addr: SocketAddr, check: &HealthCheck) -> bool {
+    let url = format!("http://{}/{}", addr, check.path.trim_start_matches('/'));
+    let resp = client
+        .get(url)
+        .timeout(Duration::from_secs(HEALTH_CHECK_TIMEOUT_SECS))
+        .send()
+        .await;
+    match resp {
+        Ok(resp) if resp.status().as_u16() == check.expected_status => true,
+        Ok(resp) => {
+            tracing::debug!("Health check of upstream {addr} got {}", resp.status());
+            false
+        }
+        Err(e) => {
+            tracing::debug!("Health check of upstream {addr} failed: {e}");
+            false
+        }
+    }
+}
+
+/// Check all upstreams of balancer once, and mark their health.
+pub(crate) async fn check_upstreams(
+    client: &reqwest::Client,
+    balancer: &Balancer,
+    check: &HealthCheck,
+) {
+    let addrs: Vec<SocketAddr> = balancer.upstreams.iter().map(|u| u.addr).collect();
+    for addr in addrs {
+        let healthy = probe(client, addr, check).await;
+        balancer.set_health(addr, healthy);
+    }
+}
+
+/// Check upstreams of balancer every `interval_secs` in background, until the balancer is dropped.
+pub(crate) fn spawn_health_check(
+    client: reqwest::Client,
+    balancer: Weak<Balancer>,
+    check: HealthCheck,
+) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(check.interval_secs));
+        loop {
+            interval.tick().await;
+            let Some(balancer) = balancer.upgrade() else {
+                break;
+            };
+            check_upstreams(&client, &balancer, &check).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pretend the upstream was marked unhealthy `by` earlier.
+    fn backdate(balancer: &Balancer, addr: SocketAddr, by: Duration) {
+        for u in balancer.upstreams.iter().filter(|u| u.addr == addr) {
+            let mut since = u.unhealthy_since.lock().unwrap();
+            *since = since.map(|t| t - by);
+        }
+    }
+
+    fn upstreams() -> Vec<Upstream> {
+        ["127.0.0.1:8001", "127.0.0.1:8002", "127.0.0.1:8003"]
+            .iter()
+            .map(|addr| Upstream {
+                addr: addr.parse().unwrap(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let ups = upstreams();
+        let balancer = Balancer::new("test", LbStrategy::RoundRobin, &ups);
+        let picked: Vec<SocketAddr> = (0..4).map(|_| balancer.pick(&[]).unwrap().addr()).collect();
+        assert_eq!(picked, vec![
+            ups[0].addr,
+            ups[1].addr,
+            ups[2].addr,
+            ups[0].addr
+        ]);
+
+        // Failover skips the tried ones.
+        let picked = balancer.pick(&[ups[1].addr]).unwrap();
+        assert_eq!(picked.addr(), ups[2].addr);
+        assert!(balancer
+            .pick(&ups.iter().map(|u| u.addr).collect::<Vec<_>>())
+            .is_none());
+    }
+
+    #[test]
+    fn test_least_connections() {
+        let ups = upstreams();
+        let balancer = Balancer::new("test", LbStrategy::LeastConnections, &ups);
+        let first = balancer.pick(&[]).unwrap();
+        let second = balancer.pick(&[]).unwrap();
+        assert_eq!(first.addr(), ups[0].addr);
+        assert_eq!(second.addr(), ups[1].addr);
+        drop(first);
+        assert_eq!(balancer.pick(&[]).unwrap().addr(), ups[0].addr);
+    }
+
+    #[test]
+    fn test_skip_unhealthy_upstream() {
+        let ups = upstreams();
+        let balancer = Balancer::new("test", LbStrategy::LeastConnections, &ups);
+
+        for _ in 0..UPSTREAM_MAX_FAILURES {
+            let picked = balancer.pick(&[]).unwrap();
+            assert_eq!(picked.addr(), ups[0].addr);
+            picked.report(false);
+        }
+        assert_eq!(balancer.health()[0], (ups[0].addr, false));
+        assert_eq!(balancer.pick(&[]).unwrap().addr(), ups[1].addr);
+
+        // Retried after the interval, and healthy again once it connects.
+        backdate(&balancer, ups[0].addr, RETRY_INTERVAL);
+        let retry = balancer.pick(&[]).unwrap();
+        assert_eq!(retry.addr(), ups[0].addr);
+        retry.report(true);
+        assert_eq!(balancer.health()[0], (ups[0].addr, true));
+    }
+
+    #[test]
+    fn test_single_retry_of_unhealthy_upstream() {
+        let ups = upstreams();
+        let balancer = Balancer::new("test", LbStrategy::RoundRobin, &ups);
+        balancer.set_health(ups[0].addr, false);
+        backdate(&balancer, ups[0].addr, RETRY_INTERVAL);
+
+        // Only one request retries it, the others go to healthy ones meanwhile.
+        let retry = balancer.pick(&[]).unwrap();
+        assert_eq!(retry.addr(), ups[0].addr);
+        for _ in 0..4 {
+            assert_ne!(balancer.pick(&[]).unwrap().addr(), ups[0].addr);
+        }
+
+        // A failed retry waits for another interval.
+        retry.report(false);
+        drop(retry);
+        for _ in 0..4 {
+            assert_ne!(balancer.pick(&[]).unwrap().addr(), ups[0].addr);
+        }
+
+        // A retry dropped without a report lets the next request retry.
+        backdate(&balancer, ups[0].addr, RETRY_INTERVAL);
+        let retry = balancer.pick(&[]).unwrap();
+        assert_eq!(retry.addr(), ups[0].addr);
+        drop(retry);
+        let picked: Vec<SocketAddr> = (0..3).map(|_| balancer.pick(&[]).unwrap().addr()).collect();
+        assert!(picked.contains(&ups[0].addr));
+    }
+
+    #[test]
+    fn test_upstream_health_metrics() {
+        let ups = upstreams();
+        let addr = ups[0].addr.to_string();
+        let labels = ["test_metrics", addr.as_str()];
+        let balancer = Balancer::new("test_metrics", LbStrategy::RoundRobin, &ups);
+        assert_eq!(metrics::UPSTREAM_HEALTHY.get(&labels), Some(1));
+
+        balancer.set_health(ups[0].addr, false);
+        assert_eq!(metrics::UPSTREAM_HEALTHY.get(&labels), Some(0));
+        assert!(metrics::render().contains(&format!(
+            "rings_upstream_healthy{{service=\"test_metrics\",upstream=\"{addr}\"}} 0\n"
+        )));
+
+        balancer.set_health(ups[0].addr, true);
+        assert_eq!(metrics::UPSTREAM_HEALTHY.get(&labels), Some(1));
+        drop(balancer);
+        assert_eq!(metrics::UPSTREAM_HEALTHY.get(&labels), None);
+    }
+}
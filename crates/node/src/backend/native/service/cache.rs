@@ -0,0 +1,397 @@
+#![warn(missing_docs)]
+//! Module cache provide an in-memory LRU cache of proxied http responses.
+//!
+//! Only 2xx responses of GET and HEAD requests are cached, keyed by method, path and
+//! the headers which change the representation of response, including the ones named by
+//! `Vary` of response. Requests carrying credentials and responses setting cookies are never
+//! cached, since they may be private to the requester. For the same reason, responses of
+//! services with `forward_origin` are cached per origin.
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use rings_core::dht::Did;
+use rings_core::metrics;
+
+use crate::backend::types::HttpRequest;
+use crate::backend::types::HttpResponse;
+
+/// Request headers which are part of cache key.
+const VARY_HEADERS: [&str; 2] = ["accept", "accept-encoding"];
+
+/// Requests with these headers are not cached.
+const PRIVATE_HEADERS: [&str; 2] = ["authorization", "cookie"];
+
+/// Conditional requests are forwarded to the service, which decides between a full
+/// response and `304 Not Modified`.
+const CONDITIONAL_HEADERS: [&str; 5] = [
+    "if-none-match",
+    "if-modified-since",
+    "if-match",
+    "if-unmodified-since",
+    "if-range",
+];
+
+/// LRU cache of http responses
+pub struct ResponseCache {
+    capacity: usize,
+    default_ttl: Duration,
+    inner: Mutex<CacheInner>,
+}
+
+#[derive(Default)]
+struct CacheInner {
+    entries: HashMap<String, CacheEntry>,
+    /// Lowercase request headers named by `Vary` of the last response, keyed by the key
+    /// of request without them.
+    varies: HashMap<String, Vec<String>>,
+    tick: u64,
+}
+
+struct CacheEntry {
+    resp: HttpResponse,
+    /// Key of request without the headers named by `Vary`.
+    base_key: String,
+    expires_at: Instant,
+    last_used: u64,
+}
+
+impl CacheInner {
+    /// Key of request, including the headers named by `Vary` of the last response.
+    fn key(&self, req: &HttpRequest, base_key: &str) -> String {
+        let mut key = base_key.to_string();
+        for h in self.varies.get(base_key).into_iter().flatten() {
+            key.push('\n');
+            key.push_str(header(&req.headers, h).unwrap_or_default());
+        }
+        key
+    }
+
+    fn remove(&mut self, key: &str) {
+        let Some(entry) = self.entries.remove(key) else {
+            return;
+        };
+        if !self.entries.values().any(|e| e.base_key == entry.base_key) {
+            self.varies.remove(&entry.base_key);
+        }
+    }
+}
+
+impl ResponseCache {
+    /// Create a cache holding at most `capacity` responses. `default_ttl` is used when
+    /// the response has no `max-age` in `Cache-Control`.
+    pub fn new(capacity: usize, default_ttl: Duration) -> Self {
+        Self {
+            capacity,
+            default_ttl,
+            inner: Mutex::new(CacheInner::default()),
+        }
+    }
+
+    /// Get cached response of request, the request id is replaced by the one of request.
+    /// `origin` should be the requesting peer if the response depends on it, see
+    /// [ServiceConfig::forward_origin](super::ServiceConfig::forward_origin).
+    pub fn get(&self, req: &HttpRequest, origin: Option<Did>) -> Option<HttpResponse> {
+        let base_key = cache_key(req, origin)?;
+        let mut inner = self.inner.lock().ok()?;
+        let key = inner.key(req, &base_key);
+        inner.tick += 1;
+        let tick = inner.tick;
+
+        let hit = match inner.entries.get_mut(&key) {
+            Some(entry) if entry.expires_at > Instant::now() => {
+                entry.last_used = tick;
+                Some(HttpResponse {
+                    rid: req.rid.clone(),
+                    ..entry.resp.clone()
+                })
+            }
+            Some(_) => {
+                inner.remove(&key);
+                None
+            }
+            None => None,
+        };
+
+        match hit {
+            Some(_) => metrics::HTTP_CACHE_HITS.inc(),
+            None => metrics::HTTP_CACHE_MISSES.inc(),
+        }
+        hit
+    }
+
+    /// Put response of request into cache if both are cacheable, `origin` is the same as
+    /// the one of [ResponseCache::get].
+    /// The least recently used response is evicted when the cache is full.
+    pub fn insert(&self, req: &HttpRequest, origin: Option<Did>, resp: &HttpResponse) {
+        if self.capacity == 0 {
+            return;
+        }
+        let Some(base_key) = cache_key(req, origin) else {
+            return;
+        };
+        let Some(ttl) = response_ttl(resp, self.default_ttl) else {
+            return;
+        };
+        let Some(vary) = response_vary(resp) else {
+            return;
+        };
+        let Ok(mut inner) = self.inner.lock() else {
+            return;
+        };
+
+        // Evict with the key of before, the varying headers are updated below.
+        let key = inner.key(req, &base_key);
+        if !inner.entries.contains_key(&key) && inner.entries.len() >= self.capacity {
+            let now = Instant::now();
+            let expired: Vec<String> = inner
+                .entries
+                .iter()
+                .filter(|(_, e)| e.expires_at <= now)
+                .map(|(k, _)| k.clone())
+                .collect();
+            for k in expired {
+                inner.remove(&k);
+            }
+        }
+        if !inner.entries.contains_key(&key) && inner.entries.len() >= self.capacity {
+            let lru = inner
+                .entries
+                .iter()
+                .min_by_key(|(_, e)| e.last_used)
+                .map(|(k, _)| k.clone());
+            if let Some(lru) = lru {
+                inner.remove(&lru);
+            }
+        }
+
+        // The representation may vary by other headers now, drop the responses of before.
+        if inner.varies.get(&base_key).cloned().unwrap_or_default() != vary {
+            inner.entries.retain(|_, e| e.base_key != base_key);
+            inner.varies.remove(&base_key);
+            if !vary.is_empty() {
+                inner.varies.insert(base_key.clone(), vary);
+            }
+        }
+        let key = inner.key(req, &base_key);
+
+        inner.tick += 1;
+        let entry = CacheEntry {
+            resp: HttpResponse {
+                rid: None,
+                ..resp.clone()
+            },
+            base_key,
+            expires_at: Instant::now() + ttl,
+            last_used: inner.tick,
+        };
+        inner.entries.insert(key, entry);
+    }
+}
+
+fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+/// Key of cacheable request, or None if the request should not be cached.
+/// The key is prefixed by `origin` if any, so that peers never share responses.
+fn cache_key(req: &HttpRequest, origin: Option<Did>) -> Option<String> {
+    let method = req.method.to_ascii_uppercase();
+    if method != "GET" && method != "HEAD" {
+        return None;
+    }
+    if PRIVATE_HEADERS
+        .iter()
+        .chain(CONDITIONAL_HEADERS.iter())
+        .any(|h| header(&req.headers, h).is_some())
+    {
+        return None;
+    }
+
+    let mut key = match origin {
+        Some(did) => format!("{} {} {}", did, method, req.path),
+        None => format!("{} {}", method, req.path),
+    };
+    for h in VARY_HEADERS {
+        key.push('\n');
+        key.push_str(header(&req.headers, h).unwrap_or_default());
+    }
+    Some(key)
+}
+
+/// How long the response can be cached, or None if it should not be cached.
+fn response_ttl(resp: &HttpResponse, default_ttl: Duration) -> Option<Duration> {
+    if !(200..300).contains(&resp.status) || header(&resp.headers, "set-cookie").is_some() {
+        return None;
+    }
+    let Some(cache_control) = header(&resp.headers, "cache-control") else {
+        return Some(default_ttl);
+    };
+
+    let mut ttl = default_ttl;
+    let directives = cache_control
+        .split(',')
+        .map(|d| d.trim().to_ascii_lowercase());
+    for directive in directives {
+        if ["no-store", "no-cache", "private"].contains(&directive.as_str()) {
+            return None;
+        }
+        if let Some(max_age) = directive.strip_prefix("max-age=") {
+            ttl = Duration::from_secs(max_age.trim_matches('"').parse().ok()?);
+        }
+    }
+    if ttl.is_zero() {
+        return None;
+    }
+    Some(ttl)
+}
+
+/// Lowercase request headers named by `Vary` of response, or None if the response
+/// varies by anything (`Vary: *`) and should not be cached.
+fn response_vary(resp: &HttpResponse) -> Option<Vec<String>> {
+    let mut vary = vec![];
+    for (k, v) in &resp.headers {
+        if !k.eq_ignore_ascii_case("vary") {
+            continue;
+        }
+        for h in v.split(',').map(|h| h.trim().to_ascii_lowercase()) {
+            if h == "*" {
+                return None;
+            }
+            if !h.is_empty() && !VARY_HEADERS.contains(&h.as_str()) && !vary.contains(&h) {
+                vary.push(h);
+            }
+        }
+    }
+    vary.sort();
+    Some(vary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, path: &str, headers: Vec<(&str, &str)>) -> HttpRequest {
+        HttpRequest {
+            rid: Some(uuid::Uuid::new_v4().to_string()),
+            service: "test".to_string(),
+            method: method.to_string(),
+            path: path.to_string(),
+            headers: headers
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            body: None,
+        }
+    }
+
+    fn response(status: u16, cache_control: Option<&str>) -> HttpResponse {
+        HttpResponse {
+            rid: None,
+            status,
+            headers: cache_control
+                .map(|v| vec![("Cache-Control".to_string(), v.to_string())])
+                .unwrap_or_default(),
+            body: Some("ok".into()),
+        }
+    }
+
+    #[test]
+    fn test_response_cache() {
+        let cache = ResponseCache::new(2, Duration::from_secs(60));
+
+        let get = request("GET", "/a", vec![]);
+        assert!(cache.get(&get, None).is_none());
+        cache.insert(&get, None, &response(200, None));
+        let hit = request("GET", "/a", vec![]);
+        let resp = cache.get(&hit, None).unwrap();
+        assert_eq!(resp.rid, hit.rid);
+        assert_eq!(resp.status, 200);
+
+        // Varies by accept header
+        let html = request("GET", "/a", vec![("Accept", "text/html")]);
+        assert!(cache.get(&html, None).is_none());
+
+        // Not cacheable
+        let post = request("POST", "/b", vec![]);
+        cache.insert(&post, None, &response(200, None));
+        assert!(cache.get(&post, None).is_none());
+        let auth = request("GET", "/b", vec![("Authorization", "token")]);
+        cache.insert(&auth, None, &response(200, None));
+        assert!(cache.get(&auth, None).is_none());
+        let conditional = request("GET", "/a", vec![("If-None-Match", "\"v1\"")]);
+        assert!(cache.get(&conditional, None).is_none());
+        let failed = request("GET", "/c", vec![]);
+        cache.insert(&failed, None, &response(500, None));
+        assert!(cache.get(&failed, None).is_none());
+        let no_store = request("GET", "/d", vec![]);
+        cache.insert(&no_store, None, &response(200, Some("no-store")));
+        assert!(cache.get(&no_store, None).is_none());
+
+        // Evict least recently used
+        cache.insert(&request("GET", "/e", vec![]), None, &response(200, None));
+        cache.get(&get, None).unwrap();
+        cache.insert(
+            &request("GET", "/f", vec![]),
+            None,
+            &response(200, Some("public, max-age=10")),
+        );
+        assert!(cache.get(&get, None).is_some());
+        assert!(cache.get(&request("GET", "/e", vec![]), None).is_none());
+        assert!(cache.get(&request("GET", "/f", vec![]), None).is_some());
+    }
+
+    #[test]
+    fn test_response_cache_private_response() {
+        let cache = ResponseCache::new(8, Duration::from_secs(60));
+
+        let cookie = request("GET", "/a", vec![]);
+        let mut resp = response(200, None);
+        resp.headers
+            .push(("Set-Cookie".to_string(), "session=1".to_string()));
+        cache.insert(&cookie, None, &resp);
+        assert!(cache.get(&cookie, None).is_none());
+
+        // Responses of peers are never shared when the origin is forwarded
+        let a = Did::from(1u32);
+        let b = Did::from(2u32);
+        let get = request("GET", "/b", vec![]);
+        cache.insert(&get, Some(a), &response(200, None));
+        assert!(cache.get(&get, Some(b)).is_none());
+        assert!(cache.get(&get, None).is_none());
+        assert!(cache.get(&get, Some(a)).is_some());
+    }
+
+    #[test]
+    fn test_response_cache_vary() {
+        let cache = ResponseCache::new(8, Duration::from_secs(60));
+
+        let mut resp = response(200, None);
+        resp.headers
+            .push(("Vary".to_string(), "Accept-Language".to_string()));
+        let en = request("GET", "/a", vec![("Accept-Language", "en")]);
+        cache.insert(&en, None, &resp);
+        assert!(cache.get(&en, None).is_some());
+        let fr = request("GET", "/a", vec![("Accept-Language", "fr")]);
+        assert!(cache.get(&fr, None).is_none());
+        cache.insert(&fr, None, &resp);
+        assert!(cache.get(&fr, None).is_some());
+        assert!(cache.get(&en, None).is_some());
+
+        // Vary of the new response replaces the old one
+        cache.insert(&en, None, &response(200, None));
+        assert!(cache.get(&fr, None).is_some());
+        assert!(cache.get(&en, None).is_some());
+        assert_eq!(cache.get(&en, None).unwrap().headers.len(), 0);
+
+        let mut any = response(200, None);
+        any.headers.push(("Vary".to_string(), "*".to_string()));
+        let get = request("GET", "/b", vec![]);
+        cache.insert(&get, None, &any);
+        assert!(cache.get(&get, None).is_none());
+    }
+}
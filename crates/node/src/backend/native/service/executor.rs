@@ -0,0 +1,184 @@
+//! Execution of http requests to services listening on tcp, see [HttpExecutor].
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::time::Duration;
+use std::time::Instant;
+
+use async_trait::async_trait;
+use rings_core::metrics;
+use serde::Deserialize;
+use serde::Serialize;
+use tokio_util::sync::CancellationToken;
+
+use crate::backend::native::service::response_body;
+use crate::backend::native::service::response_headers;
+use crate::backend::native::service::stream::BodyReceiver;
+use crate::backend::types::HttpRequest;
+use crate::backend::types::HttpResponse;
+use crate::consts::SAME_HOST_MAX_REDIRECTS;
+use crate::consts::TCP_SERVER_TIMEOUT;
+use crate::error::Error;
+use crate::error::Result;
+
+/// How redirects responded by a service are followed, see `redirect_policy` of [super::ServiceConfig].
+/// A redirect not followed is relayed to the requester as is.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RedirectPolicy {
+    /// Never follow.
+    None,
+    /// Follow at most this many redirects to any host.
+    Limited(usize),
+    /// Follow redirects to the host and port of the request only, so that a request can't be
+    /// bounced to a host the service is not meant to reach.
+    #[default]
+    SameHostOnly,
+}
+
+impl RedirectPolicy {
+    /// Build the redirect policy of reqwest. The path of each redirect target is checked by
+    /// `is_path_allowed` as well, such as `path_allowlist` of service, so that a redirect
+    /// can't reach a path the requester is not allowed to; such a redirect is not followed.
+    pub fn into_reqwest<F>(self, is_path_allowed: F) -> reqwest::redirect::Policy
+    where F: Fn(&str) -> bool + Send + Sync + 'static {
+        match self {
+            RedirectPolicy::None => reqwest::redirect::Policy::none(),
+            RedirectPolicy::Limited(max) => reqwest::redirect::Policy::custom(move |attempt| {
+                if !is_path_allowed(attempt.url().path()) {
+                    tracing::info!("Stop redirect to {}: path not allowed", attempt.url());
+                    attempt.stop()
+                } else if attempt.previous().len() > max {
+                    attempt.error("too many redirects")
+                } else {
+                    attempt.follow()
+                }
+            }),
+            RedirectPolicy::SameHostOnly => reqwest::redirect::Policy::custom(move |attempt| {
+                let same_host = attempt.previous().first().map_or(false, |origin| {
+                    origin.host_str() == attempt.url().host_str()
+                        && origin.port_or_known_default() == attempt.url().port_or_known_default()
+                });
+                if !same_host {
+                    tracing::info!("Stop redirect to {}: not the same host", attempt.url());
+                    attempt.stop()
+                } else if !is_path_allowed(attempt.url().path()) {
+                    tracing::info!("Stop redirect to {}: path not allowed", attempt.url());
+                    attempt.stop()
+                } else if attempt.previous().len() > SAME_HOST_MAX_REDIRECTS {
+                    attempt.error("too many redirects")
+                } else {
+                    attempt.follow()
+                }
+            }),
+        }
+    }
+}
+
+/// Sends http requests to a service listening on tcp. Services use [ReqwestExecutor] by default,
+/// another one, such as a mock of service in tests, can be set by
+/// [ServiceProvider::http_executor](super::ServiceProvider::http_executor).
+#[cfg_attr(feature = "browser", async_trait(?Send))]
+#[cfg_attr(not(feature = "browser"), async_trait)]
+pub trait HttpExecutor: Send + Sync {
+    /// Send request to the service at addr, and collect its response.
+    /// The body is streamed from `body` if it's provided, otherwise `body` of request is sent.
+    /// Failing to connect should be reported as [Error::HttpConnectError], so that the
+    /// request is retried on the next upstream.
+    /// Once `cancel` is cancelled, such as the requesting peer is gone, the request should be
+    /// aborted with [Error::Cancelled] to free the connection to service.
+    async fn execute(
+        &self,
+        addr: SocketAddr,
+        req: &HttpRequest,
+        body: Option<BodyReceiver>,
+        cancel: CancellationToken,
+    ) -> Result<HttpResponse>;
+}
+
+/// [HttpExecutor] sending requests by a pooled [reqwest::Client].
+#[derive(Clone, Debug)]
+pub struct ReqwestExecutor {
+    client: reqwest::Client,
+    max_response_headers_bytes: Option<usize>,
+}
+
+impl ReqwestExecutor {
+    /// Create a new ReqwestExecutor with client.
+    pub fn new(client: reqwest::Client) -> Self {
+        Self {
+            client,
+            max_response_headers_bytes: None,
+        }
+    }
+
+    /// Fail with [Error::ResponseHeadersTooLarge] before reading the body of a response,
+    /// if its header names and values add up to more than max bytes. Unlimited if not provided.
+    pub fn max_response_headers_bytes(mut self, max: Option<usize>) -> Self {
+        self.max_response_headers_bytes = max;
+        self
+    }
+}
+
+#[cfg_attr(feature = "browser", async_trait(?Send))]
+#[cfg_attr(not(feature = "browser"), async_trait)]
+impl HttpExecutor for ReqwestExecutor {
+    async fn execute(
+        &self,
+        addr: SocketAddr,
+        req: &HttpRequest,
+        body: Option<BodyReceiver>,
+        cancel: CancellationToken,
+    ) -> Result<HttpResponse> {
+        let url = format!("http://{}/{}", addr, req.path.trim_start_matches('/'));
+        tracing::info!("Handle http request on url: {:?} start", url);
+        let method =
+            http::Method::from_str(req.method.as_str()).map_err(|_| Error::InvalidMethod)?;
+
+        let headers_map: HashMap<String, String> = req.headers.iter().cloned().collect();
+        let headers = (&headers_map).try_into().map_err(|e| {
+            tracing::info!("invalid_headers: {}", e);
+            Error::InvalidHeaders
+        })?;
+
+        let request = self
+            .client
+            .request(method, url)
+            .headers(headers)
+            .timeout(Duration::from_secs(TCP_SERVER_TIMEOUT));
+
+        let request = match (body, req.body.as_ref()) {
+            (Some(stream), _) => request.body(reqwest::Body::wrap_stream(stream)),
+            (None, Some(body)) => request.body(body.to_vec()),
+            (None, None) => request,
+        };
+
+        let start = Instant::now();
+        let exchange = async {
+            let resp = request.send().await?;
+
+            let status = resp.status().as_u16();
+
+            let headers = response_headers(resp.headers(), self.max_response_headers_bytes)?;
+
+            let body = resp.bytes().await?;
+            Ok::<_, Error>((status, headers, body))
+        };
+        let (status, headers, body) = tokio::select! {
+            resp = exchange => resp?,
+            _ = cancel.cancelled() => {
+                tracing::info!("Abort http request {:?}: cancelled", req.rid);
+                return Err(Error::Cancelled);
+            }
+        };
+        metrics::HTTP_PROXY_LATENCY_MS.observe(start.elapsed().as_millis() as u64);
+        tracing::info!("Handle http request done, responding");
+        Ok(HttpResponse {
+            status,
+            headers,
+            body: response_body(status, body),
+            rid: req.rid.clone(),
+        })
+    }
+}
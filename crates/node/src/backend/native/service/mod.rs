@@ -14,28 +14,74 @@
 //! A Rings Service Provider is a structure that serves Rings Service. Sometimes referred to as
 //! "hidden-services," the Rings Service Provider exclusively handles the ServiceMessage type
 //! of BackendMessage. This component is crucial for managing the flow of messages within decentralized networks.
+mod balancer;
+mod cache;
+mod executor;
+mod ratelimit;
+mod stream;
 mod tcp_proxy;
+mod trace;
+mod ws_proxy;
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::net::SocketAddr;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
+use std::time::Instant;
 
 use dashmap::DashMap;
+use futures::Stream;
+use futures::StreamExt;
+use rings_core::dht::Did;
+use rings_core::message::encode_data_gzip;
 use rings_core::message::MessagePayload;
 use rings_core::message::MessageVerificationExt;
+use rings_core::metrics;
 use rings_rpc::method::Method;
 use serde::Deserialize;
 use serde::Serialize;
+use tokio::sync::oneshot;
+use tokio::sync::OwnedSemaphorePermit;
+use tokio::sync::Semaphore;
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
 
+use crate::backend::native::service::balancer::spawn_health_check;
+use crate::backend::native::service::balancer::Balancer;
+pub use crate::backend::native::service::balancer::HealthCheck;
+pub use crate::backend::native::service::balancer::LbStrategy;
+pub use crate::backend::native::service::balancer::Upstream;
+use crate::backend::native::service::cache::ResponseCache;
+pub use crate::backend::native::service::executor::HttpExecutor;
+pub use crate::backend::native::service::executor::RedirectPolicy;
+pub use crate::backend::native::service::executor::ReqwestExecutor;
+pub use crate::backend::native::service::ratelimit::RateLimitConfig;
+use crate::backend::native::service::ratelimit::RateLimiter;
+pub use crate::backend::native::service::stream::BodyReceiver;
+use crate::backend::native::service::stream::BodyStream;
+use crate::backend::native::service::stream::Chunk;
+use crate::backend::native::service::stream::EarlyChunks;
+use crate::backend::native::service::stream::StreamDefeat;
 use crate::backend::native::service::tcp_proxy::tcp_connect_with_timeout;
 use crate::backend::native::service::tcp_proxy::Tunnel;
+use crate::backend::native::service::ws_proxy::send_to_peer;
+use crate::backend::native::service::ws_proxy::WsTunnel;
 use crate::backend::native::MessageHandler;
 use crate::backend::types::BackendMessage;
 use crate::backend::types::HttpRequest;
 use crate::backend::types::HttpResponse;
 use crate::backend::types::ServiceMessage;
+use crate::backend::types::TunnelDefeat;
 use crate::backend::types::TunnelId;
+use crate::backend::types::WebSocketMessage;
+use crate::consts::BUSY_RETRY_AFTER_SECS;
+use crate::consts::DEFAULT_CACHE_TTL;
+use crate::consts::DEFAULT_COMPRESS_MIN_BYTES;
+use crate::consts::DEFAULT_ORIGIN_DID_HEADER;
+use crate::consts::DEFAULT_TUNNEL_BUFFER;
+use crate::consts::RINGS_CONTENT_ENCODING;
 use crate::consts::TCP_SERVER_TIMEOUT;
 use crate::error::Error;
 use crate::error::Result;
@@ -50,8 +96,257 @@ pub struct ServiceConfig {
     /// will register to dht storage if provided
     pub register_service: Option<String>,
 
-    /// target address on server
-    pub addr: SocketAddr,
+    /// target address on server, required unless `unix_socket` or `upstreams` is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub addr: Option<SocketAddr>,
+
+    /// addresses to balance http requests among, conflicts with `addr` and `unix_socket`
+    /// a request is retried on the next upstream if it can't connect, and upstreams failing
+    /// repeatedly are skipped for a while; tcp and websocket tunnels are not balanced
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub upstreams: Vec<Upstream>,
+
+    /// how to pick an upstream for each http request
+    #[serde(default)]
+    pub strategy: LbStrategy,
+
+    /// check upstreams periodically in background, requires `upstreams`
+    /// an upstream failing the check is skipped until it passes again
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health_check: Option<HealthCheck>,
+
+    /// path of a unix domain socket to proxy http requests to, conflicts with `addr`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unix_socket: Option<String>,
+
+    /// gzip response bodies before sending them back, the requester decompresses them
+    #[serde(default)]
+    pub compress_responses: bool,
+
+    /// responses smaller than this are sent as is
+    #[serde(default = "default_compress_min_bytes")]
+    pub compress_min_bytes: usize,
+
+    /// http requests with a larger body are rejected with 413, unlimited if not provided
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_request_bytes: Option<usize>,
+
+    /// responses with headers larger than this, counting both names and values, are dropped
+    /// before reading the body, and the requester is answered with 502; unlimited if not provided
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_response_headers_bytes: Option<usize>,
+
+    /// tell the service which peer a http request comes from, by setting `origin_did_header`
+    /// and `forwarded_for_header` to its Did; headers of the same names sent by the peer are
+    /// dropped, leave it off for privacy-sensitive deployments
+    #[serde(default)]
+    pub forward_origin: bool,
+
+    /// header carrying the Did of the requesting peer
+    #[serde(default = "default_origin_did_header")]
+    pub origin_did_header: String,
+
+    /// header to append the Did of the requesting peer to, such as `X-Forwarded-For`,
+    /// not set if not provided
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forwarded_for_header: Option<String>,
+
+    /// only http requests and websockets to paths matching one of these are forwarded,
+    /// all paths if not provided
+    /// a pattern containing `*` is a glob, otherwise it's a prefix
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path_allowlist: Option<Vec<String>>,
+
+    /// http requests and websockets to paths matching one of these are rejected with 403,
+    /// even if allowlisted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path_denylist: Option<Vec<String>>,
+
+    /// cache 2xx responses of GET and HEAD requests, holding at most this many responses
+    /// caching is disabled if not provided
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_capacity: Option<usize>,
+
+    /// seconds to cache a response if upstream doesn't set `max-age` in `Cache-Control`
+    #[serde(default = "default_cache_ttl")]
+    pub cache_default_ttl: u64,
+
+    /// count of packages or frames buffered for each TCP or WebSocket tunnel
+    /// when it's full, handling of further messages from the peer waits in `send` until
+    /// the local service catches up, which stalls the whole backend; raise it for services
+    /// receiving bursts, or lower it to bound memory on constrained nodes
+    #[serde(default = "default_channel_buffer")]
+    pub channel_buffer: usize,
+
+    /// talk http/2 to the service without negotiation, for cleartext h2c services such as grpc
+    #[serde(default)]
+    pub http2_prior_knowledge: bool,
+
+    /// only talk http/1 to the service, conflicts with `http2_prior_knowledge`
+    #[serde(default)]
+    pub http1_only: bool,
+
+    /// maximum count of idle keep-alive connections to the service, unlimited if not provided
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pool_max_idle_per_host: Option<usize>,
+
+    /// open a keep-alive connection to the service on startup, so that the first proxied
+    /// request skips connection setup, idle connections are then held without timeout
+    #[serde(default)]
+    pub preconnect: bool,
+
+    /// how redirects responded by the service are followed, only redirects to the same host
+    /// and port are followed by default; not applied to services on `unix_socket`
+    #[serde(default)]
+    pub redirect_policy: RedirectPolicy,
+}
+
+impl Default for ServiceConfig {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            register_service: None,
+            addr: None,
+            upstreams: vec![],
+            strategy: LbStrategy::default(),
+            health_check: None,
+            unix_socket: None,
+            compress_responses: false,
+            compress_min_bytes: default_compress_min_bytes(),
+            max_request_bytes: None,
+            max_response_headers_bytes: None,
+            forward_origin: false,
+            origin_did_header: default_origin_did_header(),
+            forwarded_for_header: None,
+            path_allowlist: None,
+            path_denylist: None,
+            cache_capacity: None,
+            cache_default_ttl: default_cache_ttl(),
+            channel_buffer: default_channel_buffer(),
+            http2_prior_knowledge: false,
+            http1_only: false,
+            pool_max_idle_per_host: None,
+            preconnect: false,
+            redirect_policy: RedirectPolicy::default(),
+        }
+    }
+}
+
+impl ServiceConfig {
+    /// Check if an http request or websocket to path is allowed by `path_allowlist` and
+    /// `path_denylist`.
+    /// The path is percent-decoded and its `.` and `..` segments are resolved before matching,
+    /// a path escaping the root is never allowed.
+    pub fn is_path_allowed(&self, path: &str) -> bool {
+        let Some(path) = normalize_path(path) else {
+            return false;
+        };
+        let matches = |patterns: &Option<Vec<String>>| {
+            patterns
+                .as_ref()
+                .map(|ps| ps.iter().any(|p| path_matches(p, &path)))
+        };
+
+        if matches(&self.path_denylist) == Some(true) {
+            return false;
+        }
+        matches(&self.path_allowlist).unwrap_or(true)
+    }
+}
+
+/// Percent-decode path without query and fragment, and resolve its `.` and `..` segments.
+/// Return None if the path is not valid utf-8 once decoded, or `..` goes above the root.
+fn normalize_path(path: &str) -> Option<String> {
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    let path = percent_decode(path)?;
+
+    let mut segments: Vec<&str> = vec![];
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop()?;
+            }
+            s => segments.push(s),
+        }
+    }
+    let trailing = path.ends_with('/') && !segments.is_empty();
+    let mut path = format!("/{}", segments.join("/"));
+    if trailing {
+        path.push('/');
+    }
+    Some(path)
+}
+
+/// Decode `%XX` escapes of s, leaving malformed escapes as they are.
+fn percent_decode(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| bytes.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(b) => {
+                decoded.push(b);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// Match path with a glob pattern, where `*` matches any characters, or with a prefix of
+/// whole segments, so that `/api` matches `/api` and `/api/users` but not `/apikeys`.
+fn path_matches(pattern: &str, path: &str) -> bool {
+    let pattern = format!("/{}", pattern.trim_start_matches('/'));
+    if !pattern.contains('*') {
+        let prefix = pattern.trim_end_matches('/');
+        return path == prefix
+            || path
+                .strip_prefix(prefix)
+                .map_or(false, |rest| rest.starts_with('/'));
+    }
+
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = path.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts = parts.peekable();
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+fn default_compress_min_bytes() -> usize {
+    DEFAULT_COMPRESS_MIN_BYTES
+}
+
+fn default_origin_did_header() -> String {
+    DEFAULT_ORIGIN_DID_HEADER.to_string()
+}
+
+fn default_cache_ttl() -> u64 {
+    DEFAULT_CACHE_TTL
+}
+
+fn default_channel_buffer() -> usize {
+    DEFAULT_TUNNEL_BUFFER
 }
 
 /// Service Provider, which hold tunnel and a list of service
@@ -60,23 +355,457 @@ pub struct ServiceProvider {
     pub services: Vec<ServiceConfig>,
     /// Services tunnel, which is a HashMap of tunnel Id and Tunnel instance
     pub tunnels: DashMap<TunnelId, Tunnel>,
+    /// Proxied WebSockets, keyed by connection Id
+    pub ws_tunnels: DashMap<TunnelId, WsTunnel>,
+    /// Http requests waiting for response, keyed by request id
+    pending_requests: DashMap<String, oneshot::Sender<Result<HttpResponse>>>,
+    /// Response caches of services, keyed by lowercase service name
+    caches: HashMap<String, ResponseCache>,
+    /// Permits of forwarding http requests concurrently, unlimited if not provided
+    request_permits: Option<Arc<Semaphore>>,
+    /// Pooled http clients of services listening on tcp, keyed by lowercase service name
+    clients: HashMap<String, reqwest::Client>,
+    /// Executors of http requests of services listening on tcp, keyed by lowercase service name
+    executors: HashMap<String, Arc<dyn HttpExecutor>>,
+    /// Balancers of services with upstreams, keyed by lowercase service name
+    balancers: HashMap<String, Arc<Balancer>>,
+    /// Dids allowed to use services, open to all if not provided
+    allowed_origins: Option<HashSet<Did>>,
+    /// Rate limiter of http requests by origin, unlimited if not provided
+    rate_limiter: Option<RateLimiter>,
+    /// Bodies of streaming http requests being received, keyed by requester and request id
+    request_streams: Arc<DashMap<(Did, String), Arc<tokio::sync::Mutex<BodyStream>>>>,
+    /// Chunks of streaming http requests received before the head of request
+    early_chunks: EarlyChunks,
+    /// Tokens cancelling http requests in flight when their origin disconnects, keyed by origin
+    origin_cancels: Arc<DashMap<Did, Arc<CancellationToken>>>,
+}
+
+/// Cancellation of a http request in flight, see [ServiceProvider::origin_cancel].
+/// The token of origin is dropped along with the last request of it, so that origins
+/// which never disconnect directly, such as relayed ones, are not kept forever.
+struct OriginCancel {
+    origin: Did,
+    parent: Option<Arc<CancellationToken>>,
+    token: CancellationToken,
+    cancels: Arc<DashMap<Did, Arc<CancellationToken>>>,
+}
+
+impl OriginCancel {
+    /// Token cancelled when the origin disconnects.
+    fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+}
+
+impl Drop for OriginCancel {
+    fn drop(&mut self) {
+        let Some(parent) = self.parent.take() else {
+            return;
+        };
+        // The count is checked and released under the lock of entry, so that a request
+        // of origin taking the token meanwhile is counted.
+        if let dashmap::mapref::entry::Entry::Occupied(e) = self.cancels.entry(self.origin) {
+            // Map and this request are the only holders, unless it's replaced after disconnection.
+            let last = Arc::ptr_eq(e.get(), &parent) && Arc::strong_count(&parent) == 2;
+            drop(parent);
+            if last {
+                e.remove();
+            }
+        }
+    }
 }
 
 impl ServiceProvider {
     /// Create a new ServiceProvider with a config list
     pub fn new(services: Vec<ServiceConfig>) -> Self {
+        let caches = services
+            .iter()
+            .filter_map(|s| {
+                let capacity = s.cache_capacity?;
+                let cache = ResponseCache::new(capacity, Duration::from_secs(s.cache_default_ttl));
+                Some((s.name.to_ascii_lowercase(), cache))
+            })
+            .collect();
+        let clients = services
+            .iter()
+            .filter(|s| s.addr.is_some() || !s.upstreams.is_empty())
+            .filter_map(|s| match http_client(s) {
+                Ok(client) => Some((s.name.to_ascii_lowercase(), client)),
+                Err(e) => {
+                    tracing::warn!("Failed to create http client of {}: {e:?}", s.name);
+                    None
+                }
+            })
+            .collect::<HashMap<_, _>>();
+        let executors = services
+            .iter()
+            .filter_map(|s| {
+                let name = s.name.to_ascii_lowercase();
+                let client = clients.get(&name)?.clone();
+                let executor: Arc<dyn HttpExecutor> = Arc::new(reqwest_executor(s, client));
+                Some((name, executor))
+            })
+            .collect();
+        let balancers = services
+            .iter()
+            .filter(|s| !s.upstreams.is_empty())
+            .map(|s| {
+                let name = s.name.to_ascii_lowercase();
+                let balancer = Balancer::new(&name, s.strategy, &s.upstreams);
+                let balancer = match s.health_check {
+                    Some(_) => balancer.probed(),
+                    None => balancer,
+                };
+                (name, Arc::new(balancer))
+            })
+            .collect();
         Self {
             services,
+            caches,
+            clients,
+            executors,
+            balancers,
             tunnels: DashMap::new(),
+            ws_tunnels: DashMap::new(),
+            pending_requests: DashMap::new(),
+            request_permits: None,
+            allowed_origins: None,
+            rate_limiter: None,
+            request_streams: Arc::new(DashMap::new()),
+            early_chunks: EarlyChunks::default(),
+            origin_cancels: Arc::new(DashMap::new()),
         }
     }
 
+    /// Limit rate of http requests from each origin. Requests over the limit are answered with
+    /// `429 Too Many Requests` without touching the service. Unlimited if not provided.
+    pub fn rate_limit(mut self, config: Option<RateLimitConfig>) -> Self {
+        self.rate_limiter = config.map(RateLimiter::new);
+        self
+    }
+
+    /// Send http requests to service by executor instead of its pooled http client.
+    /// Health checks and preconnection of the service still use the client.
+    pub fn http_executor(mut self, service: &str, executor: impl HttpExecutor + 'static) -> Self {
+        self.executors
+            .insert(service.to_ascii_lowercase(), Arc::new(executor));
+        self
+    }
+
+    /// Only serve requests from the given Dids. Requests of others are answered with
+    /// `403 Forbidden`, and their tunnels are refused. Services are open to all if not provided.
+    pub fn allowed_origins(mut self, origins: Option<Vec<Did>>) -> Self {
+        self.allowed_origins = origins.map(|v| v.into_iter().collect());
+        self
+    }
+
+    /// Check if peer is allowed to use services, see [ServiceProvider::allowed_origins].
+    fn is_origin_allowed(&self, peer: Did) -> bool {
+        let allowed = self
+            .allowed_origins
+            .as_ref()
+            .map_or(true, |origins| origins.contains(&peer));
+        if !allowed {
+            tracing::warn!("Deny service message from {peer}: origin is not allowed");
+        }
+        allowed
+    }
+
+    /// A response rejecting http request of peer, if it's not allowed or over the rate limit.
+    fn reject_origin(&self, peer: Did, req: &HttpRequest) -> Option<HttpResponse> {
+        if !self.is_origin_allowed(peer) {
+            return Some(error_response(req, http::StatusCode::FORBIDDEN));
+        }
+        let wait = self.rate_limiter.as_ref()?.check(peer).err()?;
+        tracing::info!(
+            "Reject http request to {} from {peer}: rate limited",
+            req.service
+        );
+        metrics::HTTP_REQUESTS_RATE_LIMITED.inc();
+        let mut resp = error_response(req, http::StatusCode::TOO_MANY_REQUESTS);
+        // Round up to whole seconds.
+        let retry_after = wait
+            .as_secs()
+            .saturating_add(u64::from(wait.subsec_nanos() > 0))
+            .max(1);
+        resp.headers.push((
+            http::header::RETRY_AFTER.to_string(),
+            retry_after.to_string(),
+        ));
+        Some(resp)
+    }
+
+    /// Limit count of http requests forwarded to services concurrently. Once it's reached,
+    /// further requests are answered with `503 Service Unavailable` instead of being queued.
+    /// Requests answered from cache are not limited.
+    pub fn max_concurrent_requests(mut self, max: Option<usize>) -> Self {
+        self.request_permits = max.map(|n| Arc::new(Semaphore::new(n)));
+        self
+    }
+
+    /// Open a keep-alive connection to each service with `preconnect` set, or to each of its
+    /// upstreams, by sending a `HEAD /` request. The connection is kept in the pool of its
+    /// client and reused by the first proxied request. Failures are logged and ignored.
+    pub async fn preconnect(&self) {
+        for service in self.services.iter().filter(|s| s.preconnect) {
+            let Some(client) = self.clients.get(&service.name.to_ascii_lowercase()) else {
+                continue;
+            };
+            let addrs = service
+                .addr
+                .into_iter()
+                .chain(service.upstreams.iter().map(|u| u.addr));
+            for addr in addrs {
+                let url = format!("http://{addr}/");
+                let resp = client
+                    .head(url)
+                    .timeout(Duration::from_secs(TCP_SERVER_TIMEOUT))
+                    .send()
+                    .await;
+                match resp {
+                    Ok(_) => tracing::info!("Preconnected to service {} at {addr}", service.name),
+                    Err(e) => tracing::warn!(
+                        "Failed to preconnect to service {} at {addr}: {e}",
+                        service.name
+                    ),
+                }
+            }
+        }
+    }
+
+    /// Start health checks of upstreams of services with `health_check` set. The checks run in
+    /// background until the provider is dropped.
+    pub fn start_health_checks(&self) {
+        for service in &self.services {
+            let name = service.name.to_ascii_lowercase();
+            let (Some(check), Some(client), Some(balancer)) = (
+                &service.health_check,
+                self.clients.get(&name),
+                self.balancers.get(&name),
+            ) else {
+                continue;
+            };
+            spawn_health_check(client.clone(), Arc::downgrade(balancer), check.clone());
+        }
+    }
+
+    /// Health of each upstream, keyed by lowercase service name.
+    pub fn upstream_health(&self) -> HashMap<String, Vec<(SocketAddr, bool)>> {
+        self.balancers
+            .iter()
+            .map(|(name, balancer)| (name.clone(), balancer.health()))
+            .collect()
+    }
+
+    /// Returns None if the limit is reached. The permit is None if requests are unlimited.
+    fn try_acquire_request_permit(&self) -> Option<Option<OwnedSemaphorePermit>> {
+        match self.request_permits.as_ref() {
+            Some(permits) => permits.clone().try_acquire_owned().ok().map(Some),
+            None => Some(None),
+        }
+    }
+
+    /// Request ids of http requests sent by [ServiceProvider::request_with_timeout]
+    /// and still waiting for response.
+    pub fn pending_requests(&self) -> Vec<String> {
+        self.pending_requests
+            .iter()
+            .map(|entry| entry.key().clone())
+            .collect()
+    }
+
+    /// Stop waiting for the response of request `rid`, its caller gets [Error::Cancelled].
+    /// Returns false if the request is not pending, such as it's responded or timed out.
+    pub fn cancel_request(&self, rid: &str) -> bool {
+        let Some((_, tx)) = self.pending_requests.remove(rid) else {
+            return false;
+        };
+        // The caller may have gone away.
+        let _ = tx.send(Err(Error::Cancelled));
+        true
+    }
+
+    /// Abort http requests of peer being forwarded to services, since no one waits for their
+    /// responses after it disconnects. Only the disconnection of a directly connected peer is
+    /// noticed, requests relayed by others from a gone peer run to the end.
+    /// WebSocket tunnels opened by peer are closed as well.
+    pub fn on_peer_disconnected(&self, peer: Did) {
+        if let Some((_, cancel)) = self.origin_cancels.remove(&peer) {
+            tracing::info!("Cancel http requests of {peer}: disconnected");
+            cancel.cancel();
+        }
+        self.ws_tunnels.retain(|cid, tunnel| {
+            let keep = tunnel.peer_did() != peer;
+            if !keep {
+                tracing::info!("Close WebSocket {cid} of {peer}: disconnected");
+            }
+            keep
+        });
+    }
+
+    /// A token cancelled when peer disconnects, see [ServiceProvider::on_peer_disconnected].
+    /// It should be held until the request finishes.
+    fn origin_cancel(&self, peer: Did) -> OriginCancel {
+        let parent = self.origin_cancels.entry(peer).or_default().clone();
+        OriginCancel {
+            origin: peer,
+            token: parent.child_token(),
+            parent: Some(parent),
+            cancels: self.origin_cancels.clone(),
+        }
+    }
+
+    /// Pair the response of request `rid`. A request id waiting for its response is rejected
+    /// with [Error::DuplicatedRequestId], so that the response can't be taken by another request.
+    fn pending_request(&self, rid: &str) -> Result<oneshot::Receiver<Result<HttpResponse>>> {
+        match self.pending_requests.entry(rid.to_string()) {
+            dashmap::mapref::entry::Entry::Occupied(_) => {
+                tracing::warn!("Reject http request {rid}: waiting for response");
+                Err(Error::DuplicatedRequestId(rid.to_string()))
+            }
+            dashmap::mapref::entry::Entry::Vacant(e) => {
+                let (tx, rx) = oneshot::channel();
+                e.insert(tx);
+                Ok(rx)
+            }
+        }
+    }
+
+    /// Send a http request to the service of a remote node and wait for its response.
+    /// A request id is generated if it's not provided, the response is paired by it.
+    /// Returns [Error::Timeout] if no response arrives in time, or [Error::Cancelled]
+    /// if it's cancelled by [ServiceProvider::cancel_request]. A provided request id
+    /// still waiting for its response is rejected with [Error::DuplicatedRequestId].
+    pub async fn request_with_timeout(
+        &self,
+        provider: Arc<Provider>,
+        destination: Did,
+        mut req: HttpRequest,
+        timeout: Duration,
+    ) -> Result<HttpResponse> {
+        let rid = req
+            .rid
+            .get_or_insert_with(|| uuid::Uuid::new_v4().to_string())
+            .clone();
+        let rx = self.pending_request(&rid)?;
+
+        let result = async {
+            let backend_message: BackendMessage = ServiceMessage::HttpRequest(req).into();
+            let params = backend_message.into_send_backend_message_request(destination)?;
+            provider.request(Method::SendBackendMessage, params).await?;
+
+            match tokio::time::timeout(timeout, rx).await {
+                Ok(Ok(resp)) => resp,
+                Ok(Err(_)) => Err(Error::HttpRequestError(
+                    "response channel closed".to_string(),
+                )),
+                Err(_) => Err(Error::Timeout),
+            }
+        }
+        .await;
+
+        self.pending_requests.remove(&rid);
+        result
+    }
+
+    /// Like [ServiceProvider::request_with_timeout], but the body is streamed to the remote
+    /// node in chunks as they are yielded by `body`, instead of being sent as a whole.
+    /// The remote node passes them on to the service as they arrive. `body` of `req` is ignored.
+    pub async fn request_stream_with_timeout<S>(
+        &self,
+        provider: Arc<Provider>,
+        destination: Did,
+        mut req: HttpRequest,
+        mut body: S,
+        timeout: Duration,
+    ) -> Result<HttpResponse>
+    where
+        S: Stream<Item = bytes::Bytes> + Unpin,
+    {
+        let rid = req
+            .rid
+            .get_or_insert_with(|| uuid::Uuid::new_v4().to_string())
+            .clone();
+        req.body = None;
+        let rx = self.pending_request(&rid)?;
+
+        let result = async {
+            let head = ServiceMessage::HttpRequestStream(req);
+            send_service_message(&provider, destination, head).await?;
+            let mut seq = 0;
+            while let Some(data) = body.next().await {
+                let chunk = ServiceMessage::HttpRequestChunk {
+                    rid: rid.clone(),
+                    seq,
+                    data,
+                    fin: false,
+                };
+                send_service_message(&provider, destination, chunk).await?;
+                seq += 1;
+            }
+            let fin = ServiceMessage::HttpRequestChunk {
+                rid: rid.clone(),
+                seq,
+                data: bytes::Bytes::new(),
+                fin: true,
+            };
+            send_service_message(&provider, destination, fin).await?;
+
+            match tokio::time::timeout(timeout, rx).await {
+                Ok(Ok(resp)) => resp,
+                Ok(Err(_)) => Err(Error::HttpRequestError(
+                    "response channel closed".to_string(),
+                )),
+                Err(_) => Err(Error::Timeout),
+            }
+        }
+        .await;
+
+        self.pending_requests.remove(&rid);
+        result
+    }
+
     fn service(&self, name: &str) -> Option<&ServiceConfig> {
         self.services
             .iter()
             .find(|x| x.name.eq_ignore_ascii_case(name))
     }
 
+    /// Push chunk of streaming http request `rid` from `origin` to its body. The stream is
+    /// removed once the body is complete or aborted.
+    async fn push_request_chunk(
+        &self,
+        provider: &Provider,
+        origin: Did,
+        rid: &str,
+        stream: &tokio::sync::Mutex<BodyStream>,
+        (seq, data, fin): Chunk,
+    ) -> Result<()> {
+        let key = (origin, rid.to_string());
+        let pushed = stream.lock().await.push(seq, data, fin).await;
+        match pushed {
+            Ok(false) => Ok(()),
+            Ok(true) => {
+                self.request_streams.remove(&key);
+                Ok(())
+            }
+            Err(defeat) => {
+                self.request_streams.remove(&key);
+                tracing::info!("Abort streaming http request {rid}: {defeat:?}");
+                if defeat != StreamDefeat::TooLarge {
+                    return Ok(());
+                }
+                let resp = HttpResponse {
+                    rid: Some(rid.to_string()),
+                    ..status_response(http::StatusCode::PAYLOAD_TOO_LARGE)
+                };
+                let msg = ServiceMessage::HttpResponse(resp);
+                send_service_message(provider, origin, msg).await
+            }
+        }
+    }
+
     async fn do_handle_message(
         &self,
         provider: Arc<Provider>,
@@ -88,7 +817,13 @@ impl ServiceProvider {
         match msg {
             ServiceMessage::TcpDial { tid, service } => {
                 let service = self.service(service).ok_or(Error::InvalidService)?;
-                match tcp_connect_with_timeout(service.addr, TCP_SERVER_TIMEOUT).await {
+                let addr = service.addr.ok_or(Error::InvalidAddress)?;
+                let dialed = if self.is_origin_allowed(peer_did) {
+                    tcp_connect_with_timeout(addr, TCP_SERVER_TIMEOUT).await
+                } else {
+                    Err(TunnelDefeat::ConnectionRefused)
+                };
+                match dialed {
                     Err(e) => {
                         let msg = ServiceMessage::TcpClose {
                             tid: *tid,
@@ -101,7 +836,7 @@ impl ServiceProvider {
                     }
 
                     Ok(local_stream) => {
-                        let mut tunnel = Tunnel::new(*tid);
+                        let mut tunnel = Tunnel::new(*tid, service.channel_buffer);
                         tunnel
                             .listen(provider.clone(), local_stream, peer_did)
                             .await;
@@ -124,15 +859,256 @@ impl ServiceProvider {
             }
             ServiceMessage::HttpRequest(req) => {
                 let service = self.service(&req.service).ok_or(Error::InvalidService)?;
-                let resp = handle_http_request(service.addr, req).await?;
-                let backend_message: BackendMessage = ServiceMessage::HttpResponse(resp).into();
-                let params = backend_message.into_send_backend_message_request(peer_did)?;
-                let resp = provider.request(Method::SendBackendMessage, params).await?;
-                tracing::info!("done calling provider {:?}", resp);
+                let cache = self.caches.get(&service.name.to_ascii_lowercase());
+                let rejected = self.reject_origin(peer_did, req);
+                // Response may depend on origin if it's forwarded, so it's cached per peer.
+                let origin = service.forward_origin.then_some(peer_did);
+                let resp = match rejected.or_else(|| cache.and_then(|c| c.get(req, origin))) {
+                    Some(resp) => resp,
+                    None => match self.try_acquire_request_permit() {
+                        Some(_permit) => {
+                            let name = service.name.to_ascii_lowercase();
+                            let executor = self.executors.get(&name).map(Arc::as_ref);
+                            let balancer = self.balancers.get(&name).map(Arc::as_ref);
+                            let forwarded = with_origin(service, req, peer_did);
+                            let cancel = self.origin_cancel(peer_did);
+                            let resp = forward_http_request_with_body(
+                                service,
+                                executor,
+                                balancer,
+                                forwarded,
+                                None,
+                                cancel.token(),
+                            )
+                            .await?;
+                            if let Some(cache) = cache {
+                                cache.insert(req, origin, &resp);
+                            }
+                            resp
+                        }
+                        None => {
+                            tracing::info!(
+                                "Reject http request to {}: too many requests in flight",
+                                service.name
+                            );
+                            metrics::HTTP_REQUESTS_SHED.inc();
+                            busy_response(req)
+                        }
+                    },
+                };
+                let resp = if service.compress_responses {
+                    compress_response(resp, service.compress_min_bytes)?
+                } else {
+                    resp
+                };
+                send_service_message(&provider, peer_did, ServiceMessage::HttpResponse(resp)).await
+            }
+            ServiceMessage::HttpRequestStream(req) => {
+                let service = self.service(&req.service).ok_or(Error::InvalidService)?;
+                let rid = req.rid.clone().ok_or(Error::InvalidMessage)?;
+                let key = (peer_did, rid.clone());
+                if self.request_streams.contains_key(&key) {
+                    tracing::warn!("Reject streaming http request {rid}: already streaming");
+                    return Err(Error::DuplicatedRequestId(rid));
+                }
+                let permit = match self.reject_origin(peer_did, req) {
+                    Some(resp) => {
+                        let msg = ServiceMessage::HttpResponse(resp);
+                        return send_service_message(&provider, peer_did, msg).await;
+                    }
+                    None => match self.try_acquire_request_permit() {
+                        Some(permit) => permit,
+                        None => {
+                            tracing::info!(
+                                "Reject http request to {}: too many requests in flight",
+                                service.name
+                            );
+                            metrics::HTTP_REQUESTS_SHED.inc();
+                            let msg = ServiceMessage::HttpResponse(busy_response(req));
+                            return send_service_message(&provider, peer_did, msg).await;
+                        }
+                    },
+                };
+
+                let (stream, body) = BodyStream::new(service.max_request_bytes);
+                let stream = Arc::new(tokio::sync::Mutex::new(stream));
+                match self.request_streams.entry(key.clone()) {
+                    dashmap::mapref::entry::Entry::Occupied(_) => {
+                        tracing::warn!("Reject streaming http request {rid}: already streaming");
+                        return Err(Error::DuplicatedRequestId(rid));
+                    }
+                    dashmap::mapref::entry::Entry::Vacant(e) => {
+                        e.insert(stream.clone());
+                    }
+                }
+
+                // Chunks of body are received by later messages, so the request is forwarded
+                // in background instead of blocking the handling of them.
+                let name = service.name.to_ascii_lowercase();
+                let executor = self.executors.get(&name).cloned();
+                let balancer = self.balancers.get(&name).cloned();
+                let req = with_origin(service, req, peer_did).into_owned();
+                let cancel = self.origin_cancel(peer_did);
+                let service = service.clone();
+                let streams = self.request_streams.clone();
+                let forwarder = provider.clone();
+                let forwarding = async move {
+                    let _permit = permit;
+                    let resp = forward_http_request_stream(
+                        &service,
+                        executor.as_deref(),
+                        balancer.as_deref(),
+                        req,
+                        body,
+                        cancel.token(),
+                    )
+                    .await;
+                    drop(cancel);
+                    streams.remove(&key);
+                    let resp = match resp {
+                        Ok(resp) if service.compress_responses => {
+                            compress_response(resp, service.compress_min_bytes)
+                        }
+                        resp => resp,
+                    };
+                    let sent = match resp {
+                        Ok(resp) => {
+                            let msg = ServiceMessage::HttpResponse(resp);
+                            send_service_message(&forwarder, peer_did, msg).await
+                        }
+                        Err(e) => Err(e),
+                    };
+                    if let Err(e) = sent {
+                        tracing::warn!("Failed to forward streaming http request {}: {e}", key.1);
+                    }
+                };
+                tokio::spawn(forwarding.in_current_span());
+
+                for chunk in self.early_chunks.take(peer_did, &rid) {
+                    self.push_request_chunk(&provider, peer_did, &rid, &stream, chunk)
+                        .await?;
+                }
                 Ok(())
             }
+            ServiceMessage::HttpRequestChunk {
+                rid,
+                seq,
+                data,
+                fin,
+            } => {
+                let key = (peer_did, rid.clone());
+                // The guard of map must be released before pushing, which may wait for service.
+                let stream = self.request_streams.get(&key).map(|s| s.value().clone());
+                let chunk = (*seq, data.clone(), *fin);
+                match stream {
+                    Some(stream) => {
+                        self.push_request_chunk(&provider, peer_did, rid, &stream, chunk)
+                            .await
+                    }
+                    None => {
+                        if !self.early_chunks.hold(peer_did, rid, chunk) {
+                            tracing::debug!(
+                                "Drop chunk {seq} of http request {rid}: not streaming"
+                            );
+                        }
+                        Ok(())
+                    }
+                }
+            }
             ServiceMessage::HttpResponse(resp) => {
                 tracing::info!("ServiceMessage from {peer_did:?} HttpResponse: {resp:?}");
+                let pending = resp
+                    .rid
+                    .as_ref()
+                    .and_then(|rid| self.pending_requests.remove(rid));
+                if let Some((_, tx)) = pending {
+                    if tx.send(Ok(resp.clone().decompress()?)).is_err() {
+                        tracing::warn!("HttpResponse {:?} arrived after timeout", resp.rid);
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Handle a proxied WebSocket message. On open, the upstream `ws://` socket of service
+    /// is connected and frames are pumped in both directions until either side closes.
+    pub async fn handle_websocket_message(
+        &self,
+        provider: Arc<Provider>,
+        ctx: &MessagePayload,
+        msg: &WebSocketMessage,
+    ) -> Result<()> {
+        let peer_did = ctx.transaction.signer();
+
+        match msg {
+            WebSocketMessage::Open { cid, service, path } => {
+                let service = self.service(service).ok_or(Error::InvalidService)?;
+                let addr = service.addr.ok_or(Error::InvalidAddress)?;
+                if !self.is_origin_allowed(peer_did) {
+                    let msg = WebSocketMessage::Close {
+                        cid: *cid,
+                        reason: Some(http::StatusCode::FORBIDDEN.to_string()),
+                    };
+                    return send_to_peer(&provider, peer_did, msg).await;
+                }
+                if !service.is_path_allowed(path) {
+                    tracing::info!(
+                        "Reject websocket to {}: path {} is not allowed",
+                        service.name,
+                        path
+                    );
+                    let msg = WebSocketMessage::Close {
+                        cid: *cid,
+                        reason: Some(http::StatusCode::FORBIDDEN.to_string()),
+                    };
+                    return send_to_peer(&provider, peer_did, msg).await;
+                }
+                let tunnel = WsTunnel::open(
+                    provider.clone(),
+                    addr,
+                    path,
+                    *cid,
+                    peer_did,
+                    TCP_SERVER_TIMEOUT,
+                    service.channel_buffer,
+                )
+                .await;
+                match tunnel {
+                    Err(e) => {
+                        let msg = WebSocketMessage::Close {
+                            cid: *cid,
+                            reason: Some(e.to_string()),
+                        };
+                        send_to_peer(&provider, peer_did, msg).await?;
+                        Err(e)
+                    }
+                    Ok(tunnel) => {
+                        // Tunnels closed by upstream are dropped here, their peers are told.
+                        self.ws_tunnels.retain(|_, tunnel| !tunnel.is_closed());
+                        self.ws_tunnels.insert(*cid, tunnel);
+                        Ok(())
+                    }
+                }
+            }
+            WebSocketMessage::Frame { cid, frame } => {
+                if self
+                    .ws_tunnels
+                    .remove_if(cid, |_, tunnel| tunnel.is_closed())
+                    .is_some()
+                {
+                    return Err(Error::TunnelNotFound);
+                }
+                self.ws_tunnels
+                    .get(cid)
+                    .ok_or(Error::TunnelNotFound)?
+                    .send(frame.clone())
+                    .await;
+                Ok(())
+            }
+            WebSocketMessage::Close { cid, reason } => {
+                tracing::info!("WebSocket {cid} closed by {peer_did:?}: {reason:?}");
+                self.ws_tunnels.remove(cid);
                 Ok(())
             }
         }
@@ -147,57 +1123,1340 @@ impl MessageHandler<ServiceMessage> for ServiceProvider {
         ctx: &MessagePayload,
         msg: &ServiceMessage,
     ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        // Spans of forwarded http requests are children of it, so that they can be found by
+        // the message carrying them.
+        let span = tracing::info_span!(
+            "service_message",
+            tx_id = %ctx.transaction.tx_id,
+            origin = %ctx.transaction.signer(),
+        );
         self.do_handle_message(provider, ctx, msg)
+            .instrument(span)
             .await
             .map_err(|e| e.into())
     }
 }
 
-async fn handle_http_request(addr: SocketAddr, req: &HttpRequest) -> Result<HttpResponse> {
-    let url = format!("http://{}/{}", addr, req.path.trim_start_matches('/'));
-    tracing::info!("Handle http request on url: {:?} start", url);
+/// Forward a request with its own body, see [forward_http_request_with_body].
+#[cfg(test)]
+async fn forward_http_request(
+    service: &ServiceConfig,
+    executor: Option<&dyn HttpExecutor>,
+    balancer: Option<&Balancer>,
+    req: &HttpRequest,
+    cancel: CancellationToken,
+) -> Result<HttpResponse> {
+    let req = Cow::Borrowed(req);
+    forward_http_request_with_body(service, executor, balancer, req, None, cancel).await
+}
+
+/// Forward a request whose body is streamed to the service from `body`,
+/// see [forward_http_request_with_body]. The size of body is limited when it's received,
+/// see [BodyStream]. The request can't be retried on the next upstream, since the body
+/// is consumed by the failed attempt.
+async fn forward_http_request_stream(
+    service: &ServiceConfig,
+    executor: Option<&dyn HttpExecutor>,
+    balancer: Option<&Balancer>,
+    req: HttpRequest,
+    body: BodyReceiver,
+    cancel: CancellationToken,
+) -> Result<HttpResponse> {
+    let req = Cow::Owned(req);
+    forward_http_request_with_body(service, executor, balancer, req, Some(body), cancel).await
+}
+
+/// Check the request against limits of service, then forward it to the service by executor,
+/// or by a new [ReqwestExecutor] if it's not provided. With upstreams, it's forwarded to the one picked
+/// by balancer, see [forward_to_upstreams].
+/// A rejected request is answered with an error status instead of [Error],
+/// so that the requester gets a response.
+/// The body is streamed from `body` if provided, otherwise `body` of request is sent.
+/// The request is copied only if it's borrowed, since the trace context is set to its headers.
+async fn forward_http_request_with_body(
+    service: &ServiceConfig,
+    executor: Option<&dyn HttpExecutor>,
+    balancer: Option<&Balancer>,
+    mut req: Cow<'_, HttpRequest>,
+    body: Option<BodyReceiver>,
+    cancel: CancellationToken,
+) -> Result<HttpResponse> {
+    let body_len = req.body.as_ref().map(|b| b.len()).unwrap_or(0);
+    if let Some(max) = service.max_request_bytes {
+        if body_len > max {
+            tracing::info!(
+                "Reject http request to {}: body of {} bytes exceeds {}",
+                service.name,
+                body_len,
+                max
+            );
+            return Ok(error_response(&req, http::StatusCode::PAYLOAD_TOO_LARGE));
+        }
+    }
+
+    if !service.is_path_allowed(&req.path) {
+        tracing::info!(
+            "Reject http request to {}: path {} is not allowed",
+            service.name,
+            req.path
+        );
+        return Ok(error_response(&req, http::StatusCode::FORBIDDEN));
+    }
+    tracing::info!("Allow http request to {}: path {}", service.name, req.path);
+
+    // Continue the trace of requester, so that the request can be followed into the service.
+    let trace = trace::propagate(req.to_mut());
+    let span = tracing::info_span!(
+        "http_request",
+        service = %service.name,
+        rid = ?req.rid,
+        trace_id = %trace.trace_id,
+        span_id = %trace.span_id,
+    );
+
+    let resp = async {
+        match (&service.unix_socket, service.addr, balancer) {
+            (Some(path), _, _) => {
+                handle_unix_socket_http_request(service, path, &req, body, cancel).await
+            }
+            (None, Some(addr), _) => match executor {
+                Some(executor) => executor.execute(addr, &req, body, cancel).await,
+                None => {
+                    let executor = reqwest_executor(service, http_client(service)?);
+                    executor.execute(addr, &req, body, cancel).await
+                }
+            },
+            (None, None, Some(balancer)) => match executor {
+                Some(executor) => {
+                    forward_to_upstreams(executor, balancer, &req, body, cancel).await
+                }
+                None => {
+                    let executor = reqwest_executor(service, http_client(service)?);
+                    forward_to_upstreams(&executor, balancer, &req, body, cancel).await
+                }
+            },
+            (None, None, None) => Err(Error::InvalidAddress),
+        }
+    }
+    .instrument(span)
+    .await;
+
+    match resp {
+        // The service answered, but its response can't be relayed to the requester.
+        Err(Error::ResponseHeadersTooLarge(_)) => {
+            Ok(error_response(&req, http::StatusCode::BAD_GATEWAY))
+        }
+        resp => resp,
+    }
+}
+
+/// Forward the request to an upstream picked by balancer. If it can't connect, the request
+/// is retried on the next one, until all available upstreams are tried.
+/// A request with streaming body is not retried, since its body can't be replayed.
+async fn forward_to_upstreams(
+    executor: &dyn HttpExecutor,
+    balancer: &Balancer,
+    req: &HttpRequest,
+    mut body: Option<BodyReceiver>,
+    cancel: CancellationToken,
+) -> Result<HttpResponse> {
+    let streaming = body.is_some();
+    let mut tried = vec![];
+    while let Some(upstream) = balancer.pick(&tried) {
+        match executor
+            .execute(upstream.addr(), req, body.take(), cancel.clone())
+            .await
+        {
+            Err(Error::HttpConnectError(e)) => {
+                tracing::warn!("Failed to connect upstream {}: {e}", upstream.addr());
+                upstream.report(false);
+                if streaming {
+                    return Err(Error::HttpConnectError(e));
+                }
+                tried.push(upstream.addr());
+            }
+            resp => {
+                upstream.report(true);
+                return resp;
+            }
+        }
+    }
+    Err(Error::HttpConnectError("no healthy upstream".to_string()))
+}
+
+/// Set origin headers of request to the Did of requesting peer if service has `forward_origin`.
+/// Those sent by the peer are dropped first, so that they can't be forged.
+fn with_origin<'a>(
+    service: &ServiceConfig,
+    req: &'a HttpRequest,
+    origin: Did,
+) -> Cow<'a, HttpRequest> {
+    if !service.forward_origin {
+        return Cow::Borrowed(req);
+    }
+    let origin = origin.to_string();
+    let mut req = req.clone();
+    let mut forwarded_for = None;
+    req.headers.retain(|(k, v)| {
+        if k.eq_ignore_ascii_case(&service.origin_did_header) {
+            return false;
+        }
+        match &service.forwarded_for_header {
+            Some(h) if k.eq_ignore_ascii_case(h) => {
+                forwarded_for = Some(v.clone());
+                false
+            }
+            _ => true,
+        }
+    });
+    req.headers
+        .push((service.origin_did_header.clone(), origin.clone()));
+    if let Some(header) = &service.forwarded_for_header {
+        // Keep the chain of proxies in front of the peer, as the header is meant to.
+        let value = match forwarded_for {
+            Some(chain) => format!("{chain}, {origin}"),
+            None => origin,
+        };
+        req.headers.push((header.clone(), value));
+    }
+    Cow::Owned(req)
+}
+
+/// Body of upstream response. Responses of status such as `304 Not Modified` have no body,
+/// they are relayed as is, so that conditional requests work through the proxy.
+pub(crate) fn response_body(status: u16, body: bytes::Bytes) -> Option<bytes::Bytes> {
+    let no_body = (100..200).contains(&status) || status == 204 || status == 304;
+    if no_body && body.is_empty() {
+        None
+    } else {
+        Some(body)
+    }
+}
+
+/// Headers of upstream response. Fails with [Error::ResponseHeadersTooLarge] if the names and
+/// values add up to more than `max_bytes`, so that an upstream can't exhaust memory by headers.
+pub(crate) fn response_headers(
+    headers: &http::HeaderMap,
+    max_bytes: Option<usize>,
+) -> Result<Vec<(String, String)>> {
+    let mut total = 0usize;
+    let mut collected = Vec::with_capacity(headers.len());
+    for (key, value) in headers.iter() {
+        total = total.saturating_add(key.as_str().len() + value.len());
+        if let Some(max) = max_bytes {
+            if total > max {
+                tracing::warn!("Drop http response: headers exceed {max} bytes");
+                return Err(Error::ResponseHeadersTooLarge(max));
+            }
+        }
+        let value = value.to_str().unwrap_or("").to_owned();
+        collected.push((key.to_string(), value));
+    }
+    Ok(collected)
+}
+
+/// A `503 Service Unavailable` response, asking the requester to retry later.
+fn busy_response(req: &HttpRequest) -> HttpResponse {
+    let mut resp = error_response(req, http::StatusCode::SERVICE_UNAVAILABLE);
+    resp.headers.push((
+        http::header::RETRY_AFTER.to_string(),
+        BUSY_RETRY_AFTER_SECS.to_string(),
+    ));
+    resp
+}
+
+/// A response of status without touching the service.
+fn error_response(req: &HttpRequest, status: http::StatusCode) -> HttpResponse {
+    HttpResponse {
+        rid: req.rid.clone(),
+        ..status_response(status)
+    }
+}
+
+/// A response of status, not paired with any request yet.
+fn status_response(status: http::StatusCode) -> HttpResponse {
+    let reason = status.canonical_reason().unwrap_or_default();
+    HttpResponse {
+        status: status.as_u16(),
+        headers: vec![("content-type".to_string(), "text/plain".to_string())],
+        body: Some(bytes::Bytes::from_static(reason.as_bytes())),
+        rid: None,
+    }
+}
+
+/// Send a service message to peer as backend message.
+async fn send_service_message(provider: &Provider, peer: Did, msg: ServiceMessage) -> Result<()> {
+    let backend_message: BackendMessage = msg.into();
+    let params = backend_message.into_send_backend_message_request(peer)?;
+    provider.request(Method::SendBackendMessage, params).await?;
+    Ok(())
+}
+
+/// [ReqwestExecutor] sending requests of service by client.
+fn reqwest_executor(service: &ServiceConfig, client: reqwest::Client) -> ReqwestExecutor {
+    ReqwestExecutor::new(client).max_response_headers_bytes(service.max_response_headers_bytes)
+}
+
+/// Http client of service. Without flags of http version, http/1.1 is used for cleartext
+/// and http/2 is negotiated by ALPN over TLS.
+fn http_client(service: &ServiceConfig) -> Result<reqwest::Client> {
+    let paths = service.clone();
+    let redirect = service
+        .redirect_policy
+        .into_reqwest(move |path| paths.is_path_allowed(path));
+    let mut builder = reqwest::Client::builder().redirect(redirect);
+    if service.http2_prior_knowledge {
+        builder = builder.http2_prior_knowledge();
+    } else if service.http1_only {
+        builder = builder.http1_only();
+    }
+    if let Some(max) = service.pool_max_idle_per_host {
+        builder = builder.pool_max_idle_per_host(max);
+    }
+    if service.preconnect {
+        builder = builder.pool_idle_timeout(None);
+    }
+    builder
+        .build()
+        .map_err(|e| Error::HttpConnectError(e.to_string()))
+}
+
+/// Send http request to a service listening on unix domain socket.
+/// Reqwest can't dial unix socket, so the request is sent over a bare hyper http1 connection.
+#[cfg(unix)]
+async fn handle_unix_socket_http_request(
+    service: &ServiceConfig,
+    path: &str,
+    req: &HttpRequest,
+    body: Option<BodyReceiver>,
+    cancel: CancellationToken,
+) -> Result<HttpResponse> {
+    tracing::info!("Handle http request on unix socket: {:?} start", path);
     let method = http::Method::from_str(req.method.as_str()).map_err(|_| Error::InvalidMethod)?;
+    let uri = format!("/{}", req.path.trim_start_matches('/'));
 
-    let headers_map: HashMap<String, String> = req.headers.iter().cloned().collect();
-    let headers = (&headers_map).try_into().map_err(|e| {
+    let mut request = hyper::Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(http::header::HOST, "localhost");
+    for (key, value) in req.headers.iter() {
+        request = request.header(key, value);
+    }
+    let body = match (body, req.body.as_ref()) {
+        (Some(stream), _) => hyper::Body::wrap_stream(stream),
+        (None, Some(body)) => hyper::Body::from(body.clone()),
+        (None, None) => hyper::Body::empty(),
+    };
+    let request = request.body(body).map_err(|e| {
         tracing::info!("invalid_headers: {}", e);
         Error::InvalidHeaders
     })?;
 
-    let request = reqwest::Client::new()
-        .request(method, url)
-        .headers(headers)
-        .timeout(Duration::from_secs(TCP_SERVER_TIMEOUT));
+    let start = Instant::now();
+    let timeout = Duration::from_secs(TCP_SERVER_TIMEOUT);
+    let exchange = tokio::time::timeout(timeout, async {
+        let stream = tokio::net::UnixStream::connect(path)
+            .await
+            .map_err(|e| Error::HttpConnectError(e.to_string()))?;
+        let (mut sender, conn) = hyper::client::conn::Builder::new()
+            .http2_only(service.http2_prior_knowledge)
+            .handshake(stream)
+            .await
+            .map_err(|e| Error::HttpConnectError(e.to_string()))?;
+        tokio::spawn(async move {
+            if let Err(e) = conn.await {
+                tracing::warn!("Unix socket connection closed with error: {:?}", e);
+            }
+        });
 
-    let request = if let Some(body) = req.body.as_ref() {
-        let body = body.to_vec();
-        request.body(body)
-    } else {
-        request
+        let resp = sender
+            .send_request(request)
+            .await
+            .map_err(|e| Error::HttpRequestError(e.to_string()))?;
+        let status = resp.status().as_u16();
+        let headers = response_headers(resp.headers(), service.max_response_headers_bytes)?;
+        let body = hyper::body::to_bytes(resp.into_body())
+            .await
+            .map_err(|e| Error::HttpRequestError(e.to_string()))?;
+        Ok::<_, Error>((status, headers, body))
+    });
+    let resp = tokio::select! {
+        resp = exchange => resp.map_err(|_| Error::Timeout)??,
+        _ = cancel.cancelled() => {
+            tracing::info!("Abort http request {:?} on unix socket: cancelled", req.rid);
+            return Err(Error::Cancelled);
+        }
     };
 
-    let resp = request
-        .send()
-        .await
-        .map_err(|e| Error::HttpRequestError(e.to_string()))?;
-
-    let status = resp.status().as_u16();
-
-    let headers = resp
-        .headers()
-        .iter()
-        .map(|(key, value)| (key.to_string(), value.to_str().unwrap_or("").to_owned()))
-        .collect();
-
-    let body = resp
-        .bytes()
-        .await
-        .map_err(|e| Error::HttpRequestError(e.to_string()))?;
-    tracing::info!("Handle http request done, responding");
+    metrics::HTTP_PROXY_LATENCY_MS.observe(start.elapsed().as_millis() as u64);
+    tracing::info!("Handle http request on unix socket done, responding");
+    let (status, headers, body) = resp;
     Ok(HttpResponse {
         status,
         headers,
-        body: Some(body),
+        body: response_body(status, body),
         rid: req.rid.clone(),
     })
 }
+
+#[cfg(not(unix))]
+async fn handle_unix_socket_http_request(
+    _service: &ServiceConfig,
+    _path: &str,
+    _req: &HttpRequest,
+    _body: Option<BodyReceiver>,
+    _cancel: CancellationToken,
+) -> Result<HttpResponse> {
+    Err(Error::HttpConnectError(
+        "unix socket is not supported on this platform".to_string(),
+    ))
+}
+
+/// Gzip the body of response, unless it's too small or already compressed.
+fn compress_response(mut resp: HttpResponse, min_bytes: usize) -> Result<HttpResponse> {
+    let Some(body) = resp.body.as_ref() else {
+        return Ok(resp);
+    };
+    if body.is_empty() || body.len() < min_bytes {
+        return Ok(resp);
+    }
+
+    let already_compressed = resp.headers.iter().any(|(k, v)| {
+        let v = v.to_ascii_lowercase();
+        (k.eq_ignore_ascii_case("content-encoding") && v != "identity")
+            || (k.eq_ignore_ascii_case("content-type") && is_compressed_content_type(&v))
+    });
+    if already_compressed {
+        return Ok(resp);
+    }
+
+    resp.body = Some(encode_data_gzip(body, 9)?);
+    resp.headers
+        .push((RINGS_CONTENT_ENCODING.to_string(), "gzip".to_string()));
+    Ok(resp)
+}
+
+fn is_compressed_content_type(content_type: &str) -> bool {
+    ["image/", "video/", "audio/", "font/woff"]
+        .iter()
+        .any(|prefix| content_type.starts_with(prefix))
+        || [
+            "application/zip",
+            "application/gzip",
+            "application/x-gzip",
+            "application/zstd",
+        ]
+        .iter()
+        .any(|t| content_type.starts_with(t))
+}
+
+#[cfg(test)]
+mod tests {
+    use rings_core::ecc::SecretKey;
+    use rings_core::message::Message;
+    use rings_core::session::SessionSk;
+
+    use super::*;
+    use crate::backend::native::service::balancer::check_upstreams;
+    use crate::backend::types::WebSocketFrame;
+    use crate::consts::UPSTREAM_MAX_FAILURES;
+    use crate::tests::native::prepare_processor;
+
+    #[tokio::test]
+    async fn test_reject_oversized_request() {
+        let service = ServiceConfig {
+            name: "test".to_string(),
+            // Nothing listens on it, the request must be rejected before connecting.
+            addr: Some("127.0.0.1:9".parse().unwrap()),
+            max_request_bytes: Some(4),
+            ..Default::default()
+        };
+        let req = HttpRequest {
+            service: "test".to_string(),
+            method: "POST".to_string(),
+            path: "/".to_string(),
+            headers: vec![],
+            body: Some(vec![0; 5]),
+            rid: Some("rid".to_string()),
+        };
+        let resp = forward_http_request(&service, None, None, &req, CancellationToken::new())
+            .await
+            .unwrap();
+        assert_eq!(resp.status, 413);
+        assert_eq!(resp.rid, Some("rid".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_relay_not_modified_response() {
+        // An upstream answering `304 Not Modified` when the etag matches.
+        let make_service = hyper::service::make_service_fn(|_| async {
+            Ok::<_, std::convert::Infallible>(hyper::service::service_fn(
+                |req: hyper::Request<hyper::Body>| async move {
+                    let matched = req
+                        .headers()
+                        .get(http::header::IF_NONE_MATCH)
+                        .map(|v| v == "\"v1\"")
+                        .unwrap_or(false);
+                    let resp = if matched {
+                        hyper::Response::builder()
+                            .status(304)
+                            .header("etag", "\"v1\"")
+                            .body(hyper::Body::empty())
+                    } else {
+                        hyper::Response::builder()
+                            .header("etag", "\"v1\"")
+                            .body(hyper::Body::from("hello"))
+                    };
+                    Ok::<_, std::convert::Infallible>(resp.unwrap())
+                },
+            ))
+        });
+        let server = hyper::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_service);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+
+        let service: ServiceConfig = serde_json::from_value(serde_json::json!({
+            "name": "test",
+            "register_service": null,
+            "addr": addr,
+        }))
+        .unwrap();
+        let mut req = HttpRequest::builder("test").rid("rid").build().unwrap();
+
+        let resp = forward_http_request(&service, None, None, &req, CancellationToken::new())
+            .await
+            .unwrap();
+        assert_eq!(resp.status, 200);
+        assert_eq!(resp.body, Some(bytes::Bytes::from("hello")));
+
+        req.headers
+            .push(("If-None-Match".to_string(), "\"v1\"".to_string()));
+        let resp = forward_http_request(&service, None, None, &req, CancellationToken::new())
+            .await
+            .unwrap();
+        assert_eq!(resp.status, 304);
+        assert_eq!(resp.body, None);
+        assert_eq!(resp.rid, Some("rid".to_string()));
+        assert_eq!(compress_response(resp, 0).unwrap().body, None);
+    }
+
+    #[tokio::test]
+    async fn test_http2_prior_knowledge() {
+        // A cleartext upstream only speaking http/2.
+        let make_service = hyper::service::make_service_fn(|_| async {
+            Ok::<_, std::convert::Infallible>(hyper::service::service_fn(
+                |req: hyper::Request<hyper::Body>| async move {
+                    let body = format!("{:?}", req.version());
+                    Ok::<_, std::convert::Infallible>(hyper::Response::new(hyper::Body::from(body)))
+                },
+            ))
+        });
+        let server = hyper::Server::bind(&"127.0.0.1:0".parse().unwrap())
+            .http2_only(true)
+            .serve(make_service);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+
+        let mut service: ServiceConfig = serde_json::from_value(serde_json::json!({
+            "name": "test",
+            "register_service": null,
+            "addr": addr,
+        }))
+        .unwrap();
+        let req = HttpRequest::builder("test").build().unwrap();
+        assert!(
+            forward_http_request(&service, None, None, &req, CancellationToken::new())
+                .await
+                .is_err()
+        );
+
+        service.http2_prior_knowledge = true;
+        let resp = forward_http_request(&service, None, None, &req, CancellationToken::new())
+            .await
+            .unwrap();
+        assert_eq!(resp.status, 200);
+        assert_eq!(resp.body, Some(bytes::Bytes::from("HTTP/2.0")));
+    }
+
+    #[tokio::test]
+    async fn test_forward_streaming_body() {
+        // An upstream echoing the request body.
+        let make_service = hyper::service::make_service_fn(|_| async {
+            Ok::<_, std::convert::Infallible>(hyper::service::service_fn(
+                |req: hyper::Request<hyper::Body>| async move {
+                    Ok::<_, std::convert::Infallible>(hyper::Response::new(req.into_body()))
+                },
+            ))
+        });
+        let server = hyper::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_service);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+
+        let service: ServiceConfig = serde_json::from_value(serde_json::json!({
+            "name": "test",
+            "register_service": null,
+            "addr": addr,
+        }))
+        .unwrap();
+        let req = HttpRequest::builder("test")
+            .method("POST")
+            .rid("stream")
+            .build()
+            .unwrap();
+
+        let (mut stream, body) = BodyStream::new(None);
+        let forwarding = tokio::spawn(async move {
+            forward_http_request_stream(&service, None, None, req, body, CancellationToken::new())
+                .await
+        });
+        let world = bytes::Bytes::from("world");
+        assert_eq!(stream.push(1, world, true).await, Ok(false));
+        let hello = bytes::Bytes::from("hello ");
+        assert_eq!(stream.push(0, hello, false).await, Ok(true));
+        drop(stream);
+
+        let resp = forwarding.await.unwrap().unwrap();
+        assert_eq!(resp.status, 200);
+        assert_eq!(resp.rid, Some("stream".to_string()));
+        assert_eq!(resp.body, Some(bytes::Bytes::from("hello world")));
+    }
+
+    #[tokio::test]
+    async fn test_preconnect() {
+        // An upstream counting accepted connections.
+        let accepted = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = accepted.clone();
+        let make_service = hyper::service::make_service_fn(move |_| {
+            counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            async {
+                Ok::<_, std::convert::Infallible>(hyper::service::service_fn(
+                    |_: hyper::Request<hyper::Body>| async {
+                        Ok::<_, std::convert::Infallible>(
+                            hyper::Response::new(hyper::Body::empty()),
+                        )
+                    },
+                ))
+            }
+        });
+        let server = hyper::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_service);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+
+        let service: ServiceConfig = serde_json::from_value(serde_json::json!({
+            "name": "test",
+            "register_service": null,
+            "addr": addr,
+            "pool_max_idle_per_host": 1,
+            "preconnect": true,
+        }))
+        .unwrap();
+        let provider = ServiceProvider::new(vec![service.clone()]);
+        provider.preconnect().await;
+        assert_eq!(accepted.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        // The first request reuses the preconnected connection.
+        let executor = provider.executors.get("test").map(Arc::as_ref);
+        let req = HttpRequest::builder("test").build().unwrap();
+        let resp = forward_http_request(&service, executor, None, &req, CancellationToken::new())
+            .await
+            .unwrap();
+        assert_eq!(resp.status, 200);
+        assert_eq!(accepted.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_failover_to_next_upstream() {
+        let make_service = hyper::service::make_service_fn(|_| async {
+            Ok::<_, std::convert::Infallible>(hyper::service::service_fn(
+                |_: hyper::Request<hyper::Body>| async {
+                    Ok::<_, std::convert::Infallible>(hyper::Response::new(hyper::Body::empty()))
+                },
+            ))
+        });
+        let server = hyper::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_service);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+
+        // Nothing listens on the first upstream.
+        let service: ServiceConfig = serde_json::from_value(serde_json::json!({
+            "name": "test",
+            "register_service": null,
+            "upstreams": [{ "addr": "127.0.0.1:9" }, { "addr": addr }],
+            "strategy": "round_robin",
+        }))
+        .unwrap();
+        let provider = ServiceProvider::new(vec![service.clone()]);
+        let executor = provider.executors.get("test").map(Arc::as_ref);
+        let balancer = provider.balancers.get("test").map(Arc::as_ref);
+        let req = HttpRequest::builder("test").build().unwrap();
+        // Round robin picks the dead one first in every other request.
+        for _ in 0..UPSTREAM_MAX_FAILURES * 2 {
+            let resp =
+                forward_http_request(&service, executor, balancer, &req, CancellationToken::new())
+                    .await
+                    .unwrap();
+            assert_eq!(resp.status, 200);
+        }
+        let health = balancer.unwrap().health();
+        assert_eq!(health, vec![
+            ("127.0.0.1:9".parse().unwrap(), false),
+            (addr, true)
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_health_check_upstreams() {
+        // An upstream answering every request with status.
+        let serve = |status: u16| {
+            let make_service = hyper::service::make_service_fn(move |_| async move {
+                Ok::<_, std::convert::Infallible>(hyper::service::service_fn(
+                    move |_: hyper::Request<hyper::Body>| async move {
+                        let mut resp = hyper::Response::new(hyper::Body::empty());
+                        *resp.status_mut() = http::StatusCode::from_u16(status).unwrap();
+                        Ok::<_, std::convert::Infallible>(resp)
+                    },
+                ))
+            });
+            let server = hyper::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_service);
+            let addr = server.local_addr();
+            tokio::spawn(server);
+            addr
+        };
+        let sick = serve(503);
+        let live = serve(200);
+
+        let service: ServiceConfig = serde_json::from_value(serde_json::json!({
+            "name": "test",
+            "register_service": null,
+            "upstreams": [{ "addr": sick }, { "addr": live }],
+            "health_check": { "path": "/health" },
+        }))
+        .unwrap();
+        let provider = ServiceProvider::new(vec![service.clone()]);
+        let client = provider.clients.get("test").unwrap();
+        let executor = provider.executors.get("test").map(Arc::as_ref);
+        let balancer = provider.balancers.get("test").unwrap();
+        check_upstreams(client, balancer, service.health_check.as_ref().unwrap()).await;
+        assert_eq!(
+            provider.upstream_health().get("test"),
+            Some(&vec![(sick, false), (live, true)])
+        );
+
+        // Requests only go to the healthy upstream.
+        let req = HttpRequest::builder("test").build().unwrap();
+        for _ in 0..3 {
+            let cancel = CancellationToken::new();
+            let resp = forward_http_request(&service, executor, Some(balancer), &req, cancel)
+                .await
+                .unwrap();
+            assert_eq!(resp.status, 200);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_redirect_policy() {
+        // Redirects `/` to location, and answers other paths with 200.
+        let serve = |location: String| {
+            let make_service = hyper::service::make_service_fn(move |_| {
+                let location = location.clone();
+                async move {
+                    Ok::<_, std::convert::Infallible>(hyper::service::service_fn(
+                        move |req: hyper::Request<hyper::Body>| {
+                            let resp = match req.uri().path() {
+                                "/" => hyper::Response::builder()
+                                    .status(302)
+                                    .header(http::header::LOCATION, location.clone()),
+                                _ => hyper::Response::builder(),
+                            };
+                            async move {
+                                Ok::<_, std::convert::Infallible>(
+                                    resp.body(hyper::Body::empty()).unwrap(),
+                                )
+                            }
+                        },
+                    ))
+                }
+            });
+            let server = hyper::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_service);
+            let addr = server.local_addr();
+            tokio::spawn(server);
+            addr
+        };
+        let other = serve("/".to_string());
+        let same_host = serve("/next".to_string());
+        let other_host = serve(format!("http://{other}/next"));
+
+        let service = |addr: SocketAddr, policy: serde_json::Value| -> ServiceConfig {
+            serde_json::from_value(serde_json::json!({
+                "name": "test",
+                "register_service": null,
+                "addr": addr,
+                "redirect_policy": policy,
+            }))
+            .unwrap()
+        };
+        let status = |service: ServiceConfig| async move {
+            let req = HttpRequest::builder("test").build().unwrap();
+            forward_http_request(&service, None, None, &req, CancellationToken::new())
+                .await
+                .unwrap()
+                .status
+        };
+
+        assert_eq!(
+            status(service(same_host, "same_host_only".into())).await,
+            200
+        );
+        assert_eq!(
+            status(service(other_host, "same_host_only".into())).await,
+            302
+        );
+        assert_eq!(
+            status(service(other_host, serde_json::json!({ "limited": 1 }))).await,
+            200
+        );
+        assert_eq!(status(service(same_host, "none".into())).await, 302);
+
+        // The redirect target is checked against path lists of service as well.
+        let mut denied = service(same_host, "same_host_only".into());
+        denied.path_denylist = Some(vec!["/next".to_string()]);
+        assert_eq!(status(denied).await, 302);
+        let mut denied = service(other_host, serde_json::json!({ "limited": 1 }));
+        denied.path_denylist = Some(vec!["/next".to_string()]);
+        assert_eq!(status(denied).await, 302);
+    }
+
+    #[tokio::test]
+    async fn test_max_response_headers_bytes() {
+        let make_service = hyper::service::make_service_fn(|_| async {
+            Ok::<_, std::convert::Infallible>(hyper::service::service_fn(
+                |_: hyper::Request<hyper::Body>| async {
+                    let resp = hyper::Response::builder()
+                        .header("x-large", "a".repeat(1024))
+                        .body(hyper::Body::from("body"))
+                        .unwrap();
+                    Ok::<_, std::convert::Infallible>(resp)
+                },
+            ))
+        });
+        let server = hyper::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_service);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+
+        let mut service: ServiceConfig = serde_json::from_value(serde_json::json!({
+            "name": "test",
+            "register_service": null,
+            "addr": addr,
+        }))
+        .unwrap();
+        let req = HttpRequest::builder("test").build().unwrap();
+        let resp = forward_http_request(&service, None, None, &req, CancellationToken::new())
+            .await
+            .unwrap();
+        assert_eq!(resp.body, Some(bytes::Bytes::from_static(b"body")));
+
+        // The requester is answered with a bad gateway instead of waiting for its timeout.
+        service.max_response_headers_bytes = Some(1024);
+        let resp = forward_http_request(&service, None, None, &req, CancellationToken::new())
+            .await
+            .unwrap();
+        assert_eq!(resp.status, 502);
+
+        // Both names and values are counted.
+        let mut headers = http::HeaderMap::new();
+        headers.insert("x-large", "a".repeat(8).parse().unwrap());
+        assert!(response_headers(&headers, Some(15)).is_ok());
+        assert!(response_headers(&headers, Some(14)).is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_unix_socket_service() {
+        let path = std::env::temp_dir().join(format!("rings-{}.sock", uuid::Uuid::new_v4()));
+        let listener = tokio::net::UnixListener::bind(&path).unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let echo = hyper::service::service_fn(|req: hyper::Request<hyper::Body>| async {
+                    let head = format!("{} {} ", req.method(), req.uri());
+                    let body = hyper::body::to_bytes(req.into_body()).await?;
+                    let body = [head.as_bytes(), &body[..]].concat();
+                    Ok::<_, hyper::Error>(hyper::Response::new(hyper::Body::from(body)))
+                });
+                tokio::spawn(hyper::server::conn::Http::new().serve_connection(stream, echo));
+            }
+        });
+
+        let service: ServiceConfig = serde_json::from_value(serde_json::json!({
+            "name": "test",
+            "register_service": null,
+            "unix_socket": path,
+        }))
+        .unwrap();
+        let req = HttpRequest::builder("test")
+            .method("POST")
+            .path("/hello?a=1")
+            .body("ping")
+            .rid("rid")
+            .build()
+            .unwrap();
+        let resp = forward_http_request(&service, None, None, &req, CancellationToken::new())
+            .await
+            .unwrap();
+        assert_eq!(resp.status, 200);
+        assert_eq!(resp.rid, Some("rid".to_string()));
+        let echo = bytes::Bytes::from_static(b"POST /hello?a=1 ping");
+        assert_eq!(resp.body, Some(echo));
+
+        // The socket is gone.
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            forward_http_request(&service, None, None, &req, CancellationToken::new()).await,
+            Err(Error::HttpConnectError(_))
+        ));
+    }
+
+    /// Answers requests without a service, and records where they were sent to.
+    #[derive(Default)]
+    struct MockExecutor {
+        dead: Vec<SocketAddr>,
+        requests: Arc<std::sync::Mutex<Vec<(SocketAddr, HttpRequest)>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl HttpExecutor for MockExecutor {
+        async fn execute(
+            &self,
+            addr: SocketAddr,
+            req: &HttpRequest,
+            _body: Option<BodyReceiver>,
+            _cancel: CancellationToken,
+        ) -> Result<HttpResponse> {
+            if self.dead.contains(&addr) {
+                return Err(Error::HttpConnectError("refused".to_string()));
+            }
+            self.requests.lock().unwrap().push((addr, req.clone()));
+            Ok(HttpResponse {
+                status: 200,
+                headers: vec![],
+                body: Some(bytes::Bytes::from_static(b"mocked")),
+                rid: req.rid.clone(),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_http_executor() {
+        let dead: SocketAddr = "127.0.0.1:9".parse().unwrap();
+        let live: SocketAddr = "127.0.0.1:10".parse().unwrap();
+        let did = Did::from_str("0x11E807fcc88dD319270493fB2e822e388Fe36ab0").unwrap();
+        let service: ServiceConfig = serde_json::from_value(serde_json::json!({
+            "name": "test",
+            "register_service": null,
+            "upstreams": [{ "addr": dead }, { "addr": live }],
+            "forward_origin": true,
+        }))
+        .unwrap();
+        let executor = MockExecutor {
+            dead: vec![dead],
+            ..Default::default()
+        };
+        let requests = executor.requests.clone();
+        let provider = ServiceProvider::new(vec![service.clone()]).http_executor("Test", executor);
+
+        let executor = provider.executors.get("test").map(Arc::as_ref);
+        let balancer = provider.balancers.get("test").map(Arc::as_ref);
+        let req = HttpRequest::builder("test")
+            .path("/hello")
+            .rid("rid")
+            .build()
+            .unwrap();
+        let forwarded = with_origin(&service, &req, did);
+        for _ in 0..2 {
+            let resp = forward_http_request(
+                &service,
+                executor,
+                balancer,
+                &forwarded,
+                CancellationToken::new(),
+            )
+            .await
+            .unwrap();
+            assert_eq!(resp.status, 200);
+            assert_eq!(resp.rid, Some("rid".to_string()));
+            assert_eq!(resp.body, Some(bytes::Bytes::from_static(b"mocked")));
+        }
+
+        // Both are sent to the live upstream, with origin of the requester.
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        for (addr, req) in requests.iter() {
+            assert_eq!(*addr, live);
+            assert_eq!(req.path, "/hello");
+            assert!(req
+                .headers
+                .contains(&("X-Rings-Origin-Did".to_string(), did.to_string())));
+        }
+    }
+
+    #[test]
+    fn test_forward_origin() {
+        let did = Did::from_str("0x11E807fcc88dD319270493fB2e822e388Fe36ab0").unwrap();
+        let mut service: ServiceConfig = serde_json::from_value(serde_json::json!({
+            "name": "test",
+            "register_service": null,
+            "addr": "127.0.0.1:8080",
+        }))
+        .unwrap();
+        let req = HttpRequest::builder("test")
+            .header("x-rings-origin-did", "forged")
+            .header("X-Forwarded-For", "10.0.0.1")
+            .build()
+            .unwrap();
+        assert_eq!(with_origin(&service, &req, did).headers, req.headers);
+
+        service.forward_origin = true;
+        assert_eq!(with_origin(&service, &req, did).headers, vec![
+            ("X-Forwarded-For".to_string(), "10.0.0.1".to_string()),
+            ("X-Rings-Origin-Did".to_string(), did.to_string()),
+        ]);
+
+        service.forwarded_for_header = Some("x-forwarded-for".to_string());
+        assert_eq!(with_origin(&service, &req, did).headers, vec![
+            ("X-Rings-Origin-Did".to_string(), did.to_string()),
+            ("x-forwarded-for".to_string(), format!("10.0.0.1, {did}")),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_on_peer_disconnected() {
+        // A service accepting connections but never responding.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut streams = vec![];
+            while let Ok((stream, _)) = listener.accept().await {
+                streams.push(stream);
+            }
+        });
+        let service: ServiceConfig = serde_json::from_value(serde_json::json!({
+            "name": "test",
+            "register_service": null,
+            "addr": addr,
+        }))
+        .unwrap();
+        let provider = ServiceProvider::new(vec![service.clone()]);
+        let peer = Did::from_str("0x11E807fcc88dD319270493fB2e822e388Fe36ab0").unwrap();
+        let other = Did::from_str("0x999999cf1046e68e36E1aA2E0E07105eDDD1f08E").unwrap();
+        let cancel = provider.origin_cancel(peer);
+        let other_cancel = provider.origin_cancel(other);
+
+        let req = HttpRequest::builder("test").build().unwrap();
+        let executor = provider.executors.get("test").map(Arc::as_ref);
+        let forwarded = forward_http_request(&service, executor, None, &req, cancel.token());
+        let disconnected = async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            provider.on_peer_disconnected(peer);
+        };
+        let (resp, _) = tokio::time::timeout(Duration::from_secs(5), async {
+            tokio::join!(forwarded, disconnected)
+        })
+        .await
+        .unwrap();
+        assert!(matches!(resp, Err(Error::Cancelled)));
+        assert!(!other_cancel.token().is_cancelled());
+
+        // Requests after reconnection are not affected, nor by the requests before.
+        let reconnected = provider.origin_cancel(peer);
+        drop(cancel);
+        assert!(!reconnected.token().is_cancelled());
+        assert!(provider.origin_cancels.contains_key(&peer));
+        drop(reconnected);
+        assert!(!provider.origin_cancels.contains_key(&peer));
+    }
+
+    #[test]
+    fn test_origin_cancel_dropped_with_last_request() {
+        let provider = ServiceProvider::new(vec![]);
+        let peer = Did::from_str("0x11E807fcc88dD319270493fB2e822e388Fe36ab0").unwrap();
+
+        let first = provider.origin_cancel(peer);
+        let second = provider.origin_cancel(peer);
+        drop(first);
+        assert!(provider.origin_cancels.contains_key(&peer));
+        drop(second);
+        assert!(provider.origin_cancels.is_empty());
+    }
+
+    /// A WebSocket upstream, which closes each connection after `hold`.
+    async fn ws_upstream(hold: Duration) -> SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+                    tokio::time::sleep(hold).await;
+                    let _ = ws.close(None).await;
+                });
+            }
+        });
+        addr
+    }
+
+    /// A payload signed by peer, only its signer matters to WebSocket messages.
+    fn ws_payload(peer_sk: &SessionSk) -> MessagePayload {
+        let did = peer_sk.account_did();
+        MessagePayload::new_send(Message::custom(b"").unwrap(), peer_sk, did, did).unwrap()
+    }
+
+    async fn open_ws_tunnel(
+        service: &ServiceProvider,
+        provider: Arc<Provider>,
+        peer_sk: &SessionSk,
+    ) -> TunnelId {
+        let cid = uuid::Uuid::new_v4();
+        let open = WebSocketMessage::Open {
+            cid,
+            service: "ws".to_string(),
+            path: "/".to_string(),
+        };
+        service
+            .handle_websocket_message(provider, &ws_payload(peer_sk), &open)
+            .await
+            .unwrap();
+        cid
+    }
+
+    fn ws_service(addr: SocketAddr) -> ServiceConfig {
+        serde_json::from_value(serde_json::json!({
+            "name": "ws",
+            "register_service": null,
+            "addr": addr,
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_close_ws_tunnels_of_disconnected_peer() {
+        let addr = ws_upstream(Duration::from_secs(60)).await;
+        let service = ServiceProvider::new(vec![ws_service(addr)]);
+        let processor = Arc::new(prepare_processor().await);
+        let provider = Arc::new(Provider::from_processor(processor));
+        let peer_sk = SessionSk::new_with_seckey(&SecretKey::random()).unwrap();
+        let other_sk = SessionSk::new_with_seckey(&SecretKey::random()).unwrap();
+
+        let cid = open_ws_tunnel(&service, provider.clone(), &peer_sk).await;
+        let other_cid = open_ws_tunnel(&service, provider.clone(), &other_sk).await;
+        assert_eq!(service.ws_tunnels.len(), 2);
+
+        service.on_peer_disconnected(peer_sk.account_did());
+        assert!(!service.ws_tunnels.contains_key(&cid));
+        assert!(service.ws_tunnels.contains_key(&other_cid));
+    }
+
+    #[tokio::test]
+    async fn test_drop_ws_tunnels_closed_by_upstream() {
+        let addr = ws_upstream(Duration::ZERO).await;
+        let service = ServiceProvider::new(vec![ws_service(addr)]);
+        let processor = Arc::new(prepare_processor().await);
+        let provider = Arc::new(Provider::from_processor(processor));
+        let peer_sk = SessionSk::new_with_seckey(&SecretKey::random()).unwrap();
+
+        let cid = open_ws_tunnel(&service, provider.clone(), &peer_sk).await;
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !service.ws_tunnels.get(&cid).unwrap().is_closed() {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        // A frame to the closed tunnel drops it.
+        let frame = WebSocketMessage::Frame {
+            cid,
+            frame: WebSocketFrame::Text("hello".to_string()),
+        };
+        let sent = service
+            .handle_websocket_message(provider.clone(), &ws_payload(&peer_sk), &frame)
+            .await;
+        assert!(matches!(sent, Err(Error::TunnelNotFound)));
+        assert!(service.ws_tunnels.is_empty());
+
+        // Closed tunnels are dropped as well once another tunnel opens.
+        let closed = open_ws_tunnel(&service, provider.clone(), &peer_sk).await;
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !service.ws_tunnels.get(&closed).unwrap().is_closed() {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await
+        .unwrap();
+        let opened = open_ws_tunnel(&service, provider, &peer_sk).await;
+        assert!(!service.ws_tunnels.contains_key(&closed));
+        assert!(service.ws_tunnels.contains_key(&opened));
+    }
+
+    #[tokio::test]
+    async fn test_reject_ws_tunnel_to_denied_path() {
+        let addr = ws_upstream(Duration::from_secs(60)).await;
+        let mut config = ws_service(addr);
+        config.path_denylist = Some(vec!["/admin".to_string()]);
+        let service = ServiceProvider::new(vec![config]);
+        let processor = Arc::new(prepare_processor().await);
+        let provider = Arc::new(Provider::from_processor(processor.clone()));
+
+        // The peer is the node itself, so that the close message can be delivered.
+        let payload = ws_payload(&processor.session_sk());
+        let open = WebSocketMessage::Open {
+            cid: uuid::Uuid::new_v4(),
+            service: "ws".to_string(),
+            path: "/admin/keys".to_string(),
+        };
+        service
+            .handle_websocket_message(provider.clone(), &payload, &open)
+            .await
+            .unwrap();
+        assert!(service.ws_tunnels.is_empty());
+
+        let peer_sk = SessionSk::new_with_seckey(&SecretKey::random()).unwrap();
+        let cid = open_ws_tunnel(&service, provider, &peer_sk).await;
+        assert!(service.ws_tunnels.contains_key(&cid));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_pending_request() {
+        let provider = ServiceProvider::new(vec![]);
+        let (tx, rx) = oneshot::channel();
+        provider.pending_requests.insert("rid".to_string(), tx);
+        assert_eq!(provider.pending_requests(), vec!["rid".to_string()]);
+
+        assert!(provider.cancel_request("rid"));
+        assert!(matches!(rx.await, Ok(Err(Error::Cancelled))));
+        assert!(provider.pending_requests().is_empty());
+        assert!(!provider.cancel_request("rid"));
+    }
+
+    #[tokio::test]
+    async fn test_reject_duplicated_request_id() {
+        let service = ServiceProvider::new(vec![]);
+        let processor = Arc::new(prepare_processor().await);
+        let provider = Arc::new(Provider::from_processor(processor.clone()));
+        let rx = service.pending_request("rid").unwrap();
+
+        let req = HttpRequest::builder("test").rid("rid").build().unwrap();
+        let duplicated = service
+            .request_with_timeout(provider, processor.did(), req, Duration::from_secs(1))
+            .await;
+        assert!(matches!(duplicated, Err(Error::DuplicatedRequestId(rid)) if rid == "rid"));
+
+        // The response is still paired with the first request.
+        assert_eq!(service.pending_requests(), vec!["rid".to_string()]);
+        assert!(service.cancel_request("rid"));
+        assert!(matches!(rx.await, Ok(Err(Error::Cancelled))));
+    }
+
+    #[test]
+    fn test_max_concurrent_requests() {
+        let unlimited = ServiceProvider::new(vec![]);
+        assert!(matches!(unlimited.try_acquire_request_permit(), Some(None)));
+
+        let provider = ServiceProvider::new(vec![]).max_concurrent_requests(Some(1));
+        let permit = provider.try_acquire_request_permit().unwrap();
+        assert!(permit.is_some());
+        assert!(provider.try_acquire_request_permit().is_none());
+        drop(permit);
+        assert!(provider.try_acquire_request_permit().is_some());
+
+        let req = HttpRequest::builder("test").rid("rid").build().unwrap();
+        let resp = busy_response(&req);
+        assert_eq!(resp.status, 503);
+        assert_eq!(resp.rid, Some("rid".to_string()));
+        assert!(resp
+            .headers
+            .contains(&("retry-after".to_string(), "1".to_string())));
+    }
+
+    #[test]
+    fn test_allowed_origins() {
+        let allowed = Did::from_str("0x11E807fcc88dD319270493fB2e822e388Fe36ab0").unwrap();
+        let denied = Did::from_str("0x999999cf1046e68e36E1aA2E0E07105eDDD1f08E").unwrap();
+
+        let open = ServiceProvider::new(vec![]).allowed_origins(None);
+        assert!(open.is_origin_allowed(allowed));
+        assert!(open.is_origin_allowed(denied));
+
+        let provider = ServiceProvider::new(vec![]).allowed_origins(Some(vec![allowed]));
+        assert!(provider.is_origin_allowed(allowed));
+        assert!(!provider.is_origin_allowed(denied));
+    }
+
+    #[test]
+    fn test_reject_origin_over_rate_limit() {
+        let did = Did::from_str("0x11E807fcc88dD319270493fB2e822e388Fe36ab0").unwrap();
+        let provider = ServiceProvider::new(vec![]).rate_limit(Some(RateLimitConfig {
+            requests_per_second: 1.0,
+            burst: 1,
+        }));
+        let req = HttpRequest::builder("test").rid("rid").build().unwrap();
+        assert!(provider.reject_origin(did, &req).is_none());
+
+        let resp = provider.reject_origin(did, &req).unwrap();
+        assert_eq!(resp.status, 429);
+        assert_eq!(resp.rid, Some("rid".to_string()));
+        assert!(resp
+            .headers
+            .contains(&("retry-after".to_string(), "1".to_string())));
+    }
+
+    #[test]
+    fn test_path_allow_and_deny_list() {
+        let mut service: ServiceConfig = serde_json::from_value(serde_json::json!({
+            "name": "test",
+            "register_service": null,
+            "addr": "127.0.0.1:8080",
+        }))
+        .unwrap();
+        assert!(service.is_path_allowed("/admin"));
+
+        service.path_allowlist = Some(vec!["/api".to_string(), "/static/*.js".to_string()]);
+        assert!(service.is_path_allowed("api/users?id=1"));
+        assert!(service.is_path_allowed("/static/js/app.js"));
+        assert!(!service.is_path_allowed("/static/app.css"));
+        assert!(!service.is_path_allowed("/admin"));
+
+        service.path_denylist = Some(vec!["/api/internal*".to_string()]);
+        assert!(service.is_path_allowed("/api/users"));
+        assert!(!service.is_path_allowed("/api/internal/keys"));
+    }
+
+    #[test]
+    fn test_path_allow_and_deny_list_normalized() {
+        let mut service: ServiceConfig = serde_json::from_value(serde_json::json!({
+            "name": "test",
+            "register_service": null,
+            "addr": "127.0.0.1:8080",
+        }))
+        .unwrap();
+
+        // Dot segments can't climb out of an allowlisted prefix.
+        service.path_allowlist = Some(vec!["/api".to_string()]);
+        assert!(!service.is_path_allowed("/api/../admin"));
+        assert!(!service.is_path_allowed("/api/%2e%2e/admin"));
+        assert!(!service.is_path_allowed("/api/../../api/users"));
+        assert!(service.is_path_allowed("/api/./users/../items"));
+
+        // Prefixes match whole segments.
+        assert!(service.is_path_allowed("/api"));
+        assert!(service.is_path_allowed("/api/"));
+        assert!(!service.is_path_allowed("/apikeys"));
+
+        // Escaped paths are decoded before checking denylist.
+        service.path_allowlist = None;
+        service.path_denylist = Some(vec!["/admin".to_string()]);
+        assert!(!service.is_path_allowed("/%61dmin"));
+        assert!(!service.is_path_allowed("/%61dmin/keys"));
+        assert!(!service.is_path_allowed("//admin"));
+        assert!(service.is_path_allowed("/administrator"));
+        assert!(!service.is_path_allowed("/%ff"));
+    }
+}
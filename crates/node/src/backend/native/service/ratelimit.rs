@@ -0,0 +1,155 @@
+//! Token bucket rate limiting of http requests by origin Did.
+//!
+//! Each origin has a bucket holding at most `burst` tokens, refilled at `requests_per_second`.
+//! A request takes one token, or is rejected if there is none. Buckets of at most
+//! [RATE_LIMIT_MAX_ORIGINS] origins are kept, the least recently used one is evicted
+//! when it's full, so that many distinct Dids cannot exhaust the memory.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use rings_core::dht::Did;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::consts::RATE_LIMIT_MAX_ORIGINS;
+
+/// Rate limit of http requests from each origin,
+/// see `rate_limit` of [BackendConfig](crate::backend::native::BackendConfig).
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct RateLimitConfig {
+    /// requests allowed per second in long run
+    pub requests_per_second: f64,
+    /// requests allowed in a burst
+    pub burst: u32,
+}
+
+/// Rate limiter of http requests, keyed by origin Did.
+pub(crate) struct RateLimiter {
+    config: RateLimitConfig,
+    capacity: usize,
+    inner: Mutex<LimiterInner>,
+}
+
+#[derive(Default)]
+struct LimiterInner {
+    buckets: HashMap<Did, Bucket>,
+    tick: u64,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+    last_used: u64,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            capacity: RATE_LIMIT_MAX_ORIGINS,
+            inner: Mutex::new(LimiterInner::default()),
+        }
+    }
+
+    /// Take a token of origin. Returns the time to wait for the next token if there is none.
+    pub fn check(&self, origin: Did) -> Result<(), Duration> {
+        self.check_at(origin, Instant::now())
+    }
+
+    fn check_at(&self, origin: Did, now: Instant) -> Result<(), Duration> {
+        let burst = self.config.burst as f64;
+        let rate = self.config.requests_per_second;
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.tick += 1;
+        let tick = inner.tick;
+
+        if !inner.buckets.contains_key(&origin) && inner.buckets.len() >= self.capacity {
+            let lru = inner
+                .buckets
+                .iter()
+                .min_by_key(|(_, b)| b.last_used)
+                .map(|(k, _)| *k);
+            if let Some(lru) = lru {
+                inner.buckets.remove(&lru);
+            }
+        }
+
+        let bucket = inner.buckets.entry(origin).or_insert(Bucket {
+            tokens: burst,
+            updated_at: now,
+            last_used: tick,
+        });
+        let elapsed = now
+            .saturating_duration_since(bucket.updated_at)
+            .as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(burst);
+        bucket.updated_at = now;
+        bucket.last_used = tick;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+        // Never refilled if rate is zero.
+        let wait = Duration::try_from_secs_f64((1.0 - bucket.tokens) / rate);
+        Err(wait.unwrap_or(Duration::MAX))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn test_rate_limit_by_origin() {
+        let a = Did::from_str("0x11E807fcc88dD319270493fB2e822e388Fe36ab0").unwrap();
+        let b = Did::from_str("0x999999cf1046e68e36E1aA2E0E07105eDDD1f08E").unwrap();
+        let limiter = RateLimiter::new(RateLimitConfig {
+            requests_per_second: 2.0,
+            burst: 2,
+        });
+        let now = Instant::now();
+
+        assert!(limiter.check_at(a, now).is_ok());
+        assert!(limiter.check_at(a, now).is_ok());
+        let wait = limiter.check_at(a, now).unwrap_err();
+        assert_eq!(wait, Duration::from_millis(500));
+        // Other origins have their own buckets.
+        assert!(limiter.check_at(b, now).is_ok());
+
+        // Refilled over time, but never above burst.
+        assert!(limiter.check_at(a, now + wait).is_ok());
+        let later = now + Duration::from_secs(10);
+        assert!(limiter.check_at(a, later).is_ok());
+        assert!(limiter.check_at(a, later).is_ok());
+        assert!(limiter.check_at(a, later).is_err());
+    }
+
+    #[test]
+    fn test_evict_least_recently_used_origin() {
+        let a = Did::from_str("0x11E807fcc88dD319270493fB2e822e388Fe36ab0").unwrap();
+        let b = Did::from_str("0x999999cf1046e68e36E1aA2E0E07105eDDD1f08E").unwrap();
+        let c = Did::from_str("0x0000000000000000000000000000000000000001").unwrap();
+        let limiter = RateLimiter {
+            capacity: 2,
+            ..RateLimiter::new(RateLimitConfig {
+                requests_per_second: 0.0,
+                burst: 1,
+            })
+        };
+        let now = Instant::now();
+
+        assert!(limiter.check_at(a, now).is_ok());
+        assert!(limiter.check_at(b, now).is_ok());
+        assert!(limiter.check_at(a, now).is_err());
+        // b is evicted for c, and gets a full bucket again.
+        assert!(limiter.check_at(c, now).is_ok());
+        assert_eq!(limiter.inner.lock().unwrap().buckets.len(), 2);
+        assert!(limiter.check_at(b, now).is_ok());
+    }
+}
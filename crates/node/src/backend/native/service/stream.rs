@@ -0,0 +1,253 @@
+//! Streaming bodies of http requests, sent by the requester in chunks.
+//!
+//! Chunks of a body are numbered from zero by `seq`, and may arrive out of order when relayed
+//! by different paths. A chunk ahead of the next expected one is held until the gap is filled,
+//! at most [REQUEST_STREAM_REORDER_WINDOW] chunks ahead, so the upload is passed on to the
+//! service as it arrives instead of being buffered as a whole. Chunks in order are passed on
+//! through a channel of [REQUEST_STREAM_BUFFER] chunks, and pushing waits when it's full, so a
+//! slow service slows down the receiving of body.
+//!
+//! Chunks may also overtake the head of their request, see [EarlyChunks].
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::io;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use bytes::Bytes;
+use futures::channel::mpsc;
+use futures::SinkExt;
+use rings_core::dht::Did;
+
+use crate::consts::REQUEST_STREAM_BUFFER;
+use crate::consts::REQUEST_STREAM_EARLY_CHUNKS_TTL_SECS;
+use crate::consts::REQUEST_STREAM_MAX_EARLY;
+use crate::consts::REQUEST_STREAM_REORDER_WINDOW;
+
+/// Receiving half of a streaming body, fed to the http client as request body.
+pub type BodyReceiver = mpsc::Receiver<io::Result<Bytes>>;
+
+/// Why a chunk can't be accepted. The stream is aborted in either case.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum StreamDefeat {
+    /// The body exceeds `max_request_bytes` of service.
+    TooLarge,
+    /// The chunk is too far ahead of the next expected one, or a chunk follows the last one.
+    OutOfWindow,
+}
+
+/// Sending half of a streaming body, reassembling chunks in order.
+pub(crate) struct BodyStream {
+    tx: mpsc::Sender<io::Result<Bytes>>,
+    next_seq: u64,
+    fin_seq: Option<u64>,
+    ahead: BTreeMap<u64, Bytes>,
+    received: usize,
+    max_bytes: Option<usize>,
+}
+
+impl BodyStream {
+    /// Create a stream and its receiver. The body is limited to `max_bytes` if provided.
+    pub fn new(max_bytes: Option<usize>) -> (Self, BodyReceiver) {
+        let (tx, rx) = mpsc::channel(REQUEST_STREAM_BUFFER);
+        let stream = Self {
+            tx,
+            next_seq: 0,
+            fin_seq: None,
+            ahead: BTreeMap::new(),
+            received: 0,
+            max_bytes,
+        };
+        (stream, rx)
+    }
+
+    /// Accept chunk `seq` of body, `fin` marks the last one. Returns true if the body is
+    /// complete, then the stream should be dropped to end the body.
+    /// On error, the receiver gets an error so that the request to service fails.
+    /// It waits until the receiver has room for the chunks in order.
+    pub async fn push(&mut self, seq: u64, data: Bytes, fin: bool) -> Result<bool, StreamDefeat> {
+        let out_of_window = seq < self.next_seq
+            || seq >= self.next_seq + REQUEST_STREAM_REORDER_WINDOW
+            || self.fin_seq.map_or(false, |last| seq > last);
+        if out_of_window {
+            return Err(self.abort(StreamDefeat::OutOfWindow).await);
+        }
+
+        self.received += data.len();
+        if self.max_bytes.map_or(false, |max| self.received > max) {
+            return Err(self.abort(StreamDefeat::TooLarge).await);
+        }
+
+        if fin {
+            self.fin_seq = Some(seq);
+        }
+        self.ahead.insert(seq, data);
+        while let Some(data) = self.ahead.remove(&self.next_seq) {
+            // The request may have failed already, the rest of body is discarded then.
+            let _ = self.tx.send(Ok(data)).await;
+            self.next_seq += 1;
+        }
+
+        Ok(self.fin_seq.map_or(false, |last| self.next_seq > last))
+    }
+
+    async fn abort(&mut self, defeat: StreamDefeat) -> StreamDefeat {
+        let reason = format!("request body stream aborted: {defeat:?}");
+        let err = io::Error::new(io::ErrorKind::InvalidData, reason);
+        let _ = self.tx.send(Err(err)).await;
+        self.tx.close_channel();
+        self.ahead.clear();
+        defeat
+    }
+}
+
+/// A chunk of body, see [BodyStream::push].
+pub(crate) type Chunk = (u64, Bytes, bool);
+
+/// Chunks received before the head of their request, which they may overtake when relayed by
+/// different paths. They are held for [REQUEST_STREAM_EARLY_CHUNKS_TTL_SECS] seconds, at most
+/// [REQUEST_STREAM_REORDER_WINDOW] chunks of each of [REQUEST_STREAM_MAX_EARLY] requests.
+pub(crate) struct EarlyChunks {
+    inner: Mutex<HashMap<(Did, String), (Instant, Vec<Chunk>)>>,
+    ttl: Duration,
+}
+
+impl Default for EarlyChunks {
+    fn default() -> Self {
+        Self {
+            inner: Mutex::new(HashMap::new()),
+            ttl: Duration::from_secs(REQUEST_STREAM_EARLY_CHUNKS_TTL_SECS),
+        }
+    }
+}
+
+impl EarlyChunks {
+    /// Hold chunk of request `rid` from `origin` until its head is received.
+    /// Returns false if the chunk is dropped since too many are held.
+    pub fn hold(&self, origin: Did, rid: &str, chunk: Chunk) -> bool {
+        let Ok(mut inner) = self.inner.lock() else {
+            return false;
+        };
+        let now = Instant::now();
+        inner.retain(|_, (since, _)| now.duration_since(*since) < self.ttl);
+
+        let key = (origin, rid.to_string());
+        if !inner.contains_key(&key) && inner.len() >= REQUEST_STREAM_MAX_EARLY {
+            return false;
+        }
+        let (_, chunks) = inner.entry(key).or_insert_with(|| (now, vec![]));
+        if chunks.len() as u64 >= REQUEST_STREAM_REORDER_WINDOW {
+            return false;
+        }
+        chunks.push(chunk);
+        true
+    }
+
+    /// Take the chunks held for request `rid` from `origin`, which are not expired.
+    pub fn take(&self, origin: Did, rid: &str) -> Vec<Chunk> {
+        let Ok(mut inner) = self.inner.lock() else {
+            return vec![];
+        };
+        match inner.remove(&(origin, rid.to_string())) {
+            Some((since, chunks)) if since.elapsed() < self.ttl => chunks,
+            _ => vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+
+    use super::*;
+
+    async fn collect(rx: BodyReceiver) -> Vec<io::Result<Bytes>> {
+        rx.collect().await
+    }
+
+    #[tokio::test]
+    async fn test_reorder_chunks() {
+        let (mut stream, rx) = BodyStream::new(None);
+        assert_eq!(stream.push(1, Bytes::from("b"), false).await, Ok(false));
+        assert_eq!(stream.push(2, Bytes::from("c"), true).await, Ok(false));
+        assert_eq!(stream.push(0, Bytes::from("a"), false).await, Ok(true));
+        drop(stream);
+
+        let body: Vec<Bytes> = collect(rx).await.into_iter().map(|r| r.unwrap()).collect();
+        assert_eq!(body, vec!["a", "b", "c"]);
+    }
+
+    #[tokio::test]
+    async fn test_abort_oversized_body() {
+        let (mut stream, rx) = BodyStream::new(Some(4));
+        assert_eq!(stream.push(0, Bytes::from("abc"), false).await, Ok(false));
+        assert_eq!(
+            stream.push(1, Bytes::from("de"), true).await,
+            Err(StreamDefeat::TooLarge)
+        );
+
+        let body = collect(rx).await;
+        assert_eq!(body.len(), 2);
+        assert!(body[1].is_err());
+    }
+
+    #[tokio::test]
+    async fn test_abort_out_of_window() {
+        let (mut stream, _rx) = BodyStream::new(None);
+        assert_eq!(
+            stream
+                .push(REQUEST_STREAM_REORDER_WINDOW, Bytes::new(), false)
+                .await,
+            Err(StreamDefeat::OutOfWindow)
+        );
+
+        let (mut stream, _rx) = BodyStream::new(None);
+        assert_eq!(stream.push(1, Bytes::new(), true).await, Ok(false));
+        assert_eq!(
+            stream.push(2, Bytes::new(), false).await,
+            Err(StreamDefeat::OutOfWindow)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_backpressure() {
+        let (mut stream, mut rx) = BodyStream::new(None);
+        // The channel has a slot reserved for the sender besides the buffer.
+        let full = REQUEST_STREAM_BUFFER as u64 + 1;
+        for seq in 0..full {
+            assert_eq!(stream.push(seq, Bytes::from("a"), false).await, Ok(false));
+        }
+
+        // The receiver is full, pushing waits until it takes a chunk.
+        let seq = full;
+        let mut push = Box::pin(stream.push(seq, Bytes::from("b"), true));
+        assert!(futures::poll!(&mut push).is_pending());
+        assert_eq!(rx.next().await.unwrap().unwrap(), "a");
+        assert_eq!(push.await, Ok(true));
+    }
+
+    #[test]
+    fn test_early_chunks() {
+        let early = EarlyChunks::default();
+        let origin = Did::from(1u32);
+        assert!(early.hold(origin, "a", (1, Bytes::from("b"), true)));
+        assert!(early.hold(origin, "a", (0, Bytes::from("a"), false)));
+        assert!(early.take(Did::from(2u32), "a").is_empty());
+        assert_eq!(early.take(origin, "a").len(), 2);
+        assert!(early.take(origin, "a").is_empty());
+
+        for seq in 0..REQUEST_STREAM_REORDER_WINDOW {
+            assert!(early.hold(origin, "b", (seq, Bytes::new(), false)));
+        }
+        assert!(!early.hold(origin, "b", (0, Bytes::new(), false)));
+
+        let expired = EarlyChunks {
+            ttl: Duration::ZERO,
+            ..Default::default()
+        };
+        assert!(expired.hold(origin, "a", (0, Bytes::new(), true)));
+        assert!(expired.take(origin, "a").is_empty());
+    }
+}
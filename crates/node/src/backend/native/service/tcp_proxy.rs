@@ -23,6 +23,7 @@ use crate::provider::Provider;
 /// Abstract Tcp Tunnel
 pub struct Tunnel {
     tid: TunnelId,
+    buffer: usize,
     remote_stream_tx: Option<mpsc::Sender<Bytes>>,
     listener_cancel_token: Option<CancellationToken>,
     listener: Option<tokio::task::JoinHandle<()>>,
@@ -56,10 +57,12 @@ impl Drop for Tunnel {
 }
 
 impl Tunnel {
-    /// Create a new tunnel with a given tunnel Id
-    pub fn new(tid: TunnelId) -> Self {
+    /// Create a new tunnel with a given tunnel Id, buffering at most `buffer` packages
+    /// not yet written to local stream.
+    pub fn new(tid: TunnelId, buffer: usize) -> Self {
         Self {
             tid,
+            buffer,
             remote_stream_tx: None,
             listener: None,
             listener_cancel_token: None,
@@ -87,7 +90,7 @@ impl Tunnel {
             return;
         }
         let provider = provider.clone();
-        let mut listener = TunnelListener::new(self.tid, local_stream, peer_did).await;
+        let mut listener = TunnelListener::new(self.tid, local_stream, peer_did, self.buffer).await;
         let listener_cancel_token = listener.cancel_token();
         let remote_stream_tx = listener.remote_stream_tx.clone();
         let listener_handler =
@@ -101,8 +104,8 @@ impl Tunnel {
 
 impl TunnelListener {
     /// Create a new listener instance with TcpStream, tunnel id, and did of a target peer
-    async fn new(tid: TunnelId, local_stream: TcpStream, peer_did: Did) -> Self {
-        let (remote_stream_tx, remote_stream_rx) = mpsc::channel(1024);
+    async fn new(tid: TunnelId, local_stream: TcpStream, peer_did: Did, buffer: usize) -> Self {
+        let (remote_stream_tx, remote_stream_rx) = mpsc::channel(buffer);
         Self {
             tid,
             local_stream,
@@ -0,0 +1,132 @@
+//! Propagation of W3C trace context through proxied http requests.
+//! See <https://www.w3.org/TR/trace-context/>.
+
+use crate::backend::types::HttpRequest;
+
+const TRACEPARENT: &str = "traceparent";
+const TRACESTATE: &str = "tracestate";
+
+/// Trace context of a proxied http request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    /// Id of the whole trace, 32 hex digits.
+    pub trace_id: String,
+    /// Id of the span of this node, 16 hex digits. It's the parent of the upstream service.
+    pub span_id: String,
+    /// Id of the span of the requester, if the request carries a trace context.
+    pub parent_id: Option<String>,
+    /// Trace flags, 2 hex digits.
+    pub flags: String,
+}
+
+impl TraceContext {
+    /// Continue the trace of `traceparent` header, or start a new one if it's absent or invalid.
+    pub fn from_headers(headers: &[(String, String)]) -> Self {
+        let parent = headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(TRACEPARENT))
+            .and_then(|(_, v)| parse_traceparent(v));
+        match parent {
+            Some((trace_id, parent_id, flags)) => Self {
+                trace_id,
+                span_id: random_hex(16),
+                parent_id: Some(parent_id),
+                flags,
+            },
+            None => Self {
+                trace_id: random_hex(32),
+                span_id: random_hex(16),
+                parent_id: None,
+                flags: "01".to_string(),
+            },
+        }
+    }
+
+    /// Value of `traceparent` header sent to upstream.
+    pub fn traceparent(&self) -> String {
+        format!("00-{}-{}-{}", self.trace_id, self.span_id, self.flags)
+    }
+}
+
+/// Set `traceparent` header of request to the span of this node, and returns the context.
+/// The `tracestate` header is kept as is, unless the trace is restarted, in which case it's dropped.
+pub fn propagate(req: &mut HttpRequest) -> TraceContext {
+    let ctx = TraceContext::from_headers(&req.headers);
+    req.headers.retain(|(k, _)| {
+        !k.eq_ignore_ascii_case(TRACEPARENT)
+            && (ctx.parent_id.is_some() || !k.eq_ignore_ascii_case(TRACESTATE))
+    });
+    req.headers
+        .push((TRACEPARENT.to_string(), ctx.traceparent()));
+    ctx
+}
+
+/// Parse `traceparent` into trace id, parent id and flags.
+fn parse_traceparent(value: &str) -> Option<(String, String, String)> {
+    let parts: Vec<&str> = value.trim().split('-').collect();
+    let [version, trace_id, parent_id, flags] = parts[..] else {
+        return None;
+    };
+    let valid = is_hex(version, 2)
+        && version != "ff"
+        && is_hex(trace_id, 32)
+        && is_hex(parent_id, 16)
+        && is_hex(flags, 2)
+        && trace_id.bytes().any(|b| b != b'0')
+        && parent_id.bytes().any(|b| b != b'0');
+    valid.then(|| {
+        (
+            trace_id.to_string(),
+            parent_id.to_string(),
+            flags.to_string(),
+        )
+    })
+}
+
+fn is_hex(s: &str, len: usize) -> bool {
+    s.len() == len && s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+fn random_hex(len: usize) -> String {
+    let mut hex = uuid::Uuid::new_v4().to_simple().to_string();
+    hex.truncate(len);
+    hex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_propagate_traceparent() {
+        let traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let mut req = HttpRequest::builder("test")
+            .header("TraceParent", traceparent)
+            .header("tracestate", "vendor=value")
+            .build()
+            .unwrap();
+        let ctx = propagate(&mut req);
+        assert_eq!(ctx.trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(ctx.parent_id.as_deref(), Some("00f067aa0ba902b7"));
+        assert_ne!(ctx.span_id, "00f067aa0ba902b7");
+        assert_eq!(req.headers, vec![
+            ("tracestate".to_string(), "vendor=value".to_string()),
+            ("traceparent".to_string(), ctx.traceparent()),
+        ]);
+
+        // Restart an invalid trace, and drop its state.
+        let zero_trace_id = "00-00000000000000000000000000000000-00f067aa0ba902b7-01";
+        let mut req = HttpRequest::builder("test")
+            .header("traceparent", zero_trace_id)
+            .header("tracestate", "vendor=value")
+            .build()
+            .unwrap();
+        let ctx = propagate(&mut req);
+        assert!(ctx.parent_id.is_none());
+        assert_eq!(ctx.trace_id.len(), 32);
+        assert_eq!(ctx.span_id.len(), 16);
+        let header = ("traceparent".to_string(), ctx.traceparent());
+        assert_eq!(req.headers, vec![header]);
+        assert!(parse_traceparent(&ctx.traceparent()).is_some());
+    }
+}
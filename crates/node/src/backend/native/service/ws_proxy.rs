@@ -0,0 +1,184 @@
+#![warn(missing_docs)]
+//! Module ws_proxy provide implementation of WebSocket based services
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::SinkExt;
+use futures::StreamExt;
+use rings_core::dht::Did;
+use rings_rpc::method::Method;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::MaybeTlsStream;
+use tokio_tungstenite::WebSocketStream;
+use tokio_util::sync::CancellationToken;
+
+use crate::backend::types::BackendMessage;
+use crate::backend::types::TunnelId;
+use crate::backend::types::WebSocketFrame;
+use crate::backend::types::WebSocketMessage;
+use crate::error::Error;
+use crate::error::Result;
+use crate::provider::Provider;
+
+type UpstreamStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// WebSocket Tunnel, pumps frames between a remote peer and a local `ws://` upstream
+pub struct WsTunnel {
+    cid: TunnelId,
+    peer_did: Did,
+    upstream_tx: mpsc::Sender<WebSocketFrame>,
+    cancel_token: CancellationToken,
+    listener: Option<tokio::task::JoinHandle<()>>,
+}
+
+impl Drop for WsTunnel {
+    fn drop(&mut self) {
+        self.cancel_token.cancel();
+
+        if let Some(listener) = self.listener.take() {
+            tokio::spawn(async move {
+                tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
+                listener.abort();
+            });
+        }
+
+        tracing::info!("WsTunnel {} dropped", self.cid);
+    }
+}
+
+impl WsTunnel {
+    /// Connect to the upstream WebSocket and spawn a task pumping frames in both directions.
+    /// At most `buffer` frames not yet sent to upstream are buffered.
+    /// The task ends when either side closes.
+    pub async fn open(
+        provider: Arc<Provider>,
+        addr: SocketAddr,
+        path: &str,
+        cid: TunnelId,
+        peer_did: Did,
+        request_timeout_s: u64,
+        buffer: usize,
+    ) -> Result<Self> {
+        let url = format!("ws://{}/{}", addr, path.trim_start_matches('/'));
+        tracing::info!("Open WebSocket upstream: {:?}", url);
+        let (upstream, _) = timeout(
+            Duration::from_secs(request_timeout_s),
+            tokio_tungstenite::connect_async(url),
+        )
+        .await
+        .map_err(|_| Error::Timeout)?
+        .map_err(|e| Error::WebSocketError(e.to_string()))?;
+
+        let (upstream_tx, upstream_rx) = mpsc::channel(buffer);
+        let cancel_token = CancellationToken::new();
+        let listener = tokio::spawn(pump(
+            provider,
+            upstream,
+            upstream_rx,
+            cid,
+            peer_did,
+            cancel_token.clone(),
+        ));
+
+        Ok(Self {
+            cid,
+            peer_did,
+            upstream_tx,
+            cancel_token,
+            listener: Some(listener),
+        })
+    }
+
+    /// The peer which opened the tunnel
+    pub fn peer_did(&self) -> Did {
+        self.peer_did
+    }
+
+    /// Whether frames are no longer pumped, since either side closed
+    pub fn is_closed(&self) -> bool {
+        self.cancel_token.is_cancelled()
+    }
+
+    /// Send frame to upstream via channel
+    pub async fn send(&self, frame: WebSocketFrame) {
+        if self.upstream_tx.send(frame).await.is_err() {
+            tracing::error!("WsTunnel {} upstream is closed", self.cid);
+        }
+    }
+}
+
+async fn pump(
+    provider: Arc<Provider>,
+    upstream: UpstreamStream,
+    mut upstream_rx: mpsc::Receiver<WebSocketFrame>,
+    cid: TunnelId,
+    peer_did: Did,
+    cancel_token: CancellationToken,
+) {
+    let (mut upstream_write, mut upstream_read) = upstream.split();
+
+    // None if closed by peer, otherwise the reason reported to peer.
+    let reason = loop {
+        tokio::select! {
+            _ = cancel_token.cancelled() => break None,
+            frame = upstream_rx.recv() => {
+                let Some(frame) = frame else {
+                    break None;
+                };
+                let msg = match frame {
+                    WebSocketFrame::Text(text) => Message::Text(text),
+                    WebSocketFrame::Binary(data) => Message::Binary(data.to_vec()),
+                };
+                if let Err(e) = upstream_write.send(msg).await {
+                    tracing::error!("Write to upstream WebSocket failed: {e:?}");
+                    break Some(e.to_string());
+                }
+            }
+            msg = upstream_read.next() => {
+                let frame = match msg {
+                    Some(Ok(Message::Text(text))) => WebSocketFrame::Text(text),
+                    Some(Ok(Message::Binary(data))) => WebSocketFrame::Binary(data.into()),
+                    Some(Ok(Message::Close(_))) | None => break Some("upstream closed".to_string()),
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => break Some(e.to_string()),
+                };
+                let msg = WebSocketMessage::Frame { cid, frame };
+                if let Err(e) = send_to_peer(&provider, peer_did, msg).await {
+                    tracing::error!("Send WebSocket frame failed: {e:?}");
+                    break Some(e.to_string());
+                }
+            }
+        }
+    };
+    // Mark the tunnel closed, so that it's dropped by the service provider.
+    cancel_token.cancel();
+
+    let _ = upstream_write.close().await;
+
+    if let Some(reason) = reason {
+        tracing::info!("WsTunnel {cid} closed: {reason}");
+        let msg = WebSocketMessage::Close {
+            cid,
+            reason: Some(reason),
+        };
+        if let Err(e) = send_to_peer(&provider, peer_did, msg).await {
+            tracing::error!("Send WebSocket close failed: {e:?}");
+        }
+    }
+}
+
+/// Send a [WebSocketMessage] to peer as [BackendMessage]
+pub(crate) async fn send_to_peer(
+    provider: &Provider,
+    peer_did: Did,
+    msg: WebSocketMessage,
+) -> Result<()> {
+    let backend_message: BackendMessage = msg.into();
+    let params = backend_message.into_send_backend_message_request(peer_did)?;
+    provider.request(Method::SendBackendMessage, params).await?;
+    Ok(())
+}
@@ -4,12 +4,23 @@
 use std::io::ErrorKind as IOErrorKind;
 use std::sync::Arc;
 
+use bincode::Options;
 use bytes::Bytes;
+use rings_core::dht::Did;
+use rings_core::message::decode_gzip_data;
 use rings_core::message::MessagePayload;
+use rings_core::utils::get_epoch_ms;
 use rings_rpc::protos::rings_node::SendBackendMessageRequest;
 use serde::Deserialize;
 use serde::Serialize;
 
+use crate::consts::BACKEND_MESSAGE_BINARY_PREFIX;
+use crate::consts::BACKEND_MESSAGE_BINARY_THRESHOLD;
+#[cfg(feature = "cbor")]
+use crate::consts::BACKEND_MESSAGE_CBOR_TAG;
+use crate::consts::BACKEND_MESSAGE_JSON_TAG;
+use crate::consts::BACKEND_MESSAGE_MAX_SIZE;
+use crate::consts::RINGS_CONTENT_ENCODING;
 use crate::error::Error;
 use crate::provider::Provider;
 
@@ -19,7 +30,69 @@ pub mod snark;
 /// TunnelId type, use uuid.
 pub type TunnelId = uuid::Uuid;
 
+/// Encoding of [BackendMessage] carried by custom messages between nodes.
+/// Except for bincode, the encoded bytes are prefixed by a one byte tag. A tag never starts
+/// bincode bytes, which begin with the variant index, so untagged messages of earlier
+/// versions are still decoded as bincode.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WireFormat {
+    /// Untagged bincode, which every node can decode.
+    #[default]
+    Bincode,
+    /// Json, tagged by [BACKEND_MESSAGE_JSON_TAG].
+    Json,
+    /// Cbor, tagged by [crate::consts::BACKEND_MESSAGE_CBOR_TAG]. It's much smaller than
+    /// json for header-heavy http messages.
+    #[cfg(feature = "cbor")]
+    Cbor,
+}
+
+impl WireFormat {
+    /// Formats this node can decode, advertised to peers in handshake metadata.
+    pub fn supported() -> Vec<Self> {
+        vec![
+            Self::Bincode,
+            Self::Json,
+            #[cfg(feature = "cbor")]
+            Self::Cbor,
+        ]
+    }
+
+    /// Name of the format in handshake metadata.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Bincode => "bincode",
+            Self::Json => "json",
+            #[cfg(feature = "cbor")]
+            Self::Cbor => "cbor",
+        }
+    }
+
+    /// Comma separated names of [WireFormat::supported].
+    pub fn supported_names() -> String {
+        Self::supported()
+            .iter()
+            .map(|f| f.name())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// Pick the format to send to a peer advertising `peer_formats`, comma separated names.
+    /// It's the most compact one supported by both sides, falling back to bincode.
+    /// Json is never picked since it's larger than bincode.
+    pub fn negotiate(peer_formats: &str) -> Self {
+        let peer_formats: Vec<&str> = peer_formats.split(',').map(str::trim).collect();
+        Self::supported()
+            .into_iter()
+            .filter(|f| *f != Self::Json)
+            .rev()
+            .find(|f| peer_formats.contains(&f.name()))
+            .unwrap_or_default()
+    }
+}
+
 /// BackendMessage struct for handling CustomMessage.
+/// The variant index is the tag on wire, new variants should be appended to the end.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[non_exhaustive]
 pub enum BackendMessage {
@@ -32,6 +105,55 @@ pub enum BackendMessage {
     /// SNARK with curve pallas and vesta
     #[cfg(feature = "snark")]
     SNARKTaskMessage(snark::SNARKTaskMessage),
+    /// Opaque application payload, routed to the handler registered for `topic`
+    Bytes {
+        /// Topic used to pick the handler
+        topic: String,
+        /// Payload
+        data: Vec<u8>,
+    },
+    /// Liveness probe, the receiving backend answers it with a [BackendMessage::Pong]
+    Ping {
+        /// Random number used to pair the pong with its ping
+        nonce: u64,
+        /// Timestamp of sender in milliseconds
+        sent_at_ms: u64,
+    },
+    /// Answer of [BackendMessage::Ping], carrying back the nonce and timestamp of the ping
+    Pong {
+        /// Nonce of the answered ping
+        nonce: u64,
+        /// Timestamp of the answered ping in milliseconds
+        sent_at_ms: u64,
+    },
+    /// WebSocket proxied to a `ws://` upstream of a service
+    WebSocket(WebSocketMessage),
+    /// Piece of a file sent by [Processor::send_file](crate::processor::Processor::send_file)
+    FileChunk {
+        /// Transfer Id
+        tid: String,
+        /// Name of file
+        name: String,
+        /// Offset of data in file
+        offset: u64,
+        /// Size of the whole file
+        total: u64,
+        /// Data
+        data: Bytes,
+    },
+    /// Message of a protocol added by downstream crates, routed to the handler registered for
+    /// `kind`, see [Backend::with_protocol_handler](crate::backend::Backend::with_protocol_handler)
+    Protocol {
+        /// Kind of protocol used to pick the handler
+        kind: String,
+        /// Payload
+        data: Vec<u8>,
+    },
+    /// Answer of [BackendMessage::Protocol] of a kind that the receiving backend has no handler for
+    ProtocolUnsupported {
+        /// Kind of the unsupported protocol
+        kind: String,
+    },
 }
 
 /// ServiceMessage
@@ -62,6 +184,71 @@ pub enum ServiceMessage {
     HttpRequest(HttpRequest),
     /// Http Response
     HttpResponse(HttpResponse),
+    /// Head of a http request whose body follows in [ServiceMessage::HttpRequestChunk]s,
+    /// `body` of the head is ignored and `rid` is required to pair the chunks
+    HttpRequestStream(HttpRequest),
+    /// Chunk of the body of a streaming http request
+    HttpRequestChunk {
+        /// Request Id
+        rid: String,
+        /// Sequence of chunk, starting from zero
+        seq: u64,
+        /// Chunk data
+        data: Bytes,
+        /// Whether it's the last chunk
+        fin: bool,
+    },
+}
+
+impl ServiceMessage {
+    /// Decompress the body of [ServiceMessage::HttpResponse], see [HttpResponse::decompress].
+    /// Other messages are returned as they are.
+    pub fn decompress(self) -> Result<Self, Error> {
+        match self {
+            ServiceMessage::HttpResponse(resp) => {
+                Ok(ServiceMessage::HttpResponse(resp.decompress()?))
+            }
+            m => Ok(m),
+        }
+    }
+}
+
+/// WebSocketMessage, frames of a WebSocket proxied through the backend.
+/// Both sides key the connection by its [TunnelId].
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub enum WebSocketMessage {
+    /// Open a WebSocket to the upstream of a service
+    Open {
+        /// Connection Id
+        cid: TunnelId,
+        /// service name
+        service: String,
+        /// Path of upstream WebSocket
+        path: String,
+    },
+    /// Data frame, sent in both directions
+    Frame {
+        /// Connection Id
+        cid: TunnelId,
+        /// Frame
+        frame: WebSocketFrame,
+    },
+    /// Close the WebSocket, sent by either side
+    Close {
+        /// Connection Id
+        cid: TunnelId,
+        /// The reason of close
+        reason: Option<String>,
+    },
+}
+
+/// Data frame of a proxied WebSocket
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub enum WebSocketFrame {
+    /// Text frame
+    Text(String),
+    /// Binary frame
+    Binary(Bytes),
 }
 
 /// A list specifying general categories of Tunnel error like [std::io::ErrorKind].
@@ -104,6 +291,139 @@ pub struct HttpRequest {
     pub body: Option<Vec<u8>>,
 }
 
+impl HttpRequest {
+    /// Create a [HttpRequestBuilder] of request to service.
+    pub fn builder(service: impl ToString) -> HttpRequestBuilder {
+        HttpRequestBuilder {
+            service: service.to_string(),
+            method: http::Method::GET.to_string(),
+            path: "/".to_string(),
+            headers: vec![],
+            body: None,
+            rid: None,
+        }
+    }
+
+    /// Methods which can be proxied to a service.
+    pub fn supported_methods() -> &'static [http::Method] {
+        &[
+            http::Method::GET,
+            http::Method::HEAD,
+            http::Method::POST,
+            http::Method::PUT,
+            http::Method::DELETE,
+            http::Method::PATCH,
+            http::Method::OPTIONS,
+        ]
+    }
+}
+
+/// Builder of [HttpRequest], validating fields on [HttpRequestBuilder::build].
+#[derive(Debug, Clone)]
+pub struct HttpRequestBuilder {
+    service: String,
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,
+    body: Option<Vec<u8>>,
+    rid: Option<String>,
+}
+
+impl HttpRequestBuilder {
+    /// Set method, default is `GET`.
+    pub fn method(mut self, method: impl ToString) -> Self {
+        self.method = method.to_string();
+        self
+    }
+
+    /// Set path with optional query, default is `/`.
+    pub fn path(mut self, path: impl ToString) -> Self {
+        self.path = path.to_string();
+        self
+    }
+
+    /// Append a header.
+    pub fn header(mut self, key: impl ToString, value: impl ToString) -> Self {
+        self.headers.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Set body.
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = Some(body.into());
+        self
+    }
+
+    /// Set request id, which is carried back by the response.
+    pub fn rid(mut self, rid: impl ToString) -> Self {
+        self.rid = Some(rid.to_string());
+        self
+    }
+
+    /// Validate and build the [HttpRequest].
+    /// The method is uppercased and the path is normalized by [normalize_path].
+    pub fn build(self) -> Result<HttpRequest, Error> {
+        if self.service.is_empty() {
+            return Err(Error::InvalidService);
+        }
+
+        let method = http::Method::from_bytes(self.method.to_ascii_uppercase().as_bytes())
+            .map_err(|_| Error::InvalidMethod)?;
+        if !HttpRequest::supported_methods().contains(&method) {
+            return Err(Error::InvalidMethod);
+        }
+
+        for (k, v) in self.headers.iter() {
+            http::HeaderName::from_bytes(k.as_bytes()).map_err(|_| Error::InvalidHeaders)?;
+            http::HeaderValue::from_str(v).map_err(|_| Error::InvalidHeaders)?;
+        }
+
+        Ok(HttpRequest {
+            rid: self.rid,
+            service: self.service,
+            method: method.to_string(),
+            path: normalize_path(&self.path)?,
+            headers: self.headers,
+            body: self.body,
+        })
+    }
+}
+
+/// Normalize path of request: ensure it's absolute, merge repeated slashes and
+/// resolve `.` and `..` segments. The query is kept as is.
+pub fn normalize_path(path: &str) -> Result<String, Error> {
+    let path = path.trim();
+    if path.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err(Error::HttpRequestError(format!("invalid path {path:?}")));
+    }
+    let (path, query) = match path.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (path, None),
+    };
+
+    let mut segments: Vec<&str> = vec![];
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            s => segments.push(s),
+        }
+    }
+    let trailing_slash = !segments.is_empty() && path.ends_with('/');
+
+    let mut normalized = format!("/{}", segments.join("/"));
+    if trailing_slash {
+        normalized.push('/');
+    }
+    if let Some(query) = query {
+        normalized.push('?');
+        normalized.push_str(query);
+    }
+    Ok(normalized)
+}
+
 /// HttpResponse
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct HttpResponse {
@@ -117,6 +437,26 @@ pub struct HttpResponse {
     pub body: Option<Bytes>,
 }
 
+impl HttpResponse {
+    /// Decompress the body if it was compressed by the responding node,
+    /// the [RINGS_CONTENT_ENCODING] marker is removed after that.
+    pub fn decompress(mut self) -> Result<Self, Error> {
+        let Some(pos) = self
+            .headers
+            .iter()
+            .position(|(k, v)| k.eq_ignore_ascii_case(RINGS_CONTENT_ENCODING) && v == "gzip")
+        else {
+            return Ok(self);
+        };
+
+        if let Some(body) = self.body.as_ref() {
+            self.body = Some(decode_gzip_data(body)?);
+        }
+        self.headers.remove(pos);
+        Ok(self)
+    }
+}
+
 /// MessageHandler trait
 #[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
 #[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
@@ -128,6 +468,20 @@ pub trait MessageHandler<T> {
         ctx: &MessagePayload,
         data: &T,
     ) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Invoked when the connection to peer is closed, so that work for it can be dropped.
+    async fn on_peer_disconnected(&self, _peer: Did) {}
+
+    /// Request ids of http requests sent by this handler and still waiting for response.
+    fn pending_requests(&self) -> Vec<String> {
+        vec![]
+    }
+
+    /// Stop waiting for the response of request `rid`.
+    /// Returns false if the request is not pending on this handler.
+    fn cancel_request(&self, _rid: &str) -> bool {
+        false
+    }
 }
 
 impl From<ServiceMessage> for BackendMessage {
@@ -136,6 +490,12 @@ impl From<ServiceMessage> for BackendMessage {
     }
 }
 
+impl From<WebSocketMessage> for BackendMessage {
+    fn from(val: WebSocketMessage) -> Self {
+        BackendMessage::WebSocket(val)
+    }
+}
+
 impl From<IOErrorKind> for TunnelDefeat {
     fn from(kind: IOErrorKind) -> TunnelDefeat {
         match kind {
@@ -186,6 +546,28 @@ macro_rules! impl_message_handler_for_tuple {
                 )+
                 Ok(())
             }
+
+            async fn on_peer_disconnected(&self, peer: Did) {
+                $(
+                    self.$n.on_peer_disconnected(peer).await;
+                )+
+            }
+
+            fn pending_requests(&self) -> Vec<String> {
+                let mut rids = vec![];
+                $(
+                    rids.extend(self.$n.pending_requests());
+                )+
+                rids
+            }
+
+            fn cancel_request(&self, rid: &str) -> bool {
+                let mut cancelled = false;
+                $(
+                    cancelled |= self.$n.cancel_request(rid);
+                )+
+                cancelled
+            }
         }
     };
 
@@ -205,6 +587,28 @@ macro_rules! impl_message_handler_for_tuple {
                 )+
                 Ok(())
             }
+
+            async fn on_peer_disconnected(&self, peer: Did) {
+                $(
+                    self.$n.on_peer_disconnected(peer).await;
+                )+
+            }
+
+            fn pending_requests(&self) -> Vec<String> {
+                let mut rids = vec![];
+                $(
+                    rids.extend(self.$n.pending_requests());
+                )+
+                rids
+            }
+
+            fn cancel_request(&self, rid: &str) -> bool {
+                let mut cancelled = false;
+                $(
+                    cancelled |= self.$n.cancel_request(rid);
+                )+
+                cancelled
+            }
         }
     };
 }
@@ -228,14 +632,351 @@ impl_message_handler_for_tuple!(T1, T2, T3, T4; 0, 1, 2, 3; wasm);
 impl_message_handler_for_tuple!(T1, T2, T3, T4, T5; 0, 1, 2, 3, 4; wasm);
 
 impl BackendMessage {
-    /// Convert to SendBackendMessageRequest
+    /// Create a [BackendMessage::Ping] stamped with current time.
+    pub fn ping(nonce: u64) -> Self {
+        BackendMessage::Ping {
+            nonce,
+            sent_at_ms: get_epoch_ms() as u64,
+        }
+    }
+
+    /// Round-trip time in milliseconds, if the message is a [BackendMessage::Pong].
+    pub fn rtt_ms(&self) -> Option<u64> {
+        match self {
+            BackendMessage::Pong { sent_at_ms, .. } => {
+                Some((get_epoch_ms() as u64).saturating_sub(*sent_at_ms))
+            }
+            _ => None,
+        }
+    }
+
+    /// Size of binary payload carried by the message, such as body of http request.
+    pub fn binary_len(&self) -> usize {
+        match self {
+            BackendMessage::Extension(data) => data.len(),
+            BackendMessage::Bytes { data, .. } => data.len(),
+            BackendMessage::Protocol { data, .. } => data.len(),
+            BackendMessage::ServiceMessage(ServiceMessage::TcpPackage { body, .. }) => body.len(),
+            BackendMessage::ServiceMessage(ServiceMessage::HttpRequest(req)) => {
+                req.body.as_ref().map(|b| b.len()).unwrap_or(0)
+            }
+            BackendMessage::ServiceMessage(ServiceMessage::HttpResponse(resp)) => {
+                resp.body.as_ref().map(|b| b.len()).unwrap_or(0)
+            }
+            BackendMessage::ServiceMessage(ServiceMessage::HttpRequestChunk { data, .. }) => {
+                data.len()
+            }
+            BackendMessage::FileChunk { data, .. } => data.len(),
+            BackendMessage::WebSocket(WebSocketMessage::Frame {
+                frame: WebSocketFrame::Binary(data),
+                ..
+            }) => data.len(),
+            _ => 0,
+        }
+    }
+
+    /// Convert to SendBackendMessageRequest.
+    /// The data is json, unless the message carries binary payload of
+    /// [BACKEND_MESSAGE_BINARY_THRESHOLD] bytes or larger, which json bloats several times.
+    /// Such a message is encoded by [BackendMessage::to_binary_wire] instead.
     pub fn into_send_backend_message_request(
         self,
         destination_did: impl ToString,
     ) -> Result<SendBackendMessageRequest, Error> {
+        let data = if self.binary_len() >= BACKEND_MESSAGE_BINARY_THRESHOLD {
+            self.to_binary_wire()?
+        } else {
+            serde_json::to_string(&self)?
+        };
         Ok(SendBackendMessageRequest {
             destination_did: destination_did.to_string(),
-            data: serde_json::to_string(&self)?,
+            data,
         })
     }
+
+    /// Encode message by bincode and base64, prefixed by [BACKEND_MESSAGE_BINARY_PREFIX].
+    pub fn to_binary_wire(&self) -> Result<String, Error> {
+        let bytes = bincode::serialize(self).map_err(|_| Error::EncodeError)?;
+        Ok(format!(
+            "{}{}",
+            BACKEND_MESSAGE_BINARY_PREFIX,
+            base64::encode(bytes)
+        ))
+    }
+
+    /// Decode data of SendBackendMessageRequest, which is either json or
+    /// encoded by [BackendMessage::to_binary_wire].
+    pub fn from_wire(data: &str) -> Result<Self, Error> {
+        match data.strip_prefix(BACKEND_MESSAGE_BINARY_PREFIX) {
+            Some(encoded) => {
+                let bytes = base64::decode(encoded).map_err(|_| Error::DecodeError)?;
+                bincode::deserialize(&bytes).map_err(|_| Error::DecodeError)
+            }
+            None => Ok(serde_json::from_str(data)?),
+        }
+    }
+
+    /// Encode message to be carried by a custom message, see [WireFormat].
+    pub fn to_bytes(&self, format: WireFormat) -> Result<Vec<u8>, Error> {
+        match format {
+            WireFormat::Bincode => bincode::serialize(self).map_err(|_| Error::EncodeError),
+            WireFormat::Json => {
+                let mut bytes = vec![BACKEND_MESSAGE_JSON_TAG];
+                serde_json::to_writer(&mut bytes, self).map_err(|_| Error::EncodeError)?;
+                Ok(bytes)
+            }
+            #[cfg(feature = "cbor")]
+            WireFormat::Cbor => {
+                let mut bytes = vec![BACKEND_MESSAGE_CBOR_TAG];
+                ciborium::ser::into_writer(self, &mut bytes).map_err(|_| Error::EncodeError)?;
+                Ok(bytes)
+            }
+        }
+    }
+
+    /// Decode message carried by a custom message, detecting its [WireFormat] by the tag.
+    /// Messages over [BACKEND_MESSAGE_MAX_SIZE] are rejected before decoding, and the
+    /// error of a malformed message carries the reason, so that protocol mismatches show up.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() > BACKEND_MESSAGE_MAX_SIZE {
+            return Err(Error::BackendMessageTooLarge {
+                size: bytes.len(),
+                limit: BACKEND_MESSAGE_MAX_SIZE,
+            });
+        }
+        let malformed = |e: &dyn std::fmt::Display| Error::MalformedBackendMessage(e.to_string());
+        match bytes.split_first() {
+            Some((&BACKEND_MESSAGE_JSON_TAG, data)) => {
+                serde_json::from_slice(data).map_err(|e| malformed(&e))
+            }
+            #[cfg(feature = "cbor")]
+            Some((&BACKEND_MESSAGE_CBOR_TAG, data)) => {
+                ciborium::de::from_reader(data).map_err(|e| malformed(&e))
+            }
+            // Same options as `bincode::deserialize`, but a forged length can't allocate
+            // more than the limit.
+            _ => bincode::DefaultOptions::new()
+                .with_fixint_encoding()
+                .allow_trailing_bytes()
+                .with_limit(BACKEND_MESSAGE_MAX_SIZE as u64)
+                .deserialize(bytes)
+                .map_err(|e| malformed(&e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_http_request_builder() {
+        let req = HttpRequest::builder("ipfs")
+            .method("post")
+            .path("api//v0/./add/../cat?arg=a/../b")
+            .header("Content-Type", "text/plain")
+            .body("hello")
+            .rid("1")
+            .build()
+            .unwrap();
+        assert_eq!(req.service, "ipfs");
+        assert_eq!(req.method, "POST");
+        assert_eq!(req.path, "/api/v0/cat?arg=a/../b");
+        let header = ("Content-Type".to_string(), "text/plain".to_string());
+        assert_eq!(req.headers, vec![header]);
+        assert_eq!(req.body, Some(b"hello".to_vec()));
+        assert_eq!(req.rid, Some("1".to_string()));
+
+        let req = HttpRequest::builder("ipfs").build().unwrap();
+        assert_eq!(req.method, "GET");
+        assert_eq!(req.path, "/");
+
+        assert!(matches!(
+            HttpRequest::builder("ipfs").method("CONNECT").build(),
+            Err(Error::InvalidMethod)
+        ));
+        assert!(matches!(
+            HttpRequest::builder("ipfs")
+                .header("bad header", "v")
+                .build(),
+            Err(Error::InvalidHeaders)
+        ));
+        assert!(matches!(
+            HttpRequest::builder("ipfs").path("/a b").build(),
+            Err(Error::HttpRequestError(_))
+        ));
+        assert!(matches!(
+            HttpRequest::builder("").build(),
+            Err(Error::InvalidService)
+        ));
+    }
+
+    #[test]
+    fn test_backend_message_wire_format() {
+        let small = BackendMessage::PlainText("hello".to_string());
+        let req = small.into_send_backend_message_request("did").unwrap();
+        assert!(req.data.starts_with('{'));
+        assert!(matches!(
+            BackendMessage::from_wire(&req.data).unwrap(),
+            BackendMessage::PlainText(text) if text == "hello"
+        ));
+
+        // A 1MB image body
+        let body: Vec<u8> = (0..1024 * 1024u32)
+            .map(|i| (i.wrapping_mul(2654435761) >> 24) as u8)
+            .collect();
+        let image = BackendMessage::from(ServiceMessage::HttpResponse(HttpResponse {
+            rid: None,
+            status: 200,
+            headers: vec![("content-type".to_string(), "image/png".to_string())],
+            body: Some(body.clone().into()),
+        }));
+        let json_len = serde_json::to_string(&image).unwrap().len();
+        let req = image.into_send_backend_message_request("did").unwrap();
+        assert!(req.data.starts_with(BACKEND_MESSAGE_BINARY_PREFIX));
+        // Json takes about 3.6 bytes per byte, base64 takes 4 bytes per 3.
+        assert!(json_len > 3 * body.len());
+        assert!(req.data.len() < body.len() * 4 / 3 + 1024);
+
+        let BackendMessage::ServiceMessage(ServiceMessage::HttpResponse(resp)) =
+            BackendMessage::from_wire(&req.data).unwrap()
+        else {
+            panic!("unexpected message");
+        };
+        assert_eq!(resp.body.unwrap().to_vec(), body);
+        assert!(BackendMessage::from_wire("bin:not base64").is_err());
+    }
+
+    #[test]
+    fn test_service_message_decompress() {
+        let body = Bytes::from("hello ".repeat(100));
+        let resp = HttpResponse {
+            rid: Some("1".to_string()),
+            status: 200,
+            headers: vec![
+                ("content-type".to_string(), "text/plain".to_string()),
+                (RINGS_CONTENT_ENCODING.to_string(), "gzip".to_string()),
+            ],
+            body: Some(rings_core::message::encode_data_gzip(&body, 9).unwrap()),
+        };
+        let ServiceMessage::HttpResponse(resp) =
+            ServiceMessage::HttpResponse(resp).decompress().unwrap()
+        else {
+            panic!("unexpected message");
+        };
+        assert_eq!(resp.body, Some(body));
+        let header = ("content-type".to_string(), "text/plain".to_string());
+        assert_eq!(resp.headers, vec![header]);
+
+        // A response not compressed and other messages are kept as they are.
+        let plain = HttpResponse {
+            rid: None,
+            status: 200,
+            headers: vec![],
+            body: Some(Bytes::from("hello")),
+        };
+        let ServiceMessage::HttpResponse(resp) =
+            ServiceMessage::HttpResponse(plain).decompress().unwrap()
+        else {
+            panic!("unexpected message");
+        };
+        assert_eq!(resp.body, Some(Bytes::from("hello")));
+        let req = HttpRequest::builder("ipfs").build().unwrap();
+        assert!(matches!(
+            ServiceMessage::HttpRequest(req).decompress().unwrap(),
+            ServiceMessage::HttpRequest(_)
+        ));
+    }
+
+    #[test]
+    fn test_backend_message_to_bytes() {
+        let req = HttpRequest::builder("ipfs")
+            .header("accept", "*/*")
+            .header("user-agent", "rings")
+            .rid("1")
+            .build()
+            .unwrap();
+        let msg = BackendMessage::from(ServiceMessage::HttpRequest(req));
+
+        // Untagged bincode of earlier versions is still decoded.
+        let legacy = bincode::serialize(&msg).unwrap();
+        assert_eq!(msg.to_bytes(WireFormat::Bincode).unwrap(), legacy);
+
+        for format in WireFormat::supported() {
+            let bytes = msg.to_bytes(format).unwrap();
+            let BackendMessage::ServiceMessage(ServiceMessage::HttpRequest(decoded)) =
+                BackendMessage::from_bytes(&bytes).unwrap()
+            else {
+                panic!("unexpected message of {format:?}");
+            };
+            assert_eq!(decoded.rid, Some("1".to_string()));
+            assert_eq!(decoded.headers.len(), 2);
+        }
+        assert_eq!(
+            msg.to_bytes(WireFormat::Json).unwrap()[0],
+            BACKEND_MESSAGE_JSON_TAG
+        );
+        assert!(matches!(
+            BackendMessage::from_bytes(&[BACKEND_MESSAGE_JSON_TAG, b'{']),
+            Err(Error::MalformedBackendMessage(_))
+        ));
+        // Plain text of a forged length, which is not allocated.
+        let forged = [2, 0, 0, 0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f];
+        assert!(matches!(
+            BackendMessage::from_bytes(&forged),
+            Err(Error::MalformedBackendMessage(_))
+        ));
+        assert!(matches!(
+            BackendMessage::from_bytes(&vec![0; BACKEND_MESSAGE_MAX_SIZE + 1]),
+            Err(Error::BackendMessageTooLarge { .. })
+        ));
+
+        assert_eq!(WireFormat::negotiate(""), WireFormat::Bincode);
+        assert_eq!(WireFormat::negotiate("bincode,json"), WireFormat::Bincode);
+        #[cfg(feature = "cbor")]
+        {
+            assert_eq!(WireFormat::negotiate("bincode, cbor"), WireFormat::Cbor);
+            let cbor = msg.to_bytes(WireFormat::Cbor).unwrap();
+            assert!(cbor.len() < msg.to_bytes(WireFormat::Json).unwrap().len());
+        }
+        #[cfg(not(feature = "cbor"))]
+        assert_eq!(WireFormat::negotiate("bincode,cbor"), WireFormat::Bincode);
+    }
+
+    #[test]
+    fn test_protocol_message() {
+        let msg = BackendMessage::Protocol {
+            kind: "chat/1".to_string(),
+            data: b"hello".to_vec(),
+        };
+        for format in WireFormat::supported() {
+            let bytes = msg.to_bytes(format).unwrap();
+            let BackendMessage::Protocol { kind, data } =
+                BackendMessage::from_bytes(&bytes).unwrap()
+            else {
+                panic!("unexpected message of {format:?}");
+            };
+            assert_eq!(kind, "chat/1");
+            assert_eq!(data, b"hello");
+        }
+
+        let unsupported = BackendMessage::ProtocolUnsupported {
+            kind: "chat/1".to_string(),
+        };
+        let req = unsupported
+            .into_send_backend_message_request("did")
+            .unwrap();
+        assert!(matches!(
+            BackendMessage::from_wire(&req.data).unwrap(),
+            BackendMessage::ProtocolUnsupported { kind } if kind == "chat/1"
+        ));
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(normalize_path("").unwrap(), "/");
+        assert_eq!(normalize_path("a/b/").unwrap(), "/a/b/");
+        assert_eq!(normalize_path("/../../a").unwrap(), "/a");
+        assert_eq!(normalize_path("//?q=1").unwrap(), "/?q=1");
+    }
 }
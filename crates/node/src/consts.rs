@@ -11,3 +11,65 @@ pub const MSG_SEND_FAILED_LIMIT: i64 = 10;
 pub const MSG_RECV_FAILED_LIMIT: i64 = 10;
 /// Timeout for proxied TCP connections
 pub const TCP_SERVER_TIMEOUT: u64 = 30;
+/// Header marking a proxied http response body compressed by rings
+pub const RINGS_CONTENT_ENCODING: &str = "rings-content-encoding";
+/// Responses smaller than this are not compressed by default
+pub const DEFAULT_COMPRESS_MIN_BYTES: usize = 1024;
+/// Seconds to cache a proxied http response without `max-age` by default
+pub const DEFAULT_CACHE_TTL: u64 = 60;
+/// Rounds of dialing bootstrap nodes before giving up
+pub const BOOTSTRAP_MAX_ATTEMPTS: u32 = 5;
+/// Backoff before the second round of dialing bootstrap nodes, doubled each round
+pub const BOOTSTRAP_INITIAL_BACKOFF_MS: u64 = 1000;
+/// Frames buffered for a proxied TCP or WebSocket tunnel by default
+pub const DEFAULT_TUNNEL_BUFFER: usize = 1024;
+/// Marker of backend message data encoded by bincode and base64 instead of json
+pub const BACKEND_MESSAGE_BINARY_PREFIX: &str = "bin:";
+/// Backend messages carrying binary payload of this size or larger are encoded by bincode
+pub const BACKEND_MESSAGE_BINARY_THRESHOLD: usize = 1024;
+/// Tag of backend message bytes encoded by json, see [crate::backend::types::WireFormat]
+pub const BACKEND_MESSAGE_JSON_TAG: u8 = 0xf1;
+/// Tag of backend message bytes encoded by cbor, see [crate::backend::types::WireFormat]
+pub const BACKEND_MESSAGE_CBOR_TAG: u8 = 0xf2;
+/// Backend messages larger than this are dropped without decoding
+pub const BACKEND_MESSAGE_MAX_SIZE: usize = TRANSPORT_MAX_SIZE;
+/// Handshake metadata key listing the backend message formats a node can decode
+pub const BACKEND_FORMATS_METADATA_KEY: &str = "backend_formats";
+/// Seconds in `Retry-After` of the response to a http request shed when the node is busy
+pub const BUSY_RETRY_AFTER_SECS: u64 = 1;
+/// Consecutive connection failures before an upstream of a service is skipped
+pub const UPSTREAM_MAX_FAILURES: u32 = 3;
+/// Max redirects of a proxied http request followed to the same host
+pub const SAME_HOST_MAX_REDIRECTS: usize = 10;
+/// Seconds between retries of an unhealthy upstream
+pub const UPSTREAM_RETRY_INTERVAL_SECS: u64 = 10;
+/// Default seconds between health checks of upstreams
+pub const DEFAULT_HEALTH_CHECK_INTERVAL_SECS: u64 = 10;
+/// Seconds to wait for the response of a health check
+pub const HEALTH_CHECK_TIMEOUT_SECS: u64 = 5;
+/// Default header carrying the Did of the peer a proxied http request comes from
+pub const DEFAULT_ORIGIN_DID_HEADER: &str = "X-Rings-Origin-Did";
+/// Prefix of the DHT topic holding subscriptions of a pubsub topic
+pub const PUBSUB_TOPIC_PREFIX: &str = "pubsub:";
+/// Max count of origins tracked by the rate limiter of http requests
+pub const RATE_LIMIT_MAX_ORIGINS: usize = 4096;
+/// Max count of chunks a streaming http request body may be received ahead of the next one
+pub const REQUEST_STREAM_REORDER_WINDOW: u64 = 64;
+/// Max count of chunks of a streaming http request body waiting to be taken by the service
+pub const REQUEST_STREAM_BUFFER: usize = 16;
+/// Seconds chunks of a streaming http request body are held before the head of request
+pub const REQUEST_STREAM_EARLY_CHUNKS_TTL_SECS: u64 = 5;
+/// Max count of streaming http requests whose chunks are held before the head of request
+pub const REQUEST_STREAM_MAX_EARLY: usize = 256;
+/// Size of pieces a file is sent in by `sendFile` rpc
+pub const FILE_TRANSFER_CHUNK_SIZE: usize = 64 * 1024;
+/// Max count of file transfers tracked for status queries, or received concurrently
+pub const MAX_TRACKED_TRANSFERS: usize = 256;
+/// Seconds after which a file being received without new chunks is dropped
+/// Max size of a file received from other nodes, if `max_receive_bytes` is not configured
+pub const DEFAULT_MAX_RECEIVE_BYTES: u64 = 1024 * 1024 * 1024;
+pub const FILE_RECEIVE_IDLE_TIMEOUT_SECS: u64 = 300;
+/// Milliseconds to wait for a virtual node fetched from the node responsible for it
+pub const STORAGE_FETCH_TIMEOUT_MS: u128 = 3000;
+/// Milliseconds between checks of local cache while waiting for a fetched virtual node
+pub const STORAGE_FETCH_POLL_INTERVAL_MS: u64 = 50;
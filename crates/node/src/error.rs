@@ -38,6 +38,10 @@ pub enum Error {
     DecodeError = 300,
     #[error("Encode error.")]
     EncodeError = 301,
+    #[error("Backend message of {size} bytes exceeds limit of {limit} bytes.")]
+    BackendMessageTooLarge { size: usize, limit: usize } = 302,
+    #[error("Malformed backend message: {0}.")]
+    MalformedBackendMessage(String) = 303,
     #[error("WASM compile error: {0}")]
     WasmCompileError(String) = 400,
     #[error("BackendMessage RwLock Error")]
@@ -68,6 +72,8 @@ pub enum Error {
     VNodeError(rings_core::error::Error) = 603,
     #[error("service register action error: {0}")]
     ServiceRegisterError(rings_core::error::Error) = 604,
+    #[error("Failed to join network via bootstrap nodes")]
+    BootstrapFailed = 605,
     #[error("JsError: {0}")]
     JsError(String) = 700,
     #[error("Invalid message")]
@@ -90,6 +96,20 @@ pub enum Error {
     Swarm(rings_core::error::Error) = 808,
     #[error("Invalid logging level: {0}")]
     InvalidLoggingLevel(String) = 809,
+    #[error("Request timeout")]
+    Timeout = 810,
+    #[error("Failed to connect http upstream: {0}")]
+    HttpConnectError(String) = 811,
+    #[error("TLS error of http upstream: {0}")]
+    HttpTlsError(String) = 812,
+    #[error("Http upstream responded status {0}")]
+    HttpUpstreamStatus(u16) = 813,
+    #[error("Request cancelled")]
+    Cancelled = 814,
+    #[error("Request id is in use: {0}")]
+    DuplicatedRequestId(String) = 815,
+    #[error("Http upstream responded headers of over {0} bytes")]
+    ResponseHeadersTooLarge(usize) = 816,
     #[error("Create File Error: {0}")]
     CreateFileError(String) = 900,
     #[error("Open File Error: {0}")]
@@ -100,6 +120,14 @@ pub enum Error {
     HomeDirError = 903,
     #[error("Cannot find parent directory")]
     ParentDirError = 904,
+    #[error("File transfer not found: {0}")]
+    TransferNotFound(String) = 905,
+    #[error("File is not allowed to send: {0}")]
+    FileNotAllowed(String) = 906,
+    #[error("Too many file transfers in progress")]
+    TooManyTransfers = 907,
+    #[error("Invalid file chunk: {0}")]
+    InvalidFileChunk(String) = 908,
     #[error("Serde json error: {0}")]
     SerdeJsonError(#[from] serde_json::Error) = 1000,
     #[error("Serde yaml error: {0}")]
@@ -122,6 +150,8 @@ pub enum Error {
     TunnelNotFound = 1303,
     #[error("Tunnel error: {0:?}")]
     TunnelError(TunnelDefeat) = 1304,
+    #[error("WebSocket error: {0}")]
+    WebSocketError(String) = 1305,
     #[cfg(feature = "snark")]
     #[error("Snark error: {0}")]
     RingsSNARKError(#[from] rings_snark::error::Error) = 1400,
@@ -141,6 +171,8 @@ pub enum Error {
     FailedToLoadFF() = 1406,
     #[error("Extend Backend Error {0}")]
     BackendError(String) = 1501,
+    #[error("Invalid backend config: {0}")]
+    InvalidBackendConfig(String) = 1502,
 }
 
 impl Error {
@@ -184,6 +216,40 @@ impl From<rings_rpc::error::Error> for Error {
     }
 }
 
+#[cfg(feature = "node")]
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            return Error::Timeout;
+        }
+        if let Some(status) = e.status() {
+            return Error::HttpUpstreamStatus(status.as_u16());
+        }
+        if e.is_connect() {
+            if is_tls_error(&e) {
+                return Error::HttpTlsError(e.to_string());
+            }
+            return Error::HttpConnectError(e.to_string());
+        }
+        Error::HttpRequestError(e.to_string())
+    }
+}
+
+/// Rustls reports handshake failures as [std::io::Error] of [std::io::ErrorKind::InvalidData].
+#[cfg(feature = "node")]
+fn is_tls_error(e: &(dyn std::error::Error + 'static)) -> bool {
+    let mut source = e.source();
+    while let Some(err) = source {
+        if let Some(io_err) = err.downcast_ref::<std::io::Error>() {
+            if io_err.kind() == std::io::ErrorKind::InvalidData {
+                return true;
+            }
+        }
+        source = err.source();
+    }
+    false
+}
+
 #[cfg(feature = "browser")]
 impl From<Error> for wasm_bindgen::JsValue {
     fn from(err: Error) -> Self {
@@ -199,4 +265,22 @@ mod tests {
         let err = Error::RemoteRpcError("Test".to_string());
         assert_eq!(err.code(), 100);
     }
+
+    #[cfg(feature = "node")]
+    #[tokio::test]
+    async fn test_reqwest_error_kind() {
+        // Nothing is listening on port 1.
+        let err: Error = reqwest::get("http://127.0.0.1:1").await.unwrap_err().into();
+        assert!(matches!(err, Error::HttpConnectError(_)));
+        assert_eq!(err.code(), 811);
+
+        let err: Error = reqwest::Client::new()
+            .get("http://127.0.0.1:1")
+            .timeout(std::time::Duration::from_nanos(1))
+            .send()
+            .await
+            .unwrap_err()
+            .into();
+        assert!(matches!(err, Error::Timeout));
+    }
 }
@@ -10,8 +10,10 @@ pub mod native;
 pub mod prelude;
 pub mod processor;
 pub mod provider;
+pub mod pubsub;
 mod rpc_impl;
 pub mod seed;
 #[cfg(test)]
 mod tests;
+pub mod transfer;
 pub mod util;
@@ -138,15 +138,18 @@ impl Client {
     }
 
     /// Sends a custom message to the specified peer.
-    pub async fn send_custom_message(&self, did: &str, data: &str) -> Output<()> {
-        self.client
+    /// If `encrypt` is set, the message can only be decrypted by the peer.
+    pub async fn send_custom_message(&self, did: &str, data: &str, encrypt: bool) -> Output<()> {
+        let resp = self
+            .client
             .send_custom_message(&SendCustomMessageRequest {
                 destination_did: did.to_string(),
                 data: data.to_string(),
+                encrypt,
             })
             .await
             .map_err(|e| anyhow::anyhow!("{}", e))?;
-        ClientOutput::ok("Done.".into(), ())
+        ClientOutput::ok(format!("Done. tx_id: {}", resp.tx_id), ())
     }
 
     /// Sends an HTTP request message to the specified peer.
@@ -161,14 +164,17 @@ impl Client {
         body: Option<Vec<u8>>,
         rid: Option<String>,
     ) -> Output<()> {
-        let req = HttpRequest {
-            service: service.to_string(),
-            method: method.to_string(),
-            path: path.to_string(),
-            headers,
-            body,
-            rid,
-        };
+        let mut builder = HttpRequest::builder(service).method(method).path(path);
+        for (k, v) in headers {
+            builder = builder.header(k, v);
+        }
+        if let Some(body) = body {
+            builder = builder.body(body);
+        }
+        if let Some(rid) = rid {
+            builder = builder.rid(rid);
+        }
+        let req = builder.build().map_err(|e| anyhow::anyhow!("{}", e))?;
 
         let backend_msg = BackendMessage::from(ServiceMessage::HttpRequest(req));
         let rpc_req = backend_msg
@@ -2,16 +2,23 @@ use std::env;
 use std::fs;
 use std::io;
 use std::path::PathBuf;
+use std::time::Duration;
 
 use serde::Deserialize;
 use serde::Serialize;
 
 use crate::backend::native::extension::ExtensionConfig;
+use crate::backend::native::service::RateLimitConfig;
 use crate::backend::native::service::ServiceConfig;
 use crate::backend::native::BackendConfig;
 use crate::error::Error;
 use crate::error::Result;
+use crate::prelude::rings_core::consts::DEFAULT_RECONNECT_INITIAL_BACKOFF_MS;
+use crate::prelude::rings_core::consts::DEFAULT_RECONNECT_MAX_BACKOFF_MS;
+use crate::prelude::rings_core::dht::Did;
 use crate::prelude::rings_core::ecc::SecretKey;
+use crate::prelude::rings_core::swarm::BootstrapEntry;
+use crate::prelude::rings_core::swarm::ReconnectPolicy;
 use crate::prelude::SessionSk;
 use crate::processor::ProcessorConfig;
 use crate::processor::ProcessorConfigSerialized;
@@ -72,6 +79,68 @@ pub struct Config {
     /// its deserialization is equivalent to `ExtensionConfig(vec![])` in Rust.
     #[serde(default)]
     pub extension: ExtensionConfig,
+    /// Seed peers to join the network through on start.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bootstrap_nodes: Vec<BootstrapEntry>,
+    /// Reconnect lost bootstrap nodes, successors, predecessor and fingers with backoff.
+    /// Lost peers are not reconnected when not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reconnect: Option<ReconnectConfig>,
+    /// Maximum count of http requests forwarded to services concurrently.
+    /// Further requests are answered with 503 until some of them are done.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrent_requests: Option<usize>,
+    /// Prefer IPv6 addresses when dialing remote nodes by http, and fall back to IPv4.
+    /// Keep the system order when not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefer_ipv6: Option<bool>,
+    /// Dids allowed to use services of this node. Any node can use them when not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_origins: Option<Vec<Did>>,
+    /// Rate limit of http requests from each origin. Unlimited when not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<RateLimitConfig>,
+    /// Directory files can be sent from by `sendFile`. Sending files is disabled when not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub send_dir: Option<String>,
+    /// Directory files sent by other nodes are written to. Receiving is disabled when not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub receive_dir: Option<String>,
+    /// Max size in bytes of a file sent by other nodes. 1 GiB when not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_receive_bytes: Option<u64>,
+}
+
+/// Backoff of reconnecting lost peers, see `reconnect` of [Config].
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+pub struct ReconnectConfig {
+    /// Delay in ms before the first attempt.
+    #[serde(default = "default_reconnect_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+    /// Cap in ms of the delay between attempts.
+    #[serde(default = "default_reconnect_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+    /// Count of attempts before giving up. Retry forever when not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_attempts: Option<u32>,
+}
+
+fn default_reconnect_initial_backoff_ms() -> u64 {
+    DEFAULT_RECONNECT_INITIAL_BACKOFF_MS
+}
+
+fn default_reconnect_max_backoff_ms() -> u64 {
+    DEFAULT_RECONNECT_MAX_BACKOFF_MS
+}
+
+impl From<ReconnectConfig> for ReconnectPolicy {
+    fn from(config: ReconnectConfig) -> Self {
+        Self {
+            initial_backoff: Duration::from_millis(config.initial_backoff_ms),
+            max_backoff: Duration::from_millis(config.max_backoff_ms),
+            max_attempts: config.max_attempts,
+        }
+    }
 }
 
 impl TryFrom<Config> for ProcessorConfigSerialized {
@@ -125,6 +194,11 @@ impl From<Config> for BackendConfig {
         Self {
             services: config.services,
             extensions: config.extension,
+            max_concurrent_requests: config.max_concurrent_requests,
+            allowed_origins: config.allowed_origins,
+            rate_limit: config.rate_limit,
+            receive_dir: config.receive_dir,
+            max_receive_bytes: config.max_receive_bytes,
         }
     }
 }
@@ -148,6 +222,15 @@ impl Config {
             data_storage: DEFAULT_DATA_STORAGE_CONFIG.clone(),
             measure_storage: DEFAULT_MEASURE_STORAGE_CONFIG.clone(),
             extension: ExtensionConfig::default(),
+            bootstrap_nodes: vec![],
+            reconnect: None,
+            max_concurrent_requests: None,
+            prefer_ipv6: None,
+            allowed_origins: None,
+            rate_limit: None,
+            send_dir: None,
+            receive_dir: None,
+            max_receive_bytes: None,
         }
     }
 
@@ -212,5 +295,21 @@ measure_storage:
         let cfg: Config = serde_yaml::from_str(yaml).unwrap();
         assert_eq!(cfg.extension, ExtensionConfig::default());
         assert_eq!(cfg.services, vec![]);
+        assert_eq!(cfg.reconnect, None);
+    }
+
+    #[test]
+    fn test_reconnect_config() {
+        let cfg: ReconnectConfig = serde_yaml::from_str("max_attempts: 5").unwrap();
+        let policy = ReconnectPolicy::from(cfg);
+        assert_eq!(
+            policy.initial_backoff,
+            Duration::from_millis(DEFAULT_RECONNECT_INITIAL_BACKOFF_MS)
+        );
+        assert_eq!(
+            policy.max_backoff,
+            Duration::from_millis(DEFAULT_RECONNECT_MAX_BACKOFF_MS)
+        );
+        assert_eq!(policy.max_attempts, Some(5));
     }
 }
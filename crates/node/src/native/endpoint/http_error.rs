@@ -6,6 +6,10 @@ use axum::response::Response;
 pub enum HttpError {
     BadRequest,
     Internal,
+    #[cfg_attr(not(feature = "signaling-server"), allow(dead_code))]
+    Unauthorized,
+    #[cfg_attr(not(feature = "signaling-server"), allow(dead_code))]
+    TooManyRequests,
 }
 
 impl IntoResponse for HttpError {
@@ -13,6 +17,8 @@ impl IntoResponse for HttpError {
         let (code, msg) = match self {
             HttpError::BadRequest => (StatusCode::BAD_REQUEST, "Bad Request"),
             HttpError::Internal => (StatusCode::INTERNAL_SERVER_ERROR, "Internal error"),
+            HttpError::Unauthorized => (StatusCode::UNAUTHORIZED, "Unauthorized"),
+            HttpError::TooManyRequests => (StatusCode::TOO_MANY_REQUESTS, "Too many requests"),
         };
 
         (code, msg).into_response()
@@ -1,6 +1,8 @@
 //! rings-node service run with `Swarm` and chord stabilization.
 #![warn(missing_docs)]
 mod http_error;
+#[cfg(feature = "signaling-server")]
+pub mod signaling;
 mod ws;
 
 use std::net::SocketAddr;
@@ -68,12 +70,14 @@ pub async fn run_internal_api(port: u16, processor: Arc<Processor>) -> anyhow::R
         )
         .route("/ws", get(ws_handler).with_state(ws_state))
         .route("/status", get(status_handler).with_state(status_state))
+        .route("/metrics", get(metrics_handler))
         .layer(CorsLayer::permissive())
         .layer(axum::middleware::from_fn(node_info_header))
         .into_make_service_with_connect_info::<SocketAddr>();
 
     println!("JSON-RPC endpoint: http://{}", binding_addr);
     println!("WebSocket endpoint: http://{}/ws", binding_addr);
+    println!("Metrics endpoint: http://{}/metrics", binding_addr);
     axum::Server::bind(&binding_addr)
         .serve(axum_make_service)
         .await?;
@@ -92,12 +96,26 @@ pub async fn run_external_api(addr: String, processor: Arc<Processor>) -> anyhow
 
     let status_state = Arc::new(StatusState { processor });
 
-    let axum_make_service = Router::new()
+    let router = Router::new()
         .route(
             "/",
             post(jsonrpc_io_handler).with_state(jsonrpc_state.clone()),
         )
-        .route("/status", get(status_handler).with_state(status_state))
+        .route("/status", get(status_handler).with_state(status_state));
+
+    #[cfg(feature = "signaling-server")]
+    let router = {
+        let signaling_state = Arc::new(signaling::SignalingState::default());
+        println!("Signaling endpoint: http://{}/signaling/:did", addr);
+        router.route(
+            "/signaling/:did",
+            get(signaling::poll_handler)
+                .post(signaling::post_handler)
+                .with_state(signaling_state),
+        )
+    };
+
+    let axum_make_service = router
         .layer(CorsLayer::permissive())
         .layer(axum::middleware::from_fn(node_info_header))
         .into_make_service_with_connect_info::<SocketAddr>();
@@ -148,6 +166,13 @@ async fn status_handler(
     Ok(axum::Json(info))
 }
 
+async fn metrics_handler() -> impl IntoResponse {
+    (
+        [("content-type", "text/plain; version=0.0.4")],
+        rings_core::metrics::render(),
+    )
+}
+
 /// JSON response struct
 #[derive(Debug, Clone)]
 pub struct JsonResponse(String);
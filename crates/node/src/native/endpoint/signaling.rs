@@ -0,0 +1,363 @@
+//! A rendezvous point for exchanging handshake between nodes, such as browsers which
+//! cannot reach each other before the connection is established.
+//!
+//! A node POSTs its offer to `/signaling/:did` of the target, the target polls
+//! `/signaling/:did` of itself to fetch it and POSTs the answer back the same way.
+//! The payloads are signed by their senders, the relay only stores and forwards them.
+//!
+//! A posted message is signed by `from` over the target did and payload, and a poll is signed
+//! by the polling did over a recent timestamp, so that nobody can post in the name of another
+//! node or drain the mailbox of another node.
+use std::collections::VecDeque;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use axum::extract::Path;
+use axum::extract::Query;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use dashmap::DashMap;
+use rings_core::dht::Did;
+use rings_core::ecc::signers::secp256k1;
+use rings_core::ecc::SecretKey;
+use rings_core::message::Encoded;
+use rings_core::utils::get_epoch_ms;
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::sync::Notify;
+
+use super::http_error::HttpError;
+
+/// Messages not fetched in it are dropped.
+const SIGNALING_MESSAGE_TTL: Duration = Duration::from_secs(120);
+/// Mailboxes neither posted to nor polled in it are removed.
+const SIGNALING_MAILBOX_IDLE_TTL: Duration = Duration::from_secs(300);
+/// Maximum count of pending messages of a did.
+const SIGNALING_MAILBOX_SIZE: usize = 32;
+/// Maximum count of pending messages of a did from the same sender, so that a sender can't
+/// fill the mailbox and lock out the others.
+const SIGNALING_MAILBOX_SIZE_PER_SENDER: usize = 4;
+/// Maximum seconds of long polling.
+const SIGNALING_MAX_WAIT: u64 = 30;
+/// Maximum milliseconds between the timestamp signed by a poll and now, either way.
+const SIGNALING_POLL_MAX_SKEW_MS: u128 = 60_000;
+
+/// A handshake relayed to the target did.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SignalingMessage {
+    /// Did of sender.
+    pub from: Did,
+    /// Encoded offer or answer.
+    pub payload: Encoded,
+    /// Base64 signature of `from` over the target did and payload, see [SignalingMessage::new].
+    pub signature: String,
+}
+
+impl SignalingMessage {
+    /// Create a message to did signed by key.
+    pub fn new(key: SecretKey, to: Did, payload: Encoded) -> Self {
+        let from: Did = key.address().into();
+        let signature = secp256k1::sign_raw(key, post_message(from, to, &payload).as_bytes());
+        Self {
+            from,
+            payload,
+            signature: base64::encode(signature),
+        }
+    }
+
+    /// Check if the message to did is signed by `from`.
+    fn verify(&self, to: Did) -> bool {
+        let msg = post_message(self.from, to, &self.payload);
+        base64::decode(&self.signature)
+            .map(|sig| secp256k1::verify(msg.as_bytes(), &self.from.into(), sig))
+            .unwrap_or(false)
+    }
+}
+
+fn post_message(from: Did, to: Did, payload: &Encoded) -> String {
+    format!("rings-signaling:post:{from}:{to}:{}", payload.value())
+}
+
+fn poll_message(did: Did, ts: u128) -> String {
+    format!("rings-signaling:poll:{did}:{ts}")
+}
+
+struct Mailbox {
+    messages: VecDeque<(Instant, SignalingMessage)>,
+    notify: Arc<Notify>,
+    last_active: Instant,
+}
+
+impl Default for Mailbox {
+    fn default() -> Self {
+        Self {
+            messages: VecDeque::new(),
+            notify: Arc::new(Notify::new()),
+            last_active: Instant::now(),
+        }
+    }
+}
+
+/// Signaling state, holding pending messages keyed by target did.
+pub struct SignalingState {
+    mailboxes: DashMap<Did, Mailbox>,
+    last_prune: Mutex<Instant>,
+}
+
+impl Default for SignalingState {
+    fn default() -> Self {
+        Self {
+            mailboxes: DashMap::new(),
+            last_prune: Mutex::new(Instant::now()),
+        }
+    }
+}
+
+impl SignalingState {
+    fn push(&self, to: Did, msg: SignalingMessage) -> Result<(), HttpError> {
+        self.prune();
+        let mut mailbox = self.mailboxes.entry(to).or_default();
+        mailbox.last_active = Instant::now();
+        mailbox
+            .messages
+            .retain(|(t, _)| t.elapsed() < SIGNALING_MESSAGE_TTL);
+        let from_sender = mailbox
+            .messages
+            .iter()
+            .filter(|(_, m)| m.from == msg.from)
+            .count();
+        if mailbox.messages.len() >= SIGNALING_MAILBOX_SIZE
+            || from_sender >= SIGNALING_MAILBOX_SIZE_PER_SENDER
+        {
+            return Err(HttpError::TooManyRequests);
+        }
+        mailbox.messages.push_back((Instant::now(), msg));
+        mailbox.notify.notify_waiters();
+        Ok(())
+    }
+
+    /// Take pending messages of did, and a notifier to wait for new ones if there is none.
+    fn take(&self, did: Did) -> (Vec<SignalingMessage>, Arc<Notify>) {
+        self.prune();
+        let mut mailbox = self.mailboxes.entry(did).or_default();
+        mailbox.last_active = Instant::now();
+        let messages = mailbox
+            .messages
+            .drain(..)
+            .filter(|(t, _)| t.elapsed() < SIGNALING_MESSAGE_TTL)
+            .map(|(_, m)| m)
+            .collect();
+        (messages, mailbox.notify.clone())
+    }
+
+    /// Remove idle mailboxes, at most once per [SIGNALING_MESSAGE_TTL].
+    fn prune(&self) {
+        {
+            let Ok(mut last_prune) = self.last_prune.try_lock() else {
+                return;
+            };
+            if last_prune.elapsed() < SIGNALING_MESSAGE_TTL {
+                return;
+            }
+            *last_prune = Instant::now();
+        }
+        self.mailboxes
+            .retain(|_, m| m.last_active.elapsed() < SIGNALING_MAILBOX_IDLE_TTL);
+    }
+}
+
+/// Query of polling.
+#[derive(Deserialize, Serialize)]
+pub struct PollQuery {
+    /// Seconds to wait for messages if there is none, capped by [SIGNALING_MAX_WAIT].
+    #[serde(default)]
+    wait: u64,
+    /// Milliseconds since epoch when the poll is signed.
+    ts: u128,
+    /// Base64 signature of the polling did over `ts`, see [PollQuery::new].
+    sig: String,
+}
+
+impl PollQuery {
+    /// Create a query polling messages to the did of key, waiting up to `wait` seconds.
+    pub fn new(key: SecretKey, wait: u64) -> Self {
+        let ts = get_epoch_ms();
+        let sig = secp256k1::sign_raw(key, poll_message(key.address().into(), ts).as_bytes());
+        Self {
+            wait,
+            ts,
+            sig: base64::encode(sig),
+        }
+    }
+
+    /// Check if the poll is signed by did recently.
+    fn verify(&self, did: Did) -> bool {
+        if get_epoch_ms().abs_diff(self.ts) > SIGNALING_POLL_MAX_SKEW_MS {
+            return false;
+        }
+        let msg = poll_message(did, self.ts);
+        base64::decode(&self.sig)
+            .map(|sig| secp256k1::verify(msg.as_bytes(), &did.into(), sig))
+            .unwrap_or(false)
+    }
+}
+
+fn parse_did(did: &str) -> Result<Did, HttpError> {
+    Did::from_str(did).map_err(|_| HttpError::BadRequest)
+}
+
+/// Relay a message to did.
+pub async fn post_handler(
+    State(state): State<Arc<SignalingState>>,
+    Path(did): Path<String>,
+    Json(msg): Json<SignalingMessage>,
+) -> Result<StatusCode, HttpError> {
+    let did = parse_did(&did)?;
+    if !msg.verify(did) {
+        tracing::debug!("Reject signaling message to {}: bad signature", did);
+        return Err(HttpError::Unauthorized);
+    }
+    tracing::debug!("Signaling message from {} to {}", msg.from, did);
+    state.push(did, msg)?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Fetch messages to did, waiting up to `wait` seconds if there is none.
+pub async fn poll_handler(
+    State(state): State<Arc<SignalingState>>,
+    Path(did): Path<String>,
+    Query(query): Query<PollQuery>,
+) -> Result<Json<Vec<SignalingMessage>>, HttpError> {
+    let did = parse_did(&did)?;
+    if !query.verify(did) {
+        tracing::debug!("Reject signaling poll of {}: bad signature", did);
+        return Err(HttpError::Unauthorized);
+    }
+    let (messages, notify) = state.take(did);
+    if !messages.is_empty() || query.wait == 0 {
+        return Ok(Json(messages));
+    }
+
+    let wait = Duration::from_secs(query.wait.min(SIGNALING_MAX_WAIT));
+    let notified = notify.notified();
+    // A message may arrive between taking and waiting.
+    let (messages, _) = state.take(did);
+    if !messages.is_empty() {
+        return Ok(Json(messages));
+    }
+    let _ = tokio::time::timeout(wait, notified).await;
+    Ok(Json(state.take(did).0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn poll(
+        state: &Arc<SignalingState>,
+        did: Did,
+        query: PollQuery,
+    ) -> impl std::future::Future<Output = Result<Json<Vec<SignalingMessage>>, HttpError>> {
+        poll_handler(State(state.clone()), Path(did.to_string()), Query(query))
+    }
+
+    #[tokio::test]
+    async fn test_signaling_relay() {
+        let state = Arc::new(SignalingState::default());
+        let (alice_key, bob_key) = (SecretKey::random(), SecretKey::random());
+        let (alice, bob): (Did, Did) = (alice_key.address().into(), bob_key.address().into());
+
+        let offer = SignalingMessage::new(alice_key, bob, Encoded::from("offer"));
+        post_handler(State(state.clone()), Path(bob.to_string()), Json(offer))
+            .await
+            .unwrap();
+
+        let Json(messages) = poll(&state, bob, PollQuery::new(bob_key, 0)).await.unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].from, alice);
+        assert_eq!(messages[0].payload, Encoded::from("offer"));
+
+        // Long polling wakes up on answer
+        let polling = tokio::spawn(poll(&state, alice, PollQuery::new(alice_key, 5)));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let answer = SignalingMessage::new(bob_key, alice, Encoded::from("answer"));
+        post_handler(State(state.clone()), Path(alice.to_string()), Json(answer))
+            .await
+            .unwrap();
+        let Json(messages) = polling.await.unwrap().unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].from, bob);
+    }
+
+    #[tokio::test]
+    async fn test_signaling_auth() {
+        let state = Arc::new(SignalingState::default());
+        let (alice_key, bob_key, eve_key) = (
+            SecretKey::random(),
+            SecretKey::random(),
+            SecretKey::random(),
+        );
+        let (alice, bob): (Did, Did) = (alice_key.address().into(), bob_key.address().into());
+
+        // Eve can't post in the name of alice.
+        let mut forged = SignalingMessage::new(eve_key, bob, Encoded::from("offer"));
+        forged.from = alice;
+        let res = post_handler(State(state.clone()), Path(bob.to_string()), Json(forged)).await;
+        assert!(matches!(res, Err(HttpError::Unauthorized)));
+
+        // A message signed to another target is rejected.
+        let offer = SignalingMessage::new(alice_key, alice, Encoded::from("offer"));
+        let res = post_handler(State(state.clone()), Path(bob.to_string()), Json(offer)).await;
+        assert!(matches!(res, Err(HttpError::Unauthorized)));
+
+        let offer = SignalingMessage::new(alice_key, bob, Encoded::from("offer"));
+        post_handler(State(state.clone()), Path(bob.to_string()), Json(offer))
+            .await
+            .unwrap();
+
+        // Eve can't drain the mailbox of bob, by her own signature or a stale one of bob.
+        let res = poll(&state, bob, PollQuery::new(eve_key, 0)).await;
+        assert!(matches!(res, Err(HttpError::Unauthorized)));
+        let mut stale = PollQuery::new(bob_key, 0);
+        stale.ts -= SIGNALING_POLL_MAX_SKEW_MS + 1;
+        let res = poll(&state, bob, stale).await;
+        assert!(matches!(res, Err(HttpError::Unauthorized)));
+
+        let Json(messages) = poll(&state, bob, PollQuery::new(bob_key, 0)).await.unwrap();
+        assert_eq!(messages.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_signaling_mailbox_limits() {
+        let state = Arc::new(SignalingState::default());
+        let (alice_key, bob_key) = (SecretKey::random(), SecretKey::random());
+        let bob: Did = bob_key.address().into();
+
+        for _ in 0..SIGNALING_MAILBOX_SIZE_PER_SENDER {
+            let offer = SignalingMessage::new(alice_key, bob, Encoded::from("offer"));
+            post_handler(State(state.clone()), Path(bob.to_string()), Json(offer))
+                .await
+                .unwrap();
+        }
+        // A flooding sender is limited, while the others can still reach bob.
+        let offer = SignalingMessage::new(alice_key, bob, Encoded::from("offer"));
+        let res = post_handler(State(state.clone()), Path(bob.to_string()), Json(offer)).await;
+        assert!(matches!(res, Err(HttpError::TooManyRequests)));
+        let offer = SignalingMessage::new(SecretKey::random(), bob, Encoded::from("offer"));
+        post_handler(State(state.clone()), Path(bob.to_string()), Json(offer))
+            .await
+            .unwrap();
+
+        // Idle mailboxes are pruned.
+        for mut mailbox in state.mailboxes.iter_mut() {
+            mailbox.last_active -= SIGNALING_MAILBOX_IDLE_TTL;
+        }
+        *state.last_prune.lock().unwrap() -= SIGNALING_MESSAGE_TTL;
+        state.prune();
+        assert!(state.mailboxes.is_empty());
+    }
+}
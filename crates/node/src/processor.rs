@@ -2,26 +2,51 @@
 
 //! Processor of rings-node rpc server.
 
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
 
+use async_trait::async_trait;
+use rings_core::dht::subring::Subring;
+use rings_core::dht::vnode::VirtualNode;
+use rings_core::dht::Chord;
 use rings_core::dht::Did;
+use rings_core::dht::PeerRingAction;
+use rings_core::dht::SuccessorReader;
 use rings_core::dht::VNodeStorage;
 use rings_core::measure::MeasureImpl;
 use rings_core::message::Encoded;
 use rings_core::message::Encoder;
 use rings_core::message::Message;
 use rings_core::prelude::uuid;
+use rings_core::storage::KvStorageInterface;
 use rings_core::storage::MemStorage;
+use rings_core::swarm::AckPolicy;
+use rings_core::swarm::BootstrapDialer;
+use rings_core::swarm::BootstrapEntry;
+use rings_core::swarm::KeepAlive;
+use rings_core::swarm::ReconnectPolicy;
+use rings_core::swarm::Reconnector;
 use rings_core::swarm::Swarm;
 use rings_core::swarm::SwarmBuilder;
+use rings_core::utils::get_epoch_ms;
+use rings_core::utils::sleep;
 use rings_rpc::protos::rings_node::*;
+use rings_rpc::protos::rings_node_handler::HandleRpc;
+use rings_transport::ice_server::IceServer;
 use serde::Deserialize;
 use serde::Serialize;
 
 use crate::backend::types::BackendMessage;
+use crate::backend::types::WireFormat;
+use crate::consts::BACKEND_FORMATS_METADATA_KEY;
+use crate::consts::BOOTSTRAP_INITIAL_BACKOFF_MS;
+use crate::consts::BOOTSTRAP_MAX_ATTEMPTS;
 use crate::consts::DATA_REDUNDANT;
+use crate::consts::STORAGE_FETCH_POLL_INTERVAL_MS;
+use crate::consts::STORAGE_FETCH_TIMEOUT_MS;
 use crate::error::Error;
 use crate::error::Result;
 use crate::measure::PeriodicMeasure;
@@ -30,6 +55,8 @@ use crate::prelude::wasm_export;
 use crate::prelude::ChordStorageInterface;
 use crate::prelude::ChordStorageInterfaceCacheChecker;
 use crate::prelude::SessionSk;
+use crate::prelude::SubringInterface;
+use crate::transfer::Transfers;
 
 /// ProcessorConfig is usually serialized as json or yaml.
 /// There is a `from_config` method in [ProcessorBuilder] used to initialize the Builder with a serialized ProcessorConfig.
@@ -187,6 +214,11 @@ pub struct ProcessorBuilder {
     storage: Option<VNodeStorage>,
     measure: Option<MeasureImpl>,
     stabilize_interval: Duration,
+    keepalive: Option<(Duration, u32)>,
+    bootstrap_nodes: Vec<BootstrapEntry>,
+    prefer_ipv6: Option<bool>,
+    reconnect: Option<ReconnectPolicy>,
+    send_dir: Option<PathBuf>,
 }
 
 /// Processor for rings-node rpc server
@@ -194,7 +226,14 @@ pub struct ProcessorBuilder {
 pub struct Processor {
     /// a swarm instance
     pub swarm: Arc<Swarm>,
-    stabilize_interval: Duration,
+    ice_servers: String,
+    #[cfg_attr(not(feature = "node"), allow(dead_code))]
+    prefer_ipv6: Option<bool>,
+    /// File transfers started by [Processor::send_file]
+    pub(crate) transfers: Arc<Transfers>,
+    /// Directory files can be sent from by [Processor::send_file]
+    #[cfg_attr(not(feature = "node"), allow(dead_code))]
+    pub(crate) send_dir: Option<PathBuf>,
 }
 
 impl ProcessorBuilder {
@@ -215,6 +254,11 @@ impl ProcessorBuilder {
             storage: None,
             measure: None,
             stabilize_interval: config.stabilize_interval,
+            keepalive: None,
+            bootstrap_nodes: vec![],
+            prefer_ipv6: None,
+            reconnect: None,
+            send_dir: None,
         })
     }
 
@@ -230,6 +274,38 @@ impl ProcessorBuilder {
         self
     }
 
+    /// Enable keepalive pings on connections, see [SwarmBuilder::keepalive].
+    pub fn keepalive(mut self, interval: Duration, miss_threshold: u32) -> Self {
+        self.keepalive = Some((interval, miss_threshold));
+        self
+    }
+
+    /// Reconnect lost bootstrap nodes and fingers with backoff, see [SwarmBuilder::reconnect].
+    pub fn reconnect(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect = Some(policy);
+        self
+    }
+
+    /// Set the seed peers to join the network through, see [Processor::bootstrap].
+    pub fn bootstrap_nodes(mut self, nodes: Vec<BootstrapEntry>) -> Self {
+        self.bootstrap_nodes = nodes;
+        self
+    }
+
+    /// Set the preferred address family when dialing remote nodes by http.
+    /// Addresses of the other family are still tried as a fallback. `None` keeps the system order.
+    pub fn prefer_ipv6(mut self, prefer_ipv6: Option<bool>) -> Self {
+        self.prefer_ipv6 = prefer_ipv6;
+        self
+    }
+
+    /// Set the directory files can be sent from by [Processor::send_file], including its
+    /// subdirectories. Sending files is disabled if not provided.
+    pub fn send_dir(mut self, dir: Option<PathBuf>) -> Self {
+        self.send_dir = dir;
+        self
+    }
+
     /// Build the [Processor].
     pub fn build(self) -> Result<Processor> {
         self.session_sk
@@ -240,7 +316,13 @@ impl ProcessorBuilder {
         let storage = self.storage.unwrap_or_else(|| Box::new(MemStorage::new()));
 
         let mut swarm_builder =
-            SwarmBuilder::new(self.network_id, &self.ice_servers, storage, self.session_sk);
+            SwarmBuilder::new(self.network_id, &self.ice_servers, storage, self.session_sk)
+                .stabilize_interval(self.stabilize_interval)
+                .bootstrap_nodes(self.bootstrap_nodes)
+                .metadata(HashMap::from([(
+                    BACKEND_FORMATS_METADATA_KEY.to_string(),
+                    WireFormat::supported_names(),
+                )]));
 
         if let Some(external_address) = self.external_address {
             swarm_builder = swarm_builder.external_address(external_address);
@@ -249,11 +331,22 @@ impl ProcessorBuilder {
         if let Some(measure) = self.measure {
             swarm_builder = swarm_builder.measure(measure);
         }
+
+        if let Some((interval, miss_threshold)) = self.keepalive {
+            swarm_builder = swarm_builder.keepalive(interval, miss_threshold);
+        }
+
+        if let Some(policy) = self.reconnect {
+            swarm_builder = swarm_builder.reconnect(policy);
+        }
         let swarm = Arc::new(swarm_builder.build());
 
         Ok(Processor {
             swarm,
-            stabilize_interval: self.stabilize_interval,
+            ice_servers: self.ice_servers,
+            prefer_ipv6: self.prefer_ipv6,
+            transfers: Arc::new(Transfers::default()),
+            send_dir: self.send_dir,
         })
     }
 }
@@ -264,10 +357,76 @@ impl Processor {
         self.swarm.did()
     }
 
-    /// Run stabilization daemon
+    /// Create a client calling the rpc of the remote node at `endpoint_url`.
+    #[cfg(feature = "node")]
+    pub(crate) fn rpc_client(&self, endpoint_url: &str) -> Result<rings_rpc::jsonrpc::Client> {
+        let client = crate::util::dns::http_client(self.prefer_ipv6)
+            .map_err(|e| Error::HttpConnectError(e.to_string()))?;
+        Ok(rings_rpc::jsonrpc::Client::new_with_http_client(
+            endpoint_url,
+            client,
+        ))
+    }
+
+    /// Create a client calling the rpc of the remote node at `endpoint_url`.
+    #[cfg(not(feature = "node"))]
+    pub(crate) fn rpc_client(&self, endpoint_url: &str) -> Result<rings_rpc::jsonrpc::Client> {
+        Ok(rings_rpc::jsonrpc::Client::new(endpoint_url))
+    }
+
+    /// Run stabilization daemon, and keepalive and reconnect daemons if they are enabled.
+    /// Lost bootstrap nodes are reconnected through their signaling endpoints.
     pub async fn listen(&self) {
-        let stabilizer = self.swarm.stabilizer();
-        Arc::new(stabilizer).wait(self.stabilize_interval).await
+        let stabilizer = Arc::new(self.swarm.stabilizer());
+        let stabilize_interval = self.swarm.stabilize_interval();
+        let keepalive = async {
+            if let Some(keepalive) = KeepAlive::new(self.swarm.clone()) {
+                Arc::new(keepalive).wait().await
+            }
+        };
+        let reconnector = async {
+            if let Some(reconnector) = Reconnector::new(self.swarm.clone()) {
+                let reconnector = reconnector.bootstrap_dialer(Arc::new(self.clone()));
+                Arc::new(reconnector).wait().await
+            }
+        };
+        futures::join!(stabilizer.wait(stabilize_interval), keepalive, reconnector);
+    }
+
+    /// Dial the bootstrap nodes in order through their signaling endpoints, until one of them
+    /// is connected, so that this node joins the ring via it. Each round of failed dials is
+    /// retried after a backoff doubled every round.
+    /// Returns the seed joined through, or None if no bootstrap node is configured.
+    pub async fn bootstrap(&self) -> Result<Option<BootstrapEntry>> {
+        let seeds = self.swarm.bootstrap_nodes();
+        if seeds.is_empty() {
+            return Ok(None);
+        }
+
+        let mut backoff = Duration::from_millis(BOOTSTRAP_INITIAL_BACKOFF_MS);
+        for attempt in 1..=BOOTSTRAP_MAX_ATTEMPTS {
+            for seed in seeds.iter().filter(|s| s.did != self.did()) {
+                let seed_did = seed.did.to_string();
+                if self.swarm.peers().iter().any(|p| p.did == seed_did)
+                    && self.swarm.wait_connected(seed.did).await
+                {
+                    tracing::info!("Bootstrap node {} is already connected", seed.did);
+                    return Ok(Some(seed.clone()));
+                }
+
+                if self.dial(seed).await {
+                    tracing::info!("Joined network via bootstrap node {}", seed.did);
+                    return Ok(Some(seed.clone()));
+                }
+            }
+
+            if attempt < BOOTSTRAP_MAX_ATTEMPTS {
+                tracing::info!("Retry bootstrap nodes in {:?}", backoff);
+                sleep(backoff).await;
+                backoff *= 2;
+            }
+        }
+        Err(Error::BootstrapFailed)
     }
 
     /// Connect peer with web3 did.
@@ -280,12 +439,25 @@ impl Processor {
         Ok(())
     }
 
+    /// Gracefully shut down the swarm, connections that cannot be closed before `timeout`
+    /// are force-closed. Returns the count of force-closed connections.
+    pub async fn shutdown(&self, timeout: Duration) -> usize {
+        self.swarm.shutdown(timeout).await
+    }
+
+    /// Leave the ring gracefully, handing off stored data to the successor before closing
+    /// connections. Returns the count of force-closed connections.
+    pub async fn leave(&self) -> usize {
+        self.swarm.leave().await
+    }
+
     /// Disconnect a peer with web3 did.
+    /// Returns [Error::ConnectionNotFound] if the peer is not connected.
     pub async fn disconnect(&self, did: Did) -> Result<()> {
-        self.swarm
-            .disconnect(did)
-            .await
-            .map_err(Error::CloseConnectionError)
+        self.swarm.disconnect(did).await.map_err(|e| match e {
+            rings_core::error::Error::PeerNotFound(_) => Error::ConnectionNotFound,
+            e => Error::CloseConnectionError(e),
+        })
     }
 
     /// Send custom message to a did.
@@ -304,16 +476,76 @@ impl Processor {
             .map_err(Error::SendMessage)
     }
 
+    /// Send custom message to a did, encrypted to the session of destination.
+    pub async fn send_encrypted_message(&self, destination: Did, msg: &[u8]) -> Result<uuid::Uuid> {
+        tracing::info!(
+            "send_encrypted_message, destination: {}, message size: {:?}",
+            destination,
+            msg.len(),
+        );
+
+        self.swarm
+            .send_encrypted_message(msg, destination)
+            .await
+            .map_err(Error::SendMessage)
+    }
+
+    /// Send custom message to a did, and wait for its ack, resending it by policy.
+    /// See [Swarm::send_reliable] for the delivery guarantee.
+    pub async fn send_reliable_message(
+        &self,
+        destination: Did,
+        msg: &[u8],
+        policy: AckPolicy,
+    ) -> Result<uuid::Uuid> {
+        tracing::info!(
+            "send_reliable_message, destination: {}, message size: {:?}",
+            destination,
+            msg.len(),
+        );
+
+        self.swarm
+            .send_reliable(msg, destination, policy)
+            .await
+            .map_err(Error::SendMessage)
+    }
+
     /// Send custom message to a did.
     pub async fn send_backend_message(
         &self,
         destination: Did,
         backend_msg: BackendMessage,
     ) -> Result<uuid::Uuid> {
-        let msg_bytes = bincode::serialize(&backend_msg).map_err(|_| Error::EncodeError)?;
+        let format = self.backend_message_format(destination).await;
+        self.send_backend_message_with_format(destination, backend_msg, format)
+            .await
+    }
+
+    /// Send backend message encoded in the given format, see [WireFormat].
+    /// The destination should be able to decode it.
+    pub async fn send_backend_message_with_format(
+        &self,
+        destination: Did,
+        backend_msg: BackendMessage,
+        format: WireFormat,
+    ) -> Result<uuid::Uuid> {
+        let msg_bytes = backend_msg.to_bytes(format)?;
         self.send_message(destination, &msg_bytes).await
     }
 
+    /// The format negotiated by handshake metadata of a connected peer.
+    /// It's bincode if the peer is not connected directly or advertised nothing.
+    async fn backend_message_format(&self, peer: Did) -> WireFormat {
+        self.swarm
+            .peer_metadata(peer)
+            .await
+            .and_then(|m| {
+                m.get(BACKEND_FORMATS_METADATA_KEY)
+                    .map(|formats| WireFormat::negotiate(formats))
+            })
+            .unwrap_or_default()
+    }
+
     /// check local cache of dht
     pub async fn storage_check_cache(&self, did: Did) -> Option<vnode::VirtualNode> {
         self.swarm.storage_check_cache(did).await
@@ -326,6 +558,33 @@ impl Processor {
             .map_err(Error::VNodeError)
     }
 
+    /// fetch virtual node from DHT and wait until it arrives in local cache.
+    /// The stale cached one is dropped first. Returns None if it's not arrived in
+    /// [STORAGE_FETCH_TIMEOUT_MS], since a missing virtual node is never replied.
+    pub async fn storage_fetch_wait(&self, did: Did) -> Result<Option<vnode::VirtualNode>> {
+        self.swarm
+            .dht()
+            .cache
+            .remove(&did.to_string())
+            .await
+            .map_err(Error::VNodeError)?;
+        self.storage_fetch(did).await?;
+        // Looked up locally, it's cached already if it's existed.
+        if self.storage_locate(did)?.0 == self.did() {
+            return Ok(self.storage_check_cache(did).await);
+        }
+        let deadline = get_epoch_ms() + STORAGE_FETCH_TIMEOUT_MS;
+        loop {
+            if let Some(vnode) = self.storage_check_cache(did).await {
+                return Ok(Some(vnode));
+            }
+            if get_epoch_ms() >= deadline {
+                return Ok(None);
+            }
+            sleep(Duration::from_millis(STORAGE_FETCH_POLL_INTERVAL_MS)).await;
+        }
+    }
+
     /// store virtual node on DHT
     pub async fn storage_store(&self, vnode: vnode::VirtualNode) -> Result<()> {
         <Swarm as ChordStorageInterface<DATA_REDUNDANT>>::storage_store(&self.swarm, vnode)
@@ -333,6 +592,34 @@ impl Processor {
             .map_err(Error::VNodeError)
     }
 
+    /// locate the node responsible for a virtual node by local routing table.
+    /// The returned flag is true if the lookup should be continued on the returned node.
+    pub fn storage_locate(&self, vid: Did) -> Result<(Did, bool)> {
+        let dht = self.swarm.dht();
+        if dht.successors().is_empty().map_err(Error::VNodeError)? {
+            return Ok((dht.did, false));
+        }
+        match dht.find_successor(vid).map_err(Error::VNodeError)? {
+            PeerRingAction::Some(did) => Ok((did, false)),
+            PeerRingAction::RemoteAction(next, _) => Ok((next, true)),
+            act => Err(Error::VNodeError(
+                rings_core::error::Error::PeerRingUnexpectedAction(act),
+            )),
+        }
+    }
+
+    /// Find the node responsible for a virtual node, which is the successor of `vid` on the ring.
+    /// Unlike [Processor::storage_locate], it asks remote nodes if the successor is not known
+    /// by local routing table, see [Swarm::lookup].
+    pub async fn storage_owner(&self, vid: Did) -> Result<Did> {
+        let dht = self.swarm.dht();
+        if dht.successors().is_empty().map_err(Error::VNodeError)? {
+            return Ok(dht.did);
+        }
+        let lookup = self.swarm.lookup(vid).await.map_err(Error::VNodeError)?;
+        Ok(lookup.successor)
+    }
+
     /// append data to a virtual node on DHT
     pub async fn storage_append_data(&self, topic: &str, data: Encoded) -> Result<()> {
         <Swarm as ChordStorageInterface<DATA_REDUNDANT>>::storage_append_data(
@@ -344,6 +631,35 @@ impl Processor {
         .map_err(Error::VNodeError)
     }
 
+    /// append data to a virtual node on DHT uniquely, moving it to the end if it's existed
+    pub async fn storage_touch_data(&self, topic: &str, data: Encoded) -> Result<()> {
+        <Swarm as ChordStorageInterface<DATA_REDUNDANT>>::storage_touch_data(
+            &self.swarm,
+            topic,
+            data,
+        )
+        .await
+        .map_err(Error::VNodeError)
+    }
+
+    /// join a subring, the subring will be created if it's not existed
+    pub async fn subring_join(&self, name: &str) -> Result<Did> {
+        <Swarm as SubringInterface<DATA_REDUNDANT>>::subring_join(&self.swarm, name)
+            .await
+            .map_err(Error::VNodeError)?;
+        VirtualNode::gen_did(name).map_err(Error::VNodeError)
+    }
+
+    /// fetch a subring from DHT and wait until it arrives, see [Processor::storage_fetch_wait]
+    pub async fn subring_fetch(&self, name: &str) -> Result<Option<Subring>> {
+        let vid = VirtualNode::gen_did(name).map_err(Error::VNodeError)?;
+        self.storage_fetch_wait(vid)
+            .await?
+            .map(Subring::try_from)
+            .transpose()
+            .map_err(Error::VNodeError)
+    }
+
     /// register service
     pub async fn register_service(&self, name: &str) -> Result<()> {
         let encoded_did = self
@@ -362,13 +678,98 @@ impl Processor {
 
     /// get node info
     pub async fn get_node_info(&self) -> Result<NodeInfoResponse> {
+        let ice_servers = IceServer::vec_from_str(&self.ice_servers)
+            .map_err(|_| Error::InvalidData)?
+            .into_iter()
+            .flat_map(|s| s.urls)
+            .collect();
         Ok(NodeInfoResponse {
             version: crate::util::build_version(),
             swarm: Some(self.swarm.inspect().await.into()),
+            did: self.did().to_string(),
+            session_expires_at_ms: self.swarm.session().expires_at_ms() as u64,
+            ice_servers,
+            reconnects: self
+                .swarm
+                .reconnect_states()
+                .into_iter()
+                .map(|x| x.into())
+                .collect(),
         })
     }
 }
 
+#[cfg_attr(feature = "browser", async_trait(?Send))]
+#[cfg_attr(not(feature = "browser"), async_trait)]
+impl BootstrapDialer for Processor {
+    /// Dial the bootstrap node through its signaling endpoint by http.
+    /// The endpoint is checked to be the expected node before handshaking, and the dial
+    /// succeeds only once the connection is open.
+    async fn dial(&self, node: &BootstrapEntry) -> bool {
+        let expected = node.did.to_string();
+        let did = match self.rpc_client(&node.endpoint) {
+            Ok(client) => client
+                .node_did(&NodeDidRequest {})
+                .await
+                .map(|resp| resp.did)
+                .map_err(|e| Error::RemoteRpcError(e.to_string())),
+            Err(e) => Err(e),
+        };
+        match did {
+            Ok(did) if did == expected => {}
+            Ok(did) => {
+                tracing::warn!(
+                    "Bootstrap node at {} is {}, expected {}",
+                    node.endpoint,
+                    did,
+                    node.did
+                );
+                return false;
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to dial bootstrap node {} at {}: {}",
+                    node.did,
+                    node.endpoint,
+                    e
+                );
+                return false;
+            }
+        }
+
+        let req = ConnectPeerViaHttpRequest {
+            url: node.endpoint.clone(),
+        };
+        match self.handle_rpc(req).await {
+            Ok(resp) if resp.did == expected => self.swarm.wait_connected(node.did).await,
+            Ok(resp) => {
+                // The endpoint was switched to another node in between.
+                tracing::warn!(
+                    "Bootstrap node at {} is {}, expected {}",
+                    node.endpoint,
+                    resp.did,
+                    node.did
+                );
+                if let Ok(did) = Did::from_str(&resp.did) {
+                    if let Err(e) = self.swarm.disconnect(did).await {
+                        tracing::warn!("Failed to disconnect {}: {}", did, e);
+                    }
+                }
+                false
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to dial bootstrap node {} at {}: {}",
+                    node.did,
+                    node.endpoint,
+                    e
+                );
+                false
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 #[cfg(feature = "node")]
 mod test {
@@ -379,6 +780,30 @@ mod test {
     use crate::prelude::*;
     use crate::tests::native::prepare_processor;
 
+    #[tokio::test]
+    async fn test_storage_owner() {
+        let p1 = prepare_processor().await;
+        let p2 = prepare_processor().await;
+        assert_eq!(p1.storage_owner(p2.did()).await.unwrap(), p1.did());
+
+        let offer = p1.swarm.create_offer(p2.did()).await.unwrap();
+        let answer = p2.swarm.answer_offer(offer).await.unwrap();
+        p1.swarm.accept_answer(answer).await.unwrap();
+        tokio::time::sleep(Duration::from_secs(1)).await;
+
+        // The owner of a vid in (p2, p1] is p1, which p1 only knows by asking p2.
+        for i in 0..32 {
+            let vid = VirtualNode::gen_did(&format!("key-{i}")).unwrap();
+            let owner = if vid.in_range(p1.did(), p1.did(), p2.did()) {
+                p2.did()
+            } else {
+                p1.did()
+            };
+            assert_eq!(p1.storage_owner(vid).await.unwrap(), owner);
+            assert_eq!(p2.storage_owner(vid).await.unwrap(), owner);
+        }
+    }
+
     #[tokio::test]
     async fn test_processor_create_offer() {
         let peer_did = SecretKey::random().address().into();
@@ -389,6 +814,51 @@ mod test {
         assert_eq!(conn_dids.first().unwrap().did, peer_did.to_string());
     }
 
+    async fn serve_external_api(processor: Processor) -> (Arc<Processor>, String) {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let addr = format!("127.0.0.1:{port}");
+        let processor = Arc::new(processor);
+        tokio::spawn(crate::native::endpoint::run_external_api(
+            addr.clone(),
+            processor.clone(),
+        ));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        (processor, format!("http://{addr}"))
+    }
+
+    #[tokio::test]
+    async fn test_dial_bootstrap_node() {
+        let p1 = prepare_processor().await;
+        let (p2, endpoint) = serve_external_api(prepare_processor().await).await;
+
+        let seed = BootstrapEntry {
+            did: p2.did(),
+            endpoint,
+        };
+        assert!(p1.dial(&seed).await);
+
+        let peer = p1.swarm.peers().into_iter().next().unwrap();
+        assert_eq!(peer.did, p2.did().to_string());
+        assert_eq!(peer.state, "Connected");
+    }
+
+    #[tokio::test]
+    async fn test_dial_bootstrap_node_with_mismatched_did() {
+        let p1 = prepare_processor().await;
+        let (_p2, endpoint) = serve_external_api(prepare_processor().await).await;
+
+        let seed = BootstrapEntry {
+            did: SecretKey::random().address().into(),
+            endpoint,
+        };
+        assert!(!p1.dial(&seed).await);
+        assert!(p1.swarm.peers().is_empty());
+    }
+
     struct SwarmCallbackInstance {
         pub msgs: Mutex<Vec<String>>,
     }
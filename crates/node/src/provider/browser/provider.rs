@@ -1,6 +1,7 @@
 #![warn(missing_docs)]
 //! Browser Provider implementation
 #![allow(non_snake_case, non_upper_case_globals, clippy::ptr_offset_with_cast)]
+use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::future::Future;
 use std::pin::Pin;
@@ -137,7 +138,8 @@ impl Provider {
             .await?;
 
             if let Some(cb) = backend_behaviour {
-                let backend: Backend = Backend::new(Arc::new(provider.clone()), Box::new(cb));
+                let backend: Backend =
+                    Backend::new(Arc::new(provider.clone()), Box::new(cb), HashMap::new());
                 provider
                     .set_swarm_callback_internal(Arc::new(backend))
                     .expect("Failed on set swarm callback");
@@ -197,7 +199,8 @@ impl Provider {
             .await
             .map_err(JsError::from)?;
             if let Some(cb) = backend_behaviour {
-                let backend: Backend = Backend::new(Arc::new(provider.clone()), Box::new(cb));
+                let backend: Backend =
+                    Backend::new(Arc::new(provider.clone()), Box::new(cb), HashMap::new());
                 provider
                     .set_swarm_callback_internal(Arc::new(backend))
                     .expect("Failed on set swarm callback");
@@ -36,6 +36,7 @@
 //!
 //! Please check python example at examples/ffi/rings.py
 
+use std::collections::HashMap;
 use std::ffi::c_char;
 use std::ffi::CStr;
 use std::ffi::CString;
@@ -274,7 +275,11 @@ pub unsafe extern "C" fn new_provider_with_callback(
     let provider = Arc::new(provider.clone());
     let callback: &FFIBackendBehaviour = unsafe { &*callback_ptr };
     let callback_with_rt = FFIBackendBehaviourWithRuntime::new(callback.clone(), runtime.clone());
-    let backend = Backend::new(provider.clone(), Box::new(callback_with_rt.clone()));
+    let backend = Backend::new(
+        provider.clone(),
+        Box::new(callback_with_rt.clone()),
+        HashMap::new(),
+    );
 
     provider
         .set_swarm_callback_internal(Arc::new(backend))
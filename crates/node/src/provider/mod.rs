@@ -1,11 +1,14 @@
 #![warn(missing_docs)]
 //! General Provider, this module provide Provider implementation for FFI and WASM
 
+use std::collections::HashMap;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
 
 use rings_core::dht::VNodeStorage;
+use rings_core::message::CustomMessage;
+use rings_core::message::EncryptedMessage;
 use rings_core::session::SessionSkBuilder;
 use rings_core::storage::MemStorage;
 use rings_core::swarm::callback::SharedSwarmCallback;
@@ -124,7 +127,7 @@ impl Provider {
     #[cfg(not(feature = "browser"))]
     pub fn set_backend_callback<T>(&self, callback: T) -> Result<()>
     where T: MessageHandler<BackendMessage> + Send + Sync + Sized + 'static {
-        let backend = Backend::new(Arc::new(self.clone()), Box::new(callback));
+        let backend = Backend::new(Arc::new(self.clone()), Box::new(callback), HashMap::new());
         self.processor
             .swarm
             .set_callback(Arc::new(backend))
@@ -135,7 +138,7 @@ impl Provider {
     #[cfg(feature = "browser")]
     pub fn set_backend_callback<T>(&self, callback: T) -> Result<()>
     where T: MessageHandler<BackendMessage> + Sized + 'static {
-        let backend = Backend::new(Arc::new(self.clone()), Box::new(callback));
+        let backend = Backend::new(Arc::new(self.clone()), Box::new(callback), HashMap::new());
         self.processor
             .swarm
             .set_callback(Arc::new(backend))
@@ -160,6 +163,11 @@ impl Provider {
             .map_err(Error::InternalError)
     }
 
+    /// Decrypt an [EncryptedMessage] sent to this node.
+    pub(crate) fn decrypt_message(&self, msg: &EncryptedMessage) -> Result<CustomMessage> {
+        Ok(self.processor.swarm.decrypt_message(msg)?)
+    }
+
     /// Request local rpc interface
     /// the internal rpc interface is provide by rings_rpc
     pub async fn request_internal(
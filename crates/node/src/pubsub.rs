@@ -0,0 +1,271 @@
+#![warn(missing_docs)]
+//! Topic based publish and subscribe over DHT.
+//!
+//! A node subscribes to a topic by writing a [Subscription] into the virtual node of
+//! the topic, which is stored on the successors responsible for it. To publish, the subscriptions
+//! are fetched from those successors, and the payload is sent to each subscriber as a
+//! [BackendMessage::Bytes] of the topic, relayed like any other custom message.
+//! Subscribers receive it by the topic handler registered to their [crate::backend::Backend].
+//!
+//! Subscriptions expire after their ttl, so a subscriber should renew it periodically.
+//! Subscribing replaces the earlier subscription of the node, and unsubscribing removes it,
+//! both dropping the expired ones. The virtual node is read, modified and overwritten as a whole,
+//! so a subscription written concurrently by another node may be lost until it's renewed.
+use std::collections::HashMap;
+use std::time::Duration;
+
+use rings_core::dht::vnode::VNodeType;
+use rings_core::dht::vnode::VirtualNode;
+use rings_core::dht::Did;
+use rings_core::message::Decoder;
+use rings_core::message::Encoder;
+use rings_core::utils::get_epoch_ms;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::backend::types::BackendMessage;
+use crate::consts::PUBSUB_TOPIC_PREFIX;
+use crate::error::Error;
+use crate::error::Result;
+use crate::processor::Processor;
+
+/// Interest of a node in a topic, stored in the virtual node of the topic.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Subscription {
+    /// The subscriber
+    pub did: Did,
+    /// Time in ms after which the subscription is ignored
+    pub expires_at_ms: u128,
+}
+
+impl Subscription {
+    /// Check if the subscription is expired at `now_ms`.
+    pub fn is_expired(&self, now_ms: u128) -> bool {
+        self.expires_at_ms <= now_ms
+    }
+}
+
+/// Key of the virtual node holding subscriptions of topic. It's prefixed, so that
+/// subscriptions don't mix with messages published by `publishMessageToTopic`.
+fn subscriptions_key(topic: &str) -> String {
+    format!("{PUBSUB_TOPIC_PREFIX}{topic}")
+}
+
+/// Subscriptions in effect, which are the latest ones of each subscriber not expired at `now_ms`.
+fn active_subscriptions(vnode: &VirtualNode, now_ms: u128) -> Vec<Subscription> {
+    let mut latest: HashMap<Did, Subscription> = HashMap::new();
+    let mut order = vec![];
+    for encoded in &vnode.data {
+        let sub = String::from_encoded(encoded)
+            .ok()
+            .and_then(|s| serde_json::from_str::<Subscription>(&s).ok());
+        let Some(sub) = sub else {
+            tracing::debug!("Skip malformed subscription in {:?}", vnode.did);
+            continue;
+        };
+        if latest.insert(sub.did, sub).is_none() {
+            order.push(sub.did);
+        }
+    }
+    order
+        .into_iter()
+        .filter_map(|did| latest.remove(&did))
+        .filter(|sub| !sub.is_expired(now_ms))
+        .collect()
+}
+
+impl Processor {
+    /// Subscribe to topic for `ttl`. Subscribe again before it expires to keep receiving.
+    pub async fn subscribe(&self, topic: &str, ttl: Duration) -> Result<()> {
+        let sub = Subscription {
+            did: self.did(),
+            expires_at_ms: get_epoch_ms() + ttl.as_millis(),
+        };
+        self.write_subscription(topic, Some(sub)).await
+    }
+
+    /// Stop receiving messages published to topic.
+    pub async fn unsubscribe(&self, topic: &str) -> Result<()> {
+        self.write_subscription(topic, None).await
+    }
+
+    /// Subscribers of topic, fetched from the successors responsible for it.
+    pub async fn subscribers(&self, topic: &str) -> Result<Vec<Did>> {
+        Ok(self
+            .fetch_subscriptions(topic, get_epoch_ms())
+            .await?
+            .into_iter()
+            .map(|sub| sub.did)
+            .collect())
+    }
+
+    /// Send payload to all subscribers of topic, returns the count of subscribers it's sent to.
+    /// Failures of sending to a subscriber are logged and skipped.
+    pub async fn publish(&self, topic: &str, data: Vec<u8>) -> Result<usize> {
+        let mut sent = 0;
+        for did in self.subscribers(topic).await? {
+            let msg = BackendMessage::Bytes {
+                topic: topic.to_string(),
+                data: data.clone(),
+            };
+            match self.send_backend_message(did, msg).await {
+                Ok(_) => sent += 1,
+                Err(e) => tracing::warn!("Failed to publish {topic} to {did}: {e}"),
+            }
+        }
+        Ok(sent)
+    }
+
+    async fn fetch_subscriptions(&self, topic: &str, now_ms: u128) -> Result<Vec<Subscription>> {
+        let vid = VirtualNode::gen_did(&subscriptions_key(topic)).map_err(Error::VNodeError)?;
+        Ok(self
+            .storage_fetch_wait(vid)
+            .await?
+            .map(|vnode| active_subscriptions(&vnode, now_ms))
+            .unwrap_or_default())
+    }
+
+    /// Replace the subscription of this node with `sub`, or remove it if `sub` is None.
+    async fn write_subscription(&self, topic: &str, sub: Option<Subscription>) -> Result<()> {
+        let did = self.did();
+        let mut subs = self.fetch_subscriptions(topic, get_epoch_ms()).await?;
+        subs.retain(|s| s.did != did);
+        subs.extend(sub);
+        let data = subs
+            .iter()
+            .map(|s| {
+                serde_json::to_string(s)?
+                    .encode()
+                    .map_err(|_| Error::EncodeError)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let vnode = VirtualNode {
+            did: VirtualNode::gen_did(&subscriptions_key(topic)).map_err(Error::VNodeError)?,
+            data,
+            kind: VNodeType::Data,
+            expires_at_ms: None,
+        };
+        self.storage_store(vnode).await
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "node")]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::tests::native::prepare_processor;
+
+    fn encode(sub: Subscription) -> rings_core::message::Encoded {
+        serde_json::to_string(&sub).unwrap().encode().unwrap()
+    }
+
+    #[test]
+    fn test_active_subscriptions() {
+        let a = Did::from_str("0x11E807fcc88dD319270493fB2e822e388Fe36ab0").unwrap();
+        let b = Did::from_str("0x999999cf1046e68e36E1aA2E0E07105eDDD1f08E").unwrap();
+        let sub = |did, expires_at_ms| Subscription { did, expires_at_ms };
+        let vnode = VirtualNode {
+            did: VirtualNode::gen_did("pubsub:test").unwrap(),
+            data: vec![
+                encode(sub(a, 100)),
+                encode(sub(b, 100)),
+                "malformed".to_string().encode().unwrap(),
+                encode(sub(a, 0)),
+            ],
+            kind: VNodeType::Data,
+            expires_at_ms: None,
+        };
+        // The latest subscription of a is unsubscribing.
+        assert_eq!(active_subscriptions(&vnode, 50), vec![sub(b, 100)]);
+        assert!(active_subscriptions(&vnode, 100).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_and_publish() {
+        let processor = prepare_processor().await;
+        let did = processor.did();
+
+        processor
+            .subscribe("test", Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_eq!(processor.subscribers("test").await.unwrap(), vec![did]);
+        assert!(processor.subscribers("other").await.unwrap().is_empty());
+        assert_eq!(
+            processor.publish("test", b"hello".to_vec()).await.unwrap(),
+            1
+        );
+
+        processor.unsubscribe("test").await.unwrap();
+        assert!(processor.subscribers("test").await.unwrap().is_empty());
+        assert_eq!(
+            processor.publish("test", b"hello".to_vec()).await.unwrap(),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_and_publish_across_nodes() {
+        let p1 = prepare_processor().await;
+        let p2 = prepare_processor().await;
+        let offer = p1.swarm.create_offer(p2.did()).await.unwrap();
+        let answer = p2.swarm.answer_offer(offer).await.unwrap();
+        p1.swarm.accept_answer(answer).await.unwrap();
+        tokio::time::sleep(Duration::from_secs(1)).await;
+
+        let ttl = Duration::from_secs(60);
+        p1.subscribe("test", ttl).await.unwrap();
+        p2.subscribe("test", ttl).await.unwrap();
+        // Renewal replaces the earlier subscription instead of appending one.
+        p1.subscribe("test", ttl).await.unwrap();
+
+        let vid = VirtualNode::gen_did(&subscriptions_key("test")).unwrap();
+        for p in [&p1, &p2] {
+            let vnode = p.storage_fetch_wait(vid).await.unwrap().unwrap();
+            assert_eq!(vnode.data.len(), 2);
+            let mut subscribers = p.subscribers("test").await.unwrap();
+            subscribers.sort();
+            let mut expected = vec![p1.did(), p2.did()];
+            expected.sort();
+            assert_eq!(subscribers, expected);
+        }
+        assert_eq!(p2.publish("test", b"hello".to_vec()).await.unwrap(), 2);
+
+        p1.unsubscribe("test").await.unwrap();
+        assert_eq!(p2.subscribers("test").await.unwrap(), vec![p2.did()]);
+        let vnode = p1.storage_fetch_wait(vid).await.unwrap().unwrap();
+        assert_eq!(vnode.data.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_prunes_expired() {
+        let processor = prepare_processor().await;
+        let other = Did::from_str("0x11E807fcc88dD319270493fB2e822e388Fe36ab0").unwrap();
+        let vid = VirtualNode::gen_did(&subscriptions_key("test")).unwrap();
+        processor
+            .storage_store(VirtualNode {
+                did: vid,
+                data: vec![encode(Subscription {
+                    did: other,
+                    expires_at_ms: 1,
+                })],
+                kind: VNodeType::Data,
+                expires_at_ms: None,
+            })
+            .await
+            .unwrap();
+
+        processor
+            .subscribe("test", Duration::from_secs(60))
+            .await
+            .unwrap();
+        let vnode = processor.storage_fetch_wait(vid).await.unwrap().unwrap();
+        assert_eq!(vnode.data.len(), 1);
+        assert_eq!(
+            processor.subscribers("test").await.unwrap(),
+            vec![processor.did()]
+        );
+    }
+}
@@ -14,6 +14,7 @@ use jsonrpc_core::types::error::Error;
 use jsonrpc_core::types::error::ErrorCode;
 use jsonrpc_core::Result;
 use rings_core::dht::Did;
+use rings_core::dht::SuccessorReader;
 use rings_core::message::Decoder;
 use rings_core::message::Encoded;
 use rings_core::message::Encoder;
@@ -23,6 +24,7 @@ use rings_core::prelude::vnode::VirtualNode;
 use rings_rpc::protos::rings_node::*;
 use rings_rpc::protos::rings_node_handler::HandleRpc;
 
+use crate::backend::types::BackendMessage;
 use crate::error::Error as ServerError;
 use crate::processor::Processor;
 use crate::seed::Seed;
@@ -34,7 +36,7 @@ impl HandleRpc<ConnectPeerViaHttpRequest, ConnectPeerViaHttpResponse> for Proces
         &self,
         req: ConnectPeerViaHttpRequest,
     ) -> Result<ConnectPeerViaHttpResponse> {
-        let client = rings_rpc::jsonrpc::Client::new(&req.url);
+        let client = self.rpc_client(&req.url)?;
 
         let did = client
             .node_did(&NodeDidRequest {})
@@ -104,12 +106,13 @@ impl HandleRpc<ConnectWithSeedRequest, ConnectWithSeedResponse> for Processor {
 #[cfg_attr(not(feature = "browser"), async_trait)]
 impl HandleRpc<ListPeersRequest, ListPeersResponse> for Processor {
     async fn handle_rpc(&self, _req: ListPeersRequest) -> Result<ListPeersResponse> {
-        let peers = self
-            .swarm
-            .peers()
-            .into_iter()
-            .map(|peer| peer.into())
-            .collect();
+        let peers = self.swarm.peers().into_iter().map(|mut peer| async move {
+            if let Ok(did) = Did::from_str(&peer.did) {
+                peer.connection_stats = self.swarm.connection_stats(did).await.ok().flatten();
+            }
+            PeerInfo::from(peer)
+        });
+        let peers = join_all(peers).await;
         Ok(ListPeersResponse { peers })
     }
 }
@@ -205,8 +208,14 @@ impl HandleRpc<SendCustomMessageRequest, SendCustomMessageResponse> for Processo
         let destination = s2d(&req.destination_did)?;
         let data = base64::decode(req.data)
             .map_err(|_| Error::invalid_params("Base64 decode data failed"))?;
-        self.send_message(destination, &data).await?;
-        Ok(SendCustomMessageResponse {})
+        let tx_id = if req.encrypt {
+            self.send_encrypted_message(destination, &data).await?
+        } else {
+            self.send_message(destination, &data).await?
+        };
+        Ok(SendCustomMessageResponse {
+            tx_id: tx_id.to_string(),
+        })
     }
 }
 
@@ -218,8 +227,8 @@ impl HandleRpc<SendBackendMessageRequest, SendBackendMessageResponse> for Proces
         req: SendBackendMessageRequest,
     ) -> Result<SendBackendMessageResponse> {
         let destination = s2d(&req.destination_did)?;
-        let data = serde_json::from_str(&req.data)
-            .map_err(|_| Error::invalid_params("Serialize data as json failed"))?;
+        let data = BackendMessage::from_wire(&req.data)
+            .map_err(|_| Error::invalid_params("Deserialize backend message failed"))?;
         self.send_backend_message(destination, data).await?;
         Ok(SendBackendMessageResponse {})
     }
@@ -325,6 +334,216 @@ impl HandleRpc<NodeDidRequest, NodeDidResponse> for Processor {
     }
 }
 
+#[cfg_attr(feature = "browser", async_trait(?Send))]
+#[cfg_attr(not(feature = "browser"), async_trait)]
+impl HandleRpc<DhtStoreRequest, DhtStoreResponse> for Processor {
+    async fn handle_rpc(&self, req: DhtStoreRequest) -> Result<DhtStoreResponse> {
+        let vnode = VirtualNode::try_from((req.key, req.value))
+            .map_err(|_| Error::invalid_params("Failed to build virtual node of key"))?;
+        let did = self.storage_owner(vnode.did).await?;
+        self.storage_store(vnode).await?;
+        Ok(DhtStoreResponse {
+            did: did.to_string(),
+        })
+    }
+}
+
+#[cfg_attr(feature = "browser", async_trait(?Send))]
+#[cfg_attr(not(feature = "browser"), async_trait)]
+impl HandleRpc<DhtFetchRequest, DhtFetchResponse> for Processor {
+    async fn handle_rpc(&self, req: DhtFetchRequest) -> Result<DhtFetchResponse> {
+        let vid = VirtualNode::gen_did(&req.key)
+            .map_err(|_| Error::invalid_params("Failed to get id of key"))?;
+        let did = self.storage_owner(vid).await?;
+
+        let values = self
+            .storage_fetch_wait(vid)
+            .await?
+            .map(|vnode| {
+                vnode
+                    .data
+                    .iter()
+                    .map(|v| v.decode())
+                    .filter_map(|v| v.ok())
+                    .collect::<Vec<String>>()
+            })
+            .unwrap_or_default();
+
+        Ok(DhtFetchResponse {
+            did: did.to_string(),
+            values,
+        })
+    }
+}
+
+#[cfg_attr(feature = "browser", async_trait(?Send))]
+#[cfg_attr(not(feature = "browser"), async_trait)]
+impl HandleRpc<DhtLocateRequest, DhtLocateResponse> for Processor {
+    async fn handle_rpc(&self, req: DhtLocateRequest) -> Result<DhtLocateResponse> {
+        let vid = VirtualNode::gen_did(&req.key)
+            .map_err(|_| Error::invalid_params("Failed to get id of key"))?;
+        let (did, remote) = self.storage_locate(vid)?;
+        Ok(DhtLocateResponse {
+            vid: vid.to_string(),
+            did: did.to_string(),
+            remote,
+        })
+    }
+}
+
+#[cfg_attr(feature = "browser", async_trait(?Send))]
+#[cfg_attr(not(feature = "browser"), async_trait)]
+impl HandleRpc<SubringJoinRequest, SubringJoinResponse> for Processor {
+    async fn handle_rpc(&self, req: SubringJoinRequest) -> Result<SubringJoinResponse> {
+        if req.name.is_empty() {
+            return Err(Error::invalid_params("Subring name is empty"));
+        }
+        let did = self.subring_join(&req.name).await?;
+        Ok(SubringJoinResponse {
+            did: did.to_string(),
+        })
+    }
+}
+
+#[cfg_attr(feature = "browser", async_trait(?Send))]
+#[cfg_attr(not(feature = "browser"), async_trait)]
+impl HandleRpc<SubringListRequest, SubringListResponse> for Processor {
+    async fn handle_rpc(&self, req: SubringListRequest) -> Result<SubringListResponse> {
+        let did = VirtualNode::gen_did(&req.name)
+            .map_err(|_| Error::invalid_params("Failed to get id of subring"))?;
+
+        let Some(subring) = self.subring_fetch(&req.name).await? else {
+            return Ok(SubringListResponse {
+                did: did.to_string(),
+                ..Default::default()
+            });
+        };
+
+        let mut members = vec![];
+        for member in subring.finger.list().iter().flatten() {
+            let member = member.to_string();
+            if !members.contains(&member) {
+                members.push(member);
+            }
+        }
+
+        Ok(SubringListResponse {
+            did: did.to_string(),
+            creator: subring.creator.to_string(),
+            members,
+        })
+    }
+}
+
+#[cfg_attr(feature = "browser", async_trait(?Send))]
+#[cfg_attr(not(feature = "browser"), async_trait)]
+impl HandleRpc<RingStatusRequest, RingStatusResponse> for Processor {
+    async fn handle_rpc(&self, _req: RingStatusRequest) -> Result<RingStatusResponse> {
+        let status = self
+            .swarm
+            .dht()
+            .ring_status()
+            .await
+            .map_err(ServerError::VNodeError)?;
+        Ok(RingStatusResponse {
+            successors: status.successors.iter().map(|x| x.to_string()).collect(),
+            predecessor: status.predecessor.map(|x| x.to_string()),
+            finger_filled: status.finger_filled as u64,
+            finger_size: status.finger_size as u64,
+            unchanged_rounds: status.unchanged_rounds as u64,
+            stabilized: status.stabilized,
+        })
+    }
+}
+
+#[cfg_attr(feature = "browser", async_trait(?Send))]
+#[cfg_attr(not(feature = "browser"), async_trait)]
+impl HandleRpc<SendFileRequest, SendFileResponse> for Processor {
+    async fn handle_rpc(&self, req: SendFileRequest) -> Result<SendFileResponse> {
+        let destination = s2d(&req.destination_did)?;
+        let transfer_id = self.send_file(destination, &req.path).await?;
+        Ok(SendFileResponse { transfer_id })
+    }
+}
+
+#[cfg_attr(feature = "browser", async_trait(?Send))]
+#[cfg_attr(not(feature = "browser"), async_trait)]
+impl HandleRpc<TransferStatusRequest, TransferStatusResponse> for Processor {
+    async fn handle_rpc(&self, req: TransferStatusRequest) -> Result<TransferStatusResponse> {
+        let transfer = self.transfer_status(&req.transfer_id)?;
+        Ok(TransferStatusResponse {
+            destination_did: transfer.destination.to_string(),
+            bytes_sent: transfer.sent(),
+            total: transfer.total,
+            state: transfer.state().as_str().to_string(),
+        })
+    }
+}
+
+#[cfg_attr(feature = "browser", async_trait(?Send))]
+#[cfg_attr(not(feature = "browser"), async_trait)]
+impl HandleRpc<CancelTransferRequest, CancelTransferResponse> for Processor {
+    async fn handle_rpc(&self, req: CancelTransferRequest) -> Result<CancelTransferResponse> {
+        let cancelled = self.cancel_transfer(&req.transfer_id)?;
+        Ok(CancelTransferResponse { cancelled })
+    }
+}
+
+#[cfg_attr(feature = "browser", async_trait(?Send))]
+#[cfg_attr(not(feature = "browser"), async_trait)]
+impl HandleRpc<FingerTableRequest, FingerTableResponse> for Processor {
+    async fn handle_rpc(&self, _req: FingerTableRequest) -> Result<FingerTableResponse> {
+        let finger = self
+            .swarm
+            .dht()
+            .lock_finger()
+            .map_err(ServerError::VNodeError)?
+            .clone();
+        let entries = finger
+            .list()
+            .iter()
+            .enumerate()
+            .map(|(index, did)| FingerEntry {
+                index: index as u64,
+                target: finger.target(index).to_string(),
+                did: did.map(|x| x.to_string()),
+            })
+            .collect();
+        Ok(FingerTableResponse { entries })
+    }
+}
+
+#[cfg_attr(feature = "browser", async_trait(?Send))]
+#[cfg_attr(not(feature = "browser"), async_trait)]
+impl HandleRpc<SuccessorListRequest, SuccessorListResponse> for Processor {
+    async fn handle_rpc(&self, _req: SuccessorListRequest) -> Result<SuccessorListResponse> {
+        let successors = self
+            .swarm
+            .dht()
+            .successors()
+            .list()
+            .map_err(ServerError::VNodeError)?;
+        Ok(SuccessorListResponse {
+            successors: successors.iter().map(|x| x.to_string()).collect(),
+        })
+    }
+}
+
+#[cfg_attr(feature = "browser", async_trait(?Send))]
+#[cfg_attr(not(feature = "browser"), async_trait)]
+impl HandleRpc<PredecessorRequest, PredecessorResponse> for Processor {
+    async fn handle_rpc(&self, _req: PredecessorRequest) -> Result<PredecessorResponse> {
+        let predecessor = *self
+            .swarm
+            .dht()
+            .lock_predecessor()
+            .map_err(ServerError::VNodeError)?;
+        Ok(PredecessorResponse {
+            predecessor: predecessor.map(|x| x.to_string()),
+        })
+    }
+}
+
 /// Get did from string or return InvalidParam Error
 fn s2d(s: &str) -> Result<Did> {
     Did::from_str(s).map_err(|_| Error::invalid_params(format!("Invalid Did: {s}")))
@@ -8,6 +8,10 @@ use crate::processor::ProcessorConfig;
 pub mod snark;
 
 pub async fn prepare_processor() -> Processor {
+    prepare_processor_builder().build().unwrap()
+}
+
+pub fn prepare_processor_builder() -> ProcessorBuilder {
     let key = SecretKey::random();
     let sm = SessionSk::new_with_seckey(&key).unwrap();
 
@@ -21,9 +25,7 @@ pub async fn prepare_processor() -> Processor {
 
     let storage = Box::new(MemStorage::new());
 
-    let procssor_builder = ProcessorBuilder::from_serialized(&config)
+    ProcessorBuilder::from_serialized(&config)
         .unwrap()
-        .storage(storage);
-
-    procssor_builder.build().unwrap()
+        .storage(storage)
 }
@@ -0,0 +1,642 @@
+#![warn(missing_docs)]
+//! File transfers to remote nodes.
+//!
+//! A local file is read in pieces of [FILE_TRANSFER_CHUNK_SIZE] bytes, and each of them is sent
+//! to the destination as a [BackendMessage::FileChunk], relayed like any other custom message.
+//! Sending runs in background, its progress is tracked by transfer id, so that it can be
+//! monitored by `transferStatus` and stopped by `cancelTransfer` rpc.
+//! Only files under the `send_dir` of [ProcessorBuilder](crate::processor::ProcessorBuilder)
+//! can be sent, sending is disabled without it.
+//!
+//! On the destination, [FileReceiver] writes the chunks into a part file under its directory,
+//! and moves it to the name of file once all chunks are received.
+
+#[cfg(feature = "node")]
+use std::collections::HashSet;
+#[cfg(feature = "node")]
+use std::path::PathBuf;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+#[cfg(feature = "node")]
+use std::time::Duration;
+#[cfg(feature = "node")]
+use std::time::Instant;
+
+use dashmap::DashMap;
+use rings_core::dht::Did;
+use rings_core::prelude::uuid;
+
+#[cfg(feature = "node")]
+use crate::backend::types::BackendMessage;
+#[cfg(feature = "node")]
+use crate::consts::DEFAULT_MAX_RECEIVE_BYTES;
+#[cfg(feature = "node")]
+use crate::consts::FILE_RECEIVE_IDLE_TIMEOUT_SECS;
+#[cfg(feature = "node")]
+use crate::consts::FILE_TRANSFER_CHUNK_SIZE;
+use crate::consts::MAX_TRACKED_TRANSFERS;
+use crate::error::Error;
+use crate::error::Result;
+use crate::processor::Processor;
+
+/// State of a file transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferState {
+    /// Chunks are being sent
+    Sending,
+    /// All chunks are sent
+    Completed,
+    /// Failed to read the file or send a chunk
+    Failed,
+    /// Cancelled before all chunks are sent
+    Cancelled,
+}
+
+impl TransferState {
+    /// Name of the state in rpc response.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Sending => "sending",
+            Self::Completed => "completed",
+            Self::Failed => "failed",
+            Self::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// Progress of a file transfer.
+pub struct Transfer {
+    /// The node receiving the file
+    pub destination: Did,
+    /// Size of the file in bytes
+    pub total: u64,
+    sent: AtomicU64,
+    state: Mutex<TransferState>,
+}
+
+impl Transfer {
+    fn new(destination: Did, total: u64) -> Self {
+        Self {
+            destination,
+            total,
+            sent: AtomicU64::new(0),
+            state: Mutex::new(TransferState::Sending),
+        }
+    }
+
+    /// Bytes sent so far.
+    pub fn sent(&self) -> u64 {
+        self.sent.load(Ordering::SeqCst)
+    }
+
+    /// Current state.
+    pub fn state(&self) -> TransferState {
+        *self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Move to `state` if it's still sending. Returns false if it's finished already.
+    fn finish(&self, state: TransferState) -> bool {
+        let mut current = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if *current != TransferState::Sending {
+            return false;
+        }
+        *current = state;
+        true
+    }
+}
+
+/// File transfers started by the node, keyed by transfer id.
+/// Finished ones are kept for status queries, until more than [MAX_TRACKED_TRANSFERS]
+/// transfers are tracked. At most [MAX_TRACKED_TRANSFERS] transfers can be in progress.
+#[derive(Default)]
+pub struct Transfers(DashMap<String, Arc<Transfer>>);
+
+impl Transfers {
+    fn insert(&self, destination: Did, total: u64) -> Result<(String, Arc<Transfer>)> {
+        if self.0.len() >= MAX_TRACKED_TRANSFERS {
+            self.0.retain(|_, t| t.state() == TransferState::Sending);
+        }
+        if self.0.len() >= MAX_TRACKED_TRANSFERS {
+            return Err(Error::TooManyTransfers);
+        }
+        let id = uuid::Uuid::new_v4().to_string();
+        let transfer = Arc::new(Transfer::new(destination, total));
+        self.0.insert(id.clone(), transfer.clone());
+        Ok((id, transfer))
+    }
+
+    fn get(&self, id: &str) -> Option<Arc<Transfer>> {
+        self.0.get(id).map(|t| t.value().clone())
+    }
+}
+
+impl Processor {
+    /// Send a local file to destination in background, returns the id of transfer.
+    /// The file is opened before returning, so that a missing file is reported at once.
+    /// Files out of `send_dir` are rejected with [Error::FileNotAllowed], after resolving
+    /// symbolic links and `..` of path.
+    #[cfg(feature = "node")]
+    pub async fn send_file(&self, destination: Did, path: &str) -> Result<String> {
+        let Some(send_dir) = &self.send_dir else {
+            return Err(Error::FileNotAllowed(format!(
+                "{path}: sending files is disabled without send_dir"
+            )));
+        };
+        let send_dir = canonicalize(send_dir).await?;
+        let path = canonicalize(path.as_ref()).await?;
+        if !path.starts_with(&send_dir) {
+            return Err(Error::FileNotAllowed(format!(
+                "{} is out of {}",
+                path.display(),
+                send_dir.display()
+            )));
+        }
+
+        let file = tokio::fs::File::open(&path)
+            .await
+            .map_err(|e| Error::OpenFileError(e.to_string()))?;
+        let total = file
+            .metadata()
+            .await
+            .map_err(|e| Error::OpenFileError(e.to_string()))?
+            .len();
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let (tid, transfer) = self.transfers.insert(destination, total)?;
+        let processor = self.clone();
+        let id = tid.clone();
+        tokio::spawn(async move {
+            match processor.pump_file(file, &tid, &name, &transfer).await {
+                Ok(()) => {
+                    transfer.finish(TransferState::Completed);
+                }
+                Err(e) => {
+                    tracing::warn!("File transfer {tid} to {destination} failed: {e}");
+                    transfer.finish(TransferState::Failed);
+                }
+            }
+        });
+        Ok(id)
+    }
+
+    /// Sending files is not supported without file system.
+    #[cfg(not(feature = "node"))]
+    pub async fn send_file(&self, _destination: Did, path: &str) -> Result<String> {
+        Err(Error::OpenFileError(format!(
+            "{path}: file system is not available"
+        )))
+    }
+
+    /// Progress of a file transfer started by [Processor::send_file].
+    pub fn transfer_status(&self, id: &str) -> Result<Arc<Transfer>> {
+        self.transfers
+            .get(id)
+            .ok_or_else(|| Error::TransferNotFound(id.to_string()))
+    }
+
+    /// Stop sending the rest of file, chunks sent already are not recalled.
+    /// Returns false if the transfer is finished already.
+    pub fn cancel_transfer(&self, id: &str) -> Result<bool> {
+        Ok(self.transfer_status(id)?.finish(TransferState::Cancelled))
+    }
+
+    #[cfg(feature = "node")]
+    async fn pump_file(
+        &self,
+        mut file: tokio::fs::File,
+        tid: &str,
+        name: &str,
+        transfer: &Transfer,
+    ) -> Result<()> {
+        use tokio::io::AsyncReadExt;
+
+        let mut offset = 0;
+        while offset < transfer.total && transfer.state() == TransferState::Sending {
+            // Chunks are aligned to FILE_TRANSFER_CHUNK_SIZE, as required by FileReceiver.
+            let n = (transfer.total - offset).min(FILE_TRANSFER_CHUNK_SIZE as u64) as usize;
+            let mut buf = vec![0; n];
+            if let Err(e) = file.read_exact(&mut buf).await {
+                return Err(match e.kind() {
+                    std::io::ErrorKind::UnexpectedEof => {
+                        Error::OpenFileError(format!("{name} is truncated after {offset} bytes"))
+                    }
+                    _ => Error::OpenFileError(e.to_string()),
+                });
+            }
+            let msg = BackendMessage::FileChunk {
+                tid: tid.to_string(),
+                name: name.to_string(),
+                offset,
+                total: transfer.total,
+                data: buf.into(),
+            };
+            self.send_backend_message(transfer.destination, msg).await?;
+            offset += n as u64;
+            transfer.sent.store(offset, Ordering::SeqCst);
+        }
+        Ok(())
+    }
+}
+
+/// Resolve symbolic links and `..` of path.
+#[cfg(feature = "node")]
+async fn canonicalize(path: &std::path::Path) -> Result<PathBuf> {
+    tokio::fs::canonicalize(path)
+        .await
+        .map_err(|e| Error::OpenFileError(format!("{}: {e}", path.display())))
+}
+
+/// A file being received, written to a part file until all of its chunks arrive.
+#[cfg(feature = "node")]
+struct Incoming {
+    name: String,
+    total: u64,
+    part: PathBuf,
+    file: tokio::fs::File,
+    offsets: HashSet<u64>,
+    received: u64,
+    last_active: Instant,
+}
+
+/// Receives files sent by [Processor::send_file] into a directory.
+/// Files being received are keyed by sender and transfer id, those receiving no chunk in
+/// [FILE_RECEIVE_IDLE_TIMEOUT_SECS] are dropped, and at most [MAX_TRACKED_TRANSFERS] files
+/// are received concurrently.
+/// Chunks must be aligned to [FILE_TRANSFER_CHUNK_SIZE], so that a file is completed only
+/// when all of its bytes are written.
+#[cfg(feature = "node")]
+pub struct FileReceiver {
+    dir: PathBuf,
+    max_file_bytes: u64,
+    incoming: DashMap<(Did, String), Arc<tokio::sync::Mutex<Incoming>>>,
+}
+
+#[cfg(feature = "node")]
+impl FileReceiver {
+    /// Create a new FileReceiver writing files into dir.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            max_file_bytes: DEFAULT_MAX_RECEIVE_BYTES,
+            incoming: DashMap::new(),
+        }
+    }
+
+    /// Reject files larger than `max` bytes, [DEFAULT_MAX_RECEIVE_BYTES] by default.
+    pub fn max_file_bytes(mut self, max: u64) -> Self {
+        self.max_file_bytes = max;
+        self
+    }
+
+    /// Write a chunk of file from a peer. Returns the path of file once all chunks are received.
+    /// A file of the same name received already is not overwritten, the new one is prefixed
+    /// by its transfer id instead.
+    pub async fn receive(
+        &self,
+        from: Did,
+        tid: &str,
+        name: &str,
+        offset: u64,
+        total: u64,
+        data: &[u8],
+    ) -> Result<Option<PathBuf>> {
+        let tid = uuid::Uuid::parse_str(tid)
+            .map_err(|_| Error::InvalidFileChunk(format!("invalid transfer id {tid}")))?
+            .to_string();
+        let valid_name = std::path::Path::new(name).file_name() == Some(std::ffi::OsStr::new(name));
+        if !valid_name || name.starts_with('.') {
+            return Err(Error::InvalidFileChunk(format!("invalid file name {name}")));
+        }
+        if total > self.max_file_bytes {
+            return Err(Error::InvalidFileChunk(format!(
+                "{name} of {total} bytes exceeds {} bytes",
+                self.max_file_bytes
+            )));
+        }
+        let len = data.len() as u64;
+        if offset.checked_add(len).map_or(true, |end| end > total) {
+            return Err(Error::InvalidFileChunk(format!(
+                "{len} bytes at {offset} is out of {total} bytes"
+            )));
+        }
+        // Chunks never overlap, so that the received bytes count the bytes written.
+        let chunk_size = FILE_TRANSFER_CHUNK_SIZE as u64;
+        if offset % chunk_size != 0 || len != (total - offset).min(chunk_size) {
+            return Err(Error::InvalidFileChunk(format!(
+                "{len} bytes at {offset} is not aligned to {chunk_size} bytes"
+            )));
+        }
+
+        let key = (from, tid.clone());
+        let incoming = self.incoming_of(&key, name, total)?;
+        let mut incoming = incoming.lock().await;
+        if incoming.name != name || incoming.total != total {
+            return Err(Error::InvalidFileChunk(format!(
+                "{name} of {total} bytes conflicts with transfer {tid}"
+            )));
+        }
+        if !incoming.offsets.insert(offset) {
+            return Ok(None);
+        }
+
+        {
+            use tokio::io::AsyncSeekExt;
+            use tokio::io::AsyncWriteExt;
+
+            let write = async {
+                incoming.file.seek(std::io::SeekFrom::Start(offset)).await?;
+                incoming.file.write_all(data).await?;
+                incoming.file.flush().await
+            };
+            write
+                .await
+                .map_err(|e| Error::CreateFileError(e.to_string()))?;
+        }
+        incoming.received += len;
+        incoming.last_active = Instant::now();
+        if incoming.received < total {
+            return Ok(None);
+        }
+
+        self.incoming.remove(&key);
+        let mut path = self.dir.join(name);
+        if tokio::fs::try_exists(&path).await.unwrap_or(true) {
+            path = self.dir.join(format!("{tid}-{name}"));
+        }
+        tokio::fs::rename(&incoming.part, &path)
+            .await
+            .map_err(|e| Error::CreateFileError(e.to_string()))?;
+        Ok(Some(path))
+    }
+
+    /// Get the file being received by key, or start receiving it into a new part file.
+    fn incoming_of(
+        &self,
+        key: &(Did, String),
+        name: &str,
+        total: u64,
+    ) -> Result<Arc<tokio::sync::Mutex<Incoming>>> {
+        if let Some(incoming) = self.incoming.get(key) {
+            return Ok(incoming.value().clone());
+        }
+
+        self.drop_idle();
+        if self.incoming.len() >= MAX_TRACKED_TRANSFERS {
+            return Err(Error::TooManyTransfers);
+        }
+        let entry = match self.incoming.entry(key.clone()) {
+            dashmap::mapref::entry::Entry::Occupied(e) => return Ok(e.get().clone()),
+            dashmap::mapref::entry::Entry::Vacant(e) => e,
+        };
+        let part = self.dir.join(format!(".{}.part", key.1));
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&part)
+            .map_err(|e| Error::CreateFileError(format!("{}: {e}", part.display())))?;
+        let incoming = Arc::new(tokio::sync::Mutex::new(Incoming {
+            name: name.to_string(),
+            total,
+            part,
+            file: tokio::fs::File::from_std(file),
+            offsets: HashSet::new(),
+            received: 0,
+            last_active: Instant::now(),
+        }));
+        entry.insert(incoming.clone());
+        Ok(incoming)
+    }
+
+    /// Drop files receiving no chunk for [FILE_RECEIVE_IDLE_TIMEOUT_SECS], with their part files.
+    fn drop_idle(&self) {
+        let timeout = Duration::from_secs(FILE_RECEIVE_IDLE_TIMEOUT_SECS);
+        self.incoming.retain(|(from, tid), incoming| {
+            // A file being written is not idle.
+            let Ok(incoming) = incoming.try_lock() else {
+                return true;
+            };
+            if incoming.last_active.elapsed() < timeout {
+                return true;
+            }
+            tracing::info!(
+                "Drop file {} of transfer {tid} from {from}: idle",
+                incoming.name
+            );
+            let _ = std::fs::remove_file(&incoming.part);
+            false
+        });
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "node")]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::backend::native::BackendBehaviour;
+    use crate::backend::native::BackendConfig;
+    use crate::backend::Backend;
+    use crate::provider::Provider;
+    use crate::tests::native::prepare_processor;
+    use crate::tests::native::prepare_processor_builder;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rings-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    async fn wait_finished(transfer: &Transfer) {
+        for _ in 0..50 {
+            if transfer.state() != TransferState::Sending {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_file() {
+        let (send_dir, receive_dir) = (temp_dir(), temp_dir());
+        let processor = Arc::new(
+            prepare_processor_builder()
+                .send_dir(Some(send_dir.clone()))
+                .build()
+                .unwrap(),
+        );
+        let config: BackendConfig = serde_json::from_value(serde_json::json!({
+            "receive_dir": receive_dir,
+        }))
+        .unwrap();
+        let behaviour = BackendBehaviour::new(config).await.unwrap();
+        let provider = Arc::new(Provider::from_processor(processor.clone()));
+        let backend = Arc::new(Backend::new(provider, Box::new(behaviour), HashMap::new()));
+        processor.swarm.set_callback(backend).unwrap();
+
+        let path = send_dir.join("data.bin");
+        let data: Vec<u8> = (0..FILE_TRANSFER_CHUNK_SIZE * 2 + 1)
+            .map(|i| i as u8)
+            .collect();
+        std::fs::write(&path, &data).unwrap();
+
+        for renamed in [false, true] {
+            let id = processor
+                .send_file(processor.did(), path.to_str().unwrap())
+                .await
+                .unwrap();
+            let transfer = processor.transfer_status(&id).unwrap();
+            assert_eq!(transfer.total, data.len() as u64);
+            wait_finished(&transfer).await;
+            assert_eq!(transfer.state(), TransferState::Completed);
+            assert_eq!(transfer.sent(), data.len() as u64);
+            assert!(!processor.cancel_transfer(&id).unwrap());
+
+            // A file of the same name is not overwritten.
+            let received = match renamed {
+                false => receive_dir.join("data.bin"),
+                true => receive_dir.join(format!("{id}-data.bin")),
+            };
+            assert_eq!(std::fs::read(&received).unwrap(), data);
+        }
+
+        assert!(processor.transfer_status("unknown").is_err());
+        assert!(matches!(
+            processor
+                .send_file(processor.did(), send_dir.join("missing").to_str().unwrap())
+                .await,
+            Err(Error::OpenFileError(_))
+        ));
+
+        // Files out of send_dir are rejected, even if reached by `..`.
+        let secret = receive_dir.join("data.bin");
+        let escaped = send_dir
+            .join("..")
+            .join(receive_dir.file_name().unwrap())
+            .join("data.bin");
+        for path in [&secret, &escaped] {
+            assert!(matches!(
+                processor
+                    .send_file(processor.did(), path.to_str().unwrap())
+                    .await,
+                Err(Error::FileNotAllowed(_))
+            ));
+        }
+
+        // Sending is disabled without send_dir.
+        let processor = prepare_processor().await;
+        assert!(matches!(
+            processor
+                .send_file(processor.did(), path.to_str().unwrap())
+                .await,
+            Err(Error::FileNotAllowed(_))
+        ));
+
+        std::fs::remove_dir_all(&send_dir).unwrap();
+        std::fs::remove_dir_all(&receive_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_receive_file() {
+        let dir = temp_dir();
+        let receiver = FileReceiver::new(&dir);
+        let from: Did = rings_core::ecc::SecretKey::random().address().into();
+        let tid = uuid::Uuid::new_v4().to_string();
+
+        // Chunks out of order and duplicated are assembled.
+        let head = vec![b'a'; FILE_TRANSFER_CHUNK_SIZE];
+        let total = FILE_TRANSFER_CHUNK_SIZE as u64 + 5;
+        let offset = FILE_TRANSFER_CHUNK_SIZE as u64;
+        assert!(receiver
+            .receive(from, &tid, "a.txt", offset, total, b"world")
+            .await
+            .unwrap()
+            .is_none());
+        assert!(receiver
+            .receive(from, &tid, "a.txt", offset, total, b"world")
+            .await
+            .unwrap()
+            .is_none());
+        let path = receiver
+            .receive(from, &tid, "a.txt", 0, total, &head)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(path, dir.join("a.txt"));
+        let expected = [head, b"world".to_vec()].concat();
+        assert_eq!(std::fs::read(&path).unwrap(), expected);
+        assert!(receiver.incoming.is_empty());
+
+        let tid = uuid::Uuid::new_v4().to_string();
+        for (tid, name, offset) in [
+            ("not-uuid", "a.txt", 0),
+            (tid.as_str(), "../a.txt", 0),
+            (tid.as_str(), ".hidden", 0),
+            (tid.as_str(), "a.txt", 7),
+            // Overlapping chunks are not aligned.
+            (tid.as_str(), "a.txt", 6),
+        ] {
+            assert!(matches!(
+                receiver
+                    .receive(from, tid, name, offset, 11, b"hello")
+                    .await,
+                Err(Error::InvalidFileChunk(_))
+            ));
+        }
+
+        // A chunk shorter than the chunk size must be the last one.
+        let short = receiver
+            .receive(from, &tid, "a.txt", 0, total, b"hello")
+            .await;
+        assert!(matches!(short, Err(Error::InvalidFileChunk(_))));
+
+        // Files larger than the limit are rejected.
+        let limited = FileReceiver::new(&dir).max_file_bytes(10);
+        let large = limited.receive(from, &tid, "a.txt", 0, 11, b"hello").await;
+        assert!(matches!(large, Err(Error::InvalidFileChunk(_))));
+        assert!(limited.incoming.is_empty());
+
+        // Files receiving nothing for long are dropped with their part files.
+        receiver
+            .receive(from, &tid, "b.txt", offset, total, b"hello")
+            .await
+            .unwrap();
+        let part = dir.join(format!(".{tid}.part"));
+        assert!(part.exists());
+        for incoming in receiver.incoming.iter() {
+            incoming.value().lock().await.last_active -=
+                Duration::from_secs(FILE_RECEIVE_IDLE_TIMEOUT_SECS);
+        }
+        receiver.drop_idle();
+        assert!(receiver.incoming.is_empty());
+        assert!(!part.exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_max_tracked_transfers() {
+        let transfers = Transfers::default();
+        let did: Did = rings_core::ecc::SecretKey::random().address().into();
+        let (_, first) = transfers.insert(did, 1).unwrap();
+        for _ in 1..MAX_TRACKED_TRANSFERS {
+            transfers.insert(did, 1).unwrap();
+        }
+        assert!(matches!(
+            transfers.insert(did, 1),
+            Err(Error::TooManyTransfers)
+        ));
+
+        // Finished transfers are evicted to make room.
+        first.finish(TransferState::Completed);
+        transfers.insert(did, 1).unwrap();
+        assert_eq!(transfers.0.len(), MAX_TRACKED_TRANSFERS);
+    }
+}
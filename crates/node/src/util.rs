@@ -139,6 +139,68 @@ pub mod loader {
     impl ResourceLoader for Seed {}
 }
 
+#[cfg(feature = "node")]
+pub mod dns {
+    //! A module to dial remote nodes over both IPv4 and IPv6.
+
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use reqwest::dns::Addrs;
+    use reqwest::dns::Name;
+    use reqwest::dns::Resolve;
+    use reqwest::dns::Resolving;
+
+    /// Timeout of connecting to a remote node, including the fallback to another address.
+    pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Resolver returning the addresses of both families with the preferred one first.
+    /// The http connector tries the first family and races the other one after a short
+    /// delay (happy eyeballs), so a host only reachable by one family is still dialed quickly.
+    #[derive(Debug, Clone, Default)]
+    pub struct DualStackResolver {
+        prefer_ipv6: Option<bool>,
+    }
+
+    impl DualStackResolver {
+        /// Create a resolver. `None` keeps the order given by the system.
+        pub fn new(prefer_ipv6: Option<bool>) -> Self {
+            Self { prefer_ipv6 }
+        }
+    }
+
+    impl Resolve for DualStackResolver {
+        fn resolve(&self, name: Name) -> Resolving {
+            let prefer_ipv6 = self.prefer_ipv6;
+            Box::pin(async move {
+                let addrs = tokio::net::lookup_host((name.as_str(), 0)).await?;
+                let addrs = sort_by_family(addrs.collect(), prefer_ipv6);
+                Ok(Box::new(addrs.into_iter()) as Addrs)
+            })
+        }
+    }
+
+    /// Move addresses of the preferred family to the front, keeping the order inside each family.
+    pub fn sort_by_family(
+        mut addrs: Vec<SocketAddr>,
+        prefer_ipv6: Option<bool>,
+    ) -> Vec<SocketAddr> {
+        if let Some(prefer_ipv6) = prefer_ipv6 {
+            addrs.sort_by_key(|addr| addr.is_ipv6() != prefer_ipv6);
+        }
+        addrs
+    }
+
+    /// Build a http client dialing with [DualStackResolver].
+    pub fn http_client(prefer_ipv6: Option<bool>) -> reqwest::Result<reqwest::Client> {
+        reqwest::Client::builder()
+            .dns_resolver(Arc::new(DualStackResolver::new(prefer_ipv6)))
+            .connect_timeout(CONNECT_TIMEOUT)
+            .build()
+    }
+}
+
 #[cfg(test)]
 #[cfg(feature = "node")]
 mod tests {
@@ -185,4 +247,46 @@ mod tests {
         let result = expand_home(input).unwrap();
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn test_sort_by_family() {
+        let v4: std::net::SocketAddr = "127.0.0.1:80".parse().unwrap();
+        let v6: std::net::SocketAddr = "[::1]:80".parse().unwrap();
+        let v4_2: std::net::SocketAddr = "10.0.0.1:80".parse().unwrap();
+        let addrs = vec![v4, v6, v4_2];
+
+        assert_eq!(dns::sort_by_family(addrs.clone(), None), addrs);
+        assert_eq!(dns::sort_by_family(addrs.clone(), Some(true)), vec![
+            v6, v4, v4_2
+        ]);
+        assert_eq!(dns::sort_by_family(addrs, Some(false)), vec![v4, v4_2, v6]);
+    }
+
+    #[tokio::test]
+    async fn test_dual_stack_fallback() {
+        // Only listen on IPv4, while localhost may be resolved to ::1 first.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            use tokio::io::AsyncReadExt;
+            use tokio::io::AsyncWriteExt;
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = [0u8; 1024];
+                let _ = stream.read(&mut buf).await;
+                let _ = stream
+                    .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nok")
+                    .await;
+            }
+        });
+
+        for prefer_ipv6 in [Some(true), Some(false), None] {
+            let client = dns::http_client(prefer_ipv6).unwrap();
+            let resp = client
+                .get(format!("http://localhost:{}/", port))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(resp.text().await.unwrap(), "ok");
+        }
+    }
 }
@@ -45,6 +45,14 @@ impl Client {
         }
     }
 
+    /// Creates a new Client instance sending requests with the given http client.
+    pub fn new_with_http_client(endpoint_url: &str, client: HttpClient) -> Self {
+        Self {
+            client,
+            endpoint_url: endpoint_url.to_string(),
+        }
+    }
+
     pub async fn call_method<T>(&self, method: Method, req: &impl Serialize) -> Result<T>
     where T: DeserializeOwned {
         use jsonrpc_core::*;
@@ -211,4 +219,73 @@ impl Client {
     pub async fn node_did(&self, req: &NodeDidRequest) -> Result<NodeDidResponse> {
         self.call_method(Method::NodeDid, req).await
     }
+
+    /// Stores a value under the given key on DHT.
+    pub async fn dht_store(&self, req: &DhtStoreRequest) -> Result<DhtStoreResponse> {
+        self.call_method(Method::DhtStore, req).await
+    }
+
+    /// Fetches the values stored under the given key from DHT.
+    pub async fn dht_fetch(&self, req: &DhtFetchRequest) -> Result<DhtFetchResponse> {
+        self.call_method(Method::DhtFetch, req).await
+    }
+
+    /// Locates the node responsible for the given key without storing anything.
+    pub async fn dht_locate(&self, req: &DhtLocateRequest) -> Result<DhtLocateResponse> {
+        self.call_method(Method::DhtLocate, req).await
+    }
+
+    /// Creates or joins the subring with the given name.
+    pub async fn subring_join(&self, req: &SubringJoinRequest) -> Result<SubringJoinResponse> {
+        self.call_method(Method::SubringJoin, req).await
+    }
+
+    /// Lists the members of the subring with the given name.
+    pub async fn subring_list(&self, req: &SubringListRequest) -> Result<SubringListResponse> {
+        self.call_method(Method::SubringList, req).await
+    }
+
+    /// Retrieves the stabilization status of the ring on the node.
+    pub async fn ring_status(&self, req: &RingStatusRequest) -> Result<RingStatusResponse> {
+        self.call_method(Method::RingStatus, req).await
+    }
+
+    /// Sends a local file of the node to a peer, returns the id of transfer.
+    pub async fn send_file(&self, req: &SendFileRequest) -> Result<SendFileResponse> {
+        self.call_method(Method::SendFile, req).await
+    }
+
+    /// Retrieves progress of a file transfer started by `send_file`.
+    pub async fn transfer_status(
+        &self,
+        req: &TransferStatusRequest,
+    ) -> Result<TransferStatusResponse> {
+        self.call_method(Method::TransferStatus, req).await
+    }
+
+    /// Stops sending the rest of a file transfer.
+    pub async fn cancel_transfer(
+        &self,
+        req: &CancelTransferRequest,
+    ) -> Result<CancelTransferResponse> {
+        self.call_method(Method::CancelTransfer, req).await
+    }
+
+    /// List entries of finger table of the node.
+    pub async fn finger_table(&self, req: &FingerTableRequest) -> Result<FingerTableResponse> {
+        self.call_method(Method::FingerTable, req).await
+    }
+
+    /// List successors of the node.
+    pub async fn successor_list(
+        &self,
+        req: &SuccessorListRequest,
+    ) -> Result<SuccessorListResponse> {
+        self.call_method(Method::SuccessorList, req).await
+    }
+
+    /// Retrieve predecessor of the node.
+    pub async fn predecessor(&self, req: &PredecessorRequest) -> Result<PredecessorResponse> {
+        self.call_method(Method::Predecessor, req).await
+    }
 }
@@ -40,6 +40,30 @@ pub enum Method {
     NodeInfo,
     /// Retrieve Node DID
     NodeDid,
+    /// Store a value under a key on DHT
+    DhtStore,
+    /// Fetch values of a key from DHT
+    DhtFetch,
+    /// Locate the node responsible for a key on DHT
+    DhtLocate,
+    /// Create or join a subring
+    SubringJoin,
+    /// List members of a subring
+    SubringList,
+    /// Retrieve stabilization status of the ring
+    RingStatus,
+    /// Send a local file to a peer in chunks
+    SendFile,
+    /// Retrieve progress of a file transfer
+    TransferStatus,
+    /// Cancel a file transfer
+    CancelTransfer,
+    /// List entries of local finger table
+    FingerTable,
+    /// List successors of local node
+    SuccessorList,
+    /// Retrieve predecessor of local node
+    Predecessor,
 }
 
 impl Method {
@@ -62,6 +86,18 @@ impl Method {
             Method::LookupService => "lookupService",
             Method::NodeInfo => "nodeInfo",
             Method::NodeDid => "nodeDid",
+            Method::DhtStore => "dhtStore",
+            Method::DhtFetch => "dhtFetch",
+            Method::DhtLocate => "dhtLocate",
+            Method::SubringJoin => "subringJoin",
+            Method::SubringList => "subringList",
+            Method::RingStatus => "ringStatus",
+            Method::SendFile => "sendFile",
+            Method::TransferStatus => "transferStatus",
+            Method::CancelTransfer => "cancelTransfer",
+            Method::FingerTable => "fingerTable",
+            Method::SuccessorList => "successorList",
+            Method::Predecessor => "predecessor",
         }
     }
 }
@@ -93,6 +129,18 @@ impl TryFrom<&str> for Method {
             "lookupService" => Method::LookupService,
             "nodeInfo" => Method::NodeInfo,
             "nodeDid" => Method::NodeDid,
+            "dhtStore" => Method::DhtStore,
+            "dhtFetch" => Method::DhtFetch,
+            "dhtLocate" => Method::DhtLocate,
+            "subringJoin" => Method::SubringJoin,
+            "subringList" => Method::SubringList,
+            "ringStatus" => Method::RingStatus,
+            "sendFile" => Method::SendFile,
+            "transferStatus" => Method::TransferStatus,
+            "cancelTransfer" => Method::CancelTransfer,
+            "fingerTable" => Method::FingerTable,
+            "successorList" => Method::SuccessorList,
+            "predecessor" => Method::Predecessor,
             _ => return Err(Error::InvalidMethod),
         })
     }
@@ -4,6 +4,9 @@ pub mod rings_node_handler;
 use rings_core::inspect::ConnectionInspect;
 use rings_core::inspect::StorageInspect;
 use rings_core::inspect::SwarmInspect;
+use rings_core::swarm::ConnectionStats;
+use rings_core::swarm::PeerStats;
+use rings_core::swarm::ReconnectState;
 
 impl From<SwarmInspect> for rings_node::SwarmInfo {
     fn from(inspect: SwarmInspect) -> Self {
@@ -54,6 +57,48 @@ impl From<ConnectionInspect> for rings_node::PeerInfo {
         rings_node::PeerInfo {
             did: value.did,
             state: value.state,
+            ice_state: value.ice_state,
+            data_channel_ready: value.data_channel_ready,
+            latency_ms: value.latency_ms,
+            stats: value.stats.map(|x| x.into()),
+            connection_stats: value.connection_stats.map(|x| x.into()),
+        }
+    }
+}
+
+impl From<PeerStats> for rings_node::PeerStats {
+    fn from(value: PeerStats) -> Self {
+        rings_node::PeerStats {
+            bytes_sent: value.current.bytes_sent,
+            bytes_recv: value.current.bytes_recv,
+            messages_sent: value.current.messages_sent,
+            messages_recv: value.current.messages_recv,
+            uptime_ms: value.uptime.as_millis() as u64,
+            total_bytes_sent: value.total.bytes_sent,
+            total_bytes_recv: value.total.bytes_recv,
+            total_messages_sent: value.total.messages_sent,
+            total_messages_recv: value.total.messages_recv,
+        }
+    }
+}
+
+impl From<ConnectionStats> for rings_node::ConnectionStats {
+    fn from(value: ConnectionStats) -> Self {
+        rings_node::ConnectionStats {
+            current_rtt_ms: value.current_rtt_ms,
+            bytes_sent: value.bytes_sent,
+            bytes_received: value.bytes_received,
+            candidate_pair_state: value.candidate_pair_state,
+        }
+    }
+}
+
+impl From<ReconnectState> for rings_node::ReconnectState {
+    fn from(value: ReconnectState) -> Self {
+        rings_node::ReconnectState {
+            did: value.peer.to_string(),
+            attempts: value.attempts,
+            next_attempt_at_ms: value.next_attempt_at_ms as u64,
         }
     }
 }
@@ -6,6 +6,60 @@ pub struct PeerInfo {
     pub did: ::prost::alloc::string::String,
     #[prost(string, tag = "2")]
     pub state: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub ice_state: ::prost::alloc::string::String,
+    #[prost(bool, tag = "4")]
+    pub data_channel_ready: bool,
+    #[prost(uint64, optional, tag = "5")]
+    #[serde(default)]
+    pub latency_ms: ::core::option::Option<u64>,
+    #[prost(message, optional, tag = "6")]
+    #[serde(default)]
+    pub stats: ::core::option::Option<PeerStats>,
+    #[prost(message, optional, tag = "7")]
+    #[serde(default)]
+    pub connection_stats: ::core::option::Option<ConnectionStats>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PeerStats {
+    /// Traffic of the current connection, reset on reconnection.
+    #[prost(uint64, tag = "1")]
+    pub bytes_sent: u64,
+    #[prost(uint64, tag = "2")]
+    pub bytes_recv: u64,
+    #[prost(uint64, tag = "3")]
+    pub messages_sent: u64,
+    #[prost(uint64, tag = "4")]
+    pub messages_recv: u64,
+    #[prost(uint64, tag = "5")]
+    pub uptime_ms: u64,
+    /// Traffic of all connections to the peer.
+    #[prost(uint64, tag = "6")]
+    pub total_bytes_sent: u64,
+    #[prost(uint64, tag = "7")]
+    pub total_bytes_recv: u64,
+    #[prost(uint64, tag = "8")]
+    pub total_messages_sent: u64,
+    #[prost(uint64, tag = "9")]
+    pub total_messages_recv: u64,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ConnectionStats {
+    /// Reported by webrtc on the selected candidate pair.
+    #[prost(double, optional, tag = "1")]
+    #[serde(default)]
+    pub current_rtt_ms: ::core::option::Option<f64>,
+    #[prost(uint64, tag = "2")]
+    pub bytes_sent: u64,
+    #[prost(uint64, tag = "3")]
+    pub bytes_received: u64,
+    #[prost(string, optional, tag = "4")]
+    #[serde(default)]
+    pub candidate_pair_state: ::core::option::Option<::prost::alloc::string::String>,
 }
 #[derive(serde::Serialize, serde::Deserialize)]
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -121,11 +175,18 @@ pub struct SendCustomMessageRequest {
     pub destination_did: ::prost::alloc::string::String,
     #[prost(string, tag = "2")]
     pub data: ::prost::alloc::string::String,
+    /// Encrypt data to the session of destination, only the destination can decrypt it.
+    #[prost(bool, tag = "3")]
+    #[serde(default)]
+    pub encrypt: bool,
 }
 #[derive(serde::Serialize, serde::Deserialize)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
-pub struct SendCustomMessageResponse {}
+pub struct SendCustomMessageResponse {
+    #[prost(string, tag = "1")]
+    pub tx_id: ::prost::alloc::string::String,
+}
 #[derive(serde::Serialize, serde::Deserialize)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -264,11 +325,35 @@ pub struct SwarmInfo {
 #[derive(serde::Serialize, serde::Deserialize)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ReconnectState {
+    #[prost(string, tag = "1")]
+    pub did: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "2")]
+    pub attempts: u32,
+    /// Timestamp in milliseconds of the next attempt.
+    #[prost(uint64, tag = "3")]
+    pub next_attempt_at_ms: u64,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
 pub struct NodeInfoResponse {
     #[prost(string, tag = "1")]
     pub version: ::prost::alloc::string::String,
     #[prost(message, optional, tag = "2")]
     pub swarm: ::core::option::Option<SwarmInfo>,
+    #[prost(string, tag = "3")]
+    pub did: ::prost::alloc::string::String,
+    /// Timestamp in milliseconds when the session of node expires.
+    #[prost(uint64, tag = "4")]
+    pub session_expires_at_ms: u64,
+    /// Urls of configured STUN/TURN servers, credentials are omitted.
+    #[prost(string, repeated, tag = "5")]
+    pub ice_servers: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// Lost peers being reconnected.
+    #[prost(message, repeated, tag = "6")]
+    #[serde(default)]
+    pub reconnects: ::prost::alloc::vec::Vec<ReconnectState>,
 }
 #[derive(serde::Serialize, serde::Deserialize)]
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -281,3 +366,206 @@ pub struct NodeDidResponse {
     #[prost(string, tag = "1")]
     pub did: ::prost::alloc::string::String,
 }
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DhtStoreRequest {
+    #[prost(string, tag = "1")]
+    pub key: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub value: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DhtStoreResponse {
+    #[prost(string, tag = "1")]
+    pub did: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DhtFetchRequest {
+    #[prost(string, tag = "1")]
+    pub key: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DhtFetchResponse {
+    #[prost(string, tag = "1")]
+    pub did: ::prost::alloc::string::String,
+    #[prost(string, repeated, tag = "2")]
+    pub values: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DhtLocateRequest {
+    #[prost(string, tag = "1")]
+    pub key: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DhtLocateResponse {
+    #[prost(string, tag = "1")]
+    pub vid: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub did: ::prost::alloc::string::String,
+    #[prost(bool, tag = "3")]
+    pub remote: bool,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SubringJoinRequest {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SubringJoinResponse {
+    #[prost(string, tag = "1")]
+    pub did: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SubringListRequest {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SubringListResponse {
+    #[prost(string, tag = "1")]
+    pub did: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub creator: ::prost::alloc::string::String,
+    #[prost(string, repeated, tag = "3")]
+    pub members: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RingStatusRequest {}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RingStatusResponse {
+    #[prost(string, repeated, tag = "1")]
+    pub successors: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "2")]
+    pub predecessor: ::core::option::Option<::prost::alloc::string::String>,
+    /// Count of filled entries in finger table.
+    #[prost(uint64, tag = "3")]
+    pub finger_filled: u64,
+    #[prost(uint64, tag = "4")]
+    pub finger_size: u64,
+    /// Count of consecutive stabilization rounds without topology change.
+    #[prost(uint64, tag = "5")]
+    pub unchanged_rounds: u64,
+    #[prost(bool, tag = "6")]
+    pub stabilized: bool,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SendFileRequest {
+    #[prost(string, tag = "1")]
+    pub destination_did: ::prost::alloc::string::String,
+    /// Path of the local file to send, which must be under send_dir of the node.
+    #[prost(string, tag = "2")]
+    pub path: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SendFileResponse {
+    #[prost(string, tag = "1")]
+    pub transfer_id: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TransferStatusRequest {
+    #[prost(string, tag = "1")]
+    pub transfer_id: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TransferStatusResponse {
+    #[prost(string, tag = "1")]
+    pub destination_did: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "2")]
+    pub bytes_sent: u64,
+    #[prost(uint64, tag = "3")]
+    pub total: u64,
+    /// One of sending, completed, failed and cancelled.
+    #[prost(string, tag = "4")]
+    pub state: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CancelTransferRequest {
+    #[prost(string, tag = "1")]
+    pub transfer_id: ::prost::alloc::string::String,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CancelTransferResponse {
+    /// False if the transfer is finished already.
+    #[prost(bool, tag = "1")]
+    pub cancelled: bool,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct FingerEntry {
+    #[prost(uint64, tag = "1")]
+    pub index: u64,
+    /// The id covered by this entry, which is did + 2^index.
+    #[prost(string, tag = "2")]
+    pub target: ::prost::alloc::string::String,
+    #[prost(string, optional, tag = "3")]
+    pub did: ::core::option::Option<::prost::alloc::string::String>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct FingerTableRequest {}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct FingerTableResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub entries: ::prost::alloc::vec::Vec<FingerEntry>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SuccessorListRequest {}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SuccessorListResponse {
+    #[prost(string, repeated, tag = "1")]
+    pub successors: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PredecessorRequest {}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PredecessorResponse {
+    #[prost(string, optional, tag = "1")]
+    pub predecessor: ::core::option::Option<::prost::alloc::string::String>,
+}
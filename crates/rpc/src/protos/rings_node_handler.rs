@@ -48,7 +48,19 @@ impl InternalRpcHandler {
             + HandleRpc<RegisterServiceRequest, RegisterServiceResponse>
             + HandleRpc<LookupServiceRequest, LookupServiceResponse>
             + HandleRpc<NodeInfoRequest, NodeInfoResponse>
-            + HandleRpc<NodeDidRequest, NodeDidResponse>,
+            + HandleRpc<NodeDidRequest, NodeDidResponse>
+            + HandleRpc<DhtStoreRequest, DhtStoreResponse>
+            + HandleRpc<DhtFetchRequest, DhtFetchResponse>
+            + HandleRpc<DhtLocateRequest, DhtLocateResponse>
+            + HandleRpc<SubringJoinRequest, SubringJoinResponse>
+            + HandleRpc<SubringListRequest, SubringListResponse>
+            + HandleRpc<RingStatusRequest, RingStatusResponse>
+            + HandleRpc<SendFileRequest, SendFileResponse>
+            + HandleRpc<TransferStatusRequest, TransferStatusResponse>
+            + HandleRpc<CancelTransferRequest, CancelTransferResponse>
+            + HandleRpc<FingerTableRequest, FingerTableResponse>
+            + HandleRpc<SuccessorListRequest, SuccessorListResponse>
+            + HandleRpc<PredecessorRequest, PredecessorResponse>,
     {
         let method = Method::try_from(method.as_str()).map_err(|_| Error {
             code: ErrorCode::MethodNotFound,
@@ -153,6 +165,78 @@ impl InternalRpcHandler {
                 let resp = processor.handle_rpc(req).await?;
                 serde_json::to_value(resp).map_err(|_| Error::new(ErrorCode::ParseError))
             }
+            Method::DhtStore => {
+                let req = serde_json::from_value::<DhtStoreRequest>(params)
+                    .map_err(|e| Error::invalid_params(e.to_string()))?;
+                let resp = processor.handle_rpc(req).await?;
+                serde_json::to_value(resp).map_err(|_| Error::new(ErrorCode::ParseError))
+            }
+            Method::DhtFetch => {
+                let req = serde_json::from_value::<DhtFetchRequest>(params)
+                    .map_err(|e| Error::invalid_params(e.to_string()))?;
+                let resp = processor.handle_rpc(req).await?;
+                serde_json::to_value(resp).map_err(|_| Error::new(ErrorCode::ParseError))
+            }
+            Method::DhtLocate => {
+                let req = serde_json::from_value::<DhtLocateRequest>(params)
+                    .map_err(|e| Error::invalid_params(e.to_string()))?;
+                let resp = processor.handle_rpc(req).await?;
+                serde_json::to_value(resp).map_err(|_| Error::new(ErrorCode::ParseError))
+            }
+            Method::SubringJoin => {
+                let req = serde_json::from_value::<SubringJoinRequest>(params)
+                    .map_err(|e| Error::invalid_params(e.to_string()))?;
+                let resp = processor.handle_rpc(req).await?;
+                serde_json::to_value(resp).map_err(|_| Error::new(ErrorCode::ParseError))
+            }
+            Method::SubringList => {
+                let req = serde_json::from_value::<SubringListRequest>(params)
+                    .map_err(|e| Error::invalid_params(e.to_string()))?;
+                let resp = processor.handle_rpc(req).await?;
+                serde_json::to_value(resp).map_err(|_| Error::new(ErrorCode::ParseError))
+            }
+            Method::RingStatus => {
+                let req = serde_json::from_value::<RingStatusRequest>(params)
+                    .map_err(|e| Error::invalid_params(e.to_string()))?;
+                let resp = processor.handle_rpc(req).await?;
+                serde_json::to_value(resp).map_err(|_| Error::new(ErrorCode::ParseError))
+            }
+            Method::SendFile => {
+                let req = serde_json::from_value::<SendFileRequest>(params)
+                    .map_err(|e| Error::invalid_params(e.to_string()))?;
+                let resp = processor.handle_rpc(req).await?;
+                serde_json::to_value(resp).map_err(|_| Error::new(ErrorCode::ParseError))
+            }
+            Method::TransferStatus => {
+                let req = serde_json::from_value::<TransferStatusRequest>(params)
+                    .map_err(|e| Error::invalid_params(e.to_string()))?;
+                let resp = processor.handle_rpc(req).await?;
+                serde_json::to_value(resp).map_err(|_| Error::new(ErrorCode::ParseError))
+            }
+            Method::CancelTransfer => {
+                let req = serde_json::from_value::<CancelTransferRequest>(params)
+                    .map_err(|e| Error::invalid_params(e.to_string()))?;
+                let resp = processor.handle_rpc(req).await?;
+                serde_json::to_value(resp).map_err(|_| Error::new(ErrorCode::ParseError))
+            }
+            Method::FingerTable => {
+                let req = serde_json::from_value::<FingerTableRequest>(params)
+                    .map_err(|e| Error::invalid_params(e.to_string()))?;
+                let resp = processor.handle_rpc(req).await?;
+                serde_json::to_value(resp).map_err(|_| Error::new(ErrorCode::ParseError))
+            }
+            Method::SuccessorList => {
+                let req = serde_json::from_value::<SuccessorListRequest>(params)
+                    .map_err(|e| Error::invalid_params(e.to_string()))?;
+                let resp = processor.handle_rpc(req).await?;
+                serde_json::to_value(resp).map_err(|_| Error::new(ErrorCode::ParseError))
+            }
+            Method::Predecessor => {
+                let req = serde_json::from_value::<PredecessorRequest>(params)
+                    .map_err(|e| Error::invalid_params(e.to_string()))?;
+                let resp = processor.handle_rpc(req).await?;
+                serde_json::to_value(resp).map_err(|_| Error::new(ErrorCode::ParseError))
+            }
         }
     }
 }
@@ -70,6 +70,16 @@ impl InnerTransportCallback {
                     tracing::error!("Callback on_message failed: {e:?}")
                 }
             }
+            TransportMessage::Ping(nonce) => {
+                if let Err(e) = self.callback.on_ping(&self.cid, *nonce).await {
+                    tracing::error!("Callback on_ping failed: {e:?}")
+                }
+            }
+            TransportMessage::Pong(nonce) => {
+                if let Err(e) = self.callback.on_pong(&self.cid, *nonce).await {
+                    tracing::error!("Callback on_pong failed: {e:?}")
+                }
+            }
         }
     }
 }
@@ -8,8 +8,10 @@ use serde::de::DeserializeOwned;
 use serde::Serialize;
 
 use crate::core::transport::ConnectionInterface;
+use crate::core::transport::DataChannelConfig;
 use crate::core::transport::TransportMessage;
 use crate::core::transport::WebrtcConnectionState;
+use crate::core::transport::WebrtcIceConnectionState;
 use crate::error::Error;
 use crate::error::Result;
 
@@ -56,17 +58,42 @@ where
 {
     type Sdp = C::Sdp;
     type Error = C::Error;
+    type DataChannel = C::DataChannel;
 
     async fn send_message(&self, msg: TransportMessage) -> Result<()> {
         self.upgrade()?.send_message(msg).await
     }
 
+    async fn open_channel(
+        &self,
+        label: &str,
+        config: DataChannelConfig,
+    ) -> Result<Arc<Self::DataChannel>> {
+        self.upgrade()?.open_channel(label, config).await
+    }
+
+    async fn send_message_on(&self, label: &str, msg: TransportMessage) -> Result<()> {
+        self.upgrade()?.send_message_on(label, msg).await
+    }
+
     fn webrtc_connection_state(&self) -> WebrtcConnectionState {
         self.upgrade()
             .map(|c| c.webrtc_connection_state())
             .unwrap_or(WebrtcConnectionState::Closed)
     }
 
+    fn webrtc_ice_connection_state(&self) -> WebrtcIceConnectionState {
+        self.upgrade()
+            .map(|c| c.webrtc_ice_connection_state())
+            .unwrap_or(WebrtcIceConnectionState::Closed)
+    }
+
+    fn is_data_channel_open(&self) -> bool {
+        self.upgrade()
+            .map(|c| c.is_data_channel_open())
+            .unwrap_or(false)
+    }
+
     async fn get_stats(&self) -> Vec<String> {
         let Ok(c) = self.upgrade() else {
             return Vec::new();
@@ -104,17 +131,42 @@ where
 {
     type Sdp = C::Sdp;
     type Error = C::Error;
+    type DataChannel = C::DataChannel;
 
     async fn send_message(&self, msg: TransportMessage) -> Result<()> {
         self.upgrade()?.send_message(msg).await
     }
 
+    async fn open_channel(
+        &self,
+        label: &str,
+        config: DataChannelConfig,
+    ) -> Result<Arc<Self::DataChannel>> {
+        self.upgrade()?.open_channel(label, config).await
+    }
+
+    async fn send_message_on(&self, label: &str, msg: TransportMessage) -> Result<()> {
+        self.upgrade()?.send_message_on(label, msg).await
+    }
+
     fn webrtc_connection_state(&self) -> WebrtcConnectionState {
         self.upgrade()
             .map(|c| c.webrtc_connection_state())
             .unwrap_or(WebrtcConnectionState::Closed)
     }
 
+    fn webrtc_ice_connection_state(&self) -> WebrtcIceConnectionState {
+        self.upgrade()
+            .map(|c| c.webrtc_ice_connection_state())
+            .unwrap_or(WebrtcIceConnectionState::Closed)
+    }
+
+    fn is_data_channel_open(&self) -> bool {
+        self.upgrade()
+            .map(|c| c.is_data_channel_open())
+            .unwrap_or(false)
+    }
+
     async fn get_stats(&self) -> Vec<String> {
         let Ok(c) = self.upgrade() else {
             return Vec::new();
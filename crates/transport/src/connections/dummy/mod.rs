@@ -5,6 +5,7 @@ use std::time::Duration;
 use async_trait::async_trait;
 use bytes::Bytes;
 use dashmap::DashMap;
+use dashmap::DashSet;
 use lazy_static::lazy_static;
 use rand::distributions::Distribution;
 use tokio::sync::mpsc;
@@ -12,11 +13,15 @@ use tokio::task::JoinHandle;
 
 use crate::callback::InnerTransportCallback;
 use crate::connection_ref::ConnectionRef;
+use crate::connections::VirtualDataChannel;
 use crate::core::callback::BoxedTransportCallback;
 use crate::core::transport::ConnectionInterface;
+use crate::core::transport::DataChannelConfig;
 use crate::core::transport::TransportInterface;
 use crate::core::transport::TransportMessage;
 use crate::core::transport::WebrtcConnectionState;
+use crate::core::transport::WebrtcIceConnectionState;
+use crate::core::transport::DEFAULT_CHANNEL_LABEL_PREFIX;
 use crate::error::Error;
 use crate::error::Result;
 use crate::ice_server::IceServer;
@@ -50,11 +55,14 @@ pub struct DummyConnection {
     remote_rand_id: Arc<Mutex<Option<String>>>,
     event_listener: JoinHandle<()>,
     webrtc_connection_state: Arc<Mutex<WebrtcConnectionState>>,
+    /// Labels of additional channels, shared with the remote connection.
+    labeled_channels: DashSet<String>,
 }
 
 /// [DummyTransport] manages all the [DummyConnection] and
 /// provides methods to create, get and close connections.
 pub struct DummyTransport {
+    ice_servers: Mutex<Vec<IceServer>>,
     pool: Pool<DummyConnection>,
 }
 
@@ -81,6 +89,7 @@ impl DummyConnection {
             remote_rand_id: Default::default(),
             event_listener,
             webrtc_connection_state: Arc::new(Mutex::new(WebrtcConnectionState::New)),
+            labeled_channels: DashSet::new(),
         }
     }
 
@@ -143,9 +152,42 @@ impl DummyConnection {
 impl DummyTransport {
     /// Create a new [DummyTransport] instance.
     pub fn new(ice_servers: &str, _external_address: Option<String>) -> Self {
-        let _ice_servers = IceServer::vec_from_str(ice_servers).unwrap();
+        let ice_servers = IceServer::vec_from_str(ice_servers).unwrap();
 
-        Self { pool: Pool::new() }
+        Self {
+            ice_servers: Mutex::new(ice_servers),
+            pool: Pool::new(),
+        }
+    }
+
+    /// Enable relay fallback. Dummy connections have no ICE, so this only checks
+    /// that at least one TURN server is configured.
+    pub fn enable_relay_fallback(&mut self) -> Result<()> {
+        IceServer::check_relay_fallback(&self.ice_servers.lock().unwrap())?;
+        Ok(())
+    }
+
+    /// Take the peers to redial with TURN candidates only. Connections here have no ICE,
+    /// so there is none.
+    pub fn take_relay_redials(&self) -> Vec<String> {
+        vec![]
+    }
+
+    /// Check data channel config. Dummy connections are always reliable and ordered,
+    /// so the config takes no effect.
+    pub fn set_data_channel_config(&mut self, config: DataChannelConfig) -> Result<()> {
+        config.validate()
+    }
+
+    /// Set the time to wait for ICE gathering. Dummy connections have no ICE,
+    /// so this takes no effect.
+    pub fn set_ice_gathering_timeout(&mut self, _timeout: Duration) {}
+
+    /// Replace the ice servers. Dummy connections have no ICE, so they are only kept
+    /// for checking relay fallback.
+    pub async fn update_ice_servers(&self, ice_servers: Vec<IceServer>) -> Result<()> {
+        *self.ice_servers.lock().unwrap() = ice_servers;
+        Ok(())
     }
 }
 
@@ -153,6 +195,7 @@ impl DummyTransport {
 impl ConnectionInterface for DummyConnection {
     type Sdp = String;
     type Error = Error;
+    type DataChannel = VirtualDataChannel;
 
     async fn send_message(&self, msg: TransportMessage) -> Result<()> {
         self.webrtc_wait_for_data_channel_open().await?;
@@ -167,10 +210,54 @@ impl ConnectionInterface for DummyConnection {
         Ok(())
     }
 
+    async fn open_channel(
+        &self,
+        label: &str,
+        config: DataChannelConfig,
+    ) -> Result<Arc<VirtualDataChannel>> {
+        config.validate()?;
+        if label.starts_with(DEFAULT_CHANNEL_LABEL_PREFIX) || self.labeled_channels.contains(label)
+        {
+            return Err(Error::ChannelLabelTaken(label.to_string()));
+        }
+        self.webrtc_wait_for_data_channel_open().await?;
+
+        // Dummy channels share the same event queue, so they only keep track of labels.
+        self.labeled_channels.insert(label.to_string());
+        if let Some(remote_conn) = self.remote_conn() {
+            remote_conn.labeled_channels.insert(label.to_string());
+        }
+        Ok(Arc::new(VirtualDataChannel::new(label)))
+    }
+
+    async fn send_message_on(&self, label: &str, msg: TransportMessage) -> Result<()> {
+        if !self.labeled_channels.contains(label) {
+            return Err(Error::ChannelNotFound(label.to_string()));
+        }
+        self.send_message(msg).await
+    }
+
     fn webrtc_connection_state(&self) -> WebrtcConnectionState {
         *self.webrtc_connection_state.lock().unwrap()
     }
 
+    fn webrtc_ice_connection_state(&self) -> WebrtcIceConnectionState {
+        // There is no ice agent in dummy connection, derive it from connection state.
+        match self.webrtc_connection_state() {
+            WebrtcConnectionState::Unspecified => WebrtcIceConnectionState::Unspecified,
+            WebrtcConnectionState::New => WebrtcIceConnectionState::New,
+            WebrtcConnectionState::Connecting => WebrtcIceConnectionState::Checking,
+            WebrtcConnectionState::Connected => WebrtcIceConnectionState::Connected,
+            WebrtcConnectionState::Disconnected => WebrtcIceConnectionState::Disconnected,
+            WebrtcConnectionState::Failed => WebrtcIceConnectionState::Failed,
+            WebrtcConnectionState::Closed => WebrtcIceConnectionState::Closed,
+        }
+    }
+
+    fn is_data_channel_open(&self) -> bool {
+        self.webrtc_connection_state() == WebrtcConnectionState::Connected
+    }
+
     async fn get_stats(&self) -> Vec<String> {
         Vec::new()
     }
@@ -0,0 +1,387 @@
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use dashmap::DashMap;
+use dashmap::DashSet;
+use lazy_static::lazy_static;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::callback::InnerTransportCallback;
+use crate::connection_ref::ConnectionRef;
+use crate::connections::VirtualDataChannel;
+use crate::core::callback::BoxedTransportCallback;
+use crate::core::transport::ConnectionInterface;
+use crate::core::transport::DataChannelConfig;
+use crate::core::transport::TransportInterface;
+use crate::core::transport::TransportMessage;
+use crate::core::transport::WebrtcConnectionState;
+use crate::core::transport::WebrtcIceConnectionState;
+use crate::core::transport::DEFAULT_CHANNEL_LABEL_PREFIX;
+use crate::error::Error;
+use crate::error::Result;
+use crate::ice_server::IceServer;
+use crate::notifier::Notifier;
+use crate::pool::Pool;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+lazy_static! {
+    static ref CONNS: DashMap<String, Arc<MemoryConnection>> = DashMap::new();
+}
+
+enum Event {
+    PeerConnectionStateChange(WebrtcConnectionState),
+    DataChannelOpen,
+    DataChannelClose,
+    Message(Bytes),
+}
+
+/// An in-process connection for deterministic testing.
+/// Implements the [ConnectionInterface] trait over in-memory channels. The offer and answer
+/// are ids of connections in the process, so there is no SDP or ICE. Unlike the dummy
+/// connection, messages are delivered at once and in order.
+pub struct MemoryConnection {
+    id: String,
+    event_sender: mpsc::UnboundedSender<Event>,
+    remote_id: Mutex<Option<String>>,
+    event_listener: JoinHandle<()>,
+    webrtc_connection_state: Mutex<WebrtcConnectionState>,
+    /// Labels of additional channels, shared with the remote connection.
+    labeled_channels: DashSet<String>,
+}
+
+/// [MemoryTransport] manages all the [MemoryConnection] and
+/// provides methods to create, get and close connections.
+pub struct MemoryTransport {
+    pool: Pool<MemoryConnection>,
+}
+
+impl MemoryConnection {
+    fn new(callback: InnerTransportCallback) -> Self {
+        let id = NEXT_ID.fetch_add(1, Ordering::SeqCst).to_string();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        let event_listener = tokio::spawn(async move {
+            while let Some(ev) = rx.recv().await {
+                match ev {
+                    Event::PeerConnectionStateChange(state) => {
+                        callback.on_peer_connection_state_change(state).await
+                    }
+                    Event::DataChannelOpen => callback.on_data_channel_open().await,
+                    Event::DataChannelClose => callback.on_data_channel_close(),
+                    Event::Message(data) => callback.on_message(&data).await,
+                }
+            }
+        });
+
+        Self {
+            id,
+            event_sender: tx,
+            remote_id: Mutex::new(None),
+            event_listener,
+            webrtc_connection_state: Mutex::new(WebrtcConnectionState::New),
+            labeled_channels: DashSet::new(),
+        }
+    }
+
+    fn remote_conn(&self) -> Option<Arc<MemoryConnection>> {
+        let id = self.remote_id.lock().unwrap().clone()?;
+        CONNS.get(&id).map(|conn| conn.clone())
+    }
+
+    fn set_remote_id(&self, id: String) -> Result<()> {
+        if !CONNS.contains_key(&id) {
+            return Err(Error::ConnectionNotFound(id));
+        }
+        *self.remote_id.lock().unwrap() = Some(id);
+        Ok(())
+    }
+
+    fn set_webrtc_connection_state(&self, state: WebrtcConnectionState) {
+        {
+            let mut webrtc_connection_state = self.webrtc_connection_state.lock().unwrap();
+            if state == *webrtc_connection_state {
+                return;
+            }
+            *webrtc_connection_state = state;
+        }
+
+        // The listener is gone only if the connection is closed, events are dropped then.
+        let _ = self
+            .event_sender
+            .send(Event::PeerConnectionStateChange(state));
+        match state {
+            WebrtcConnectionState::Connected => {
+                let _ = self.event_sender.send(Event::DataChannelOpen);
+            }
+            WebrtcConnectionState::Closed | WebrtcConnectionState::Disconnected => {
+                let _ = self.event_sender.send(Event::DataChannelClose);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl MemoryTransport {
+    /// Create a new [MemoryTransport] instance. Ice servers are not used.
+    pub fn new(_ice_servers: &str, _external_address: Option<String>) -> Self {
+        Self { pool: Pool::new() }
+    }
+
+    /// Enable relay fallback. Memory connections have no ICE, so this takes no effect.
+    pub fn enable_relay_fallback(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Take the peers to redial with TURN candidates only. Connections here have no ICE,
+    /// so there is none.
+    pub fn take_relay_redials(&self) -> Vec<String> {
+        vec![]
+    }
+
+    /// Check data channel config. Memory connections are always reliable and ordered,
+    /// so the config takes no effect.
+    pub fn set_data_channel_config(&mut self, config: DataChannelConfig) -> Result<()> {
+        config.validate()
+    }
+
+    /// Set the time to wait for ICE gathering. Memory connections have no ICE,
+    /// so this takes no effect.
+    pub fn set_ice_gathering_timeout(&mut self, _timeout: Duration) {}
+
+    /// Replace the ice servers. Memory connections have no ICE, so this takes no effect.
+    pub async fn update_ice_servers(&self, _ice_servers: Vec<IceServer>) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ConnectionInterface for MemoryConnection {
+    type Sdp = String;
+    type Error = Error;
+    type DataChannel = VirtualDataChannel;
+
+    async fn send_message(&self, msg: TransportMessage) -> Result<()> {
+        self.webrtc_wait_for_data_channel_open().await?;
+
+        let data = bincode::serialize(&msg).map(Bytes::from)?;
+        let remote_conn = self
+            .remote_conn()
+            .ok_or_else(|| Error::DataChannelOpen("Remote connection is gone".to_string()))?;
+        remote_conn
+            .event_sender
+            .send(Event::Message(data))
+            .map_err(|_| Error::DataChannelOpen("Remote connection is closed".to_string()))
+    }
+
+    async fn open_channel(
+        &self,
+        label: &str,
+        config: DataChannelConfig,
+    ) -> Result<Arc<VirtualDataChannel>> {
+        config.validate()?;
+        if label.starts_with(DEFAULT_CHANNEL_LABEL_PREFIX) || self.labeled_channels.contains(label)
+        {
+            return Err(Error::ChannelLabelTaken(label.to_string()));
+        }
+        self.webrtc_wait_for_data_channel_open().await?;
+
+        // Memory channels share the same event queue, so they only keep track of labels.
+        self.labeled_channels.insert(label.to_string());
+        if let Some(remote_conn) = self.remote_conn() {
+            remote_conn.labeled_channels.insert(label.to_string());
+        }
+        Ok(Arc::new(VirtualDataChannel::new(label)))
+    }
+
+    async fn send_message_on(&self, label: &str, msg: TransportMessage) -> Result<()> {
+        if !self.labeled_channels.contains(label) {
+            return Err(Error::ChannelNotFound(label.to_string()));
+        }
+        self.send_message(msg).await
+    }
+
+    fn webrtc_connection_state(&self) -> WebrtcConnectionState {
+        *self.webrtc_connection_state.lock().unwrap()
+    }
+
+    fn webrtc_ice_connection_state(&self) -> WebrtcIceConnectionState {
+        // There is no ice agent in memory connection, derive it from connection state.
+        match self.webrtc_connection_state() {
+            WebrtcConnectionState::Unspecified => WebrtcIceConnectionState::Unspecified,
+            WebrtcConnectionState::New => WebrtcIceConnectionState::New,
+            WebrtcConnectionState::Connecting => WebrtcIceConnectionState::Checking,
+            WebrtcConnectionState::Connected => WebrtcIceConnectionState::Connected,
+            WebrtcConnectionState::Disconnected => WebrtcIceConnectionState::Disconnected,
+            WebrtcConnectionState::Failed => WebrtcIceConnectionState::Failed,
+            WebrtcConnectionState::Closed => WebrtcIceConnectionState::Closed,
+        }
+    }
+
+    fn is_data_channel_open(&self) -> bool {
+        self.webrtc_connection_state() == WebrtcConnectionState::Connected
+    }
+
+    async fn get_stats(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    async fn webrtc_create_offer(&self) -> Result<Self::Sdp> {
+        self.set_webrtc_connection_state(WebrtcConnectionState::New);
+        Ok(self.id.clone())
+    }
+
+    async fn webrtc_answer_offer(&self, offer: Self::Sdp) -> Result<Self::Sdp> {
+        self.set_remote_id(offer)?;
+        self.set_webrtc_connection_state(WebrtcConnectionState::Connecting);
+        Ok(self.id.clone())
+    }
+
+    async fn webrtc_accept_answer(&self, answer: Self::Sdp) -> Result<()> {
+        self.set_remote_id(answer)?;
+        self.set_webrtc_connection_state(WebrtcConnectionState::Connected);
+        if let Some(remote_conn) = self.remote_conn() {
+            remote_conn.set_webrtc_connection_state(WebrtcConnectionState::Connected);
+        }
+        Ok(())
+    }
+
+    async fn webrtc_wait_for_data_channel_open(&self) -> Result<()> {
+        // The connecting state means an offer is answered but not accepted by the other side yet,
+        // messages sent then are queued by the remote until it's connected.
+        if matches!(
+            self.webrtc_connection_state(),
+            WebrtcConnectionState::Connected | WebrtcConnectionState::Connecting
+        ) {
+            Ok(())
+        } else {
+            Err(Error::DataChannelOpen(
+                "State is not connected in memory connection".to_string(),
+            ))
+        }
+    }
+
+    async fn close(&self) -> Result<()> {
+        CONNS.remove(&self.id);
+        self.event_listener.abort();
+        self.set_webrtc_connection_state(WebrtcConnectionState::Closed);
+
+        // Simulate remote closing if it's not closed.
+        if let Some(remote_conn) = self.remote_conn() {
+            if remote_conn.webrtc_connection_state() != WebrtcConnectionState::Closed {
+                remote_conn.set_webrtc_connection_state(WebrtcConnectionState::Disconnected);
+                remote_conn.set_webrtc_connection_state(WebrtcConnectionState::Closed);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TransportInterface for MemoryTransport {
+    type Connection = MemoryConnection;
+    type Error = Error;
+
+    async fn new_connection(&self, cid: &str, callback: BoxedTransportCallback) -> Result<()> {
+        if let Ok(existed_conn) = self.pool.connection(cid) {
+            if matches!(
+                existed_conn.webrtc_connection_state(),
+                WebrtcConnectionState::New
+                    | WebrtcConnectionState::Connecting
+                    | WebrtcConnectionState::Connected
+            ) {
+                return Err(Error::ConnectionAlreadyExists(cid.to_string()));
+            }
+        }
+
+        let inner_callback = InnerTransportCallback::new(cid, callback, Notifier::default());
+        let conn = MemoryConnection::new(inner_callback);
+
+        self.pool.safely_insert(cid, conn)?;
+
+        let conn = self.connection(cid)?.upgrade()?;
+        CONNS.insert(conn.id.clone(), conn);
+
+        Ok(())
+    }
+
+    async fn close_connection(&self, cid: &str) -> Result<()> {
+        self.pool.safely_remove(cid).await
+    }
+
+    fn connection(&self, cid: &str) -> Result<ConnectionRef<Self::Connection>> {
+        self.pool.connection(cid)
+    }
+
+    fn connections(&self) -> Vec<(String, ConnectionRef<Self::Connection>)> {
+        self.pool.connections()
+    }
+
+    fn connection_ids(&self) -> Vec<String> {
+        self.pool.connection_ids()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::callback::TransportCallback;
+
+    struct Recorder(mpsc::UnboundedSender<Bytes>);
+
+    #[async_trait]
+    impl TransportCallback for Recorder {
+        async fn on_message(
+            &self,
+            _cid: &str,
+            msg: &[u8],
+        ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+            self.0.send(Bytes::copy_from_slice(msg)).unwrap();
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_memory_connection_in_order() {
+        let (tx, _) = mpsc::unbounded_channel();
+        let (tx2, mut rx2) = mpsc::unbounded_channel();
+        let t1 = MemoryTransport::new("", None);
+        let t2 = MemoryTransport::new("", None);
+        t1.new_connection("b", Box::new(Recorder(tx)))
+            .await
+            .unwrap();
+        t2.new_connection("a", Box::new(Recorder(tx2)))
+            .await
+            .unwrap();
+        let c1 = t1.connection("b").unwrap();
+        let c2 = t2.connection("a").unwrap();
+
+        let offer = c1.webrtc_create_offer().await.unwrap();
+        let answer = c2.webrtc_answer_offer(offer).await.unwrap();
+        c1.webrtc_accept_answer(answer).await.unwrap();
+        assert!(c1.is_data_channel_open());
+        assert!(c2.is_data_channel_open());
+
+        for i in 0..10u8 {
+            let msg = TransportMessage::Custom(vec![i]);
+            c1.send_message(msg).await.unwrap();
+        }
+        for i in 0..10u8 {
+            assert_eq!(rx2.recv().await.unwrap(), Bytes::from(vec![i]));
+        }
+
+        t1.close_connection("b").await.unwrap();
+        assert!(c1
+            .send_message(TransportMessage::Custom(vec![]))
+            .await
+            .is_err());
+        assert!(c2.webrtc_answer_offer("unknown".to_string()).await.is_err());
+    }
+}
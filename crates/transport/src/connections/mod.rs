@@ -1,9 +1,11 @@
 //! Default using `WebrtcConnection` for native environment.
 //! Plus a `WebSysWebrtcConnection` for wasm environment.
-//! Also provide a `DummyConnection` for testing.
+//! Also provide a `DummyConnection` and a `MemoryConnection` for testing.
 
 #[cfg(feature = "dummy")]
 mod dummy;
+#[cfg(feature = "memory")]
+mod memory;
 #[cfg(feature = "native-webrtc")]
 mod native_webrtc;
 #[cfg(feature = "web-sys-webrtc")]
@@ -13,6 +15,10 @@ mod web_sys_webrtc;
 pub use crate::connections::dummy::DummyConnection;
 #[cfg(feature = "dummy")]
 pub use crate::connections::dummy::DummyTransport;
+#[cfg(feature = "memory")]
+pub use crate::connections::memory::MemoryConnection;
+#[cfg(feature = "memory")]
+pub use crate::connections::memory::MemoryTransport;
 #[cfg(feature = "native-webrtc")]
 pub use crate::connections::native_webrtc::WebrtcConnection;
 #[cfg(feature = "native-webrtc")]
@@ -21,3 +27,135 @@ pub use crate::connections::native_webrtc::WebrtcTransport;
 pub use crate::connections::web_sys_webrtc::WebSysWebrtcConnection;
 #[cfg(feature = "web-sys-webrtc")]
 pub use crate::connections::web_sys_webrtc::WebSysWebrtcTransport;
+
+/// A data channel opened on [DummyConnection] or [MemoryConnection]. Messages of those
+/// connections share one event queue, so the channel only keeps its label.
+#[cfg(any(feature = "dummy", feature = "memory"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VirtualDataChannel {
+    label: String,
+}
+
+#[cfg(any(feature = "dummy", feature = "memory"))]
+impl VirtualDataChannel {
+    fn new(label: &str) -> Self {
+        Self {
+            label: label.to_string(),
+        }
+    }
+
+    /// Label of the channel.
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+}
+
+/// Add up to a tenth of `timeout` to it by `random` in `[0, 1)`, so that handshakes started
+/// together don't give up ICE gathering at the same moment.
+#[cfg(any(feature = "native-webrtc", feature = "web-sys-webrtc"))]
+fn jittered(timeout: std::time::Duration, random: f64) -> std::time::Duration {
+    timeout + timeout.mul_f64(random.clamp(0.0, 1.0) / 10.0)
+}
+
+/// Count ICE candidates in a session description.
+#[cfg(any(feature = "native-webrtc", feature = "web-sys-webrtc"))]
+fn candidate_count(sdp: &str) -> usize {
+    sdp.lines()
+        .filter(|l| l.starts_with("a=candidate:"))
+        .count()
+}
+
+/// Time in ms a peer is kept relay only after its ICE failed.
+#[cfg(any(feature = "native-webrtc", feature = "web-sys-webrtc"))]
+const RELAY_ONLY_TTL_MS: u64 = 10 * 60 * 1000;
+
+/// Peers whose ICE failed with relay fallback enabled. Their connections only use TURN
+/// candidates until the marks expire in [RELAY_ONLY_TTL_MS], and each of them is to be
+/// redialed once, see [RelayMarks::take_redials].
+#[cfg(any(feature = "native-webrtc", feature = "web-sys-webrtc"))]
+#[derive(Default)]
+struct RelayMarks {
+    /// Time in ms when each peer is marked.
+    marks: dashmap::DashMap<String, u64>,
+    /// Peers marked but not redialed yet.
+    redials: dashmap::DashSet<String>,
+}
+
+#[cfg(any(feature = "native-webrtc", feature = "web-sys-webrtc"))]
+impl RelayMarks {
+    /// Mark the peer of cid as relay only, and drop the expired marks.
+    fn mark(&self, cid: &str, now_ms: u64) {
+        self.marks
+            .retain(|_, since| now_ms.saturating_sub(*since) < RELAY_ONLY_TTL_MS);
+        self.redials.retain(|c| self.marks.contains_key(c));
+        self.marks.insert(cid.to_string(), now_ms);
+        self.redials.insert(cid.to_string());
+    }
+
+    /// Check if the peer of cid is relay only. An expired mark is dropped.
+    fn is_marked(&self, cid: &str, now_ms: u64) -> bool {
+        self.marks
+            .remove_if(cid, |_, since| {
+                now_ms.saturating_sub(*since) >= RELAY_ONLY_TTL_MS
+            })
+            .is_none()
+            && self.marks.contains_key(cid)
+    }
+
+    /// Take the peers to redial, whose marks are not expired.
+    fn take_redials(&self, now_ms: u64) -> Vec<String> {
+        let cids: Vec<String> = self.redials.iter().map(|c| c.clone()).collect();
+        cids.into_iter()
+            .filter(|c| self.redials.remove(c).is_some() && self.is_marked(c, now_ms))
+            .collect()
+    }
+}
+
+/// Current time in ms, for [RelayMarks].
+#[cfg(any(feature = "native-webrtc", feature = "web-sys-webrtc"))]
+fn now_ms() -> u64 {
+    chrono::Utc::now().timestamp_millis() as u64
+}
+
+#[cfg(test)]
+#[cfg(any(feature = "native-webrtc", feature = "web-sys-webrtc"))]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_jittered_gathering_timeout() {
+        let timeout = Duration::from_secs(3);
+        assert_eq!(jittered(timeout, 0.0), timeout);
+        assert_eq!(jittered(timeout, 0.5), Duration::from_millis(3150));
+        assert_eq!(jittered(timeout, 2.0), Duration::from_millis(3300));
+    }
+
+    #[test]
+    fn test_relay_marks() {
+        let marks = RelayMarks::default();
+        assert!(!marks.is_marked("a", 0));
+        marks.mark("a", 1000);
+        assert!(marks.is_marked("a", 1000));
+        // Redialed only once.
+        assert_eq!(marks.take_redials(2000), vec!["a".to_string()]);
+        assert!(marks.take_redials(2000).is_empty());
+        assert!(marks.is_marked("a", 2000));
+
+        // Expired marks are neither relay only nor redialed.
+        marks.mark("b", 2000);
+        assert!(!marks.is_marked("a", 1000 + RELAY_ONLY_TTL_MS));
+        assert!(marks.take_redials(2000 + RELAY_ONLY_TTL_MS).is_empty());
+        assert!(marks.marks.is_empty());
+        assert!(marks.redials.is_empty());
+    }
+
+    #[test]
+    fn test_candidate_count() {
+        let sdp = "v=0\r\na=candidate:1 1 udp 2130706431 10.0.0.1 50000 typ host\r\n\
+                   a=candidate:2 1 udp 1694498815 1.2.3.4 50000 typ srflx\r\na=end-of-candidates";
+        assert_eq!(candidate_count(sdp), 2);
+        assert_eq!(candidate_count(""), 0);
+    }
+}
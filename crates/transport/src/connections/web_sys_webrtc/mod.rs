@@ -1,6 +1,9 @@
 use std::sync::Arc;
+use std::sync::RwLock;
+use std::time::Duration;
 
 use async_trait::async_trait;
+use dashmap::DashMap;
 use js_sys::Array;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsValue;
@@ -10,10 +13,13 @@ use web_sys::MessageEvent;
 use web_sys::RtcConfiguration;
 use web_sys::RtcDataChannel;
 use web_sys::RtcDataChannelEvent;
+use web_sys::RtcDataChannelInit;
 use web_sys::RtcDataChannelState;
+use web_sys::RtcIceConnectionState;
 use web_sys::RtcIceCredentialType;
 use web_sys::RtcIceGatheringState;
 use web_sys::RtcIceServer;
+use web_sys::RtcIceTransportPolicy;
 use web_sys::RtcPeerConnection;
 use web_sys::RtcPeerConnectionState;
 use web_sys::RtcSdpType;
@@ -23,15 +29,23 @@ use web_sys::RtcStatsReport;
 
 use crate::callback::InnerTransportCallback;
 use crate::connection_ref::ConnectionRef;
+use crate::connections::candidate_count;
+use crate::connections::jittered;
+use crate::connections::now_ms;
+use crate::connections::RelayMarks;
 use crate::core::callback::BoxedTransportCallback;
 use crate::core::pool::MessageSenderPool;
 use crate::core::pool::RoundRobin;
 use crate::core::pool::RoundRobinPool;
 use crate::core::pool::StatusPool;
 use crate::core::transport::ConnectionInterface;
+use crate::core::transport::DataChannelConfig;
 use crate::core::transport::TransportInterface;
 use crate::core::transport::TransportMessage;
 use crate::core::transport::WebrtcConnectionState;
+use crate::core::transport::WebrtcIceConnectionState;
+use crate::core::transport::DEFAULT_CHANNEL_LABEL_PREFIX;
+use crate::core::transport::DEFAULT_ICE_GATHERING_TIMEOUT;
 use crate::error::Error;
 use crate::error::Result;
 use crate::ice_server::IceCredentialType;
@@ -40,24 +54,130 @@ use crate::notifier::Notifier;
 use crate::pool::Pool;
 
 const WEBRTC_WAIT_FOR_DATA_CHANNEL_OPEN_TIMEOUT: u8 = 8; // seconds
-const WEBRTC_GATHER_TIMEOUT: u8 = 60; // seconds
 /// pool size of data channel
 const DATA_CHANNEL_POOL_SIZE: u8 = 4;
+/// Sending waits when buffered amount of data channel is above it
+const DATA_CHANNEL_BUFFERED_AMOUNT_HIGH: u32 = 1024 * 1024;
+/// Sending resumes when buffered amount of data channel drops to it
+const DATA_CHANNEL_BUFFERED_AMOUNT_LOW: u32 = 256 * 1024;
+const DATA_CHANNEL_BUFFER_WAIT_TIMEOUT: u8 = 8; // seconds
+
+/// Wait until the buffered amount of channel drops below the high-water mark, so that a burst
+/// of relayed messages doesn't overflow the send buffer and get the channel closed by browser.
+async fn wait_for_buffered_amount_low(channel: &RtcDataChannel) -> Result<()> {
+    for _ in 0..DATA_CHANNEL_BUFFER_WAIT_TIMEOUT {
+        if matches!(
+            channel.ready_state(),
+            RtcDataChannelState::Closing | RtcDataChannelState::Closed
+        ) {
+            return Err(Error::ChannelClosed(channel.label()));
+        }
+        if channel.buffered_amount() <= DATA_CHANNEL_BUFFERED_AMOUNT_HIGH {
+            return Ok(());
+        }
+
+        let notifier = Notifier::default();
+        let on_low = notifier.clone();
+        let on_low = Closure::once_into_js(move || on_low.wake());
+        channel.set_buffered_amount_low_threshold(DATA_CHANNEL_BUFFERED_AMOUNT_LOW);
+        channel.set_onbufferedamountlow(Some(on_low.as_ref().unchecked_ref()));
+        // Check again every second, in case the event fired before the callback was set.
+        notifier.set_timeout(1);
+        notifier.await;
+        channel.set_onbufferedamountlow(None);
+    }
+    Err(Error::ChannelBufferFull(channel.label()))
+}
+
+/// Send message on channel, waiting for its buffer to drain if needed.
+async fn send_on_channel(channel: &RtcDataChannel, msg: TransportMessage) -> Result<()> {
+    if matches!(
+        channel.ready_state(),
+        RtcDataChannelState::Closing | RtcDataChannelState::Closed
+    ) {
+        return Err(Error::ChannelClosed(channel.label()));
+    }
+    let data = bincode::serialize(&msg)?;
+    wait_for_buffered_amount_low(channel).await?;
+    if let Err(e) = channel
+        .send_with_u8_array(&data)
+        .map_err(Error::WebSysWebrtc)
+    {
+        tracing::error!("{:?}, Data size: {:?}", e, data.len());
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// Deliver messages received on channel to the callback of connection.
+fn on_channel_message(channel: &RtcDataChannel, inner_cb: Arc<InnerTransportCallback>) {
+    let on_message = Box::new(move |ev: MessageEvent| {
+        let data = ev.data();
+
+        let inner_cb = inner_cb.clone();
+
+        spawn_local(async move {
+            let msg = if data.has_type::<web_sys::Blob>() {
+                let data: web_sys::Blob = data.clone().into();
+                if data.size() == 0f64 {
+                    return;
+                }
+                let data_buffer = wasm_bindgen_futures::JsFuture::from(data.array_buffer()).await;
+                if let Err(e) = data_buffer {
+                    tracing::error!("Failed to read array_buffer from Blob, {:?}", e);
+                    return;
+                }
+                js_sys::Uint8Array::new(&data_buffer.unwrap()).to_vec()
+            } else {
+                js_sys::Uint8Array::new(data.as_ref()).to_vec()
+            };
+
+            if msg.is_empty() {
+                tracing::debug!("Received empty DataChannelMessage from {}", inner_cb.cid);
+                return;
+            }
+
+            tracing::debug!(
+                "Received DataChannelMessage from {}: {:?}",
+                inner_cb.cid,
+                data
+            );
+
+            inner_cb.on_message(&msg.into()).await;
+        })
+    });
+
+    let c = Closure::wrap(on_message as Box<dyn FnMut(MessageEvent)>);
+    channel.set_onmessage(Some(c.as_ref().unchecked_ref()));
+    c.forget();
+}
+
+/// Keep an additional channel of label until it's closed, see [ConnectionInterface::open_channel].
+fn register_labeled_channel(
+    channels: &Arc<DashMap<String, RtcDataChannel>>,
+    channel: RtcDataChannel,
+    inner_cb: Arc<InnerTransportCallback>,
+) {
+    let label = channel.label();
+    on_channel_message(&channel, inner_cb);
+    let on_close_channels = channels.clone();
+    let on_close_label = label.clone();
+    let on_close = Box::new(move || {
+        tracing::debug!("DataChannel {on_close_label} closed");
+        on_close_channels.remove(&on_close_label);
+    });
+    let c = Closure::wrap(on_close as Box<dyn FnMut()>);
+    channel.set_onclose(Some(c.as_ref().unchecked_ref()));
+    c.forget();
+    channels.insert(label, channel);
+}
 
 #[async_trait(?Send)]
 impl MessageSenderPool<RtcDataChannel> for RoundRobinPool<RtcDataChannel> {
     type Message = TransportMessage;
     async fn send(&self, msg: TransportMessage) -> Result<()> {
         let channel = self.select()?;
-        let data = bincode::serialize(&msg)?;
-        if let Err(e) = channel
-            .send_with_u8_array(&data)
-            .map_err(Error::WebSysWebrtc)
-        {
-            tracing::error!("{:?}, Data size: {:?}", e, data.len());
-            return Err(e.into());
-        }
-        Ok(())
+        send_on_channel(&channel, msg).await
     }
 }
 
@@ -73,13 +193,23 @@ pub struct WebSysWebrtcConnection {
     webrtc_conn: RtcPeerConnection,
     webrtc_data_channel: Arc<RoundRobinPool<RtcDataChannel>>,
     webrtc_data_channel_state_notifier: Notifier,
+    /// Additional channels by label, see [ConnectionInterface::open_channel].
+    labeled_channels: Arc<DashMap<String, RtcDataChannel>>,
+    callback: Arc<InnerTransportCallback>,
+    /// Time to wait for ICE gathering before using the candidates gathered so far.
+    gather_timeout: Duration,
 }
 
 /// [WebSysWebrtcTransport] manages all the [WebSysWebrtcConnection] and
 /// provides methods to create, get and close connections.
 pub struct WebSysWebrtcTransport {
-    ice_servers: Vec<IceServer>,
+    ice_servers: RwLock<Vec<IceServer>>,
     pool: Pool<WebSysWebrtcConnection>,
+    relay_fallback: bool,
+    data_channel_config: DataChannelConfig,
+    /// Connections that failed on ICE, they will only use TURN candidates on next handshakes.
+    relay_only: Arc<RelayMarks>,
+    gather_timeout: Duration,
 }
 
 impl WebSysWebrtcConnection {
@@ -87,14 +217,35 @@ impl WebSysWebrtcConnection {
         webrtc_conn: RtcPeerConnection,
         webrtc_data_channel: Arc<RoundRobinPool<RtcDataChannel>>,
         webrtc_data_channel_state_notifier: Notifier,
+        labeled_channels: Arc<DashMap<String, RtcDataChannel>>,
+        callback: Arc<InnerTransportCallback>,
+        gather_timeout: Duration,
     ) -> Self {
         Self {
             webrtc_conn,
             webrtc_data_channel,
             webrtc_data_channel_state_notifier,
+            labeled_channels,
+            callback,
+            gather_timeout,
         }
     }
 
+    /// Apply new ice servers to the connection. Other fields of the configuration,
+    /// such as the ICE transport policy, are kept unchanged.
+    fn set_ice_servers(&self, ice_servers: &[IceServer]) -> Result<()> {
+        let mut config = self.webrtc_conn.get_configuration();
+        let ice_servers: js_sys::Array =
+            js_sys::Array::from_iter(ice_servers.iter().cloned().map(RtcIceServer::from));
+        config.ice_servers(&ice_servers.into());
+        self.webrtc_conn
+            .set_configuration(&config)
+            .map_err(Error::WebSysWebrtc)
+    }
+
+    /// Wait for ICE gathering and return the local sdp. Some browsers never fire
+    /// `icegatheringstatechange` with `complete`, so the candidates gathered so far
+    /// are used once the timeout is reached.
     async fn webrtc_gather(&self) -> Result<String> {
         let notifier = Notifier::default();
 
@@ -112,20 +263,27 @@ impl WebSysWebrtcConnection {
             .set_onicegatheringstatechange(Some(c.as_ref().unchecked_ref()));
         c.forget();
 
-        notifier.set_timeout(WEBRTC_GATHER_TIMEOUT);
-        notifier.await;
+        let timeout = jittered(self.gather_timeout, js_sys::Math::random());
         if self.webrtc_conn.ice_gathering_state() != RtcIceGatheringState::Complete {
-            return Err(Error::WebrtcLocalSdpGenerationError(format!(
-                "Webrtc gathering is not completed in {WEBRTC_GATHER_TIMEOUT} seconds"
-            )));
+            notifier.set_timeout_ms(timeout.as_millis().try_into().unwrap_or(u32::MAX));
+            notifier.await;
         }
+        self.webrtc_conn.set_onicegatheringstatechange(None);
 
-        self.webrtc_conn
+        let sdp = self
+            .webrtc_conn
             .local_description()
             .ok_or(Error::WebrtcLocalSdpGenerationError(
                 "local_description is None".to_string(),
             ))
-            .map(|x| x.sdp())
+            .map(|x| x.sdp())?;
+        if self.webrtc_conn.ice_gathering_state() != RtcIceGatheringState::Complete {
+            tracing::warn!(
+                "ICE gathering is not completed in {timeout:?}, proceed with {} candidates",
+                candidate_count(&sdp)
+            );
+        }
+        Ok(sdp)
     }
 }
 
@@ -135,9 +293,74 @@ impl WebSysWebrtcTransport {
         let ice_servers = IceServer::vec_from_str(ice_servers).unwrap();
 
         Self {
-            ice_servers,
+            ice_servers: RwLock::new(ice_servers),
             pool: Pool::new(),
+            relay_fallback: false,
+            data_channel_config: DataChannelConfig::default(),
+            relay_only: Arc::new(RelayMarks::default()),
+            gather_timeout: DEFAULT_ICE_GATHERING_TIMEOUT,
+        }
+    }
+
+    /// Enable relay fallback. When ICE of a connection failed, the next handshake with
+    /// the same peer will only gather TURN candidates, which helps peers both behind
+    /// symmetric NATs. At least one TURN server is required.
+    pub fn enable_relay_fallback(&mut self) -> Result<()> {
+        IceServer::check_relay_fallback(&self.ice_servers()?)?;
+        self.relay_fallback = true;
+        Ok(())
+    }
+
+    /// Take the peers whose ICE failed since last call, they are to be redialed with TURN
+    /// candidates only. Each of them is returned once, see [Self::enable_relay_fallback].
+    pub fn take_relay_redials(&self) -> Vec<String> {
+        self.relay_only.take_redials(now_ms())
+    }
+
+    /// Set reliability of data channels of connections created afterwards.
+    /// Both peers should use the same config, since each side sends on its own channels.
+    pub fn set_data_channel_config(&mut self, config: DataChannelConfig) -> Result<()> {
+        config.validate()?;
+        self.data_channel_config = config;
+        Ok(())
+    }
+
+    /// Set the time to wait for ICE gathering of connections created afterwards,
+    /// default to [DEFAULT_ICE_GATHERING_TIMEOUT]. A little jitter is added to it.
+    pub fn set_ice_gathering_timeout(&mut self, timeout: Duration) {
+        self.gather_timeout = timeout;
+    }
+
+    fn ice_servers(&self) -> Result<Vec<IceServer>> {
+        self.ice_servers
+            .read()
+            .map(|servers| servers.clone())
+            .map_err(|_| Error::RwLockRead("Failed to read ice servers".to_string()))
+    }
+
+    /// Replace the ice servers, such as rotating expired TURN credentials, without restart.
+    /// New connections use them, and existing connections use them on next candidate gathering.
+    /// Established connections are not interrupted.
+    /// Returns error if relay fallback is enabled but no TURN server is given.
+    pub async fn update_ice_servers(&self, ice_servers: Vec<IceServer>) -> Result<()> {
+        if self.relay_fallback {
+            IceServer::check_relay_fallback(&ice_servers)?;
         }
+        *self
+            .ice_servers
+            .write()
+            .map_err(|_| Error::RwLockWrite("Failed to write ice servers".to_string()))? =
+            ice_servers.clone();
+
+        for (cid, conn) in self.pool.connections() {
+            let Ok(conn) = conn.upgrade() else {
+                continue;
+            };
+            if let Err(e) = conn.set_ice_servers(&ice_servers) {
+                tracing::warn!("Failed to update ice servers of connection {cid}: {e}");
+            }
+        }
+        Ok(())
     }
 }
 
@@ -145,6 +368,7 @@ impl WebSysWebrtcTransport {
 impl ConnectionInterface for WebSysWebrtcConnection {
     type Sdp = String;
     type Error = Error;
+    type DataChannel = RtcDataChannel;
 
     async fn send_message(&self, msg: TransportMessage) -> Result<()> {
         self.webrtc_wait_for_data_channel_open().await?;
@@ -152,10 +376,67 @@ impl ConnectionInterface for WebSysWebrtcConnection {
         Ok(())
     }
 
+    async fn open_channel(
+        &self,
+        label: &str,
+        config: DataChannelConfig,
+    ) -> Result<Arc<RtcDataChannel>> {
+        config.validate()?;
+        if label.starts_with(DEFAULT_CHANNEL_LABEL_PREFIX)
+            || self.labeled_channels.contains_key(label)
+        {
+            return Err(Error::ChannelLabelTaken(label.to_string()));
+        }
+        self.webrtc_wait_for_data_channel_open().await?;
+
+        let channel = self
+            .webrtc_conn
+            .create_data_channel_with_data_channel_dict(label, &config.into());
+        let notifier = Notifier::default();
+        let on_open = notifier.clone();
+        let on_open = Closure::once_into_js(move || on_open.wake());
+        channel.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+        register_labeled_channel(
+            &self.labeled_channels,
+            channel.clone(),
+            self.callback.clone(),
+        );
+
+        if channel.ready_state() != RtcDataChannelState::Open {
+            notifier.set_timeout(WEBRTC_WAIT_FOR_DATA_CHANNEL_OPEN_TIMEOUT);
+            notifier.await;
+        }
+        channel.set_onopen(None);
+        if channel.ready_state() != RtcDataChannelState::Open {
+            self.labeled_channels.remove(label);
+            return Err(Error::DataChannelOpen(format!(
+                "DataChannel {label} not open in {WEBRTC_WAIT_FOR_DATA_CHANNEL_OPEN_TIMEOUT} seconds"
+            )));
+        }
+        Ok(Arc::new(channel))
+    }
+
+    async fn send_message_on(&self, label: &str, msg: TransportMessage) -> Result<()> {
+        let channel = self
+            .labeled_channels
+            .get(label)
+            .map(|c| c.clone())
+            .ok_or_else(|| Error::ChannelNotFound(label.to_string()))?;
+        send_on_channel(&channel, msg).await
+    }
+
     fn webrtc_connection_state(&self) -> WebrtcConnectionState {
         self.webrtc_conn.connection_state().into()
     }
 
+    fn webrtc_ice_connection_state(&self) -> WebrtcIceConnectionState {
+        self.webrtc_conn.ice_connection_state().into()
+    }
+
+    fn is_data_channel_open(&self) -> bool {
+        self.webrtc_data_channel.all_ready().unwrap_or(false)
+    }
+
     async fn get_stats(&self) -> Vec<String> {
         let promise = self.webrtc_conn.get_stats();
         let Ok(value) = wasm_bindgen_futures::JsFuture::from(promise).await else {
@@ -276,8 +557,13 @@ impl TransportInterface for WebSysWebrtcTransport {
         //
         let mut config = RtcConfiguration::new();
         let ice_servers: js_sys::Array =
-            js_sys::Array::from_iter(self.ice_servers.iter().cloned().map(RtcIceServer::from));
+            js_sys::Array::from_iter(self.ice_servers()?.into_iter().map(RtcIceServer::from));
         config.ice_servers(&ice_servers.into());
+        let relay_only = self.relay_only.is_marked(cid, now_ms());
+        if relay_only {
+            tracing::info!("connection {cid} will only use relay candidates");
+            config.ice_transport_policy(RtcIceTransportPolicy::Relay);
+        }
 
         //
         // Create webrtc connection
@@ -298,11 +584,17 @@ impl TransportInterface for WebSysWebrtcTransport {
         let data_channel_inner_cb = inner_cb.clone();
         let channel_pool = Arc::new(RoundRobinPool::default());
         let channel_pool_ref = channel_pool.clone();
+        let labeled_channels = Arc::new(DashMap::new());
+        let labeled_channels_ref = labeled_channels.clone();
 
         let on_data_channel = Box::new(move |ev: RtcDataChannelEvent| {
             let d = ev.channel();
             let d_label = d.label();
             tracing::debug!("New DataChannel {d_label}");
+            if !d_label.starts_with(DEFAULT_CHANNEL_LABEL_PREFIX) {
+                register_labeled_channel(&labeled_channels_ref, d, data_channel_inner_cb.clone());
+                return;
+            }
             let channel_pool = channel_pool_ref.clone();
             let on_open_inner_cb = data_channel_inner_cb.clone();
             let on_open = Box::new(move || {
@@ -322,44 +614,6 @@ impl TransportInterface for WebSysWebrtcTransport {
                 on_close_inner_cb.on_data_channel_close();
             });
 
-            let on_message_inner_cb = data_channel_inner_cb.clone();
-            let on_message = Box::new(move |ev: MessageEvent| {
-                let data = ev.data();
-
-                let inner_cb = on_message_inner_cb.clone();
-
-                spawn_local(async move {
-                    let msg = if data.has_type::<web_sys::Blob>() {
-                        let data: web_sys::Blob = data.clone().into();
-                        if data.size() == 0f64 {
-                            return;
-                        }
-                        let data_buffer =
-                            wasm_bindgen_futures::JsFuture::from(data.array_buffer()).await;
-                        if let Err(e) = data_buffer {
-                            tracing::error!("Failed to read array_buffer from Blob, {:?}", e);
-                            return;
-                        }
-                        js_sys::Uint8Array::new(&data_buffer.unwrap()).to_vec()
-                    } else {
-                        js_sys::Uint8Array::new(data.as_ref()).to_vec()
-                    };
-
-                    if msg.is_empty() {
-                        tracing::debug!("Received empty DataChannelMessage from {}", inner_cb.cid);
-                        return;
-                    }
-
-                    tracing::debug!(
-                        "Received DataChannelMessage from {}: {:?}",
-                        inner_cb.cid,
-                        data
-                    );
-
-                    inner_cb.on_message(&msg.into()).await;
-                })
-            });
-
             let c = Closure::wrap(on_open as Box<dyn FnMut()>);
             d.set_onopen(Some(c.as_ref().unchecked_ref()));
             c.forget();
@@ -368,9 +622,7 @@ impl TransportInterface for WebSysWebrtcTransport {
             d.set_onclose(Some(c.as_ref().unchecked_ref()));
             c.forget();
 
-            let c = Closure::wrap(on_message as Box<dyn FnMut(MessageEvent)>);
-            d.set_onmessage(Some(c.as_ref().unchecked_ref()));
-            c.forget();
+            on_channel_message(&d, data_channel_inner_cb.clone());
         });
 
         let peer_connection_state_change_inner_cb = inner_cb.clone();
@@ -394,11 +646,32 @@ impl TransportInterface for WebSysWebrtcTransport {
         webrtc_conn.set_onconnectionstatechange(Some(c.as_ref().unchecked_ref()));
         c.forget();
 
+        if self.relay_fallback && !relay_only {
+            let relay_only = self.relay_only.clone();
+            let relay_cid = cid.to_string();
+            let ice_connection_state_change_webrtc_conn = webrtc_conn.clone();
+            let on_ice_connection_state_change = Box::new(move |_| {
+                if ice_connection_state_change_webrtc_conn.ice_connection_state()
+                    == RtcIceConnectionState::Failed
+                {
+                    tracing::warn!("ICE of {relay_cid} failed, fallback to relay");
+                    relay_only.mark(&relay_cid, now_ms());
+                }
+            });
+
+            let c = Closure::wrap(on_ice_connection_state_change as Box<dyn FnMut(web_sys::Event)>);
+            webrtc_conn.set_oniceconnectionstatechange(Some(c.as_ref().unchecked_ref()));
+            c.forget();
+        }
+
         //
         // Create data channel
         //
         for i in 0..DATA_CHANNEL_POOL_SIZE {
-            let ch = webrtc_conn.create_data_channel(&format!("rings_data_channel_{}", i));
+            let ch = webrtc_conn.create_data_channel_with_data_channel_dict(
+                &format!("{DEFAULT_CHANNEL_LABEL_PREFIX}{i}"),
+                &self.data_channel_config.into(),
+            );
             channel_pool.push(ch)?;
         }
 
@@ -409,6 +682,9 @@ impl TransportInterface for WebSysWebrtcTransport {
             webrtc_conn,
             channel_pool,
             webrtc_data_channel_state_notifier,
+            labeled_channels,
+            inner_cb,
+            self.gather_timeout,
         );
 
         self.pool.safely_insert(cid, conn)?;
@@ -442,6 +718,20 @@ impl From<IceCredentialType> for RtcIceCredentialType {
     }
 }
 
+impl From<DataChannelConfig> for RtcDataChannelInit {
+    fn from(config: DataChannelConfig) -> Self {
+        let mut ret = RtcDataChannelInit::new();
+        ret.ordered(config.ordered);
+        if let Some(n) = config.max_retransmits {
+            ret.max_retransmits(n);
+        }
+        if let Some(ms) = config.max_packet_lifetime {
+            ret.max_packet_life_time(ms);
+        }
+        ret
+    }
+}
+
 impl From<IceServer> for RtcIceServer {
     fn from(s: IceServer) -> Self {
         let mut ret = RtcIceServer::new();
@@ -486,6 +776,24 @@ impl From<RtcPeerConnectionState> for WebrtcConnectionState {
     }
 }
 
+impl From<RtcIceConnectionState> for WebrtcIceConnectionState {
+    fn from(s: RtcIceConnectionState) -> Self {
+        match s {
+            RtcIceConnectionState::New => Self::New,
+            RtcIceConnectionState::Checking => Self::Checking,
+            RtcIceConnectionState::Connected => Self::Connected,
+            RtcIceConnectionState::Completed => Self::Completed,
+            RtcIceConnectionState::Disconnected => Self::Disconnected,
+            RtcIceConnectionState::Failed => Self::Failed,
+            RtcIceConnectionState::Closed => Self::Closed,
+            _ => {
+                tracing::warn!("Unknown RtcIceConnectionState: {s:?}");
+                Self::Unspecified
+            }
+        }
+    }
+}
+
 fn dump_stats_entry(entry: &Option<JsValue>) -> Option<String> {
     js_sys::JSON::stringify(entry.as_ref()?)
         .ok()
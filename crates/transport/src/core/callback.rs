@@ -25,6 +25,16 @@ pub trait TransportCallback {
         Ok(())
     }
 
+    /// This method is invoked on a keepalive ping arrival over the data channel of webrtc.
+    async fn on_ping(&self, _cid: &str, _nonce: u64) -> Result<(), CallbackError> {
+        Ok(())
+    }
+
+    /// This method is invoked on a keepalive pong arrival over the data channel of webrtc.
+    async fn on_pong(&self, _cid: &str, _nonce: u64) -> Result<(), CallbackError> {
+        Ok(())
+    }
+
     /// This method is invoked when the state of connection has changed.
     async fn on_peer_connection_state_change(
         &self,
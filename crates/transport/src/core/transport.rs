@@ -4,6 +4,9 @@
 //! There is also a [TransportInterface] trait, which is used to specify the management of all
 //! [ConnectionInterface] objects.
 
+use std::sync::Arc;
+use std::time::Duration;
+
 use async_trait::async_trait;
 use serde::de::DeserializeOwned;
 use serde::Deserialize;
@@ -11,6 +14,7 @@ use serde::Serialize;
 
 use crate::connection_ref::ConnectionRef;
 use crate::core::callback::BoxedTransportCallback;
+use crate::error::Error;
 
 /// Wrapper for the data that is sent over the data channel.
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -18,6 +22,10 @@ pub enum TransportMessage {
     /// The custom message is sent by an external invoker and
     /// should be handled by the on_message callback.
     Custom(Vec<u8>),
+    /// Keepalive probe with a nonce, the remote peer should answer with a [TransportMessage::Pong].
+    Ping(u64),
+    /// Answer of [TransportMessage::Ping] carrying the same nonce.
+    Pong(u64),
 }
 
 /// The state of the WebRTC connection.
@@ -58,6 +66,134 @@ pub enum WebrtcConnectionState {
     Closed,
 }
 
+/// The state of the ICE agent of a WebRTC connection.
+/// This enum is used to define a same interface for all the platforms.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WebrtcIceConnectionState {
+    /// Unspecified
+    #[default]
+    Unspecified,
+
+    /// The ICE agent is gathering addresses or is waiting to be given remote candidates.
+    New,
+
+    /// The ICE agent has been given remote candidates and is checking pairs of candidates.
+    Checking,
+
+    /// A usable pairing of local and remote candidates has been found.
+    Connected,
+
+    /// The ICE agent has finished gathering candidates and found a connection for all components.
+    Completed,
+
+    /// Checks to ensure that components are still connected failed for at least one component.
+    Disconnected,
+
+    /// The ICE agent has checked all candidate pairs and failed to find a working one.
+    Failed,
+
+    /// The ICE agent has shut down and is no longer responding to STUN requests.
+    Closed,
+}
+
+/// Label prefix of the default data channels of a connection, which carry messages sent by
+/// [ConnectionInterface::send_message].
+pub const DEFAULT_CHANNEL_LABEL_PREFIX: &str = "rings_data_channel_";
+
+/// Default time to wait for ICE gathering to complete. When it's reached, the handshake
+/// proceeds with the candidates gathered so far.
+pub const DEFAULT_ICE_GATHERING_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Reliability of the data channels of a connection.
+/// By default, channels are reliable and ordered. Latency-sensitive applications can opt into
+/// partial reliability, where messages are dropped after `max_retransmits` retransmissions or
+/// `max_packet_lifetime` milliseconds. At most one of the two limits can be set.
+#[derive(Deserialize, Serialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DataChannelConfig {
+    /// Deliver messages in the order they are sent.
+    pub ordered: bool,
+    /// Max count of retransmissions of an undelivered message.
+    pub max_retransmits: Option<u16>,
+    /// Max time in ms to retransmit an undelivered message.
+    pub max_packet_lifetime: Option<u16>,
+}
+
+impl Default for DataChannelConfig {
+    fn default() -> Self {
+        Self {
+            ordered: true,
+            max_retransmits: None,
+            max_packet_lifetime: None,
+        }
+    }
+}
+
+impl DataChannelConfig {
+    /// Check if messages are retransmitted until delivered.
+    pub fn is_reliable(&self) -> bool {
+        self.max_retransmits.is_none() && self.max_packet_lifetime.is_none()
+    }
+
+    /// Check that at most one of the limits of retransmission is set.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.max_retransmits.is_some() && self.max_packet_lifetime.is_some() {
+            return Err(Error::InvalidDataChannelConfig(
+                "max_retransmits and max_packet_lifetime cannot be both set".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Stats of the selected candidate pair of a connection, see [ConnectionInterface::connection_stats].
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct ConnectionStats {
+    /// Current round trip time in ms measured by STUN, None if it's not measured yet.
+    pub current_rtt_ms: Option<f64>,
+    /// Bytes sent on the candidate pair.
+    pub bytes_sent: u64,
+    /// Bytes received on the candidate pair.
+    pub bytes_received: u64,
+    /// State of the candidate pair, such as `succeeded`, None if no pair is selected.
+    pub candidate_pair_state: Option<String>,
+}
+
+impl ConnectionStats {
+    /// Parse stats of the selected candidate pair from the entries dumped by
+    /// [ConnectionInterface::get_stats], which follow the W3C webrtc-stats names.
+    /// The pair is the one referred by `selectedCandidatePairId` of transport, marked as
+    /// `selected`, or nominated, in that order. Entries can't be parsed are skipped.
+    /// An entry is either the stats object, or an `[id, stats]` pair as dumped from the map of
+    /// reports by both native and browser implementations.
+    /// Return None if there is no candidate pair.
+    pub fn from_stats(entries: &[String]) -> Option<Self> {
+        let entries = entries
+            .iter()
+            .filter_map(|e| serde_json::from_str::<serde_json::Value>(e).ok())
+            .map(|e| match e {
+                serde_json::Value::Array(mut pair) if pair.len() == 2 => pair.swap_remove(1),
+                e => e,
+            })
+            .collect::<Vec<_>>();
+        let of_type = |ty: &'static str| entries.iter().filter(move |e| e["type"] == ty);
+
+        let selected_id = of_type("transport").find_map(|t| t["selectedCandidatePairId"].as_str());
+        let pair = of_type("candidate-pair")
+            .find(|p| selected_id.is_some() && p["id"].as_str() == selected_id)
+            .or_else(|| of_type("candidate-pair").find(|p| p["selected"] == true))
+            .or_else(|| of_type("candidate-pair").find(|p| p["nominated"] == true));
+        let pair = pair?;
+
+        Some(Self {
+            // It's in seconds.
+            current_rtt_ms: pair["currentRoundTripTime"].as_f64().map(|s| s * 1000.0),
+            bytes_sent: pair["bytesSent"].as_u64().unwrap_or_default(),
+            bytes_received: pair["bytesReceived"].as_u64().unwrap_or_default(),
+            candidate_pair_state: pair["state"].as_str().map(|s| s.to_string()),
+        })
+    }
+}
+
 /// The [ConnectionInterface] trait defines how to
 /// make webrtc ice handshake with a remote peer and then send data channel message to it.
 #[cfg_attr(feature = "web-sys-webrtc", async_trait(?Send))]
@@ -67,16 +203,44 @@ pub trait ConnectionInterface {
     type Sdp: Serialize + DeserializeOwned;
     /// The error type that is returned by connection.
     type Error: std::error::Error;
+    /// The data channel opened by [ConnectionInterface::open_channel].
+    type DataChannel;
 
     /// Send a [TransportMessage] to the remote peer.
     async fn send_message(&self, msg: TransportMessage) -> Result<(), Self::Error>;
 
+    /// Open an additional data channel of label on the established connection, so that
+    /// a stream of messages, such as a bulk transfer, doesn't block the others.
+    /// The channel is shared by both peers, and messages received on it go to the same callback.
+    /// Labels of the default channels are reserved.
+    async fn open_channel(
+        &self,
+        label: &str,
+        config: DataChannelConfig,
+    ) -> Result<Arc<Self::DataChannel>, Self::Error>;
+
+    /// Send a [TransportMessage] on the data channel of label, opened by either peer.
+    async fn send_message_on(&self, label: &str, msg: TransportMessage) -> Result<(), Self::Error>;
+
     /// Get current webrtc connection state.
     fn webrtc_connection_state(&self) -> WebrtcConnectionState;
 
+    /// Get current ice connection state.
+    fn webrtc_ice_connection_state(&self) -> WebrtcIceConnectionState;
+
+    /// Check if all the data channels of the connection are open.
+    fn is_data_channel_open(&self) -> bool;
+
     /// This is a debug method to dump the stats of webrtc connection.
     async fn get_stats(&self) -> Vec<String>;
 
+    /// Get the stats of the selected candidate pair, such as current rtt and bytes transferred,
+    /// for diagnosing flaky connections. It's parsed from [ConnectionInterface::get_stats].
+    /// Return None if there is no candidate pair yet.
+    async fn connection_stats(&self) -> Option<ConnectionStats> {
+        ConnectionStats::from_stats(&self.get_stats().await)
+    }
+
     /// Create a webrtc offer to start handshake.
     async fn webrtc_create_offer(&self) -> Result<Self::Sdp, Self::Error>;
 
@@ -140,3 +304,93 @@ pub type BoxedTransport<C, E> =
 /// Used to store a boxed [TransportInterface] trait object.
 #[cfg(feature = "web-sys-webrtc")]
 pub type BoxedTransport<C, E> = Box<dyn TransportInterface<Connection = C, Error = E>>;
+
+#[cfg(test)]
+mod test {
+    use super::ConnectionStats;
+    use super::DataChannelConfig;
+
+    #[test]
+    fn test_data_channel_config() {
+        let config = DataChannelConfig::default();
+        assert!(config.ordered && config.is_reliable());
+        assert!(config.validate().is_ok());
+
+        let partial = DataChannelConfig {
+            ordered: false,
+            max_retransmits: Some(0),
+            max_packet_lifetime: None,
+        };
+        assert!(!partial.is_reliable());
+        assert!(partial.validate().is_ok());
+
+        let both = DataChannelConfig {
+            max_packet_lifetime: Some(100),
+            ..partial
+        };
+        assert!(both.validate().is_err());
+    }
+
+    #[test]
+    fn test_connection_stats() {
+        assert_eq!(ConnectionStats::from_stats(&[]), None);
+
+        let stats = [
+            r#"{"id":"P1","type":"candidate-pair","state":"failed","nominated":true}"#,
+            r#"{"id":"P2","type":"candidate-pair","state":"succeeded","nominated":true,"currentRoundTripTime":0.025,"bytesSent":100,"bytesReceived":200}"#,
+            r#"{"id":"T1","type":"transport","selectedCandidatePairId":"P2"}"#,
+            "not json",
+        ]
+        .map(|s| s.to_string());
+        assert_eq!(ConnectionStats::from_stats(&stats).unwrap(), ConnectionStats {
+            current_rtt_ms: Some(25.0),
+            bytes_sent: 100,
+            bytes_received: 200,
+            candidate_pair_state: Some("succeeded".to_string()),
+        });
+
+        // Without transport, the nominated pair is taken.
+        let stats = [stats[0].clone()];
+        assert_eq!(ConnectionStats::from_stats(&stats).unwrap(), ConnectionStats {
+            current_rtt_ms: None,
+            bytes_sent: 0,
+            bytes_received: 0,
+            candidate_pair_state: Some("failed".to_string()),
+        });
+    }
+
+    #[test]
+    fn test_connection_stats_of_dump() {
+        // Dumped by native webrtc, as (id, report) of the map of reports.
+        let native = [
+            r#"["RTCPeerConnection",{"timestamp":1700000000000.0,"type":"peer-connection","id":"RTCPeerConnection","dataChannelsOpened":1,"dataChannelsClosed":0}]"#,
+            r#"["candidate:1",{"timestamp":1700000000000.0,"type":"local-candidate","id":"candidate:1","candidateType":"host","ip":"192.168.1.2","port":50000}]"#,
+            r#"["candidate:1-candidate:2",{"timestamp":1700000000000.0,"type":"candidate-pair","id":"candidate:1-candidate:2","localCandidateId":"candidate:1","remoteCandidateId":"candidate:2","state":"succeeded","nominated":true,"packetsSent":10,"packetsReceived":12,"bytesSent":1024,"bytesReceived":2048,"totalRoundTripTime":0.1,"currentRoundTripTime":0.05,"requestsReceived":1,"requestsSent":1,"responsesReceived":1,"responsesSent":1}]"#,
+            r#"["ice_transport",{"timestamp":1700000000000.0,"type":"transport","id":"ice_transport","bytesSent":1024,"bytesReceived":2048}]"#,
+        ]
+        .map(|s| s.to_string());
+        assert_eq!(ConnectionStats::from_stats(&native).unwrap(), ConnectionStats {
+            current_rtt_ms: Some(50.0),
+            bytes_sent: 1024,
+            bytes_received: 2048,
+            candidate_pair_state: Some("succeeded".to_string()),
+        });
+
+        // Dumped by browser, as [key, value] of RTCStatsReport.entries().
+        let browser = [
+            r#"["CPabc_def",{"id":"CPabc_def","timestamp":1700000000000.5,"type":"candidate-pair","transportId":"T01","localCandidateId":"Iabc","remoteCandidateId":"Idef","state":"in-progress","priority":1,"nominated":false,"writable":false,"packetsSent":2,"packetsReceived":0,"bytesSent":0,"bytesReceived":0,"totalRoundTripTime":0,"requestsReceived":0,"requestsSent":2,"responsesReceived":0,"responsesSent":0}]"#,
+            r#"["CPabc_ghi",{"id":"CPabc_ghi","timestamp":1700000000000.5,"type":"candidate-pair","transportId":"T01","localCandidateId":"Iabc","remoteCandidateId":"Ighi","state":"succeeded","priority":2,"nominated":true,"writable":true,"packetsSent":30,"packetsReceived":28,"bytesSent":4096,"bytesReceived":3072,"totalRoundTripTime":0.012,"currentRoundTripTime":0.004,"requestsReceived":3,"requestsSent":3,"responsesReceived":3,"responsesSent":3}]"#,
+            r#"["T01",{"id":"T01","timestamp":1700000000000.5,"type":"transport","bytesSent":4096,"bytesReceived":3072,"dtlsState":"connected","iceState":"connected","selectedCandidatePairId":"CPabc_ghi","selectedCandidatePairChanges":1}]"#,
+        ]
+        .map(|s| s.to_string());
+        assert_eq!(ConnectionStats::from_stats(&browser).unwrap(), ConnectionStats {
+            current_rtt_ms: Some(4.0),
+            bytes_sent: 4096,
+            bytes_received: 3072,
+            candidate_pair_state: Some("succeeded".to_string()),
+        });
+
+        // No candidate pair before ice gathering.
+        assert_eq!(ConnectionStats::from_stats(&native[..1]), None);
+    }
+}
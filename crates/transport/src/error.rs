@@ -12,6 +12,12 @@ pub enum IceServerError {
 
     #[error("Cannot extract host from url")]
     UrlMissHost,
+
+    #[error("Relay fallback requires at least one TURN server")]
+    RelayFallbackWithoutTurn,
+
+    #[error("Invalid ice servers: {}", .0.join(", "))]
+    InvalidEntries(Vec<String>),
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -48,6 +54,21 @@ pub enum Error {
     #[error("Connection {0} is released")]
     ConnectionReleased(String),
 
+    #[error("Data channel {0} is closed")]
+    ChannelClosed(String),
+
+    #[error("Data channel {0} buffer is not drained in time")]
+    ChannelBufferFull(String),
+
+    #[error("Data channel {0} not found")]
+    ChannelNotFound(String),
+
+    #[error("Data channel label {0} is reserved or taken")]
+    ChannelLabelTaken(String),
+
+    #[error("Invalid data channel config: {0}")]
+    InvalidDataChannelConfig(String),
+
     #[error("Rwlock try write failed: {0}")]
     RwLockWrite(String),
 
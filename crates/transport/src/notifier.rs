@@ -33,21 +33,27 @@ impl Notifier {
     }
 
     /// Wake the notifier after the specified time.
-    #[cfg(feature = "native-webrtc")]
+    #[cfg(any(feature = "native-webrtc", feature = "web-sys-webrtc"))]
     pub fn set_timeout(&self, seconds: u8) {
+        self.set_timeout_ms(seconds as u32 * 1000)
+    }
+
+    /// Wake the notifier after the specified time in milliseconds.
+    #[cfg(feature = "native-webrtc")]
+    pub fn set_timeout_ms(&self, millis: u32) {
         let this = self.clone();
         tokio::spawn(async move {
-            tokio::time::sleep(tokio::time::Duration::from_secs(seconds.into())).await;
+            tokio::time::sleep(tokio::time::Duration::from_millis(millis.into())).await;
             this.wake();
         });
     }
 
-    /// Wake the notifier after the specified time.
+    /// Wake the notifier after the specified time in milliseconds.
     #[cfg(feature = "web-sys-webrtc")]
-    pub fn set_timeout(&self, seconds: u8) {
+    pub fn set_timeout_ms(&self, millis: u32) {
         use wasm_bindgen::JsCast;
 
-        let millis = seconds as i32 * 1000;
+        let millis = millis.min(i32::MAX as u32) as i32;
 
         let this = self.clone();
         let wake = wasm_bindgen::closure::Closure::once_into_js(move || {